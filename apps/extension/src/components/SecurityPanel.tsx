@@ -1,5 +1,6 @@
 import { useState } from 'react'
 import { Shield, Lock, Clock, CheckCircle2 } from 'lucide-react'
+import { SDKEY_POLICY_VERSION } from '../lib/wasm'
 
 export default function SecurityPanel() {
   const [dailyLimit, setDailyLimit] = useState('10000')
@@ -9,6 +10,7 @@ export default function SecurityPanel() {
 
   const savePolicy = () => {
     const policy = {
+      version: SDKEY_POLICY_VERSION,
       daily_limit: dailyLimit ? parseInt(dailyLimit) : null,
       trusted_contracts: trustedContracts ? trustedContracts.split(',').map(s => s.trim()) : null,
       time_lock_until: timeLock ? new Date(timeLock).getTime() / 1000 : null,
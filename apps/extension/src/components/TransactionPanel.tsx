@@ -1,6 +1,6 @@
 import { useState } from 'react'
 import { Send, CheckCircle, AlertCircle } from 'lucide-react'
-import { createSession } from '../lib/wasm'
+import { createSession, SDKEY_POLICY_VERSION } from '../lib/wasm'
 
 export default function TransactionPanel() {
   const [userId] = useState('alice')
@@ -17,7 +17,10 @@ export default function TransactionPanel() {
     setResult(null)
 
     try {
-      const session = await createSession(userId)
+      // Mock 部署: 函数指纹需为 CFC 验证数据哈希，这里用固定占位值
+      const session = await createSession(userId, undefined, {
+        [contractId]: [{ name: functionName, fingerprint: '11'.repeat(32) }],
+      })
       const txResult = await session.exec_cfc(contractId, functionName, args)
       setResult(txResult)
       console.log('交易结果:', txResult)
@@ -36,6 +39,7 @@ export default function TransactionPanel() {
     try {
       const session = await createSession(userId)
       const policy = JSON.stringify({
+        version: SDKEY_POLICY_VERSION,
         daily_limit: 10000,
         trusted_contracts: [contractId],
         time_lock_until: null,
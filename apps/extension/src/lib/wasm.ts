@@ -27,7 +27,24 @@ export function getWasm() {
   return wasmModule
 }
 
-export async function createSession(userId: string) {
+// SDKey 策略模式版本，与 psyguard-core 的 SDKEY_POLICY_VERSION 保持一致
+export const SDKEY_POLICY_VERSION = 3
+
+export interface ContractFunction {
+  name: string
+  fingerprint: string
+}
+
+// contracts: 会话开始前部署的 Mock 合约 (合约 ID -> 函数清单)
+export async function createSession(
+  userId: string,
+  uconJson?: string,
+  contracts?: Record<string, ContractFunction[]>
+) {
   const wasm = getWasm()
-  return new wasm.WasmUpsSession(userId)
+  return await wasm.WasmUpsSession.create(
+    userId,
+    uconJson,
+    contracts ? JSON.stringify(contracts) : undefined
+  )
 }
@@ -4,10 +4,249 @@
 
 use crate::types::*;
 use crate::error::{PsyGuardError, Result};
-use sha2::{Sha256, Digest};
+use crate::hash::MerkleHasher;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
 
 pub use crate::types::CftVerificationResult;
 
+/// 验证数据编码域分隔标签
+pub const VERIFIER_DATA_DOMAIN: &[u8] = b"psyguard/cfc-verifier-data";
+
+/// 验证数据编码版本
+pub const VERIFIER_DATA_VERSION: u8 = 1;
+
+/// 叶哈希域分隔标签
+pub const CFT_LEAF_TAG: u8 = 0x00;
+
+/// 内部节点哈希域分隔标签
+pub const CFT_NODE_TAG: u8 = 0x01;
+
+/// 支持的最大 CFT 深度
+pub const MAX_CFT_DEPTH: u32 = 32;
+
+/// 填充叶 (不对应任何函数)
+pub const CFT_EMPTY_LEAF: Hash = [0u8; 32];
+
+impl CfcVerifierData {
+    /// 规范化编码: `len(domain) | domain | version | constants_root | circuit_digest`
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(4 + VERIFIER_DATA_DOMAIN.len() + 65);
+        out.extend_from_slice(&(VERIFIER_DATA_DOMAIN.len() as u32).to_le_bytes());
+        out.extend_from_slice(VERIFIER_DATA_DOMAIN);
+        out.push(VERIFIER_DATA_VERSION);
+        out.extend_from_slice(&self.constants_root);
+        out.extend_from_slice(&self.circuit_digest);
+        out
+    }
+}
+
+impl CfcFingerprint {
+    /// 由 CFC 验证数据计算指纹
+    pub fn from_verifier_data(hasher: &dyn MerkleHasher, data: &CfcVerifierData) -> Self {
+        Self(hasher.hash_bytes(&data.encode()))
+    }
+
+    /// 解析 64 位十六进制指纹 (可带 0x 前缀)
+    pub fn from_hex(s: &str) -> Result<Self> {
+        let digits = s.strip_prefix("0x").unwrap_or(s);
+        if digits.len() != 64 {
+            return Err(PsyGuardError::InvalidFingerprint(format!(
+                "需要 64 位十六进制字符，实际 {} 位: {:?}",
+                digits.len(),
+                s
+            )));
+        }
+
+        let bytes = hex::decode(digits)
+            .map_err(|e| PsyGuardError::InvalidFingerprint(format!("{:?}: {}", s, e)))?;
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&bytes);
+        Ok(Self(hash))
+    }
+
+    /// 十六进制表示
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+}
+
+impl fmt::Display for CfcFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl FromStr for CfcFingerprint {
+    type Err = PsyGuardError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_hex(s)
+    }
+}
+
+impl TryFrom<String> for CfcFingerprint {
+    type Error = PsyGuardError;
+
+    fn try_from(s: String) -> Result<Self> {
+        Self::from_hex(&s)
+    }
+}
+
+impl From<CfcFingerprint> for String {
+    fn from(fingerprint: CfcFingerprint) -> Self {
+        fingerprint.to_hex()
+    }
+}
+
+/// 固定深度的 CFT
+///
+/// 深度为 ceil(log2(函数数))，叶数不足 2^depth 时以零叶填充。
+/// 叶与内部节点使用不同的域标签，内部节点无法被当作叶提交。
+/// 参考: 《6-Smart Contracts.md》- 部署时生成 CFT
+#[derive(Debug, Clone)]
+pub struct CftTree {
+    /// levels[0] 为叶层，最后一层只有根
+    levels: Vec<Vec<Hash>>,
+    /// 实际函数数 (不含填充叶)
+    leaf_count: usize,
+}
+
+impl CftTree {
+    /// 从函数指纹列表构建
+    pub fn build(hasher: &dyn MerkleHasher, fingerprints: &[CfcFingerprint]) -> Result<Self> {
+        if fingerprints.is_empty() {
+            return Err(PsyGuardError::InvalidInput("CFT 至少需要一个函数".to_string()));
+        }
+
+        let depth = Self::depth_for(fingerprints.len())?;
+        let mut leaves: Vec<Hash> = fingerprints
+            .iter()
+            .map(|fp| Self::leaf_hash(hasher, fp))
+            .collect();
+        leaves.resize(1usize << depth, CFT_EMPTY_LEAF);
+
+        let mut levels = vec![leaves];
+        while levels.last().map_or(0, Vec::len) > 1 {
+            let next = levels
+                .last()
+                .expect("至少一层")
+                .chunks(2)
+                .map(|pair| Self::node_hash(hasher, &pair[0], &pair[1]))
+                .collect();
+            levels.push(next);
+        }
+
+        Ok(Self {
+            levels,
+            leaf_count: fingerprints.len(),
+        })
+    }
+
+    /// 函数数对应的树深度
+    pub fn depth_for(function_count: usize) -> Result<u32> {
+        let depth = function_count.next_power_of_two().trailing_zeros();
+        if depth > MAX_CFT_DEPTH {
+            return Err(PsyGuardError::InvalidInput(format!(
+                "函数数 {} 超出 CFT 容量",
+                function_count
+            )));
+        }
+        Ok(depth)
+    }
+
+    /// 树深度
+    pub fn depth(&self) -> u32 {
+        (self.levels.len() - 1) as u32
+    }
+
+    /// 实际函数数 (不含填充叶)
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// CFT 根
+    pub fn root(&self) -> CftRoot {
+        CftRoot(self.levels[self.levels.len() - 1][0])
+    }
+
+    /// 生成第 index 个函数的包含证明，填充叶不可证明
+    pub fn prove(&self, index: usize) -> Result<CftInclusionProof> {
+        if index >= self.leaf_count {
+            return Err(PsyGuardError::NotFound(format!("指纹索引 {} 超出范围", index)));
+        }
+
+        let merkle_path = self.levels[..self.levels.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(level, nodes)| nodes[(index >> level) ^ 1])
+            .collect();
+
+        Ok(CftInclusionProof {
+            leaf_index: index as u64,
+            merkle_path,
+            cft_root: self.root(),
+        })
+    }
+
+    /// 生成多个函数的批量包含证明
+    ///
+    /// 每层只收录无法由已知节点推出的兄弟节点，多个叶共享的路径只出现一次。
+    pub fn prove_multi(&self, indices: &[usize]) -> Result<CftMultiProof> {
+        let mut known: Vec<usize> = indices.to_vec();
+        known.sort_unstable();
+        if known.is_empty() || known.windows(2).any(|w| w[0] == w[1]) {
+            return Err(PsyGuardError::InvalidInput("批量证明需要非空且不重复的叶索引".to_string()));
+        }
+        if let Some(index) = known.iter().find(|i| **i >= self.leaf_count) {
+            return Err(PsyGuardError::NotFound(format!("指纹索引 {} 超出范围", index)));
+        }
+
+        let leaf_indices = known.iter().map(|i| *i as u64).collect();
+        let mut nodes = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            for (position, index) in known.iter().enumerate() {
+                let sibling = index ^ 1;
+                let sibling_known = if index & 1 == 0 {
+                    known.get(position + 1) == Some(&sibling)
+                } else {
+                    position > 0 && known[position - 1] == sibling
+                };
+                if !sibling_known {
+                    nodes.push(level[sibling]);
+                }
+            }
+            known = known.iter().map(|i| i >> 1).collect();
+            known.dedup();
+        }
+
+        Ok(CftMultiProof {
+            leaf_indices,
+            depth: self.depth(),
+            nodes,
+            cft_root: self.root(),
+        })
+    }
+
+    /// 叶哈希: H(LEAF_TAG || fingerprint)
+    pub fn leaf_hash(hasher: &dyn MerkleHasher, fingerprint: &CfcFingerprint) -> Hash {
+        let mut preimage = [0u8; 33];
+        preimage[0] = CFT_LEAF_TAG;
+        preimage[1..].copy_from_slice(&fingerprint.0);
+        hasher.hash_bytes(&preimage)
+    }
+
+    /// 内部节点哈希: H(NODE_TAG || left || right)
+    pub fn node_hash(hasher: &dyn MerkleHasher, left: &Hash, right: &Hash) -> Hash {
+        let mut preimage = [0u8; 65];
+        preimage[0] = CFT_NODE_TAG;
+        preimage[1..33].copy_from_slice(left);
+        preimage[33..].copy_from_slice(right);
+        hasher.hash_bytes(&preimage)
+    }
+}
+
 /// CFT 校验器
 pub struct CftVerifier;
 
@@ -15,32 +254,99 @@ impl CftVerifier {
     /// 校验函数指纹是否在 CFT 中
     /// 参考: 《6-Smart Contracts.md》- 函数指纹 Merkle 校验
     pub fn verify_inclusion(
+        hasher: &dyn MerkleHasher,
         fingerprint: &CfcFingerprint,
         proof: &CftInclusionProof,
     ) -> Result<bool> {
-        // 1. 计算指纹哈希
-        let leaf_hash = Self::hash_fingerprint(fingerprint);
+        // 1. 叶索引必须落在路径长度决定的树内
+        let depth = proof.merkle_path.len();
+        if depth > MAX_CFT_DEPTH as usize || proof.leaf_index >> depth != 0 {
+            return Ok(false);
+        }
 
-        // 2. 沿 Merkle 路径向上计算
-        let mut current_hash = leaf_hash;
-        for sibling in &proof.merkle_path {
-            current_hash = Self::hash_pair(&current_hash, sibling);
+        // 2. 按叶索引逐位决定左右，沿 Merkle 路径向上计算
+        let mut current_hash = CftTree::leaf_hash(hasher, fingerprint);
+        for (level, sibling) in proof.merkle_path.iter().enumerate() {
+            current_hash = if (proof.leaf_index >> level) & 1 == 0 {
+                CftTree::node_hash(hasher, &current_hash, sibling)
+            } else {
+                CftTree::node_hash(hasher, sibling, &current_hash)
+            };
         }
 
         // 3. 验证是否等于 CFT 根
         Ok(current_hash == proof.cft_root.0)
     }
 
+    /// 校验批量包含证明，fingerprints 与 proof.leaf_indices 一一对应
+    pub fn verify_multi(
+        hasher: &dyn MerkleHasher,
+        fingerprints: &[CfcFingerprint],
+        proof: &CftMultiProof,
+    ) -> Result<bool> {
+        // 1. 形状检查: 数量一致、索引严格递增且在树内
+        let depth = proof.depth;
+        if fingerprints.is_empty()
+            || fingerprints.len() != proof.leaf_indices.len()
+            || depth > MAX_CFT_DEPTH
+            || proof.leaf_indices.windows(2).any(|w| w[0] >= w[1])
+            || proof.leaf_indices.iter().any(|i| i >> depth != 0)
+        {
+            return Ok(false);
+        }
+
+        // 2. 逐层合并已知节点，缺失的兄弟按顺序从证明中取
+        let mut level: BTreeMap<u64, Hash> = proof
+            .leaf_indices
+            .iter()
+            .zip(fingerprints)
+            .map(|(index, fp)| (*index, CftTree::leaf_hash(hasher, fp)))
+            .collect();
+        let mut supplied = proof.nodes.iter();
+
+        for _ in 0..depth {
+            let mut next = BTreeMap::new();
+            let mut entries = level.iter().peekable();
+            while let Some((index, hash)) = entries.next() {
+                let (left, right) = if index & 1 == 0 {
+                    match entries.peek() {
+                        Some((next_index, next_hash)) if **next_index == index + 1 => {
+                            let pair = (*hash, **next_hash);
+                            entries.next();
+                            pair
+                        }
+                        _ => match supplied.next() {
+                            Some(sibling) => (*hash, *sibling),
+                            None => return Ok(false),
+                        },
+                    }
+                } else {
+                    match supplied.next() {
+                        Some(sibling) => (*sibling, *hash),
+                        None => return Ok(false),
+                    }
+                };
+                next.insert(index >> 1, CftTree::node_hash(hasher, &left, &right));
+            }
+            level = next;
+        }
+
+        // 3. 证明节点必须恰好用完，且归约到 CFT 根
+        Ok(supplied.next().is_none() && level.get(&0) == Some(&proof.cft_root.0))
+    }
+
     /// 完整的 CFT 校验,返回详细结果
-    /// 用于前端展示
+    /// 用于前端展示，指纹为十六进制字符串，格式无效时返回 InvalidFingerprint
     pub fn verify_with_details(
-        fingerprint: &CfcFingerprint,
+        hasher: &dyn MerkleHasher,
+        fingerprint: &str,
         proof: &CftInclusionProof,
     ) -> Result<CftVerificationResult> {
-        let in_cft = Self::verify_inclusion(fingerprint, proof)?;
+        let fingerprint = CfcFingerprint::from_hex(fingerprint)?;
+        let in_cft = Self::verify_inclusion(hasher, &fingerprint, proof)?;
         
         Ok(CftVerificationResult {
-            fingerprint: fingerprint.clone(),
+            fingerprint,
             in_cft,
             cft_root: proof.cft_root.clone(),
             depth: proof.merkle_path.len(),
@@ -49,131 +355,232 @@ impl CftVerifier {
         })
     }
 
-    /// 计算指纹哈希
-    fn hash_fingerprint(fingerprint: &CfcFingerprint) -> Hash {
-        let mut hasher = Sha256::new();
-        hasher.update(fingerprint.0.as_bytes());
-        let result = hasher.finalize();
-        let mut hash = [0u8; 32];
-        hash.copy_from_slice(&result);
-        hash
-    }
-
-    /// 计算两个哈希的父节点
-    fn hash_pair(left: &Hash, right: &Hash) -> Hash {
-        let mut hasher = Sha256::new();
-        hasher.update(left);
-        hasher.update(right);
-        let result = hasher.finalize();
-        let mut hash = [0u8; 32];
-        hash.copy_from_slice(&result);
-        hash
-    }
-
     /// 构建 CFT (从函数指纹列表)
     /// 参考: 《6-Smart Contracts.md》- 部署时生成 CFT
-    pub fn build_cft(fingerprints: &[CfcFingerprint]) -> CftRoot {
-        if fingerprints.is_empty() {
-            return CftRoot([0u8; 32]);
-        }
-
-        // 1. 计算所有叶节点哈希
-        let mut level: Vec<Hash> = fingerprints
-            .iter()
-            .map(|fp| Self::hash_fingerprint(fp))
-            .collect();
-
-        // 2. 逐层向上构建 Merkle 树
-        while level.len() > 1 {
-            let mut next_level = Vec::new();
-            for chunk in level.chunks(2) {
-                if chunk.len() == 2 {
-                    next_level.push(Self::hash_pair(&chunk[0], &chunk[1]));
-                } else {
-                    // 奇数个节点，最后一个直接提升
-                    next_level.push(chunk[0]);
-                }
-            }
-            level = next_level;
-        }
-
-        CftRoot(level[0])
+    pub fn build_cft(hasher: &dyn MerkleHasher, fingerprints: &[CfcFingerprint]) -> Result<CftRoot> {
+        Ok(CftTree::build(hasher, fingerprints)?.root())
     }
 
     /// 生成 Merkle 包含证明
     pub fn generate_proof(
+        hasher: &dyn MerkleHasher,
         fingerprints: &[CfcFingerprint],
         target_index: usize,
     ) -> Result<CftInclusionProof> {
-        if target_index >= fingerprints.len() {
-            return Err(PsyGuardError::NotFound(format!(
-                "指纹索引 {} 超出范围",
-                target_index
-            )));
+        CftTree::build(hasher, fingerprints)?.prove(target_index)
+    }
+
+    /// 生成批量包含证明
+    pub fn generate_multi_proof(
+        hasher: &dyn MerkleHasher,
+        fingerprints: &[CfcFingerprint],
+        target_indices: &[usize],
+    ) -> Result<CftMultiProof> {
+        CftTree::build(hasher, fingerprints)?.prove_multi(target_indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::Sha256Hasher;
+
+    fn fingerprints(count: usize) -> Vec<CfcFingerprint> {
+        (1..=count)
+            .map(|i| {
+                let data = CfcVerifierData {
+                    constants_root: [i as u8; 32],
+                    circuit_digest: [0xc0; 32],
+                };
+                CfcFingerprint::from_verifier_data(&Sha256Hasher, &data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cft_build_and_verify() {
+        let fingerprints = fingerprints(3);
+
+        let cft_root = CftVerifier::build_cft(&Sha256Hasher, &fingerprints).unwrap();
+        let proof = CftVerifier::generate_proof(&Sha256Hasher, &fingerprints, 1).unwrap();
+
+        assert_eq!(proof.cft_root.0, cft_root.0);
+
+        let verified = CftVerifier::verify_inclusion(&Sha256Hasher, &fingerprints[1], &proof).unwrap();
+        assert!(verified);
+    }
+
+    #[test]
+    fn test_cft_fixed_depth_and_padding() {
+        assert_eq!(CftTree::depth_for(1).unwrap(), 0);
+        assert_eq!(CftTree::depth_for(3).unwrap(), 2);
+        assert_eq!(CftTree::depth_for(4).unwrap(), 2);
+        assert_eq!(CftTree::depth_for(5).unwrap(), 3);
+        assert!(CftVerifier::build_cft(&Sha256Hasher, &[]).is_err());
+
+        let fingerprints = fingerprints(5);
+        let tree = CftTree::build(&Sha256Hasher, &fingerprints).unwrap();
+        assert_eq!(tree.depth(), 3);
+
+        for (i, fp) in fingerprints.iter().enumerate() {
+            let proof = tree.prove(i).unwrap();
+            assert_eq!(proof.leaf_index, i as u64);
+            assert_eq!(proof.merkle_path.len(), 3);
+            assert!(CftVerifier::verify_inclusion(&Sha256Hasher, fp, &proof).unwrap());
         }
 
-        // 1. 计算所有叶节点
-        let mut level: Vec<Hash> = fingerprints
-            .iter()
-            .map(|fp| Self::hash_fingerprint(fp))
-            .collect();
+        // 填充叶不能冒充最后一个函数被提升到上层
+        let last = tree.prove(4).unwrap();
+        assert_eq!(last.merkle_path[0], CFT_EMPTY_LEAF);
+    }
 
-        let mut merkle_path = Vec::new();
-        let mut current_index = target_index;
+    #[test]
+    fn test_cft_rejects_wrong_index_and_node_as_leaf() {
+        let fingerprints = fingerprints(4);
+        let tree = CftTree::build(&Sha256Hasher, &fingerprints).unwrap();
 
-        // 2. 逐层收集兄弟节点
-        while level.len() > 1 {
-            let sibling_index = if current_index % 2 == 0 {
-                current_index + 1
-            } else {
-                current_index - 1
-            };
+        // 路径相同但索引错误
+        let mut proof = tree.prove(2).unwrap();
+        proof.leaf_index = 3;
+        assert!(!CftVerifier::verify_inclusion(&Sha256Hasher, &fingerprints[2], &proof).unwrap());
 
-            if sibling_index < level.len() {
-                merkle_path.push(level[sibling_index]);
-            }
+        // 索引超出路径表示的树
+        proof.leaf_index = 6;
+        assert!(!CftVerifier::verify_inclusion(&Sha256Hasher, &fingerprints[2], &proof).unwrap());
 
-            // 构建下一层
-            let mut next_level = Vec::new();
-            for chunk in level.chunks(2) {
-                if chunk.len() == 2 {
-                    next_level.push(Self::hash_pair(&chunk[0], &chunk[1]));
-                } else {
-                    next_level.push(chunk[0]);
-                }
-            }
+        // 第二原像: 把第 1 层内部节点 (叶 2、3 的父节点) 当作叶，
+        // 配上其在深度 depth-1 树中的真实路径提交
+        let full = tree.prove(2).unwrap();
+        let node = CftTree::node_hash(
+            &Sha256Hasher,
+            &CftTree::leaf_hash(&Sha256Hasher, &fingerprints[2]),
+            &CftTree::leaf_hash(&Sha256Hasher, &fingerprints[3]),
+        );
+        let forged = CftInclusionProof {
+            leaf_index: 1,
+            merkle_path: full.merkle_path[1..].to_vec(),
+            cft_root: tree.root(),
+        };
+        assert!(!CftVerifier::verify_inclusion(&Sha256Hasher, &CfcFingerprint(node), &forged).unwrap());
+
+        let forged_multi = CftMultiProof {
+            leaf_indices: vec![1],
+            depth: tree.depth() - 1,
+            nodes: full.merkle_path[1..].to_vec(),
+            cft_root: tree.root(),
+        };
+        assert!(!CftVerifier::verify_multi(&Sha256Hasher, &[CfcFingerprint(node)], &forged_multi).unwrap());
+
+        // 同一路径若省略叶标签即可通过，说明伪造证明本身构造正确
+        let untagged = CftTree::node_hash(&Sha256Hasher, &full.merkle_path[1], &node);
+        assert_eq!(untagged, tree.root().0);
+    }
 
-            level = next_level;
-            current_index /= 2;
+    #[test]
+    fn test_cft_rejects_padding_indices() {
+        let fingerprints = fingerprints(5);
+        let tree = CftTree::build(&Sha256Hasher, &fingerprints).unwrap();
+        assert_eq!(tree.leaf_count(), 5);
+
+        for index in 5..8 {
+            assert!(matches!(tree.prove(index), Err(PsyGuardError::NotFound(_))));
+            assert!(matches!(tree.prove_multi(&[0, index]), Err(PsyGuardError::NotFound(_))));
         }
+        assert!(CftVerifier::generate_proof(&Sha256Hasher, &fingerprints, 5).is_err());
+    }
 
-        let cft_root = CftRoot(level[0]);
+    #[test]
+    fn test_fingerprint_parsing() {
+        let data = CfcVerifierData {
+            constants_root: [1u8; 32],
+            circuit_digest: [2u8; 32],
+        };
+        let fingerprint = CfcFingerprint::from_verifier_data(&Sha256Hasher, &data);
+        assert_eq!(CfcFingerprint::from_hex(&fingerprint.to_hex()).unwrap(), fingerprint);
+        assert_eq!(
+            format!("0x{}", fingerprint).parse::<CfcFingerprint>().unwrap(),
+            fingerprint
+        );
 
-        Ok(CftInclusionProof {
-            merkle_path,
-            cft_root,
-        })
+        // 验证数据任一部分变化都会改变指纹
+        let other = CfcVerifierData { circuit_digest: [3u8; 32], ..data };
+        assert_ne!(CfcFingerprint::from_verifier_data(&Sha256Hasher, &other), fingerprint);
+
+        // JSON 中为十六进制字符串，反序列化时校验
+        let json = serde_json::to_string(&fingerprint).unwrap();
+        assert_eq!(json, format!("\"{}\"", fingerprint.to_hex()));
+        assert_eq!(serde_json::from_str::<CfcFingerprint>(&json).unwrap(), fingerprint);
+        assert!(serde_json::from_str::<CfcFingerprint>("\"func1\"").is_err());
+
+        for malformed in ["func1", "", "0x1234", &"zz".repeat(32), &"00".repeat(33)] {
+            assert!(matches!(
+                CfcFingerprint::from_hex(malformed),
+                Err(PsyGuardError::InvalidFingerprint(_))
+            ));
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_verify_with_details() {
+        let fingerprints = fingerprints(3);
+        let proof = CftVerifier::generate_proof(&Sha256Hasher, &fingerprints, 2).unwrap();
+
+        let result =
+            CftVerifier::verify_with_details(&Sha256Hasher, &fingerprints[2].to_hex(), &proof).unwrap();
+        assert!(result.in_cft);
+        assert_eq!(result.fingerprint, fingerprints[2]);
+        assert_eq!(result.depth, 2);
+
+        let other = CftVerifier::verify_with_details(&Sha256Hasher, &fingerprints[0].to_hex(), &proof).unwrap();
+        assert!(!other.in_cft);
+
+        assert!(matches!(
+            CftVerifier::verify_with_details(&Sha256Hasher, "func1", &proof),
+            Err(PsyGuardError::InvalidFingerprint(_))
+        ));
+    }
 
     #[test]
-    fn test_cft_build_and_verify() {
-        let fingerprints = vec![
-            CfcFingerprint("func1".to_string()),
-            CfcFingerprint("func2".to_string()),
-            CfcFingerprint("func3".to_string()),
-        ];
+    fn test_cft_multi_proof() {
+        let fingerprints = fingerprints(7);
+        let tree = CftTree::build(&Sha256Hasher, &fingerprints).unwrap();
 
-        let cft_root = CftVerifier::build_cft(&fingerprints);
-        let proof = CftVerifier::generate_proof(&fingerprints, 1).unwrap();
+        let indices = [0usize, 1, 5];
+        let proof = CftVerifier::generate_multi_proof(&Sha256Hasher, &fingerprints, &[5, 0, 1]).unwrap();
+        assert_eq!(proof.leaf_indices, vec![0, 1, 5]);
+        assert_eq!(proof.cft_root.0, tree.root().0);
+        let selected: Vec<_> = indices.iter().map(|i| fingerprints[*i]).collect();
+        assert!(CftVerifier::verify_multi(&Sha256Hasher, &selected, &proof).unwrap());
 
-        assert_eq!(proof.cft_root.0, cft_root.0);
+        // 共享节点只出现一次: 少于三条独立路径的兄弟总数
+        let single_total: usize = indices.iter().map(|i| tree.prove(*i).unwrap().merkle_path.len()).sum();
+        assert!(proof.nodes.len() < single_total);
+        assert_eq!(proof.nodes.len(), 3);
 
-        let verified = CftVerifier::verify_inclusion(&fingerprints[1], &proof).unwrap();
-        assert!(verified);
+        // 全部叶无需额外节点
+        let all: Vec<usize> = (0..7).collect();
+        let full = tree.prove_multi(&all).unwrap();
+        assert_eq!(full.nodes, vec![CFT_EMPTY_LEAF]);
+        assert!(CftVerifier::verify_multi(&Sha256Hasher, &fingerprints, &full).unwrap());
+
+        let full = CftVerifier::generate_multi_proof(&Sha256Hasher, &fingerprints[..4], &[0, 1, 2, 3]).unwrap();
+        assert!(full.nodes.is_empty());
+
+        // 错误指纹、顺序不符、多余或缺失节点都应失败
+        let mut wrong = selected.clone();
+        wrong.swap(0, 1);
+        assert!(!CftVerifier::verify_multi(&Sha256Hasher, &wrong, &proof).unwrap());
+        assert!(!CftVerifier::verify_multi(&Sha256Hasher, &selected[..2], &proof).unwrap());
+
+        let mut extra = proof.clone();
+        extra.nodes.push([0u8; 32]);
+        assert!(!CftVerifier::verify_multi(&Sha256Hasher, &selected, &extra).unwrap());
+
+        let mut missing = proof.clone();
+        missing.nodes.pop();
+        assert!(!CftVerifier::verify_multi(&Sha256Hasher, &selected, &missing).unwrap());
+
+        assert!(tree.prove_multi(&[1, 1]).is_err());
+        assert!(tree.prove_multi(&[8]).is_err());
     }
 }
@@ -0,0 +1,81 @@
+//! Checkpoint 叶
+//!
+//! 每个区块的 CHKP 叶承诺该区块结束时的全局树根 (GUSR、GCON)，
+//! UPS 绑定的 chkp_root 即该叶的哈希；用户叶与合约叶的 Merkle 证明都锚定到这里。
+//! 参考: 《3-How a Block is Made.md》- PsyCheckpointLeaf 含新的全局根
+//! 参考: 《5-Local Proving (UPS).md》- ULEAF 的 Merkle 证明锚定到 CHKP 根
+
+use crate::types::*;
+use crate::error::{PsyGuardError, Result};
+use crate::hash::MerkleHasher;
+use serde::{Deserialize, Serialize};
+
+/// CHKP 叶哈希域分隔标签
+const CHKP_LEAF_DOMAIN: &[u8] = b"psyguard/chkp-leaf";
+
+/// 区块的 CHKP 叶
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointLeaf {
+    pub block_number: u64,
+    /// 全局用户树根
+    pub gusr_root: Hash,
+    /// 全局合约树根
+    pub gcon_root: Hash,
+}
+
+impl CheckpointLeaf {
+    /// CHKP 叶哈希
+    pub fn hash(&self, hasher: &dyn MerkleHasher) -> Hash {
+        let mut preimage = Vec::with_capacity(CHKP_LEAF_DOMAIN.len() + 72);
+        preimage.extend_from_slice(CHKP_LEAF_DOMAIN);
+        preimage.extend_from_slice(&self.block_number.to_le_bytes());
+        preimage.extend_from_slice(&self.gusr_root);
+        preimage.extend_from_slice(&self.gcon_root);
+        hasher.hash_bytes(&preimage)
+    }
+
+    /// 该叶对应的 CheckpointRef
+    pub fn checkpoint_ref(&self, hasher: &dyn MerkleHasher) -> CheckpointRef {
+        CheckpointRef {
+            chkp_root: self.hash(hasher),
+            block_number: self.block_number,
+        }
+    }
+
+    /// 校验叶与 CheckpointRef 一致
+    pub fn verify(&self, hasher: &dyn MerkleHasher, chkp: &CheckpointRef) -> Result<()> {
+        if self.block_number != chkp.block_number || self.hash(hasher) != chkp.chkp_root {
+            return Err(PsyGuardError::MerkleProofInvalid(format!(
+                "CHKP 叶 (区块 {}) 与 checkpoint {} (区块 {}) 不一致",
+                self.block_number,
+                hex::encode(chkp.chkp_root),
+                chkp.block_number
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::Sha256Hasher;
+
+    #[test]
+    fn test_checkpoint_leaf_commits_roots() {
+        let leaf = CheckpointLeaf { block_number: 7, gusr_root: [1u8; 32], gcon_root: [2u8; 32] };
+        let chkp = leaf.checkpoint_ref(&Sha256Hasher);
+        assert!(leaf.verify(&Sha256Hasher, &chkp).is_ok());
+
+        for forged in [
+            CheckpointLeaf { block_number: 8, ..leaf.clone() },
+            CheckpointLeaf { gusr_root: [3u8; 32], ..leaf.clone() },
+            CheckpointLeaf { gcon_root: [3u8; 32], ..leaf.clone() },
+        ] {
+            assert!(matches!(
+                forged.verify(&Sha256Hasher, &chkp),
+                Err(PsyGuardError::MerkleProofInvalid(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,626 @@
+//! 二进制线格式
+//!
+//! End Cap、步骤证明、状态变更与提交收据的紧凑、确定性编码，
+//! 供 WASM 层与中继之间传输，以及计算稳定的内容哈希。
+//!
+//! 帧格式: `magic(4) | version(1) | kind(1) | body`。
+//! body 内整数一律小端，变长字节与字符串带 u32 长度前缀，
+//! 列表带 u32 元素数前缀，`Option` 以 0/1 标记。
+//! 相同结构总是得到相同字节，因此也总是得到相同哈希。
+
+use crate::types::*;
+use crate::error::{PsyGuardError, Result};
+use crate::state::ParthTransfer;
+use sha2::{Sha256, Digest};
+
+/// 帧魔数
+pub const WIRE_MAGIC: [u8; 4] = *b"PSYG";
+
+/// 线格式版本
+pub const WIRE_VERSION: u8 = 3;
+
+/// 帧头长度
+pub const WIRE_HEADER_LEN: usize = 6;
+
+/// 可编码为线格式帧的消息
+pub trait WireMessage: Sized {
+    /// 帧类型标记
+    const KIND: u8;
+
+    /// 编码消息体
+    fn encode_body(&self, w: &mut WireWriter);
+
+    /// 解码消息体
+    fn decode_body(r: &mut WireReader) -> Result<Self>;
+}
+
+/// 编码为完整帧
+pub fn encode<T: WireMessage>(value: &T) -> Vec<u8> {
+    let mut w = WireWriter::new();
+    w.buf.extend_from_slice(&WIRE_MAGIC);
+    w.put_u8(WIRE_VERSION);
+    w.put_u8(T::KIND);
+    value.encode_body(&mut w);
+    w.into_bytes()
+}
+
+/// 从完整帧解码，拒绝魔数/版本/类型不符以及多余字节
+pub fn decode<T: WireMessage>(bytes: &[u8]) -> Result<T> {
+    let mut r = WireReader::new(bytes);
+    let value = decode_frame(&mut r)?;
+    r.finish()?;
+    Ok(value)
+}
+
+/// 解码开头的一帧，返回其后剩余的字节 (用于依次拼接的多帧)
+pub fn decode_prefix<T: WireMessage>(bytes: &[u8]) -> Result<(T, &[u8])> {
+    let mut r = WireReader::new(bytes);
+    let value = decode_frame(&mut r)?;
+    Ok((value, r.remaining()))
+}
+
+fn decode_frame<T: WireMessage>(r: &mut WireReader) -> Result<T> {
+    if r.take(4)? != WIRE_MAGIC {
+        return Err(PsyGuardError::SerializationError("线格式魔数不匹配".to_string()));
+    }
+    let version = r.get_u8()?;
+    if version != WIRE_VERSION {
+        return Err(PsyGuardError::SerializationError(format!(
+            "不支持的线格式版本 {}",
+            version
+        )));
+    }
+    let kind = r.get_u8()?;
+    if kind != T::KIND {
+        return Err(PsyGuardError::SerializationError(format!(
+            "帧类型 {} 与预期 {} 不符",
+            kind,
+            T::KIND
+        )));
+    }
+
+    T::decode_body(r)
+}
+
+/// 帧的内容哈希 (SHA-256)
+pub fn wire_hash<T: WireMessage>(value: &T) -> Hash {
+    let result = Sha256::digest(encode(value));
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&result);
+    hash
+}
+
+/// 线格式写入器
+#[derive(Debug, Default)]
+pub struct WireWriter {
+    buf: Vec<u8>,
+}
+
+impl WireWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn put_u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    pub fn put_bool(&mut self, v: bool) {
+        self.put_u8(v as u8);
+    }
+
+    pub fn put_u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn put_u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn put_hash(&mut self, v: &Hash) {
+        self.buf.extend_from_slice(v);
+    }
+
+    pub fn put_bytes(&mut self, v: &[u8]) {
+        self.put_len(v.len());
+        self.buf.extend_from_slice(v);
+    }
+
+    pub fn put_str(&mut self, v: &str) {
+        self.put_bytes(v.as_bytes());
+    }
+
+    pub fn put_len(&mut self, len: usize) {
+        self.put_u32(u32::try_from(len).expect("线格式长度超过 u32"));
+    }
+}
+
+/// 线格式读取器
+#[derive(Debug)]
+pub struct WireReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> WireReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or_else(|| PsyGuardError::SerializationError(format!(
+                "线格式数据截断: 偏移 {} 处需要 {} 字节",
+                self.pos, len
+            )))?;
+        let slice = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    pub fn get_u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn get_bool(&mut self) -> Result<bool> {
+        match self.get_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            v => Err(PsyGuardError::SerializationError(format!("无效的布尔值 {}", v))),
+        }
+    }
+
+    pub fn get_u32(&mut self) -> Result<u32> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn get_u64(&mut self) -> Result<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn get_hash(&mut self) -> Result<Hash> {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(self.take(32)?);
+        Ok(hash)
+    }
+
+    pub fn get_bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.get_len()?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    pub fn get_str(&mut self) -> Result<String> {
+        String::from_utf8(self.get_bytes()?)
+            .map_err(|e| PsyGuardError::SerializationError(format!("无效的 UTF-8: {}", e)))
+    }
+
+    /// 读取长度前缀，并确保剩余数据足以容纳 (防止恶意长度导致大量分配)
+    pub fn get_len(&mut self) -> Result<usize> {
+        let len = self.get_u32()? as usize;
+        if len > self.buf.len() - self.pos {
+            return Err(PsyGuardError::SerializationError(format!(
+                "长度前缀 {} 超出剩余数据",
+                len
+            )));
+        }
+        Ok(len)
+    }
+
+    /// 尚未读取的数据
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.buf[self.pos..]
+    }
+
+    /// 确认已读完全部数据
+    pub fn finish(&self) -> Result<()> {
+        if self.pos != self.buf.len() {
+            return Err(PsyGuardError::SerializationError(format!(
+                "线格式帧末尾有 {} 字节多余数据",
+                self.buf.len() - self.pos
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn put_debts(w: &mut WireWriter, debts: &[(ContractId, u64)]) {
+    w.put_len(debts.len());
+    for (contract_id, amount) in debts {
+        w.put_str(&contract_id.0);
+        w.put_u64(*amount);
+    }
+}
+
+fn get_debts(r: &mut WireReader) -> Result<Vec<(ContractId, u64)>> {
+    let len = r.get_len()?;
+    let mut debts = Vec::with_capacity(len);
+    for _ in 0..len {
+        debts.push((ContractId(r.get_str()?), r.get_u64()?));
+    }
+    Ok(debts)
+}
+
+fn put_header(w: &mut WireWriter, header: &UpsHeader) {
+    w.put_str(&header.user_id.0);
+    w.put_hash(&header.checkpoint_ref.chkp_root);
+    w.put_u64(header.checkpoint_ref.block_number);
+    w.put_hash(&header.user_leaf_ctx.uleaf_hash);
+    w.put_hash(&header.user_leaf_ctx.ucon_root);
+    w.put_hash(&header.user_leaf_ctx.public_key_hash);
+    w.put_u64(header.user_leaf_ctx.balance);
+    w.put_u64(header.user_leaf_ctx.nonce);
+    w.put_str(&header.session_id);
+}
+
+fn get_header(r: &mut WireReader) -> Result<UpsHeader> {
+    Ok(UpsHeader {
+        user_id: UserId(r.get_str()?),
+        checkpoint_ref: CheckpointRef {
+            chkp_root: r.get_hash()?,
+            block_number: r.get_u64()?,
+        },
+        user_leaf_ctx: UserLeafCtx {
+            uleaf_hash: r.get_hash()?,
+            ucon_root: r.get_hash()?,
+            public_key_hash: r.get_hash()?,
+            balance: r.get_u64()?,
+            nonce: r.get_u64()?,
+        },
+        session_id: r.get_str()?,
+    })
+}
+
+fn put_signature(w: &mut WireWriter, sig: &SignatureProof) {
+    w.put_bytes(&sig.proof_data);
+    w.put_hash(&sig.public_key_hash);
+    w.put_hash(&sig.message_hash);
+    w.put_len(sig.policy_satisfied.len());
+    for item in &sig.policy_satisfied {
+        w.put_str(item);
+    }
+    match &sig.second_factor {
+        Some(attestation) => {
+            w.put_bool(true);
+            put_second_factor(w, attestation);
+        }
+        None => w.put_bool(false),
+    }
+}
+
+/// 第二因素凭证编码 (也用于计算其承诺)
+pub(crate) fn put_second_factor(w: &mut WireWriter, attestation: &SecondFactorAttestation) {
+    match attestation {
+        SecondFactorAttestation::Totp { credential_id, time_step, session_binding } => {
+            w.put_u8(0);
+            w.put_hash(credential_id);
+            w.put_u64(*time_step);
+            w.put_hash(session_binding);
+        }
+        SecondFactorAttestation::Challenge { public_key, signature, session_binding } => {
+            w.put_u8(1);
+            w.put_hash(public_key);
+            w.put_bytes(signature);
+            w.put_hash(session_binding);
+        }
+    }
+}
+
+fn get_second_factor(r: &mut WireReader) -> Result<SecondFactorAttestation> {
+    match r.get_u8()? {
+        0 => Ok(SecondFactorAttestation::Totp {
+            credential_id: r.get_hash()?,
+            time_step: r.get_u64()?,
+            session_binding: r.get_hash()?,
+        }),
+        1 => Ok(SecondFactorAttestation::Challenge {
+            public_key: r.get_hash()?,
+            signature: r.get_bytes()?,
+            session_binding: r.get_hash()?,
+        }),
+        tag => Err(PsyGuardError::SerializationError(format!("未知的第二因素类型 {}", tag))),
+    }
+}
+
+fn get_signature(r: &mut WireReader) -> Result<SignatureProof> {
+    let proof_data = r.get_bytes()?;
+    let public_key_hash = r.get_hash()?;
+    let message_hash = r.get_hash()?;
+    let len = r.get_len()?;
+    let mut policy_satisfied = Vec::with_capacity(len);
+    for _ in 0..len {
+        policy_satisfied.push(r.get_str()?);
+    }
+    let second_factor = if r.get_bool()? {
+        Some(get_second_factor(r)?)
+    } else {
+        None
+    };
+
+    Ok(SignatureProof {
+        proof_data,
+        public_key_hash,
+        message_hash,
+        policy_satisfied,
+        second_factor,
+    })
+}
+
+impl WireMessage for UpsStepProof {
+    const KIND: u8 = 0x01;
+
+    fn encode_body(&self, w: &mut WireWriter) {
+        w.put_u32(self.step_number);
+        w.put_bytes(&self.accumulated_proof);
+        w.put_hash(&self.current_ucon_root);
+        put_debts(w, &self.current_debts);
+    }
+
+    fn decode_body(r: &mut WireReader) -> Result<Self> {
+        Ok(Self {
+            step_number: r.get_u32()?,
+            accumulated_proof: r.get_bytes()?,
+            current_ucon_root: r.get_hash()?,
+            current_debts: get_debts(r)?,
+        })
+    }
+}
+
+impl WireMessage for EndCapProof {
+    const KIND: u8 = 0x02;
+
+    fn encode_body(&self, w: &mut WireWriter) {
+        put_header(w, &self.ups_header);
+        self.final_step.encode_body(w);
+        put_signature(w, &self.signature_proof);
+        w.put_u64(self.timestamp);
+    }
+
+    fn decode_body(r: &mut WireReader) -> Result<Self> {
+        Ok(Self {
+            ups_header: get_header(r)?,
+            final_step: UpsStepProof::decode_body(r)?,
+            signature_proof: get_signature(r)?,
+            timestamp: r.get_u64()?,
+        })
+    }
+}
+
+impl WireMessage for CstateDelta {
+    const KIND: u8 = 0x03;
+
+    fn encode_body(&self, w: &mut WireWriter) {
+        w.put_str(&self.contract_id.0);
+        w.put_len(self.modified_slots.len());
+        for (slot, value) in &self.modified_slots {
+            w.put_u64(*slot);
+            w.put_bytes(value);
+        }
+    }
+
+    fn decode_body(r: &mut WireReader) -> Result<Self> {
+        let contract_id = ContractId(r.get_str()?);
+        let len = r.get_len()?;
+        let mut modified_slots = Vec::with_capacity(len);
+        for _ in 0..len {
+            modified_slots.push((r.get_u64()?, r.get_bytes()?));
+        }
+
+        Ok(Self {
+            contract_id,
+            modified_slots,
+        })
+    }
+}
+
+/// 一次提交附带的全部状态变更 (保持顺序)
+impl WireMessage for Vec<CstateDelta> {
+    const KIND: u8 = 0x04;
+
+    fn encode_body(&self, w: &mut WireWriter) {
+        w.put_len(self.len());
+        for delta in self {
+            delta.encode_body(w);
+        }
+    }
+
+    fn decode_body(r: &mut WireReader) -> Result<Self> {
+        let len = r.get_len()?;
+        let mut deltas = Vec::with_capacity(len);
+        for _ in 0..len {
+            deltas.push(CstateDelta::decode_body(r)?);
+        }
+        Ok(deltas)
+    }
+}
+
+impl WireMessage for SubmitReceipt {
+    const KIND: u8 = 0x05;
+
+    fn encode_body(&self, w: &mut WireWriter) {
+        w.put_str(&self.receipt_id);
+        w.put_u64(self.timestamp);
+        match &self.guta_path {
+            Some(path) => {
+                w.put_bool(true);
+                w.put_str(&path.realm_segment);
+                w.put_str(&path.coordinator_segment);
+                w.put_hash(&path.global_root);
+                w.put_u32(path.nca_count);
+                w.put_str(&path.proof_summary);
+                w.put_u64(path.height);
+            }
+            None => w.put_bool(false),
+        }
+    }
+
+    fn decode_body(r: &mut WireReader) -> Result<Self> {
+        let receipt_id = r.get_str()?;
+        let timestamp = r.get_u64()?;
+        let guta_path = if r.get_bool()? {
+            Some(GutaPath {
+                realm_segment: r.get_str()?,
+                coordinator_segment: r.get_str()?,
+                global_root: r.get_hash()?,
+                nca_count: r.get_u32()?,
+                proof_summary: r.get_str()?,
+                height: r.get_u64()?,
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            receipt_id,
+            timestamp,
+            guta_path,
+        })
+    }
+}
+
+/// PARTH 收件箱记录 (写入 CSTATE 槽位)
+impl WireMessage for ParthTransfer {
+    const KIND: u8 = 0x06;
+
+    fn encode_body(&self, w: &mut WireWriter) {
+        w.put_str(&self.from.0);
+        w.put_str(&self.to.0);
+        w.put_u64(self.amount);
+        w.put_u64(self.timestamp);
+    }
+
+    fn decode_body(r: &mut WireReader) -> Result<Self> {
+        Ok(Self {
+            from: UserId(r.get_str()?),
+            to: UserId(r.get_str()?),
+            amount: r.get_u64()?,
+            timestamp: r.get_u64()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_endcap() -> EndCapProof {
+        EndCapProof {
+            ups_header: UpsHeader {
+                user_id: UserId("alice".to_string()),
+                checkpoint_ref: CheckpointRef { chkp_root: [1u8; 32], block_number: 7 },
+                user_leaf_ctx: UserLeafCtx {
+                    uleaf_hash: [2u8; 32],
+                    ucon_root: [3u8; 32],
+                    public_key_hash: [10u8; 32],
+                    balance: 1000,
+                    nonce: 4,
+                },
+                session_id: "ups_1".to_string(),
+            },
+            final_step: UpsStepProof {
+                step_number: 2,
+                accumulated_proof: vec![5, 6, 7],
+                current_ucon_root: [8u8; 32],
+                current_debts: vec![(ContractId("token".to_string()), 10)],
+            },
+            signature_proof: SignatureProof {
+                proof_data: vec![9],
+                public_key_hash: [10u8; 32],
+                message_hash: [11u8; 32],
+                policy_satisfied: vec!["daily_limit_checked".to_string()],
+                second_factor: Some(SecondFactorAttestation::Totp {
+                    credential_id: [12u8; 32],
+                    time_step: 56_666_666,
+                    session_binding: [13u8; 32],
+                }),
+            },
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_endcap_round_trip_and_hash() {
+        let endcap = sample_endcap();
+        let bytes = encode(&endcap);
+        assert_eq!(&bytes[..4], &WIRE_MAGIC);
+        assert_eq!(bytes[4], WIRE_VERSION);
+
+        let decoded: EndCapProof = decode(&bytes).unwrap();
+        assert_eq!(encode(&decoded), bytes);
+        assert_eq!(wire_hash(&decoded), wire_hash(&endcap));
+        assert_eq!(decoded.signature_proof.second_factor, endcap.signature_proof.second_factor);
+
+        let mut changed = endcap;
+        changed.final_step.step_number += 1;
+        assert_ne!(wire_hash(&changed), wire_hash(&decoded));
+    }
+
+    #[test]
+    fn test_deltas_and_receipt_round_trip() {
+        let deltas = vec![
+            CstateDelta {
+                contract_id: ContractId("token".to_string()),
+                modified_slots: vec![(0, vec![1, 2]), (5, vec![])],
+            },
+            CstateDelta {
+                contract_id: ContractId("nft".to_string()),
+                modified_slots: vec![],
+            },
+        ];
+        let decoded: Vec<CstateDelta> = decode(&encode(&deltas)).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].modified_slots, deltas[0].modified_slots);
+
+        // 依次拼接的帧
+        let mut frames = encode(&sample_endcap());
+        frames.extend_from_slice(&encode(&deltas));
+        let (endcap, rest) = decode_prefix::<EndCapProof>(&frames).unwrap();
+        assert_eq!(encode(&endcap), encode(&sample_endcap()));
+        assert_eq!(decode::<Vec<CstateDelta>>(rest).unwrap().len(), 2);
+
+        let receipt = SubmitReceipt {
+            receipt_id: "receipt_1".to_string(),
+            timestamp: 42,
+            guta_path: Some(GutaPath {
+                realm_segment: "realm_0".to_string(),
+                coordinator_segment: "coordinator".to_string(),
+                global_root: [1u8; 32],
+                nca_count: 3,
+                proof_summary: "ok".to_string(),
+                height: 8,
+            }),
+        };
+        let bytes = encode(&receipt);
+        let decoded: SubmitReceipt = decode(&bytes).unwrap();
+        assert_eq!(encode(&decoded), bytes);
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_frames() {
+        let bytes = encode(&sample_endcap());
+
+        // 类型不符
+        assert!(decode::<SubmitReceipt>(&bytes).is_err());
+
+        // 截断
+        assert!(decode::<EndCapProof>(&bytes[..bytes.len() - 1]).is_err());
+
+        // 多余字节
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert!(decode::<EndCapProof>(&extended).is_err());
+
+        // 版本不符
+        let mut future = bytes;
+        future[4] = WIRE_VERSION + 1;
+        assert!(decode::<EndCapProof>(&future).is_err());
+    }
+}
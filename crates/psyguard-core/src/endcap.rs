@@ -0,0 +1,256 @@
+//! End Cap 校验
+//!
+//! 在转发给 Realm 之前检查 End Cap 与随附的状态变更，拒绝明显无效的提交。
+//! 参考: 《5-Local Proving (UPS).md》- End Cap 终结与提交
+//! 参考: 《4_Global User Tree Aggregation (GUTA).md》- Realm 接收 End Cap 前的校验
+
+use crate::types::*;
+use crate::traits::Prover;
+use crate::error::{PsyGuardError, Result};
+use crate::message::SessionMessage;
+use crate::sdkey::{SdkeyPolicyValidator, REQUIRE_2FA_CONSTRAINT};
+
+/// 校验 End Cap 时的外部预期
+///
+/// 2FA 要求须取自与 SDKey 公钥一致的策略 (见 `for_policy`)，不能由提交者自报。
+#[derive(Debug, Clone)]
+pub struct EndCapExpectations {
+    /// 提交所针对的 finalized checkpoint
+    pub checkpoint: CheckpointRef,
+    /// 用户叶中登记的 SDKey 公钥哈希 (须取自已校验的链上状态，而非提交者)
+    pub sdkey_public_key_hash: Hash,
+    /// 策略要求 2FA
+    pub require_2fa: bool,
+    /// 策略登记的第二因素凭据标识 (None 表示接受任一已验证因素)
+    pub second_factor_key: Option<Hash>,
+}
+
+impl EndCapExpectations {
+    /// 按 SDKey 策略构造预期
+    ///
+    /// 公钥哈希由签名电路 verifier data 与策略公共输入导出，2FA 要求与登记凭据
+    /// 因此和公钥绑定；调用方仍须核对公钥哈希与用户叶一致。
+    pub fn for_policy(checkpoint: CheckpointRef, verifier_data: &[u8], policy: &SdkeyPolicy) -> Self {
+        Self {
+            checkpoint,
+            sdkey_public_key_hash: SdkeyPolicyValidator::compute_public_key_hash(verifier_data, policy),
+            require_2fa: policy.require_2fa,
+            second_factor_key: policy.second_factor_key,
+        }
+    }
+}
+
+/// 校验 End Cap 及其状态变更
+///
+/// 1. 头部与 checkpoint 一致
+/// 2. 步骤数与状态变更数一致
+/// 3. 签名消息绑定到本会话和状态变更；声称满足 2FA 的签名须携带绑定本会话的第二因素；
+///    策略要求 2FA 时必须声称并携带，且凭据与登记的 `second_factor_key` 一致
+/// 4. 签名公钥与声明的 SDKey 一致
+/// 5. 由证明后端校验密码学部分
+///
+/// TOTP 凭证只记录凭据标识与时间步，密钥只在登记方，第三方无法复验验证码，
+/// 其可信度等同于签名者本身；由中继等第三方校验时，策略应登记挑战-应答凭据，
+/// 其签名在此处公开复验。
+pub fn verify(
+    endcap: &EndCapProof,
+    state_deltas: &[CstateDelta],
+    expected: &EndCapExpectations,
+    prover: &dyn Prover,
+) -> Result<()> {
+    // 1. 头部 / checkpoint
+    let header = &endcap.ups_header;
+    if header.user_id.0.is_empty() {
+        return Err(PsyGuardError::EndCapVerificationFailed("用户 ID 为空".to_string()));
+    }
+    if header.checkpoint_ref.chkp_root != expected.checkpoint.chkp_root
+        || header.checkpoint_ref.block_number != expected.checkpoint.block_number
+    {
+        return Err(PsyGuardError::EndCapVerificationFailed(format!(
+            "checkpoint 不一致: End Cap 绑定区块 {}，预期区块 {}",
+            header.checkpoint_ref.block_number, expected.checkpoint.block_number
+        )));
+    }
+    if endcap.final_step.step_number == 0
+        && endcap.final_step.current_ucon_root != header.user_leaf_ctx.ucon_root
+    {
+        return Err(PsyGuardError::EndCapVerificationFailed(
+            "空会话不能改变 UCON 根".to_string(),
+        ));
+    }
+
+    // 2. 步骤数 / 状态变更
+    if endcap.final_step.step_number as usize != state_deltas.len() {
+        return Err(PsyGuardError::EndCapVerificationFailed(format!(
+            "步骤数 {} 与状态变更数 {} 不一致",
+            endcap.final_step.step_number,
+            state_deltas.len()
+        )));
+    }
+
+    // 3. 签名消息
+    if !SessionMessage::verify_endcap(endcap, state_deltas)? {
+        return Err(PsyGuardError::EndCapVerificationFailed(
+            "签名消息未绑定本会话".to_string(),
+        ));
+    }
+    let claims_2fa = endcap.signature_proof.policy_satisfied.iter().any(|c| c == REQUIRE_2FA_CONSTRAINT);
+    if claims_2fa && endcap.signature_proof.second_factor.is_none() {
+        return Err(PsyGuardError::EndCapVerificationFailed(
+            "签名声称满足 2FA，但未携带第二因素".to_string(),
+        ));
+    }
+    if expected.require_2fa && !claims_2fa {
+        return Err(PsyGuardError::EndCapVerificationFailed(
+            "策略要求 2FA，但签名未声称满足".to_string(),
+        ));
+    }
+    if let (Some(key), Some(attestation)) =
+        (&expected.second_factor_key, &endcap.signature_proof.second_factor)
+    {
+        if attestation.credential_id() != *key {
+            return Err(PsyGuardError::EndCapVerificationFailed(format!(
+                "第二因素凭据 {} 不是策略登记的凭据",
+                hex::encode(attestation.credential_id())
+            )));
+        }
+    }
+
+    // 4. SDKey 公钥
+    if endcap.signature_proof.public_key_hash != expected.sdkey_public_key_hash {
+        return Err(PsyGuardError::EndCapVerificationFailed(format!(
+            "SDKey 公钥不匹配: {}",
+            hex::encode(endcap.signature_proof.public_key_hash)
+        )));
+    }
+
+    // 5. 密码学校验
+    if !prover.verify_endcap(endcap)? {
+        return Err(PsyGuardError::EndCapVerificationFailed(
+            "End Cap 证明校验未通过".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 只实现校验的证明器
+    struct StubVerifier {
+        accept: bool,
+    }
+
+    impl Prover for StubVerifier {
+        fn prove_cfc(&self, _witness: &CfcWitness) -> Result<(CfcProof, TxEndCtx)> {
+            Err(PsyGuardError::InternalError("未实现".to_string()))
+        }
+
+        fn ups_integrate_step(&self, _witness: &UpsStepWitness) -> Result<UpsStepProof> {
+            Err(PsyGuardError::InternalError("未实现".to_string()))
+        }
+
+        fn finalize_endcap(&self, _header: &UpsHeader, _last_step: &UpsStepProof,
+            _sdkey_sig: &SignatureProof) -> Result<EndCapProof> {
+            Err(PsyGuardError::InternalError("未实现".to_string()))
+        }
+
+        fn sign_with_sdkey(&self, _message: &[u8], _policy: &SdkeyPolicy) -> Result<SignatureProof> {
+            Err(PsyGuardError::InternalError("未实现".to_string()))
+        }
+
+        fn verify_endcap(&self, _endcap: &EndCapProof) -> Result<bool> {
+            Ok(self.accept)
+        }
+    }
+
+    fn checkpoint() -> CheckpointRef {
+        CheckpointRef { chkp_root: [1u8; 32], block_number: 7 }
+    }
+
+    fn sample(state_deltas: &[CstateDelta]) -> EndCapProof {
+        let ups_header = UpsHeader {
+            user_id: UserId("alice".to_string()),
+            checkpoint_ref: checkpoint(),
+            user_leaf_ctx: UserLeafCtx {
+                uleaf_hash: [0u8; 32],
+                ucon_root: [0u8; 32],
+                public_key_hash: [0u8; 32],
+                balance: 1000,
+                nonce: 0,
+            },
+            session_id: "ups_test".to_string(),
+        };
+        let final_step = UpsStepProof {
+            step_number: state_deltas.len() as u32,
+            accumulated_proof: vec![1],
+            current_ucon_root: [4u8; 32],
+            current_debts: vec![],
+        };
+        let message_hash = SessionMessage::new(&ups_header, &final_step, state_deltas).hash();
+
+        EndCapProof {
+            ups_header,
+            final_step,
+            signature_proof: SignatureProof {
+                proof_data: vec![1],
+                public_key_hash: [9u8; 32],
+                message_hash,
+                policy_satisfied: vec![],
+                second_factor: None,
+            },
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_verify_endcap() {
+        let deltas = vec![CstateDelta {
+            contract_id: ContractId("token".to_string()),
+            modified_slots: vec![(0, vec![1])],
+        }];
+        let endcap = sample(&deltas);
+        let expected = EndCapExpectations {
+            checkpoint: checkpoint(),
+            sdkey_public_key_hash: [9u8; 32],
+            require_2fa: false,
+            second_factor_key: None,
+        };
+        let prover = StubVerifier { accept: true };
+
+        assert!(verify(&endcap, &deltas, &expected, &prover).is_ok());
+
+        // 状态变更数与步骤数不符
+        assert!(verify(&endcap, &[], &expected, &prover).is_err());
+
+        // 过期 checkpoint
+        let stale = EndCapExpectations {
+            checkpoint: CheckpointRef { chkp_root: [1u8; 32], block_number: 8 },
+            ..expected.clone()
+        };
+        assert!(verify(&endcap, &deltas, &stale, &prover).is_err());
+
+        // 其他 SDKey
+        let other_key = EndCapExpectations {
+            sdkey_public_key_hash: [8u8; 32],
+            ..expected.clone()
+        };
+        assert!(verify(&endcap, &deltas, &other_key, &prover).is_err());
+
+        // 策略要求 2FA，签名未声称
+        let requires_2fa = EndCapExpectations { require_2fa: true, ..expected.clone() };
+        assert!(matches!(
+            verify(&endcap, &deltas, &requires_2fa, &prover),
+            Err(PsyGuardError::EndCapVerificationFailed(_))
+        ));
+
+        // 证明后端拒绝
+        let rejecting = StubVerifier { accept: false };
+        assert!(matches!(
+            verify(&endcap, &deltas, &expected, &rejecting),
+            Err(PsyGuardError::EndCapVerificationFailed(_))
+        ));
+    }
+}
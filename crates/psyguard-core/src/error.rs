@@ -5,6 +5,18 @@ pub enum PsyGuardError {
     #[error("CFT 校验失败: 函数指纹 {0} 不在白名单中")]
     CftVerificationFailed(String),
 
+    #[error("函数指纹格式无效: {0}")]
+    InvalidFingerprint(String),
+
+    #[error("GCON 合约叶校验失败: {0}")]
+    GconVerificationFailed(String),
+
+    #[error("Merkle 证明无效: {0}")]
+    MerkleProofInvalid(String),
+
+    #[error("End Cap 校验失败: {0}")]
+    EndCapVerificationFailed(String),
+
     #[error("UPS 会话错误: {0}")]
     UpsSessionError(String),
 
@@ -17,12 +29,18 @@ pub enum PsyGuardError {
     #[error("SDKey 策略违规: {0}")]
     SdkeyPolicyViolation(String),
 
+    #[error("第二因素验证失败: {0}")]
+    SecondFactorFailed(String),
+
     #[error("网络错误: {0}")]
     NetworkError(String),
 
     #[error("序列化错误: {0}")]
     SerializationError(String),
 
+    #[error("输入无效: {0}")]
+    InvalidInput(String),
+
     #[error("未找到: {0}")]
     NotFound(String),
 
@@ -0,0 +1,414 @@
+//! GUTA 聚合本地模拟
+//!
+//! 在同一 CHKP 上下文中把多个 End Cap 的新用户叶写入 GUSR，
+//! 先在各 Realm 段内、再在 Coordinator 层按最近公共祖先 (NCA) 合并，
+//! 为每个用户产出带真实分段根与 NCA 计数的 GutaPath。
+//! 参考: 《4_Global User Tree Aggregation (GUTA).md》
+
+use crate::types::*;
+use crate::error::{PsyGuardError, Result};
+use crate::hash::HashAlgorithm;
+use crate::smt::SparseMerkleTree;
+use sha2::{Sha256, Digest};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// 用户叶哈希域分隔标签
+const ULEAF_DOMAIN: &[u8] = b"psyguard/uleaf";
+
+/// GUTA 模拟参数
+#[derive(Debug, Clone)]
+pub struct GutaConfig {
+    /// GUSR 树高
+    pub gusr_height: u32,
+    /// 每个 Realm 负责的子树高度 (Realm 数 = 2^(gusr_height - realm_height))
+    pub realm_height: u32,
+    /// GUSR 与用户叶使用的哈希
+    pub hash_algorithm: HashAlgorithm,
+}
+
+impl Default for GutaConfig {
+    fn default() -> Self {
+        Self {
+            gusr_height: 32,
+            realm_height: 24,
+            hash_algorithm: HashAlgorithm::default(),
+        }
+    }
+}
+
+impl GutaConfig {
+    /// 用户在 GUSR 中的叶索引
+    pub fn user_index(&self, user_id: &UserId) -> u64 {
+        let digest = Sha256::digest(user_id.0.as_bytes());
+        let mut index = [0u8; 8];
+        index.copy_from_slice(&digest[..8]);
+        let index = u64::from_be_bytes(index);
+
+        if self.gusr_height >= 64 {
+            index
+        } else {
+            index & ((1u64 << self.gusr_height) - 1)
+        }
+    }
+
+    /// ULEAF 哈希
+    /// 参考: 《7-Psy Jargon.md》- ULEAF 含公钥承诺、余额、nonce、最近同步的 checkpoint 与 UCON 根
+    pub fn user_leaf_hash(
+        &self,
+        public_key_hash: &Hash,
+        balance: u64,
+        nonce: u64,
+        checkpoint_id: u64,
+        ucon_root: &Hash,
+    ) -> Hash {
+        let mut preimage = Vec::with_capacity(ULEAF_DOMAIN.len() + 88);
+        preimage.extend_from_slice(ULEAF_DOMAIN);
+        preimage.extend_from_slice(public_key_hash);
+        preimage.extend_from_slice(&balance.to_le_bytes());
+        preimage.extend_from_slice(&nonce.to_le_bytes());
+        preimage.extend_from_slice(&checkpoint_id.to_le_bytes());
+        preimage.extend_from_slice(ucon_root);
+        self.hash_algorithm.hasher().hash_bytes(&preimage)
+    }
+}
+
+/// 单个 Realm 的聚合结果
+#[derive(Debug, Clone)]
+pub struct RealmAggregate {
+    pub realm_id: u64,
+    pub old_root: Hash,
+    pub new_root: Hash,
+    pub user_count: u32,
+    pub nca_count: u32,
+}
+
+/// 一个区块的 GUTA 聚合结果
+#[derive(Debug, Clone)]
+pub struct GutaBlock {
+    pub checkpoint: CheckpointRef,
+    pub old_gusr_root: Hash,
+    pub new_gusr_root: Hash,
+    pub realms: Vec<RealmAggregate>,
+    /// Coordinator 层 (Realm 根之上) 的 NCA 合并次数
+    pub coordinator_nca_count: u32,
+    /// 每个用户叶的 GUSR 过渡 (按叶索引排序)
+    pub user_deltas: Vec<(UserId, DeltaMerkleProof)>,
+    /// 每个用户的聚合路径
+    pub paths: HashMap<UserId, GutaPath>,
+}
+
+/// GUTA 聚合模拟器，持有当前 GUSR
+pub struct GutaSimulator {
+    config: GutaConfig,
+    gusr: SparseMerkleTree,
+}
+
+impl GutaSimulator {
+    pub fn new(config: GutaConfig) -> Result<Self> {
+        if config.realm_height == 0 || config.realm_height > config.gusr_height {
+            return Err(PsyGuardError::InvalidInput(format!(
+                "Realm 子树高度 {} 必须在 1..={} 之间",
+                config.realm_height, config.gusr_height
+            )));
+        }
+        let gusr = SparseMerkleTree::with_hasher(config.gusr_height, config.hash_algorithm.hasher())?;
+
+        Ok(Self { config, gusr })
+    }
+
+    /// 当前 GUSR 根
+    pub fn gusr_root(&self) -> Hash {
+        self.gusr.root()
+    }
+
+    /// 当前用户叶
+    pub fn user_leaf(&self, user_id: &UserId) -> Hash {
+        self.gusr.get_leaf(self.user_index(user_id))
+    }
+
+    /// 用户在 GUSR 中的叶索引
+    pub fn user_index(&self, user_id: &UserId) -> u64 {
+        self.config.user_index(user_id)
+    }
+
+    /// 用户所在的 Realm
+    pub fn realm_of(&self, user_id: &UserId) -> u64 {
+        self.user_index(user_id) >> self.config.realm_height
+    }
+
+    /// ULEAF 哈希
+    pub fn user_leaf_hash(
+        &self,
+        public_key_hash: &Hash,
+        balance: u64,
+        nonce: u64,
+        checkpoint_id: u64,
+        ucon_root: &Hash,
+    ) -> Hash {
+        self.config.user_leaf_hash(public_key_hash, balance, nonce, checkpoint_id, ucon_root)
+    }
+
+    /// End Cap 之后的新用户叶
+    ///
+    /// 公钥哈希沿用会话头部中已登记的值，End Cap 不能借此轮换用户密钥。
+    pub fn end_user_leaf(&self, endcap: &EndCapProof) -> Hash {
+        let ctx = &endcap.ups_header.user_leaf_ctx;
+        self.user_leaf_hash(
+            &ctx.public_key_hash,
+            ctx.balance,
+            ctx.nonce + 1,
+            endcap.ups_header.checkpoint_ref.block_number,
+            &endcap.final_step.current_ucon_root,
+        )
+    }
+
+    /// 聚合一个区块的 End Cap
+    ///
+    /// 所有 End Cap 必须绑定同一 checkpoint，每个用户至多一个，
+    /// 且其起始用户叶必须等于当前 GUSR 中的叶。任一检查失败时 GUSR 保持不变。
+    pub fn aggregate(
+        &mut self,
+        checkpoint: &CheckpointRef,
+        endcaps: &[EndCapProof],
+    ) -> Result<GutaBlock> {
+        // 1. 入口校验: 同一 CHKP 上下文、用户唯一、起始叶一致
+        let mut updates: BTreeMap<u64, (UserId, Hash)> = BTreeMap::new();
+        for endcap in endcaps {
+            let header = &endcap.ups_header;
+            if header.checkpoint_ref.chkp_root != checkpoint.chkp_root
+                || header.checkpoint_ref.block_number != checkpoint.block_number
+            {
+                return Err(PsyGuardError::InvalidStateTransition(format!(
+                    "用户 {:?} 的 End Cap 绑定了不同的 checkpoint",
+                    header.user_id
+                )));
+            }
+
+            let index = self.user_index(&header.user_id);
+            if self.gusr.get_leaf(index) != header.user_leaf_ctx.uleaf_hash {
+                return Err(PsyGuardError::InvalidStateTransition(format!(
+                    "用户 {:?} 的起始用户叶与 GUSR 不一致",
+                    header.user_id
+                )));
+            }
+
+            let end_leaf = self.end_user_leaf(endcap);
+            if let Some((existing, _)) = updates.insert(index, (header.user_id.clone(), end_leaf)) {
+                return Err(PsyGuardError::InvalidStateTransition(if existing == header.user_id {
+                    format!("用户 {:?} 在同一区块提交了多个 End Cap", existing)
+                } else {
+                    format!("用户 {:?} 与 {:?} 的 GUSR 索引冲突", existing, header.user_id)
+                }));
+            }
+        }
+
+        let realm_height = self.config.realm_height;
+        let gusr_height = self.config.gusr_height;
+        let old_gusr_root = self.gusr.root();
+        let active_realms: BTreeSet<u64> = updates.keys().map(|i| i >> realm_height).collect();
+        let old_realm_roots: HashMap<u64, Hash> = active_realms
+            .iter()
+            .map(|realm| (*realm, self.gusr.node(realm_height, *realm)))
+            .collect();
+
+        // 2. 写入新用户叶
+        let mut user_deltas = Vec::with_capacity(updates.len());
+        for (index, (user_id, end_leaf)) in &updates {
+            let delta = self.gusr.set_leaf(*index, *end_leaf)?;
+            user_deltas.push((user_id.clone(), delta));
+        }
+
+        // 3. 逐层统计 NCA 合并: 同一父节点下两侧都有活动时发生一次合并
+        let indices: Vec<u64> = updates.keys().copied().collect();
+        let merge_levels = Self::nca_merges(&indices, gusr_height);
+
+        let realms: Vec<RealmAggregate> = active_realms
+            .iter()
+            .map(|realm| RealmAggregate {
+                realm_id: *realm,
+                old_root: old_realm_roots[realm],
+                new_root: self.gusr.node(realm_height, *realm),
+                user_count: indices.iter().filter(|i| *i >> realm_height == *realm).count() as u32,
+                nca_count: merge_levels
+                    .iter()
+                    .filter(|(level, node)| *level <= realm_height && node >> (realm_height - level) == *realm)
+                    .count() as u32,
+            })
+            .collect();
+        let coordinator_nca_count = merge_levels
+            .iter()
+            .filter(|(level, _)| *level > realm_height)
+            .count() as u32;
+
+        // 4. 每个用户的聚合路径
+        let new_gusr_root = self.gusr.root();
+        let new_block = checkpoint.block_number + 1;
+        let mut paths = HashMap::with_capacity(updates.len());
+        for (index, (user_id, _)) in &updates {
+            let realm_id = index >> realm_height;
+            let realm_ncas = (1..=realm_height)
+                .filter(|level| merge_levels.contains(&(*level, Self::ancestor(*index, *level))))
+                .count() as u32;
+            let coordinator_ncas = (realm_height + 1..=gusr_height)
+                .filter(|level| merge_levels.contains(&(*level, Self::ancestor(*index, *level))))
+                .count() as u32;
+            let realm = realms.iter().find(|r| r.realm_id == realm_id).expect("活跃 Realm");
+
+            paths.insert(user_id.clone(), GutaPath {
+                realm_segment: format!("realm_{}:{}", realm_id, hex::encode(realm.new_root)),
+                coordinator_segment: format!(
+                    "coordinator:{}_realms:{}",
+                    realms.len(),
+                    hex::encode(new_gusr_root)
+                ),
+                global_root: new_gusr_root,
+                nca_count: realm_ncas + coordinator_ncas,
+                proof_summary: format!(
+                    "realm {} 内 {} 次 NCA 合并 ({} 个用户)，coordinator 层 {} 次 NCA 合并",
+                    realm_id, realm_ncas, realm.user_count, coordinator_ncas
+                ),
+                height: new_block,
+            });
+        }
+
+        Ok(GutaBlock {
+            checkpoint: checkpoint.clone(),
+            old_gusr_root,
+            new_gusr_root,
+            realms,
+            coordinator_nca_count,
+            user_deltas,
+            paths,
+        })
+    }
+
+    /// 返回发生 NCA 合并的节点 (层, 层内索引)
+    fn nca_merges(indices: &[u64], height: u32) -> BTreeSet<(u32, u64)> {
+        let mut merges = BTreeSet::new();
+        let mut level_nodes: BTreeSet<u64> = indices.iter().copied().collect();
+
+        for level in 1..=height {
+            let mut parents = BTreeSet::new();
+            for node in &level_nodes {
+                let parent = node >> 1;
+                if !parents.insert(parent) {
+                    merges.insert((level, parent));
+                }
+            }
+            level_nodes = parents;
+        }
+
+        merges
+    }
+
+    fn ancestor(index: u64, level: u32) -> u64 {
+        if level >= 64 { 0 } else { index >> level }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{MerkleHasher, Sha256Hasher};
+
+    fn endcap(user: &str, checkpoint: &CheckpointRef) -> EndCapProof {
+        // 每个用户的密钥、余额与 UCON 根各不相同
+        let seed = Sha256Hasher.hash_bytes(user.as_bytes());
+        EndCapProof {
+            ups_header: UpsHeader {
+                user_id: UserId(user.to_string()),
+                checkpoint_ref: checkpoint.clone(),
+                user_leaf_ctx: UserLeafCtx {
+                    uleaf_hash: [0u8; 32],
+                    ucon_root: [0u8; 32],
+                    public_key_hash: seed,
+                    balance: 100 + seed[0] as u64,
+                    nonce: 0,
+                },
+                session_id: format!("ups_{}", user),
+            },
+            final_step: UpsStepProof {
+                step_number: 1,
+                accumulated_proof: vec![1],
+                current_ucon_root: Sha256Hasher.hash_bytes(&seed),
+                current_debts: vec![],
+            },
+            signature_proof: SignatureProof {
+                proof_data: vec![],
+                public_key_hash: [0xee; 32],
+                message_hash: [0u8; 32],
+                policy_satisfied: vec![],
+                second_factor: None,
+            },
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_guta_aggregation_paths() {
+        let config = GutaConfig { gusr_height: 16, realm_height: 13, ..GutaConfig::default() };
+        let mut sim = GutaSimulator::new(config).unwrap();
+        let checkpoint = CheckpointRef { chkp_root: [1u8; 32], block_number: 5 };
+
+        let users: Vec<String> = (0..12).map(|i| format!("user{}", i)).collect();
+        let endcaps: Vec<_> = users.iter().map(|u| endcap(u, &checkpoint)).collect();
+        let block = sim.aggregate(&checkpoint, &endcaps).unwrap();
+
+        assert_eq!(block.new_gusr_root, sim.gusr_root());
+        assert_ne!(block.old_gusr_root, block.new_gusr_root);
+        assert!(SparseMerkleTree::verify_delta_chain(
+            sim.gusr.hasher().as_ref(),
+            16,
+            &block.old_gusr_root,
+            &block.new_gusr_root,
+            &block.user_deltas.iter().map(|(_, d)| d.clone()).collect::<Vec<_>>(),
+        ));
+
+        // n 个活动叶两两合并，总共 n - 1 次 NCA 合并
+        let realm_ncas: u32 = block.realms.iter().map(|r| r.nca_count).sum();
+        assert_eq!(realm_ncas + block.coordinator_nca_count, endcaps.len() as u32 - 1);
+        assert_eq!(block.coordinator_nca_count, block.realms.len() as u32 - 1);
+
+        for (user, endcap) in users.iter().zip(&endcaps) {
+            let user_id = UserId(user.clone());
+            let path = &block.paths[&user_id];
+            assert_eq!(path.global_root, block.new_gusr_root);
+            assert_eq!(path.height, 6);
+            assert!(path.realm_segment.starts_with(&format!("realm_{}:", sim.realm_of(&user_id))));
+            assert_eq!(sim.user_leaf(&user_id), sim.end_user_leaf(endcap));
+
+            // 新叶使用头部登记的公钥哈希，而非签名证明中的
+            let ctx = &endcap.ups_header.user_leaf_ctx;
+            let expected = sim.user_leaf_hash(
+                &ctx.public_key_hash,
+                ctx.balance,
+                ctx.nonce + 1,
+                checkpoint.block_number,
+                &endcap.final_step.current_ucon_root,
+            );
+            assert_eq!(sim.user_leaf(&user_id), expected);
+        }
+
+        let leaves: BTreeSet<Hash> = users.iter().map(|u| sim.user_leaf(&UserId(u.clone()))).collect();
+        assert_eq!(leaves.len(), users.len());
+    }
+
+    #[test]
+    fn test_guta_rejects_inconsistent_endcaps() {
+        let mut sim = GutaSimulator::new(GutaConfig::default()).unwrap();
+        let checkpoint = CheckpointRef { chkp_root: [1u8; 32], block_number: 5 };
+
+        // 不同 checkpoint
+        let other = CheckpointRef { chkp_root: [2u8; 32], block_number: 5 };
+        assert!(sim.aggregate(&checkpoint, &[endcap("alice", &other)]).is_err());
+
+        // 同一用户重复提交
+        let twice = vec![endcap("alice", &checkpoint), endcap("alice", &checkpoint)];
+        assert!(sim.aggregate(&checkpoint, &twice).is_err());
+        assert_eq!(sim.gusr_root(), [0u8; 32]);
+
+        // 起始叶已过期
+        sim.aggregate(&checkpoint, &[endcap("alice", &checkpoint)]).unwrap();
+        assert!(sim.aggregate(&checkpoint, &[endcap("alice", &checkpoint)]).is_err());
+    }
+}
@@ -0,0 +1,313 @@
+//! SDKey 日限额消费账本
+//!
+//! 按用户、按代币合约记录 24 小时滚动窗口内的已花费金额，使日限额跨交易、
+//! 跨 UpsQueue 队列项、跨 UPS 会话累计生效，而不是只比较单笔金额。
+//! 已提交的记录经可插拔的 `SpendStore` 持久化；队列中已授权但尚未提交的金额
+//! 作为预留保存在账本实例内，同样计入限额。每笔预留有独立标识，
+//! 可单独释放 (队列项失败) 或提交 (所在 End Cap 提交成功)。
+//! 时间戳晚于当前时间的记录 (时钟回拨或乱序授权) 同样计入、不会被清理。
+//! 参考: 《7-Psy Jargon.md》- SDKey 策略约束
+
+use crate::types::*;
+use crate::error::{PsyGuardError, Result};
+use crate::sdkey::SdkeyPolicyValidator;
+use crate::policy_expr::{PolicyCounters, TxContext};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// 日限额的滚动窗口 (秒)
+pub const SPEND_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+/// 一笔消费记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpendRecord {
+    pub contract_id: ContractId,
+    pub amount: u64,
+    /// Unix 时间戳 (秒)
+    pub timestamp: u64,
+}
+
+/// 一笔预留的标识 (在所属账本内唯一)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReservationId(u64);
+
+/// 消费记录存储
+pub trait SpendStore: Send + Sync {
+    /// 读取用户的全部记录
+    fn load(&self, user_id: &UserId) -> Result<Vec<SpendRecord>>;
+
+    /// 覆盖写入用户的全部记录
+    fn save(&self, user_id: &UserId, records: &[SpendRecord]) -> Result<()>;
+}
+
+/// 内存存储 (测试与单进程使用)
+#[derive(Default)]
+pub struct MemorySpendStore {
+    records: Mutex<HashMap<UserId, Vec<SpendRecord>>>,
+}
+
+impl MemorySpendStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SpendStore for MemorySpendStore {
+    fn load(&self, user_id: &UserId) -> Result<Vec<SpendRecord>> {
+        Ok(self.records.lock().unwrap().get(user_id).cloned().unwrap_or_default())
+    }
+
+    fn save(&self, user_id: &UserId, records: &[SpendRecord]) -> Result<()> {
+        self.records.lock().unwrap().insert(user_id.clone(), records.to_vec());
+        Ok(())
+    }
+}
+
+/// 用户消费账本
+pub struct SpendLedger {
+    user_id: UserId,
+    store: Arc<dyn SpendStore>,
+    window_secs: u64,
+    /// 已授权、尚未提交的金额
+    reserved: Mutex<Vec<(ReservationId, SpendRecord)>>,
+    next_reservation: AtomicU64,
+}
+
+impl SpendLedger {
+    pub fn new(user_id: UserId, store: Arc<dyn SpendStore>) -> Self {
+        Self {
+            user_id,
+            store,
+            window_secs: SPEND_WINDOW_SECS,
+            reserved: Mutex::new(Vec::new()),
+            next_reservation: AtomicU64::new(0),
+        }
+    }
+
+    /// 自定义滚动窗口长度
+    pub fn with_window(mut self, window_secs: u64) -> Self {
+        self.window_secs = window_secs;
+        self
+    }
+
+    /// 账本所属用户
+    pub fn user_id(&self) -> &UserId {
+        &self.user_id
+    }
+
+    /// 窗口内该合约的已花费金额 (已提交 + 预留)
+    pub fn spent(&self, contract_id: &ContractId, now: u64) -> Result<u64> {
+        let reserved = self.reserved.lock().unwrap();
+        self.spent_with(&reserved, contract_id, now)
+    }
+
+    /// 本笔之前的策略计数器: 会话内已预留的笔数与本合约金额，以及窗口内累计
+    pub fn counters(&self, contract_id: &ContractId, now: u64) -> Result<PolicyCounters> {
+        let reserved = self.reserved.lock().unwrap();
+        self.counters_with(&reserved, contract_id, now)
+    }
+
+    /// 按策略校验本笔消费并预留额度，返回满足的约束
+    ///
+    /// 同一账本上依次授权的队列项彼此累计；违规时不预留。
+    pub fn authorize(&self, policy: &SdkeyPolicy, tx: &TxContext) -> Result<Vec<String>> {
+        self.reserve(policy, tx).map(|(_, satisfied)| satisfied)
+    }
+
+    /// 同 `authorize`，并返回本笔预留的标识
+    ///
+    /// 校验与预留在同一把锁内完成，并发的预留不会一起越过限额。
+    pub fn reserve(&self, policy: &SdkeyPolicy, tx: &TxContext) -> Result<(ReservationId, Vec<String>)> {
+        let mut reserved = self.reserved.lock().unwrap();
+        let counters = self.counters_with(&reserved, tx.contract_id, tx.timestamp)?;
+        let satisfied = SdkeyPolicyValidator::accept(
+            SdkeyPolicyValidator::evaluate_with_counters(policy, tx, counters)?,
+        )?;
+        let id = ReservationId(self.next_reservation.fetch_add(1, Ordering::SeqCst));
+        reserved.push((id, SpendRecord {
+            contract_id: tx.contract_id.clone(),
+            amount: tx.amount,
+            timestamp: tx.timestamp,
+        }));
+        Ok((id, satisfied))
+    }
+
+    /// 将预留写入存储 (End Cap 提交成功后调用)，同时清理窗口外的旧记录
+    pub fn commit(&self, now: u64) -> Result<()> {
+        let mut reserved = self.reserved.lock().unwrap();
+        self.persist(reserved.iter().map(|(_, record)| record.clone()), Some(now))?;
+        reserved.clear();
+        Ok(())
+    }
+
+    /// 只提交一笔预留 (所在 End Cap 提交成功后调用)；已释放或已提交的标识被忽略
+    pub fn commit_reservation(&self, id: ReservationId) -> Result<()> {
+        let mut reserved = self.reserved.lock().unwrap();
+        let Some(position) = reserved.iter().position(|(reservation, _)| *reservation == id) else {
+            return Ok(());
+        };
+        self.persist(std::iter::once(reserved[position].1.clone()), None)?;
+        reserved.remove(position);
+        Ok(())
+    }
+
+    /// 放弃尚未提交的预留 (会话放弃或提交失败)
+    pub fn release(&self) {
+        self.reserved.lock().unwrap().clear();
+    }
+
+    /// 只放弃一笔预留，返回其是否仍在预留中
+    pub fn release_reservation(&self, id: ReservationId) -> bool {
+        let mut reserved = self.reserved.lock().unwrap();
+        let before = reserved.len();
+        reserved.retain(|(reservation, _)| *reservation != id);
+        reserved.len() != before
+    }
+
+    /// 尚未提交的预留
+    pub fn reserved(&self) -> Vec<SpendRecord> {
+        self.reserved.lock().unwrap().iter().map(|(_, record)| record.clone()).collect()
+    }
+
+    fn spent_with(&self, reserved: &[(ReservationId, SpendRecord)], contract_id: &ContractId, now: u64) -> Result<u64> {
+        let committed = self.store.load(&self.user_id)?;
+        committed
+            .iter()
+            .chain(reserved.iter().map(|(_, record)| record))
+            .filter(|record| record.contract_id == *contract_id && self.in_window(record, now))
+            .try_fold(0u64, |total, record| total.checked_add(record.amount))
+            .ok_or_else(|| PsyGuardError::SdkeyPolicyViolation("消费累计溢出".to_string()))
+    }
+
+    fn counters_with(
+        &self,
+        reserved: &[(ReservationId, SpendRecord)],
+        contract_id: &ContractId,
+        now: u64,
+    ) -> Result<PolicyCounters> {
+        Ok(PolicyCounters {
+            session_txs: reserved.len() as u64,
+            session_amount: reserved
+                .iter()
+                .map(|(_, record)| record)
+                .filter(|record| record.contract_id == *contract_id)
+                .fold(0u64, |total, record| total.saturating_add(record.amount)),
+            daily_amount: self.spent_with(reserved, contract_id, now)?,
+        })
+    }
+
+    /// 追加记录；给定 `now` 时同时清理已过期的旧记录 (晚于 `now` 的记录保留)
+    fn persist(&self, new_records: impl Iterator<Item = SpendRecord>, now: Option<u64>) -> Result<()> {
+        let mut records = self.store.load(&self.user_id)?;
+        records.extend(new_records);
+        if let Some(now) = now {
+            records.retain(|record| record.timestamp.saturating_add(self.window_secs) > now);
+        }
+        self.store.save(&self.user_id, &records)
+    }
+
+    /// 记录与 `now` 相距不到一个窗口 (晚于 `now` 的记录同样计入)
+    fn in_window(&self, record: &SpendRecord, now: u64) -> bool {
+        record.timestamp.abs_diff(now) < self.window_secs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdkey::SdkeyPolicyBuilder;
+    use crate::policy_expr::DAILY_LIMIT_RULE;
+
+    fn ledger(store: &Arc<MemorySpendStore>) -> SpendLedger {
+        SpendLedger::new(UserId("alice".to_string()), store.clone())
+    }
+
+    #[test]
+    fn test_split_transfers_hit_daily_limit() {
+        let store = Arc::new(MemorySpendStore::new());
+        let policy = SdkeyPolicyBuilder::new().with_daily_limit(1000).build();
+        let token = ContractId("token".to_string());
+        let nft = ContractId("nft".to_string());
+        let now = 1_700_000_000;
+
+        // 第一个会话: 999 + 1 用满额度，再多 1 即被拒绝；其他合约单独计算
+        let first = ledger(&store);
+        first.authorize(&policy, &TxContext::new(&token, 999, now)).unwrap();
+        first.authorize(&policy, &TxContext::new(&token, 1, now + 1)).unwrap();
+        assert!(matches!(
+            first.authorize(&policy, &TxContext::new(&token, 1, now + 2)),
+            Err(PsyGuardError::SdkeyPolicyViolation(_))
+        ));
+        first.authorize(&policy, &TxContext::new(&nft, 999, now + 2)).unwrap();
+        assert_eq!(first.reserved().len(), 3);
+        first.commit(now + 3).unwrap();
+
+        // 第二个会话读取同一存储
+        let second = ledger(&store);
+        assert_eq!(second.spent(&token, now + 10).unwrap(), 1000);
+        assert!(second.authorize(&policy, &TxContext::new(&token, 1, now + 10)).is_err());
+        let eval = SdkeyPolicyValidator::evaluate(&policy, &TxContext::new(&token, 1, now + 10), Some(&second)).unwrap();
+        assert_eq!(eval.rule(DAILY_LIMIT_RULE).unwrap().outcome, RuleOutcome::Failed);
+
+        // 滚动窗口过后额度恢复，过期记录在提交时清理
+        let later = now + SPEND_WINDOW_SECS + 1;
+        assert_eq!(second.spent(&token, later).unwrap(), 0);
+        second.authorize(&policy, &TxContext::new(&token, 500, later)).unwrap();
+        second.commit(later).unwrap();
+        assert_eq!(store.load(&UserId("alice".to_string())).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_released_reservations_do_not_count() {
+        let store = Arc::new(MemorySpendStore::new());
+        let policy = SdkeyPolicyBuilder::new().with_daily_limit(100).build();
+        let token = ContractId("token".to_string());
+
+        let ledger = ledger(&store);
+        ledger.authorize(&policy, &TxContext::new(&token, 100, 10)).unwrap();
+        assert!(ledger.authorize(&policy, &TxContext::new(&token, 1, 11)).is_err());
+
+        ledger.release();
+        assert_eq!(ledger.spent(&token, 12).unwrap(), 0);
+        ledger.authorize(&policy, &TxContext::new(&token, 100, 12)).unwrap();
+    }
+
+    #[test]
+    fn test_out_of_order_timestamps_share_the_limit() {
+        let store = Arc::new(MemorySpendStore::new());
+        let policy = SdkeyPolicyBuilder::new().with_daily_limit(100).build();
+        let token = ContractId("token".to_string());
+
+        // 先在 t=100 用满额度，再以更早的 t=50 授权也被拒绝
+        let ledger = ledger(&store);
+        ledger.authorize(&policy, &TxContext::new(&token, 100, 100)).unwrap();
+        assert!(ledger.authorize(&policy, &TxContext::new(&token, 1, 50)).is_err());
+
+        // 在更早的时间提交不会清理"未来"的记录
+        ledger.commit(50).unwrap();
+        assert_eq!(store.load(&UserId("alice".to_string())).unwrap().len(), 1);
+        assert_eq!(ledger.spent(&token, 50).unwrap(), 100);
+        assert!(ledger.authorize(&policy, &TxContext::new(&token, 1, 60)).is_err());
+    }
+
+    #[test]
+    fn test_concurrent_reservations_respect_the_limit() {
+        let store = Arc::new(MemorySpendStore::new());
+        let policy = SdkeyPolicyBuilder::new().with_daily_limit(1000).build();
+        let token = ContractId("token".to_string());
+        let ledger = Arc::new(ledger(&store));
+
+        let handles: Vec<_> = (0..16)
+            .map(|_| {
+                let (ledger, policy, token) = (ledger.clone(), policy.clone(), token.clone());
+                std::thread::spawn(move || ledger.authorize(&policy, &TxContext::new(&token, 100, 10)).is_ok())
+            })
+            .collect();
+        let granted = handles.into_iter().map(|handle| handle.join().unwrap()).filter(|granted| *granted).count();
+
+        assert_eq!(granted, 10);
+        assert_eq!(ledger.spent(&token, 10).unwrap(), 1000);
+    }
+}
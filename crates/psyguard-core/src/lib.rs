@@ -13,10 +13,23 @@ pub mod traits;
 pub mod ups;
 pub mod cft;
 pub mod sdkey;
+pub mod policy_expr;
+pub mod policy_inputs;
+pub mod ledger;
+pub mod second_factor;
 pub mod state;
+pub mod smt;
 pub mod error;
 pub mod preview;
 pub mod queue;
+pub mod message;
+pub mod endcap;
+pub mod codec;
+pub mod guta;
+pub mod hash;
+pub mod profile;
+pub mod registry;
+pub mod checkpoint;
 
 pub use types::*;
 pub use traits::*;
@@ -0,0 +1,225 @@
+//! UPS 会话签名消息
+//!
+//! End Cap 中 SDKey 签名所覆盖的会话承诺，采用规范化、带域分隔和版本号的二进制编码，
+//! 防止签名被重放到其他会话。会话终结前验证的第二因素以承诺形式编入消息，
+//! 其凭证又绑定到不含 2FA 的消息哈希，二者互相锁定。
+//! 参考: 《5-Local Proving (UPS).md》- End Cap 终结
+//! 参考: 《7-Psy Jargon.md》- SDKey 签名电路
+
+use crate::types::*;
+use crate::error::Result;
+use sha2::{Sha256, Digest};
+
+/// 会话消息域分隔标签
+pub const SESSION_MESSAGE_DOMAIN: &[u8] = b"psyguard/ups-session-message";
+
+/// 会话消息编码版本
+pub const SESSION_MESSAGE_VERSION: u8 = 2;
+
+/// 状态变更哈希域分隔标签
+pub const STATE_DELTAS_DOMAIN: &[u8] = b"psyguard/cstate-deltas";
+
+/// 会话承诺
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionMessage {
+    pub user_id: UserId,
+    pub chkp_root: Hash,
+    pub block_number: u64,
+    pub start_ucon_root: Hash,
+    pub end_ucon_root: Hash,
+    pub nonce: u64,
+    pub step_count: u32,
+    pub state_deltas_hash: Hash,
+    /// 第二因素凭证承诺 (未验证 2FA 时为 None)
+    pub second_factor: Option<Hash>,
+}
+
+impl SessionMessage {
+    /// 从会话头、最后一步和状态变更构建
+    pub fn new(header: &UpsHeader, last_step: &UpsStepProof, state_deltas: &[CstateDelta]) -> Self {
+        Self {
+            user_id: header.user_id.clone(),
+            chkp_root: header.checkpoint_ref.chkp_root,
+            block_number: header.checkpoint_ref.block_number,
+            start_ucon_root: header.user_leaf_ctx.ucon_root,
+            end_ucon_root: last_step.current_ucon_root,
+            nonce: header.user_leaf_ctx.nonce,
+            step_count: last_step.step_number,
+            state_deltas_hash: Self::hash_state_deltas(state_deltas),
+            second_factor: None,
+        }
+    }
+
+    /// 编入已验证的第二因素
+    pub fn with_second_factor(mut self, attestation: &SecondFactorAttestation) -> Self {
+        self.second_factor = Some(attestation.commitment());
+        self
+    }
+
+    /// 从 End Cap 和提交的状态变更重新计算 (含签名证明携带的第二因素)
+    pub fn from_endcap(endcap: &EndCapProof, state_deltas: &[CstateDelta]) -> Self {
+        let message = Self::new(&endcap.ups_header, &endcap.final_step, state_deltas);
+        match &endcap.signature_proof.second_factor {
+            Some(attestation) => message.with_second_factor(attestation),
+            None => message,
+        }
+    }
+
+    /// 规范化编码
+    ///
+    /// `len(domain) | domain | version | len(user_id) | user_id | chkp_root | block_number |
+    /// start_ucon_root | end_ucon_root | nonce | step_count | state_deltas_hash |
+    /// has_second_factor | [second_factor]`，整数一律小端，长度前缀为 u32。
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(256);
+        put_bytes(&mut out, SESSION_MESSAGE_DOMAIN);
+        out.push(SESSION_MESSAGE_VERSION);
+        put_bytes(&mut out, self.user_id.0.as_bytes());
+        out.extend_from_slice(&self.chkp_root);
+        out.extend_from_slice(&self.block_number.to_le_bytes());
+        out.extend_from_slice(&self.start_ucon_root);
+        out.extend_from_slice(&self.end_ucon_root);
+        out.extend_from_slice(&self.nonce.to_le_bytes());
+        out.extend_from_slice(&self.step_count.to_le_bytes());
+        out.extend_from_slice(&self.state_deltas_hash);
+        match &self.second_factor {
+            Some(commitment) => {
+                out.push(1);
+                out.extend_from_slice(commitment);
+            }
+            None => out.push(0),
+        }
+        out
+    }
+
+    /// 消息哈希 (写入 SignatureProof.message_hash)
+    pub fn hash(&self) -> Hash {
+        Self::hash_bytes(&self.encode())
+    }
+
+    /// 计算已编码消息的哈希
+    pub fn hash_bytes(message: &[u8]) -> Hash {
+        let result = Sha256::digest(message);
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&result);
+        hash
+    }
+
+    /// 状态变更列表的哈希 (保持提交顺序)
+    pub fn hash_state_deltas(state_deltas: &[CstateDelta]) -> Hash {
+        let mut out = Vec::new();
+        put_bytes(&mut out, STATE_DELTAS_DOMAIN);
+        out.extend_from_slice(&(state_deltas.len() as u32).to_le_bytes());
+        for delta in state_deltas {
+            put_bytes(&mut out, delta.contract_id.0.as_bytes());
+            out.extend_from_slice(&(delta.modified_slots.len() as u32).to_le_bytes());
+            for (slot, value) in &delta.modified_slots {
+                out.extend_from_slice(&slot.to_le_bytes());
+                put_bytes(&mut out, value);
+            }
+        }
+        Self::hash_bytes(&out)
+    }
+
+    /// 校验 End Cap 的签名消息是否绑定到该会话及提交的状态变更
+    ///
+    /// 携带第二因素时，其凭证还须绑定到同一会话 (不含 2FA 的消息哈希)。
+    pub fn verify_endcap(endcap: &EndCapProof, state_deltas: &[CstateDelta]) -> Result<bool> {
+        let message = Self::from_endcap(endcap, state_deltas);
+        if endcap.signature_proof.message_hash != message.hash() {
+            return Ok(false);
+        }
+        match &endcap.signature_proof.second_factor {
+            Some(attestation) => {
+                let binding = Self { second_factor: None, ..message }.hash();
+                Ok(attestation.check(&binding).is_ok())
+            }
+            None => Ok(true),
+        }
+    }
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_endcap(state_deltas: &[CstateDelta]) -> EndCapProof {
+        let ups_header = UpsHeader {
+            user_id: UserId("alice".to_string()),
+            checkpoint_ref: CheckpointRef { chkp_root: [1u8; 32], block_number: 7 },
+            user_leaf_ctx: UserLeafCtx {
+                uleaf_hash: [0u8; 32],
+                ucon_root: [2u8; 32],
+                public_key_hash: [0u8; 32],
+                balance: 1000,
+                nonce: 3,
+            },
+            session_id: "ups_test".to_string(),
+        };
+        let final_step = UpsStepProof {
+            step_number: state_deltas.len() as u32,
+            accumulated_proof: vec![],
+            current_ucon_root: [4u8; 32],
+            current_debts: vec![],
+        };
+        let message_hash = SessionMessage::new(&ups_header, &final_step, state_deltas).hash();
+
+        EndCapProof {
+            ups_header,
+            final_step,
+            signature_proof: SignatureProof {
+                proof_data: vec![],
+                public_key_hash: [0u8; 32],
+                message_hash,
+                policy_satisfied: vec![],
+                second_factor: None,
+            },
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_session_message_binds_endcap() {
+        let deltas = vec![CstateDelta {
+            contract_id: ContractId("token".to_string()),
+            modified_slots: vec![(0, vec![1, 2])],
+        }];
+        let endcap = sample_endcap(&deltas);
+        assert!(SessionMessage::verify_endcap(&endcap, &deltas).unwrap());
+
+        // 篡改状态变更
+        let mut tampered = deltas.clone();
+        tampered[0].modified_slots[0].1 = vec![9];
+        assert!(!SessionMessage::verify_endcap(&endcap, &tampered).unwrap());
+
+        // 重放到另一个会话
+        let mut replayed = endcap.clone();
+        replayed.ups_header.user_id = UserId("bob".to_string());
+        assert!(!SessionMessage::verify_endcap(&replayed, &deltas).unwrap());
+
+        let mut advanced = endcap;
+        advanced.ups_header.checkpoint_ref.block_number += 1;
+        assert!(!SessionMessage::verify_endcap(&advanced, &deltas).unwrap());
+    }
+
+    #[test]
+    fn test_session_message_encoding_is_stable() {
+        let endcap = sample_endcap(&[]);
+        let message = SessionMessage::from_endcap(&endcap, &[]);
+        let encoded = message.encode();
+
+        assert_eq!(encoded, message.clone().encode());
+        assert_eq!(&encoded[4..4 + SESSION_MESSAGE_DOMAIN.len()], SESSION_MESSAGE_DOMAIN);
+        assert_eq!(encoded[4 + SESSION_MESSAGE_DOMAIN.len()], SESSION_MESSAGE_VERSION);
+
+        // 长度前缀防止 user_id 与后续字段混淆
+        let mut other = message.clone();
+        other.user_id = UserId("alic".to_string());
+        assert_ne!(other.encode(), encoded);
+    }
+}
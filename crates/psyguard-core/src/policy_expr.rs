@@ -0,0 +1,859 @@
+//! SDKey 策略表达式
+//!
+//! 在固定字段之外，以带类型的小型表达式描述组合规则：AND/OR/NOT/蕴含、
+//! 按函数/合约/收款方的谓词、金额与时间条件，以及会话内计数器。
+//! 策略的固定字段也编译为同一组规则 (见 `SdkeyPolicy::compiled_rules`)，
+//! 求值返回逐条规则的通过/未通过原因。
+//!
+//! 文本形式，每行或以 `;` 分隔一条规则:
+//!
+//! ```text
+//! rule large_transfer: function == "transfer" and amount > 500 and not recipient in ["alice", "bob"] implies 2fa
+//! rule business_hours: function == "approve" implies hour in 9..18 and weekday < 5
+//! rule session_cap: tx_count <= 3
+//! ```
+//!
+//! 优先级从低到高: `implies` (右结合)、`or`、`and`、`not`。JSON 形式即 `PolicyRule` 的 serde 编码。
+//! 参考: 《7-Psy Jargon.md》- SDKey 可编程策略
+
+use crate::types::*;
+use crate::error::{PsyGuardError, Result};
+use crate::second_factor::VerifiedFactor;
+use std::collections::HashSet;
+use std::fmt;
+
+/// 日限额编译出的内置规则名
+pub const DAILY_LIMIT_RULE: &str = "daily_limit";
+
+/// 合约白名单编译出的内置规则名
+pub const TRUSTED_CONTRACTS_RULE: &str = "trusted_contracts";
+
+/// 时间锁编译出的内置规则名
+pub const TIME_LOCK_RULE: &str = "time_lock";
+
+/// 2FA 要求编译出的内置规则名
+pub const REQUIRE_2FA_RULE: &str = "require_2fa";
+
+/// 待求值的交易
+#[derive(Debug, Clone, Copy)]
+pub struct TxContext<'a> {
+    pub contract_id: &'a ContractId,
+    /// 函数名 (未知时为空)
+    pub function: &'a str,
+    pub recipient: Option<&'a str>,
+    pub amount: u64,
+    /// Unix 时间戳 (秒)
+    pub timestamp: u64,
+    pub second_factor: Option<&'a VerifiedFactor>,
+}
+
+impl<'a> TxContext<'a> {
+    pub fn new(contract_id: &'a ContractId, amount: u64, timestamp: u64) -> Self {
+        Self {
+            contract_id,
+            function: "",
+            recipient: None,
+            amount,
+            timestamp,
+            second_factor: None,
+        }
+    }
+
+    pub fn with_function(mut self, function: &'a str) -> Self {
+        self.function = function;
+        self
+    }
+
+    pub fn with_recipient(mut self, recipient: Option<&'a str>) -> Self {
+        self.recipient = recipient;
+        self
+    }
+
+    pub fn with_second_factor(mut self, second_factor: Option<&'a VerifiedFactor>) -> Self {
+        self.second_factor = second_factor;
+        self
+    }
+}
+
+/// 本笔之前的计数器 (由 `SpendLedger::counters` 给出，无账本时为零)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PolicyCounters {
+    /// 会话内已授权的交易数
+    pub session_txs: u64,
+    /// 会话内本合约已授权的金额
+    pub session_amount: u64,
+    /// 24 小时窗口内本合约已花费的金额 (含会话内已授权)
+    pub daily_amount: u64,
+}
+
+/// 按规则逐条求值
+///
+/// 不成立的规则若在验证第二因素后成立，结果为 `RequiresSecondFactor`。
+pub fn evaluate(rules: &[PolicyRule], tx: &TxContext, counters: PolicyCounters) -> PolicyEvaluation {
+    let verified = tx.second_factor.is_some();
+    let facts = Facts { tx, counters, second_factor: verified };
+    let upgraded = Facts { second_factor: true, ..facts };
+
+    let rules = rules
+        .iter()
+        .map(|rule| {
+            let (outcome, reason) = if rule.expr.holds(&facts) {
+                (RuleOutcome::Passed, format!("通过: {}", rule.expr))
+            } else if !verified && rule.expr.holds(&upgraded) {
+                (RuleOutcome::RequiresSecondFactor, format!("需要 2FA: {}", rule.expr))
+            } else {
+                (RuleOutcome::Failed, rule.expr.explain(&facts))
+            };
+            RuleResult { name: rule.name.clone(), outcome, reason }
+        })
+        .collect();
+    PolicyEvaluation { rules }
+}
+
+impl PolicyEvaluation {
+    /// 最差的规则结果 (无规则时通过)
+    pub fn outcome(&self) -> RuleOutcome {
+        self.rules.iter().map(|r| r.outcome).max().unwrap_or(RuleOutcome::Passed)
+    }
+
+    /// 全部规则通过
+    pub fn allowed(&self) -> bool {
+        self.outcome() == RuleOutcome::Passed
+    }
+
+    /// 按名称查找规则结果
+    pub fn rule(&self, name: &str) -> Option<&RuleResult> {
+        self.rules.iter().find(|r| r.name == name)
+    }
+
+    /// 未通过的规则
+    pub fn violations(&self) -> impl Iterator<Item = &RuleResult> {
+        self.rules.iter().filter(|r| r.outcome != RuleOutcome::Passed)
+    }
+}
+
+/// 求值所需的全部事实
+#[derive(Clone, Copy)]
+struct Facts<'a> {
+    tx: &'a TxContext<'a>,
+    counters: PolicyCounters,
+    second_factor: bool,
+}
+
+impl Facts<'_> {
+    fn num(&self, field: NumField) -> u64 {
+        let tx = self.tx;
+        match field {
+            NumField::Amount => tx.amount,
+            NumField::Timestamp => tx.timestamp,
+            NumField::Hour => tx.timestamp / 3600 % 24,
+            // 1970-01-01 是周四
+            NumField::Weekday => (tx.timestamp / 86_400 + 3) % 7,
+            NumField::TxCount => self.counters.session_txs.saturating_add(1),
+            NumField::SessionAmount => self.counters.session_amount.saturating_add(tx.amount),
+            NumField::DailyAmount => self.counters.daily_amount.saturating_add(tx.amount),
+        }
+    }
+
+    fn text(&self, field: StrField) -> Option<&str> {
+        match field {
+            StrField::Function => Some(self.tx.function).filter(|f| !f.is_empty()),
+            StrField::Contract => Some(self.tx.contract_id.0.as_str()),
+            StrField::Recipient => self.tx.recipient,
+        }
+    }
+
+    fn describe(&self, field: StrField) -> String {
+        self.text(field).map_or_else(|| "(无)".to_string(), |v| format!("{:?}", v))
+    }
+}
+
+impl PolicyExpr {
+    fn holds(&self, facts: &Facts) -> bool {
+        match self {
+            PolicyExpr::Const { value } => *value,
+            PolicyExpr::And { args } => args.iter().all(|a| a.holds(facts)),
+            PolicyExpr::Or { args } => args.iter().any(|a| a.holds(facts)),
+            PolicyExpr::Not { arg } => !arg.holds(facts),
+            PolicyExpr::Implies { when, then } => !when.holds(facts) || then.holds(facts),
+            PolicyExpr::Cmp { field, cmp, value } => {
+                let actual = facts.num(*field);
+                match cmp {
+                    CmpOp::Eq => actual == *value,
+                    CmpOp::Ne => actual != *value,
+                    CmpOp::Lt => actual < *value,
+                    CmpOp::Le => actual <= *value,
+                    CmpOp::Gt => actual > *value,
+                    CmpOp::Ge => actual >= *value,
+                }
+            }
+            PolicyExpr::InRange { field, start, end } => (*start..*end).contains(&facts.num(*field)),
+            PolicyExpr::Is { field, value } => facts.text(*field) == Some(value.as_str()),
+            PolicyExpr::In { field, values } => {
+                facts.text(*field).is_some_and(|v| values.iter().any(|x| x == v))
+            }
+            PolicyExpr::SecondFactor => facts.second_factor,
+        }
+    }
+
+    /// 不成立的原因
+    fn explain(&self, facts: &Facts) -> String {
+        match self {
+            PolicyExpr::Const { .. } => "规则恒不成立".to_string(),
+            PolicyExpr::And { args } => args
+                .iter()
+                .find(|a| !a.holds(facts))
+                .map_or_else(|| "规则恒不成立".to_string(), |a| a.explain(facts)),
+            PolicyExpr::Or { args } if args.is_empty() => "规则恒不成立".to_string(),
+            PolicyExpr::Or { args } => format!(
+                "以下条件均不满足: {}",
+                args.iter().map(|a| a.explain(facts)).collect::<Vec<_>>().join("; ")
+            ),
+            PolicyExpr::Not { arg } => format!("不应满足: {}", arg),
+            PolicyExpr::Implies { when, then } => {
+                format!("{} 时要求 {}，但 {}", when, then, then.explain(facts))
+            }
+            PolicyExpr::Cmp { field, cmp, value } => {
+                format!("{} = {}，要求 {} {}", field, facts.num(*field), cmp, value)
+            }
+            PolicyExpr::InRange { field, start, end } => {
+                format!("{} = {}，要求在 {}..{} 内", field, facts.num(*field), start, end)
+            }
+            PolicyExpr::Is { field, value } => {
+                format!("{} = {}，要求为 {:?}", field, facts.describe(*field), value)
+            }
+            PolicyExpr::In { field, values } => {
+                format!("{} = {}，不在白名单 {:?} 中", field, facts.describe(*field), values)
+            }
+            PolicyExpr::SecondFactor => "未验证 2FA".to_string(),
+        }
+    }
+
+    /// 打印优先级 (越大越紧)
+    fn precedence(&self) -> u8 {
+        match self {
+            PolicyExpr::Implies { .. } => 1,
+            PolicyExpr::Or { args } | PolicyExpr::And { args } if args.len() == 1 => args[0].precedence(),
+            PolicyExpr::Or { args } if args.len() > 1 => 2,
+            PolicyExpr::And { args } if args.len() > 1 => 3,
+            PolicyExpr::Not { .. } => 4,
+            _ => 5,
+        }
+    }
+}
+
+fn write_operand(f: &mut fmt::Formatter<'_>, expr: &PolicyExpr, min_precedence: u8) -> fmt::Result {
+    if expr.precedence() < min_precedence {
+        write!(f, "({})", expr)
+    } else {
+        write!(f, "{}", expr)
+    }
+}
+
+fn write_joined(f: &mut fmt::Formatter<'_>, args: &[PolicyExpr], op: &str, precedence: u8) -> fmt::Result {
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            write!(f, " {} ", op)?;
+        }
+        write_operand(f, arg, precedence)?;
+    }
+    Ok(())
+}
+
+fn write_str(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    write!(f, "\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// 文本形式 (可由 `parse_expr` 解析回相同的表达式)
+impl fmt::Display for PolicyExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyExpr::Const { value } => write!(f, "{}", value),
+            PolicyExpr::And { args } if args.is_empty() => write!(f, "true"),
+            PolicyExpr::Or { args } if args.is_empty() => write!(f, "false"),
+            PolicyExpr::And { args } => write_joined(f, args, "and", 3),
+            PolicyExpr::Or { args } => write_joined(f, args, "or", 2),
+            PolicyExpr::Not { arg } => {
+                write!(f, "not ")?;
+                write_operand(f, arg, 4)
+            }
+            PolicyExpr::Implies { when, then } => {
+                write_operand(f, when, 2)?;
+                write!(f, " implies ")?;
+                write_operand(f, then, 1)
+            }
+            PolicyExpr::Cmp { field, cmp, value } => write!(f, "{} {} {}", field, cmp, value),
+            PolicyExpr::InRange { field, start, end } => write!(f, "{} in {}..{}", field, start, end),
+            PolicyExpr::Is { field, value } => {
+                write!(f, "{} == ", field)?;
+                write_str(f, value)
+            }
+            PolicyExpr::In { field, values } => {
+                write!(f, "{} in [", field)?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_str(f, value)?;
+                }
+                write!(f, "]")
+            }
+            PolicyExpr::SecondFactor => write!(f, "2fa"),
+        }
+    }
+}
+
+impl fmt::Display for PolicyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rule {}: {}", self.name, self.expr)
+    }
+}
+
+impl NumField {
+    const ALL: [NumField; 7] = [
+        NumField::Amount,
+        NumField::Timestamp,
+        NumField::Hour,
+        NumField::Weekday,
+        NumField::TxCount,
+        NumField::SessionAmount,
+        NumField::DailyAmount,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NumField::Amount => "amount",
+            NumField::Timestamp => "timestamp",
+            NumField::Hour => "hour",
+            NumField::Weekday => "weekday",
+            NumField::TxCount => "tx_count",
+            NumField::SessionAmount => "session_amount",
+            NumField::DailyAmount => "daily_amount",
+        }
+    }
+}
+
+impl StrField {
+    const ALL: [StrField; 3] = [StrField::Function, StrField::Contract, StrField::Recipient];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StrField::Function => "function",
+            StrField::Contract => "contract",
+            StrField::Recipient => "recipient",
+        }
+    }
+}
+
+impl fmt::Display for NumField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for StrField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for CmpOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        })
+    }
+}
+
+/// 解析规则列表 (文本形式，或以 `[` 开头的 JSON 形式)，并检查其合法性
+pub fn parse_rules(source: &str) -> Result<Vec<PolicyRule>> {
+    let rules = if source.trim_start().starts_with('[') {
+        serde_json::from_str(source)
+            .map_err(|e| PsyGuardError::SerializationError(format!("规则解析失败: {}", e)))?
+    } else {
+        let mut parser = Parser::new(source)?;
+        let rules = parser.rules()?;
+        parser.finish()?;
+        rules
+    };
+    validate_rules(&rules)?;
+    Ok(rules)
+}
+
+/// 解析单个表达式
+pub fn parse_expr(source: &str) -> Result<PolicyExpr> {
+    let mut parser = Parser::new(source)?;
+    let expr = parser.expr()?;
+    parser.finish()?;
+    Ok(expr)
+}
+
+/// 检查规则名唯一且为标识符、区间非空
+pub fn validate_rules(rules: &[PolicyRule]) -> Result<()> {
+    let mut names = HashSet::new();
+    for rule in rules {
+        let valid_name = rule.name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && rule.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(PsyGuardError::InvalidInput(format!("规则名无效: {:?}", rule.name)));
+        }
+        if !names.insert(rule.name.as_str()) {
+            return Err(PsyGuardError::InvalidInput(format!("规则名重复: {}", rule.name)));
+        }
+        validate_expr(&rule.expr)
+            .map_err(|e| PsyGuardError::InvalidInput(format!("规则 {}: {}", rule.name, e)))?;
+    }
+    Ok(())
+}
+
+fn validate_expr(expr: &PolicyExpr) -> std::result::Result<(), String> {
+    match expr {
+        PolicyExpr::And { args } | PolicyExpr::Or { args } => args.iter().try_for_each(validate_expr),
+        PolicyExpr::Not { arg } => validate_expr(arg),
+        PolicyExpr::Implies { when, then } => {
+            validate_expr(when)?;
+            validate_expr(then)
+        }
+        PolicyExpr::InRange { field, start, end } if start >= end => {
+            Err(format!("{} 的区间 {}..{} 为空", field, start, end))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Num(u64),
+    Str(String),
+    Cmp(CmpOp),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Colon,
+    DotDot,
+    /// 规则分隔 (`;` 或换行)
+    Sep,
+}
+
+/// 递归下降解析器
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Self> {
+        Ok(Self { tokens: tokenize(source)?, pos: 0 })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(t, _)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn error(&self, message: impl fmt::Display) -> PsyGuardError {
+        let line = self
+            .tokens
+            .get(self.pos.min(self.tokens.len().saturating_sub(1)))
+            .map_or(1, |(_, line)| *line);
+        PsyGuardError::InvalidInput(format!("策略表达式第 {} 行: {}", line, message))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(word)) if word == keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            other => {
+                self.pos -= 1;
+                Err(self.error(format!("应为 {:?}，实际为 {:?}", expected, other)))
+            }
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self.peek() == Some(&Token::Sep) {
+            self.pos += 1;
+        }
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.skip_separators();
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(self.error(format!("多余的输入 {:?}", token))),
+        }
+    }
+
+    fn rules(&mut self) -> Result<Vec<PolicyRule>> {
+        let mut rules = Vec::new();
+        self.skip_separators();
+        while self.peek().is_some() {
+            if !self.eat_keyword("rule") {
+                return Err(self.error("规则应以 rule 开头"));
+            }
+            let name = match self.next() {
+                Some(Token::Ident(name)) => name,
+                other => {
+                    self.pos -= 1;
+                    return Err(self.error(format!("应为规则名，实际为 {:?}", other)));
+                }
+            };
+            self.expect(Token::Colon)?;
+            rules.push(PolicyRule { name, expr: self.expr()? });
+
+            match self.peek() {
+                None => break,
+                Some(Token::Sep) => self.skip_separators(),
+                Some(token) => return Err(self.error(format!("规则之间应以换行或 ; 分隔，实际为 {:?}", token))),
+            }
+        }
+        Ok(rules)
+    }
+
+    fn expr(&mut self) -> Result<PolicyExpr> {
+        let when = self.or_expr()?;
+        if self.eat_keyword("implies") {
+            let then = self.expr()?;
+            return Ok(PolicyExpr::Implies { when: Box::new(when), then: Box::new(then) });
+        }
+        Ok(when)
+    }
+
+    fn or_expr(&mut self) -> Result<PolicyExpr> {
+        let mut args = vec![self.and_expr()?];
+        while self.eat_keyword("or") {
+            args.push(self.and_expr()?);
+        }
+        Ok(if args.len() == 1 { args.remove(0) } else { PolicyExpr::Or { args } })
+    }
+
+    fn and_expr(&mut self) -> Result<PolicyExpr> {
+        let mut args = vec![self.unary()?];
+        while self.eat_keyword("and") {
+            args.push(self.unary()?);
+        }
+        Ok(if args.len() == 1 { args.remove(0) } else { PolicyExpr::And { args } })
+    }
+
+    fn unary(&mut self) -> Result<PolicyExpr> {
+        if self.eat_keyword("not") {
+            return Ok(PolicyExpr::Not { arg: Box::new(self.unary()?) });
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<PolicyExpr> {
+        match self.next() {
+            Some(Token::LParen) => {
+                let expr = self.expr()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::Ident(word)) => match word.as_str() {
+                "true" => Ok(PolicyExpr::Const { value: true }),
+                "false" => Ok(PolicyExpr::Const { value: false }),
+                "2fa" => Ok(PolicyExpr::SecondFactor),
+                _ => {
+                    if let Some(field) = NumField::ALL.into_iter().find(|f| f.name() == word) {
+                        self.num_predicate(field)
+                    } else if let Some(field) = StrField::ALL.into_iter().find(|f| f.name() == word) {
+                        self.str_predicate(field)
+                    } else {
+                        self.pos -= 1;
+                        Err(self.error(format!("未知字段 {}", word)))
+                    }
+                }
+            },
+            other => {
+                self.pos -= 1;
+                Err(self.error(format!("应为条件，实际为 {:?}", other)))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<u64> {
+        match self.next() {
+            Some(Token::Num(value)) => Ok(value),
+            other => {
+                self.pos -= 1;
+                Err(self.error(format!("应为数字，实际为 {:?}", other)))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::Str(value)) => Ok(value),
+            other => {
+                self.pos -= 1;
+                Err(self.error(format!("应为字符串，实际为 {:?}", other)))
+            }
+        }
+    }
+
+    fn num_predicate(&mut self, field: NumField) -> Result<PolicyExpr> {
+        if self.eat_keyword("in") {
+            let start = self.number()?;
+            self.expect(Token::DotDot)?;
+            let end = self.number()?;
+            return Ok(PolicyExpr::InRange { field, start, end });
+        }
+        match self.next() {
+            Some(Token::Cmp(cmp)) => Ok(PolicyExpr::Cmp { field, cmp, value: self.number()? }),
+            other => {
+                self.pos -= 1;
+                Err(self.error(format!("{} 后应为比较运算或 in，实际为 {:?}", field, other)))
+            }
+        }
+    }
+
+    fn str_predicate(&mut self, field: StrField) -> Result<PolicyExpr> {
+        if self.eat_keyword("in") {
+            self.expect(Token::LBracket)?;
+            let mut values = Vec::new();
+            if self.peek() != Some(&Token::RBracket) {
+                values.push(self.string()?);
+                while self.peek() == Some(&Token::Comma) {
+                    self.pos += 1;
+                    values.push(self.string()?);
+                }
+            }
+            self.expect(Token::RBracket)?;
+            return Ok(PolicyExpr::In { field, values });
+        }
+        match self.next() {
+            Some(Token::Cmp(CmpOp::Eq)) => Ok(PolicyExpr::Is { field, value: self.string()? }),
+            Some(Token::Cmp(CmpOp::Ne)) => Ok(PolicyExpr::Not {
+                arg: Box::new(PolicyExpr::Is { field, value: self.string()? }),
+            }),
+            other => {
+                self.pos -= 1;
+                Err(self.error(format!("{} 后应为 ==、!= 或 in，实际为 {:?}", field, other)))
+            }
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    let error = |line: usize, message: String| {
+        PsyGuardError::InvalidInput(format!("策略表达式第 {} 行: {}", line, message))
+    };
+
+    while let Some(&c) = chars.peek() {
+        let token = match c {
+            '\n' | ';' => {
+                chars.next();
+                let token = (Token::Sep, line);
+                if c == '\n' {
+                    line += 1;
+                }
+                tokens.push(token);
+                continue;
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '#' => {
+                // 注释到行尾
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+                continue;
+            }
+            '(' | ')' | '[' | ']' | ',' | ':' => {
+                chars.next();
+                match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    '[' => Token::LBracket,
+                    ']' => Token::RBracket,
+                    ',' => Token::Comma,
+                    _ => Token::Colon,
+                }
+            }
+            '.' => {
+                chars.next();
+                if chars.next() != Some('.') {
+                    return Err(error(line, "应为 ..".to_string()));
+                }
+                Token::DotDot
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                match (c, eq) {
+                    ('=', true) => Token::Cmp(CmpOp::Eq),
+                    ('!', true) => Token::Cmp(CmpOp::Ne),
+                    ('<', true) => Token::Cmp(CmpOp::Le),
+                    ('>', true) => Token::Cmp(CmpOp::Ge),
+                    ('<', false) => Token::Cmp(CmpOp::Lt),
+                    ('>', false) => Token::Cmp(CmpOp::Gt),
+                    _ => return Err(error(line, format!("未知运算符 {}", c))),
+                }
+            }
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '\\')) => value.push(escaped),
+                            other => return Err(error(line, format!("无效的转义 {:?}", other))),
+                        },
+                        Some('\n') | None => return Err(error(line, "字符串未结束".to_string())),
+                        Some(c) => value.push(c),
+                    }
+                }
+                Token::Str(value)
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    word.push(c);
+                }
+                if word.starts_with(|c: char| c.is_ascii_digit()) && word.chars().all(|c| c.is_ascii_digit() || c == '_') {
+                    let value = word
+                        .replace('_', "")
+                        .parse()
+                        .map_err(|_| error(line, format!("数字超出范围: {}", word)))?;
+                    Token::Num(value)
+                } else {
+                    Token::Ident(word)
+                }
+            }
+            other => return Err(error(line, format!("无法识别的字符 {:?}", other))),
+        };
+        tokens.push((token, line));
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::second_factor::TotpEnrollment;
+
+    const RULES: &str = r#"
+        # 大额转账给白名单外的收款方需要 2FA
+        rule large_transfer: function == "transfer" and amount > 500 and not recipient in ["alice", "bob"] implies 2fa
+        rule business_hours: function == "approve" implies hour in 9..18 and weekday < 5
+        rule session_cap: tx_count <= 3
+    "#;
+
+    /// 2023-11-14 (周二) 22:13:20 UTC
+    const TUESDAY_NIGHT: u64 = 1_700_000_000;
+
+    fn outcome(eval: &PolicyEvaluation, name: &str) -> RuleOutcome {
+        eval.rule(name).unwrap().outcome
+    }
+
+    #[test]
+    fn test_parse_and_print_round_trip() {
+        let rules = parse_rules(RULES).unwrap();
+        assert_eq!(rules.len(), 3);
+        assert_eq!(
+            rules[0].to_string(),
+            r#"rule large_transfer: function == "transfer" and amount > 500 and not recipient in ["alice", "bob"] implies 2fa"#
+        );
+        for rule in &rules {
+            assert_eq!(parse_expr(&rule.expr.to_string()).unwrap(), rule.expr);
+        }
+
+        // JSON 形式与文本形式等价
+        let json = serde_json::to_string(&rules).unwrap();
+        assert!(json.contains(r#""op":"implies""#));
+        assert_eq!(parse_rules(&json).unwrap(), rules);
+
+        // 优先级: not > and > or > implies，括号保留
+        let expr = parse_expr("not (amount > 1 or hour < 2) and 2fa or tx_count == 1").unwrap();
+        assert_eq!(expr.to_string(), "not (amount > 1 or hour < 2) and 2fa or tx_count == 1");
+        assert!(matches!(expr, PolicyExpr::Or { .. }));
+        let nested = parse_expr("(2fa implies true) implies contract != \"a\\\"b\"").unwrap();
+        assert_eq!(parse_expr(&nested.to_string()).unwrap(), nested);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (source, fragment) in [
+            ("rule a: amount >", "应为数字"),
+            ("rule a: balance > 1", "未知字段"),
+            ("rule a: function > \"x\"", "function 后"),
+            ("rule a: amount > 1 rule b: true", "分隔"),
+            ("rule a: true\nrule a: false", "重复"),
+            ("rule a: hour in 18..9", "区间"),
+            ("rule a: (true", "RParen"),
+            ("amount > 1", "rule 开头"),
+            ("rule a: amount > 99999999999999999999", "超出范围"),
+        ] {
+            let error = parse_rules(source).unwrap_err().to_string();
+            assert!(error.contains(fragment), "{}: {}", source, error);
+        }
+        let error = parse_rules("rule a: true\n\nrule b: amount ? 1").unwrap_err().to_string();
+        assert!(error.contains("第 3 行"), "{}", error);
+    }
+
+    #[test]
+    fn test_evaluate_rules() {
+        let rules = parse_rules(RULES).unwrap();
+        let token = ContractId("token".to_string());
+
+        // 白名单内的大额转账通过
+        let tx = TxContext::new(&token, 800, TUESDAY_NIGHT).with_function("transfer").with_recipient(Some("bob"));
+        assert!(evaluate(&rules, &tx, PolicyCounters::default()).allowed());
+
+        // 白名单外的大额转账需要 2FA，验证后通过
+        let tx = tx.with_recipient(Some("mallory"));
+        let eval = evaluate(&rules, &tx, PolicyCounters::default());
+        assert_eq!(eval.outcome(), RuleOutcome::RequiresSecondFactor);
+        assert_eq!(outcome(&eval, "large_transfer"), RuleOutcome::RequiresSecondFactor);
+        assert_eq!(outcome(&eval, "session_cap"), RuleOutcome::Passed);
+
+        let mut totp = TotpEnrollment::new(b"12345678901234567890".to_vec()).unwrap();
+        let factor = totp.verify(&totp.code_at(TUESDAY_NIGHT), TUESDAY_NIGHT, &[0u8; 32]).unwrap();
+        assert!(evaluate(&rules, &tx.with_second_factor(Some(&factor)), PolicyCounters::default()).allowed());
+
+        // 工作时间外的 approve 被拒绝，2FA 也无济于事
+        let approve = TxContext::new(&token, 0, TUESDAY_NIGHT).with_function("approve").with_second_factor(Some(&factor));
+        let eval = evaluate(&rules, &approve, PolicyCounters::default());
+        assert_eq!(outcome(&eval, "business_hours"), RuleOutcome::Failed);
+        assert!(eval.rule("business_hours").unwrap().reason.contains("hour = 22"));
+        let morning = TUESDAY_NIGHT - 12 * 3600;
+        assert!(evaluate(&rules, &TxContext { timestamp: morning, ..approve }, PolicyCounters::default()).allowed());
+        let saturday = TUESDAY_NIGHT + 4 * 86_400 - 12 * 3600;
+        assert!(!evaluate(&rules, &TxContext { timestamp: saturday, ..approve }, PolicyCounters::default()).allowed());
+
+        // 会话内第 4 笔交易
+        let small = TxContext::new(&token, 1, TUESDAY_NIGHT);
+        let counters = PolicyCounters { session_txs: 3, ..PolicyCounters::default() };
+        let eval = evaluate(&rules, &small, counters);
+        assert_eq!(outcome(&eval, "session_cap"), RuleOutcome::Failed);
+        assert_eq!(eval.violations().count(), 1);
+        assert!(eval.rule("session_cap").unwrap().reason.contains("tx_count = 4"));
+    }
+}
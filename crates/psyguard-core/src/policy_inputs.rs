@@ -0,0 +1,355 @@
+//! SDKey 签名电路公共输入
+//!
+//! 把策略的每个字段规范化编码为定长的 Goldilocks 域元素序列，作为签名电路
+//! 的公共输入；SDKey 公钥是对 verifier data 承诺与全部公共输入的 Poseidon
+//! 哈希，因此修改任一策略字段 (含合约白名单与登记的 2FA 凭据) 都会改变公钥。
+//!
+//! 布局 (共 `POLICY_PUBLIC_INPUTS_LEN` 个元素):
+//!
+//! | 下标    | 内容                                      |
+//! |---------|-------------------------------------------|
+//! | 0       | 策略模式版本                              |
+//! | 1..=3   | 日限额: 是否启用, 低 32 位, 高 32 位      |
+//! | 4..=6   | 时间锁: 是否启用, 低 32 位, 高 32 位      |
+//! | 7..=12  | 白名单: 是否启用, 合约数, Merkle 根 (4)   |
+//! | 13      | 是否要求 2FA                              |
+//! | 14..=22 | 2FA 凭据: 是否登记, 凭据标识 (8 × 32 位)  |
+//! | 23..=27 | 组合规则: 条数, 规则承诺 (4)              |
+//!
+//! 白名单按集合语义编码 (排序去重)，列表顺序不影响公钥；组合规则按顺序编码。
+//! 参考: 《7-Psy Jargon.md》- SDKey 签名电路
+
+use crate::types::*;
+use crate::codec::WireWriter;
+use crate::hash::{MerkleHasher, PoseidonGoldilocksHasher};
+
+/// 公共输入个数
+pub const POLICY_PUBLIC_INPUTS_LEN: usize = 28;
+
+/// 公钥哈希域分隔标签
+pub const PUBLIC_KEY_DOMAIN: &[u8] = b"psyguard/sdkey-public-key";
+
+/// 组合规则承诺域分隔标签
+pub const RULES_DOMAIN: &[u8] = b"psyguard/sdkey-policy-rules";
+
+/// 白名单叶哈希域分隔标签
+pub const WHITELIST_LEAF_TAG: u8 = 0x00;
+
+/// 白名单内部节点哈希域分隔标签
+pub const WHITELIST_NODE_TAG: u64 = 0x01;
+
+/// 白名单填充叶 (空白名单的根亦为此值)
+pub const WHITELIST_EMPTY_LEAF: Hash = [0u8; 32];
+
+/// 策略的电路公共输入
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyPublicInputs {
+    elements: [u64; POLICY_PUBLIC_INPUTS_LEN],
+}
+
+impl PolicyPublicInputs {
+    /// 规范化编码策略的全部字段
+    pub fn encode(policy: &SdkeyPolicy) -> Self {
+        let mut out = Vec::with_capacity(POLICY_PUBLIC_INPUTS_LEN);
+        out.push(policy.version as u64);
+
+        push_optional_u64(&mut out, policy.daily_limit);
+        push_optional_u64(&mut out, policy.time_lock_until);
+
+        match &policy.trusted_contracts {
+            Some(contracts) => {
+                let set = canonical_whitelist(contracts);
+                out.push(1);
+                out.push(set.len() as u64);
+                out.extend_from_slice(&poseidon_elements(&merkle_root(&set)));
+            }
+            None => out.extend_from_slice(&[0; 6]),
+        }
+
+        out.push(policy.require_2fa as u64);
+
+        match &policy.second_factor_key {
+            Some(key) => {
+                out.push(1);
+                out.extend(key.chunks(4).map(|limb| u32::from_le_bytes(limb.try_into().unwrap()) as u64));
+            }
+            None => out.extend_from_slice(&[0; 9]),
+        }
+
+        out.push(policy.rules.len() as u64);
+        out.extend_from_slice(&poseidon_elements(&rules_commitment(&policy.rules)));
+
+        Self { elements: out.try_into().expect("公共输入布局长度固定") }
+    }
+
+    /// 域元素序列
+    pub fn elements(&self) -> &[u64] {
+        &self.elements
+    }
+}
+
+/// 由 verifier data 与策略公共输入导出 SDKey 公钥哈希
+///
+/// `Poseidon(domain[4] | Poseidon(verifier_data)[4] | inputs[28])`
+pub fn public_key_hash(verifier_data: &[u8], inputs: &PolicyPublicInputs) -> Hash {
+    let hasher = PoseidonGoldilocksHasher;
+    let mut elements = Vec::with_capacity(8 + POLICY_PUBLIC_INPUTS_LEN);
+    elements.extend_from_slice(&poseidon_elements(&hasher.hash_bytes(PUBLIC_KEY_DOMAIN)));
+    elements.extend_from_slice(&poseidon_elements(&hasher.hash_bytes(verifier_data)));
+    elements.extend_from_slice(inputs.elements());
+    poseidon_hash(&elements)
+}
+
+/// 受信合约白名单的 Merkle 根 (排序去重后建树)
+pub fn whitelist_root(contracts: &[ContractId]) -> Hash {
+    merkle_root(&canonical_whitelist(contracts))
+}
+
+/// 组合规则的承诺 (规则名与表达式树的规范编码)
+pub fn rules_commitment(rules: &[PolicyRule]) -> Hash {
+    let mut w = WireWriter::new();
+    w.put_bytes(RULES_DOMAIN);
+    w.put_len(rules.len());
+    for rule in rules {
+        w.put_str(&rule.name);
+        put_expr(&mut w, &rule.expr);
+    }
+    PoseidonGoldilocksHasher.hash_bytes(&w.into_bytes())
+}
+
+/// Poseidon 哈希的域元素 (输入均为本模块的 Poseidon 输出或零叶，总是规范的)
+fn poseidon_elements(hash: &Hash) -> [u64; 4] {
+    PoseidonGoldilocksHasher::hash_to_elements(hash).expect("Poseidon 输出为规范域元素")
+}
+
+/// 对域元素求 Poseidon 哈希 (本模块的布局保证每个元素都小于模数)
+fn poseidon_hash(elements: &[u64]) -> Hash {
+    PoseidonGoldilocksHasher::hash_elements(elements).expect("公共输入元素均为规范域元素")
+}
+
+fn push_optional_u64(out: &mut Vec<u64>, value: Option<u64>) {
+    match value {
+        Some(v) => out.extend_from_slice(&[1, v & 0xFFFF_FFFF, v >> 32]),
+        None => out.extend_from_slice(&[0; 3]),
+    }
+}
+
+fn canonical_whitelist(contracts: &[ContractId]) -> Vec<&str> {
+    let mut set: Vec<&str> = contracts.iter().map(|c| c.0.as_str()).collect();
+    set.sort_unstable();
+    set.dedup();
+    set
+}
+
+fn merkle_root(contracts: &[&str]) -> Hash {
+    let hasher = PoseidonGoldilocksHasher;
+    let mut level: Vec<Hash> = contracts
+        .iter()
+        .map(|id| {
+            let mut data = Vec::with_capacity(1 + id.len());
+            data.push(WHITELIST_LEAF_TAG);
+            data.extend_from_slice(id.as_bytes());
+            hasher.hash_bytes(&data)
+        })
+        .collect();
+    if level.is_empty() {
+        return WHITELIST_EMPTY_LEAF;
+    }
+    level.resize(level.len().next_power_of_two(), WHITELIST_EMPTY_LEAF);
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let mut elements = vec![WHITELIST_NODE_TAG];
+                elements.extend_from_slice(&poseidon_elements(&pair[0]));
+                elements.extend_from_slice(&poseidon_elements(&pair[1]));
+                poseidon_hash(&elements)
+            })
+            .collect();
+    }
+    level[0]
+}
+
+fn put_expr(w: &mut WireWriter, expr: &PolicyExpr) {
+    match expr {
+        PolicyExpr::Const { value } => {
+            w.put_u8(0);
+            w.put_bool(*value);
+        }
+        PolicyExpr::And { args } | PolicyExpr::Or { args } => {
+            w.put_u8(if matches!(expr, PolicyExpr::And { .. }) { 1 } else { 2 });
+            w.put_len(args.len());
+            for arg in args {
+                put_expr(w, arg);
+            }
+        }
+        PolicyExpr::Not { arg } => {
+            w.put_u8(3);
+            put_expr(w, arg);
+        }
+        PolicyExpr::Implies { when, then } => {
+            w.put_u8(4);
+            put_expr(w, when);
+            put_expr(w, then);
+        }
+        PolicyExpr::Cmp { field, cmp, value } => {
+            w.put_u8(5);
+            w.put_u8(num_field_code(*field));
+            w.put_u8(cmp_op_code(*cmp));
+            w.put_u64(*value);
+        }
+        PolicyExpr::InRange { field, start, end } => {
+            w.put_u8(6);
+            w.put_u8(num_field_code(*field));
+            w.put_u64(*start);
+            w.put_u64(*end);
+        }
+        PolicyExpr::Is { field, value } => {
+            w.put_u8(7);
+            w.put_u8(str_field_code(*field));
+            w.put_str(value);
+        }
+        PolicyExpr::In { field, values } => {
+            w.put_u8(8);
+            w.put_u8(str_field_code(*field));
+            w.put_len(values.len());
+            for value in values {
+                w.put_str(value);
+            }
+        }
+        PolicyExpr::SecondFactor => w.put_u8(9),
+    }
+}
+
+fn num_field_code(field: NumField) -> u8 {
+    match field {
+        NumField::Amount => 0,
+        NumField::Timestamp => 1,
+        NumField::Hour => 2,
+        NumField::Weekday => 3,
+        NumField::TxCount => 4,
+        NumField::SessionAmount => 5,
+        NumField::DailyAmount => 6,
+    }
+}
+
+fn str_field_code(field: StrField) -> u8 {
+    match field {
+        StrField::Function => 0,
+        StrField::Contract => 1,
+        StrField::Recipient => 2,
+    }
+}
+
+fn cmp_op_code(cmp: CmpOp) -> u8 {
+    match cmp {
+        CmpOp::Eq => 0,
+        CmpOp::Ne => 1,
+        CmpOp::Lt => 2,
+        CmpOp::Le => 3,
+        CmpOp::Gt => 4,
+        CmpOp::Ge => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::GOLDILOCKS_MODULUS;
+    use crate::policy_expr::parse_rules;
+    use crate::sdkey::{SdkeyPolicyBuilder, SdkeyPolicyValidator};
+    use std::collections::HashSet;
+
+    const VERIFIER_DATA: &[u8] = b"sdkey-circuit-verifier-data";
+
+    fn contracts(ids: &[&str]) -> Vec<ContractId> {
+        ids.iter().map(|id| ContractId(id.to_string())).collect()
+    }
+
+    fn full_policy() -> SdkeyPolicy {
+        SdkeyPolicyBuilder::new()
+            .with_daily_limit(10_000)
+            .with_time_lock(1_700_000_000)
+            .with_trusted_contracts(contracts(&["token", "nft", "dex"]))
+            .with_2fa()
+            .with_second_factor_key([7u8; 32])
+            .with_rule(parse_rules("rule office_hours: hour in 9..18").unwrap().remove(0))
+            .build()
+    }
+
+    fn key(policy: &SdkeyPolicy) -> String {
+        hex::encode(SdkeyPolicyValidator::compute_public_key_hash(VERIFIER_DATA, policy))
+    }
+
+    #[test]
+    fn test_public_key_vectors() {
+        assert_eq!(
+            key(&SdkeyPolicyBuilder::new().build()),
+            "ab1c0aa2d4b369cffd827a1dfed630f106f11cb97d125bc844513e6697b486fe"
+        );
+        assert_eq!(
+            key(&full_policy()),
+            "4b72d3e47d0f2c423fc3e1ef761173fe280d4c847f6a68d39db72ec91f27f8ce"
+        );
+
+        let inputs = PolicyPublicInputs::encode(&full_policy());
+        assert_eq!(inputs.elements().len(), POLICY_PUBLIC_INPUTS_LEN);
+        assert!(inputs.elements().iter().all(|&e| e < GOLDILOCKS_MODULUS));
+        assert_eq!(&inputs.elements()[..8], &[SDKEY_POLICY_VERSION as u64, 1, 10_000, 0, 1, 1_700_000_000, 0, 1]);
+        assert_eq!(inputs.elements()[8], 3);
+        assert_eq!(&inputs.elements()[14..16], &[1, 0x0707_0707]);
+    }
+
+    #[test]
+    fn test_every_policy_change_changes_key() {
+        let base = full_policy();
+        let mut variants = vec![SdkeyPolicy::default(), base.clone()];
+        let mutations: Vec<fn(&mut SdkeyPolicy)> = vec![
+            |p| p.version = 2,
+            |p| p.daily_limit = None,
+            |p| p.daily_limit = Some(10_001),
+            |p| p.daily_limit = Some(10_000 + (1 << 32)),
+            |p| p.time_lock_until = None,
+            |p| p.time_lock_until = Some(1_700_000_001),
+            |p| p.trusted_contracts = None,
+            |p| p.trusted_contracts = Some(vec![]),
+            |p| p.trusted_contracts = Some(contracts(&["token", "nft"])),
+            |p| p.trusted_contracts = Some(contracts(&["token", "nft", "dex", "bridge"])),
+            |p| p.trusted_contracts = Some(contracts(&["token", "nft", "dey"])),
+            |p| p.require_2fa = false,
+            |p| p.second_factor_key = None,
+            |p| p.second_factor_key = Some([8u8; 32]),
+            |p| p.rules.clear(),
+            |p| p.rules[0].name = "office".to_string(),
+            |p| p.rules = parse_rules("rule office_hours: hour in 9..17").unwrap(),
+            |p| p.rules = parse_rules("rule office_hours: hour in 9..18; rule cap: amount <= 5").unwrap(),
+        ];
+        for mutate in &mutations {
+            let mut policy = base.clone();
+            mutate(&mut policy);
+            assert_ne!(policy, base);
+            variants.push(policy);
+        }
+
+        let keys: HashSet<String> = variants.iter().map(key).collect();
+        assert_eq!(keys.len(), variants.len());
+
+        // 同一策略换一个签名电路也得到不同公钥
+        assert_ne!(
+            SdkeyPolicyValidator::compute_public_key_hash(b"other-circuit", &base),
+            SdkeyPolicyValidator::compute_public_key_hash(VERIFIER_DATA, &base)
+        );
+    }
+
+    #[test]
+    fn test_whitelist_is_a_set() {
+        let mut reordered = full_policy();
+        reordered.trusted_contracts = Some(contracts(&["dex", "token", "nft", "token"]));
+        assert_eq!(key(&reordered), key(&full_policy()));
+
+        assert_eq!(whitelist_root(&[]), WHITELIST_EMPTY_LEAF);
+        assert_ne!(whitelist_root(&contracts(&["a"])), whitelist_root(&contracts(&["a", "b"])));
+        assert_ne!(whitelist_root(&contracts(&["ab"])), whitelist_root(&contracts(&["a", "b"])));
+    }
+}
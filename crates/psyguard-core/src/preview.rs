@@ -6,6 +6,9 @@
 use crate::types::*;
 use crate::error::{PsyGuardError, Result};
 use crate::traits::NetworkState;
+use crate::sdkey::SdkeyPolicyValidator;
+use crate::policy_expr::{TxContext, DAILY_LIMIT_RULE};
+use crate::ledger::SpendLedger;
 
 /// 只读预演器
 pub struct ReadOnlyPreview;
@@ -16,19 +19,22 @@ impl ReadOnlyPreview {
     /// 1. 拉取历史 CSTATE 叶 + Merkle 路径
     /// 2. 本地只读执行
     /// 3. 预测改动的槽位/键数量、余额变化、是否触发限额或 2FA
+    ///
+    /// 给出消费账本时，限额按窗口内累计消费预测。
     pub fn preview_execution(
         network_state: &dyn NetworkState,
         user_id: &UserId,
         cfc_id: &CfcId,
         args: &str,
         sdkey_policy: &SdkeyPolicy,
+        ledger: Option<&SpendLedger>,
     ) -> Result<ReadOnlyPreviewResult> {
         log::info!("开始只读预演: {:?}", cfc_id);
 
         // 1. 拉取历史 CSTATE 叶
-        let checkpoint = network_state.latest_finalized_chkp();
-        let user_leaf = network_state.fetch_user_leaf(user_id, &checkpoint);
-        let (cft_root, cstate_height) = network_state.fetch_contract_meta(cfc_id.contract_id.clone());
+        let checkpoint = network_state.latest_finalized_chkp()?;
+        let user_leaf = network_state.fetch_user_leaf(user_id, &checkpoint)?;
+        let (_cft_root, cstate_height) = network_state.fetch_contract_meta(&cfc_id.contract_id, &checkpoint)?;
 
         log::info!("拉取历史状态完成: CSTATE height = {}", cstate_height);
 
@@ -39,6 +45,7 @@ impl ReadOnlyPreview {
             args,
             &user_leaf,
             sdkey_policy,
+            ledger,
         )?;
 
         log::info!("预演完成: success = {}, 槽位修改数 = {}", 
@@ -51,36 +58,57 @@ impl ReadOnlyPreview {
 
     /// 模拟执行 (Mock 实现)
     fn simulate_execution(
-        user_id: &UserId,
+        _user_id: &UserId,
         cfc_id: &CfcId,
         args: &str,
         user_leaf: &UserLeafCtx,
         sdkey_policy: &SdkeyPolicy,
+        ledger: Option<&SpendLedger>,
     ) -> Result<ReadOnlyPreviewResult> {
         // 解析参数
         let parsed_args: serde_json::Value = serde_json::from_str(args)
             .map_err(|e| PsyGuardError::InvalidInput(format!("参数解析失败: {}", e)))?;
 
         // Mock: 根据函数类型预测影响
-        let (slots_to_modify, balance_changes, will_trigger_limit, requires_2fa) = 
-            match cfc_id.function_name.as_str() {
-                "transfer" => Self::preview_transfer(&parsed_args, user_leaf, sdkey_policy)?,
-                "approve" => Self::preview_approve(&parsed_args, user_leaf, sdkey_policy)?,
-                "claim" => Self::preview_claim(&parsed_args, user_leaf, sdkey_policy)?,
-                _ => {
-                    // 默认预测
-                    (vec![], vec![], false, false)
-                }
-            };
+        let (slots_to_modify, balance_changes) = match cfc_id.function_name.as_str() {
+            "transfer" => Self::preview_transfer(&parsed_args, user_leaf)?,
+            "approve" => Self::preview_approve(&parsed_args, user_leaf)?,
+            "claim" => Self::preview_claim(&parsed_args, user_leaf)?,
+            _ => {
+                // 默认预测
+                (vec![], vec![])
+            }
+        };
+
+        // 策略规则统一由 SdkeyPolicyValidator 求值，结论只取自整体结果，与签名时一致：
+        // 有规则未通过即不可执行 (含超过日限额)，仅缺少第二因素时提示追加 2FA
+        let amount = parsed_args.get("amount").and_then(|v| v.as_u64()).unwrap_or(0);
+        let tx = TxContext::new(&cfc_id.contract_id, amount, chrono::Utc::now().timestamp() as u64)
+            .with_function(&cfc_id.function_name)
+            .with_recipient(parsed_args.get("to").and_then(|v| v.as_str()));
+        let evaluation = SdkeyPolicyValidator::evaluate(sdkey_policy, &tx, ledger)?;
+        let outcome = evaluation.outcome();
+        let will_trigger_limit = evaluation
+            .rule(DAILY_LIMIT_RULE)
+            .is_some_and(|rule| rule.outcome != RuleOutcome::Passed);
+        let error_message = (outcome == RuleOutcome::Failed).then(|| {
+            evaluation
+                .violations()
+                .filter(|rule| rule.outcome == RuleOutcome::Failed)
+                .map(|rule| format!("{}: {}", rule.name, rule.reason))
+                .collect::<Vec<_>>()
+                .join("; ")
+        });
 
         Ok(ReadOnlyPreviewResult {
-            success: true,
+            success: outcome != RuleOutcome::Failed,
             slots_to_modify,
             balance_changes,
             will_trigger_limit,
-            requires_2fa,
+            requires_2fa: outcome == RuleOutcome::RequiresSecondFactor,
             estimated_gas: 21000,
-            error_message: None,
+            error_message,
+            policy_rules: evaluation.rules,
         })
     }
 
@@ -88,8 +116,7 @@ impl ReadOnlyPreview {
     fn preview_transfer(
         args: &serde_json::Value,
         user_leaf: &UserLeafCtx,
-        sdkey_policy: &SdkeyPolicy,
-    ) -> Result<(Vec<SlotModification>, Vec<BalanceChange>, bool, bool)> {
+    ) -> Result<(Vec<SlotModification>, Vec<BalanceChange>)> {
         let amount = args.get("amount")
             .and_then(|v| v.as_u64())
             .ok_or_else(|| PsyGuardError::InvalidInput("缺少 amount 参数".to_string()))?;
@@ -98,14 +125,6 @@ impl ReadOnlyPreview {
             .and_then(|v| v.as_str())
             .ok_or_else(|| PsyGuardError::InvalidInput("缺少 to 参数".to_string()))?;
 
-        // 检查是否触发限额
-        let will_trigger_limit = sdkey_policy.daily_limit
-            .map(|limit| amount > limit)
-            .unwrap_or(false);
-
-        // 检查是否需要 2FA
-        let requires_2fa = sdkey_policy.require_2fa || will_trigger_limit;
-
         // 预测槽位修改
         let slots_to_modify = vec![
             SlotModification {
@@ -132,15 +151,14 @@ impl ReadOnlyPreview {
             },
         ];
 
-        Ok((slots_to_modify, balance_changes, will_trigger_limit, requires_2fa))
+        Ok((slots_to_modify, balance_changes))
     }
 
     /// 预测 approve 操作的影响
     fn preview_approve(
         args: &serde_json::Value,
-        user_leaf: &UserLeafCtx,
-        sdkey_policy: &SdkeyPolicy,
-    ) -> Result<(Vec<SlotModification>, Vec<BalanceChange>, bool, bool)> {
+        _user_leaf: &UserLeafCtx,
+    ) -> Result<(Vec<SlotModification>, Vec<BalanceChange>)> {
         let amount = args.get("amount")
             .and_then(|v| v.as_u64())
             .unwrap_or(0);
@@ -154,15 +172,14 @@ impl ReadOnlyPreview {
             },
         ];
 
-        Ok((slots_to_modify, vec![], false, sdkey_policy.require_2fa))
+        Ok((slots_to_modify, vec![]))
     }
 
     /// 预测 claim 操作的影响
     fn preview_claim(
         args: &serde_json::Value,
         user_leaf: &UserLeafCtx,
-        sdkey_policy: &SdkeyPolicy,
-    ) -> Result<(Vec<SlotModification>, Vec<BalanceChange>, bool, bool)> {
+    ) -> Result<(Vec<SlotModification>, Vec<BalanceChange>)> {
         let amount = args.get("amount")
             .and_then(|v| v.as_u64())
             .unwrap_or(100);
@@ -185,27 +202,7 @@ impl ReadOnlyPreview {
             },
         ];
 
-        Ok((slots_to_modify, balance_changes, false, false))
-    }
-}
-
-/// SDKey 策略 (简化版)
-#[derive(Debug, Clone)]
-pub struct SdkeyPolicy {
-    pub daily_limit: Option<u64>,
-    pub trusted_contracts: Vec<ContractId>,
-    pub time_lock_until: Option<u64>,
-    pub require_2fa: bool,
-}
-
-impl Default for SdkeyPolicy {
-    fn default() -> Self {
-        Self {
-            daily_limit: Some(10000),
-            trusted_contracts: vec![],
-            time_lock_until: None,
-            require_2fa: false,
-        }
+        Ok((slots_to_modify, balance_changes))
     }
 }
 
@@ -213,27 +210,118 @@ impl Default for SdkeyPolicy {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_preview_transfer() {
-        let user_leaf = UserLeafCtx {
+    fn user_leaf() -> UserLeafCtx {
+        UserLeafCtx {
             uleaf_hash: [0u8; 32],
             ucon_root: [0u8; 32],
+            public_key_hash: [0u8; 32],
             balance: 1000,
             nonce: 0,
-        };
+        }
+    }
 
+    fn transfer() -> CfcId {
+        CfcId {
+            contract_id: ContractId("token".to_string()),
+            function_name: "transfer".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_preview_transfer() {
         let policy = SdkeyPolicy::default();
+        let args = r#"{"to": "bob", "amount": 100}"#;
 
-        let args = serde_json::json!({
-            "to": "bob",
-            "amount": 100
-        });
+        let result = ReadOnlyPreview::simulate_execution(
+            &UserId("alice".to_string()),
+            &transfer(),
+            args,
+            &user_leaf(),
+            &policy,
+            None,
+        )
+        .unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.slots_to_modify.len(), 1); // 1 个槽位修改
+        assert_eq!(result.balance_changes.len(), 2); // 2 个余额变化
+        assert!(!result.will_trigger_limit);   // 不触发限额
+        assert!(!result.requires_2fa);   // 不需要 2FA
+    }
+
+    #[test]
+    fn test_preview_matches_signing_policy() {
+        let policy = crate::sdkey::SdkeyPolicyBuilder::new()
+            .with_daily_limit(50)
+            .with_trusted_contracts(vec![ContractId("nft".to_string())])
+            .build();
+        let args = r#"{"to": "bob", "amount": 100}"#;
+
+        let result = ReadOnlyPreview::simulate_execution(
+            &UserId("alice".to_string()),
+            &transfer(),
+            args,
+            &user_leaf(),
+            &policy,
+            None,
+        )
+        .unwrap();
+
+        // 与签名时的求值一致: 超限与白名单外的合约都被拒绝
+        assert!(result.will_trigger_limit);
+        assert!(!result.requires_2fa);
+        assert!(!result.success);
+        assert!(result.error_message.unwrap().contains("白名单"));
+        assert!(SdkeyPolicyValidator::validate_transaction(&policy, &TxContext::new(&transfer().contract_id, 100, 0), None).is_err());
+    }
+
+    #[test]
+    fn test_preview_rejects_trusted_contract_over_limit() {
+        let policy = crate::sdkey::SdkeyPolicyBuilder::new()
+            .with_daily_limit(50)
+            .with_trusted_contracts(vec![transfer().contract_id])
+            .build();
+        let preview = |amount: u64| {
+            ReadOnlyPreview::simulate_execution(
+                &UserId("alice".to_string()),
+                &transfer(),
+                &format!(r#"{{"to": "bob", "amount": {}}}"#, amount),
+                &user_leaf(),
+                &policy,
+                None,
+            )
+            .unwrap()
+        };
+        let signs = |amount: u64| {
+            SdkeyPolicyValidator::validate_transaction(&policy, &TxContext::new(&transfer().contract_id, amount, 0), None)
+                .is_ok()
+        };
 
-        let result = ReadOnlyPreview::preview_transfer(&args, &user_leaf, &policy).unwrap();
-        
-        assert_eq!(result.0.len(), 1); // 1 个槽位修改
-        assert_eq!(result.1.len(), 2); // 2 个余额变化
-        assert_eq!(result.2, false);   // 不触发限额
-        assert_eq!(result.3, false);   // 不需要 2FA
+        // 只有日限额未通过: 预演与签名都拒绝，且追加 2FA 也无济于事
+        let over = preview(100);
+        assert!(over.will_trigger_limit);
+        assert!(!over.success);
+        assert!(!over.requires_2fa);
+        assert!(over.error_message.unwrap().contains(DAILY_LIMIT_RULE));
+        assert!(!signs(100));
+
+        let within = preview(50);
+        assert!(within.success && !within.will_trigger_limit && !within.requires_2fa);
+        assert!(signs(50));
+    }
+
+    #[test]
+    fn test_preview_default_policy_limit() {
+        let result = ReadOnlyPreview::simulate_execution(
+            &UserId("alice".to_string()),
+            &transfer(),
+            &format!(r#"{{"to": "bob", "amount": {}}}"#, DEFAULT_DAILY_LIMIT + 1),
+            &UserLeafCtx { balance: DEFAULT_DAILY_LIMIT * 2, ..user_leaf() },
+            &SdkeyPolicy::default(),
+            None,
+        )
+        .unwrap();
+        assert!(result.will_trigger_limit);
+        assert!(!result.success);
     }
 }
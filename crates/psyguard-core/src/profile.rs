@@ -0,0 +1,89 @@
+//! 网络配置
+//!
+//! 不同网络对 Merkle 树使用不同的哈希函数: 本地开发默认 SHA-256，
+//! Psy 网络使用电路友好的 Poseidon (Goldilocks)。
+//! 参考: 《7-Psy Jargon.md》- Psy 电路基于 Plonky2
+
+use crate::guta::GutaConfig;
+use crate::hash::{HashAlgorithm, MerkleHasher};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// 网络配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkProfile {
+    /// 网络名称
+    pub name: String,
+    /// CFT、UCON/CSTATE 与 GUSR 使用的哈希
+    pub hash_algorithm: HashAlgorithm,
+}
+
+impl NetworkProfile {
+    /// 本地开发 / Mock
+    pub fn local() -> Self {
+        Self {
+            name: "local".to_string(),
+            hash_algorithm: HashAlgorithm::Sha256,
+        }
+    }
+
+    /// Psy 网络
+    pub fn psy() -> Self {
+        Self {
+            name: "psy".to_string(),
+            hash_algorithm: HashAlgorithm::PoseidonGoldilocks,
+        }
+    }
+
+    /// 该网络的树哈希实现
+    pub fn hasher(&self) -> Arc<dyn MerkleHasher> {
+        self.hash_algorithm.hasher()
+    }
+
+    /// 该网络的 GUTA 模拟参数
+    pub fn guta_config(&self) -> GutaConfig {
+        GutaConfig {
+            hash_algorithm: self.hash_algorithm,
+            ..GutaConfig::default()
+        }
+    }
+}
+
+impl Default for NetworkProfile {
+    fn default() -> Self {
+        Self::local()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Cstate;
+    use crate::types::ContractId;
+
+    #[test]
+    fn test_profile_selects_tree_hash() {
+        let local = NetworkProfile::local();
+        let psy = NetworkProfile::psy();
+        assert_eq!(local.hasher().name(), "sha256");
+        assert_eq!(psy.hasher().name(), "poseidon-goldilocks");
+        assert_eq!(psy.guta_config().hash_algorithm, HashAlgorithm::PoseidonGoldilocks);
+
+        let root_for = |profile: &NetworkProfile| {
+            let mut cstate = Cstate::with_hasher(
+                ContractId("token".to_string()),
+                crate::state::DEFAULT_CSTATE_HEIGHT,
+                profile.hasher(),
+            )
+            .unwrap();
+            cstate.write_slot(1, vec![1]).unwrap();
+            cstate.root
+        };
+        assert_ne!(root_for(&local), root_for(&psy));
+        assert_eq!(root_for(&local), {
+            let mut cstate = Cstate::new(ContractId("token".to_string()));
+            cstate.write_slot(1, vec![1]).unwrap();
+            cstate.root
+        });
+    }
+}
@@ -5,6 +5,13 @@
 
 use crate::types::*;
 use crate::error::{PsyGuardError, Result};
+use crate::cft::CftVerifier;
+use crate::registry::CftRegistry;
+use crate::ledger::{ReservationId, SpendLedger};
+use crate::second_factor::VerifiedFactor;
+use crate::policy_expr::TxContext;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// UPS 队列管理器
@@ -13,7 +20,12 @@ pub struct UpsQueue {
     items: Vec<UpsQueueItem>,
     /// 累积信息
     accumulated_info: UpsAccumulatedInfo,
+    /// 消费账本 (授权队列项消费时必需)
+    ledger: Option<Arc<SpendLedger>>,
+    /// 已授权队列项的预留与满足的约束；预留已提交 (End Cap 提交成功) 后为 None
+    spends: HashMap<u32, (Option<ReservationId>, Vec<String>)>,
     /// 开始时间
+    #[allow(dead_code)]
     start_time: u64,
 }
 
@@ -34,10 +46,18 @@ impl UpsQueue {
                 old_ucon_root: initial_ucon_root,
                 new_ucon_root: initial_ucon_root,
             },
+            ledger: None,
+            spends: HashMap::new(),
             start_time: now,
         }
     }
 
+    /// 使用消费账本授权队列项
+    pub fn with_ledger(mut self, ledger: Arc<SpendLedger>) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
     /// 添加队列项
     pub fn add_item(&mut self, cfc_id: CfcId, args: String) -> u32 {
         let index = self.items.len() as u32;
@@ -95,6 +115,113 @@ impl UpsQueue {
         Ok(())
     }
 
+    /// 批量填充尚未校验的队列项的 CFT 校验结果
+    ///
+    /// 按合约分组，每个合约只获取一次 GCON 记录并生成一份批量证明。
+    /// 函数不在 CFT 中 (或合约未登记) 的队列项标记为失败。
+    pub fn verify_cft(&mut self, registry: &CftRegistry, chkp: &CheckpointRef) -> Result<()> {
+        // 1. 按合约分组 (保持队列顺序)
+        let mut groups: Vec<(ContractId, Vec<u32>)> = Vec::new();
+        for item in self.items.iter().filter(|item| item.cft_verification.is_none()) {
+            match groups.iter_mut().find(|(id, _)| *id == item.cfc_id.contract_id) {
+                Some((_, indices)) => indices.push(item.index),
+                None => groups.push((item.cfc_id.contract_id.clone(), vec![item.index])),
+            }
+        }
+
+        for (contract_id, indices) in groups {
+            let mut names: Vec<String> = indices
+                .iter()
+                .map(|i| self.items[*i as usize].cfc_id.function_name.clone())
+                .collect();
+            names.sort();
+            names.dedup();
+
+            // 2. 一份批量证明覆盖该合约的全部函数
+            let (cft_root, depth, proven) = match registry.prove_multi(&contract_id, &names, chkp) {
+                Ok(multi) => {
+                    let fingerprints: Vec<CfcFingerprint> =
+                        multi.functions.iter().map(|(_, fp)| *fp).collect();
+                    let valid = CftVerifier::verify_multi(
+                        registry.hasher().as_ref(),
+                        &fingerprints,
+                        &multi.proof,
+                    )?;
+                    let proven = if valid { multi.functions } else { vec![] };
+                    (multi.proof.cft_root, multi.proof.depth as usize, proven)
+                }
+                Err(PsyGuardError::CftVerificationFailed(_)) => {
+                    let (cft_root, _) = registry.contract_meta(&contract_id, chkp)?;
+                    (cft_root, 0, vec![])
+                }
+                Err(e) => return Err(e),
+            };
+
+            // 3. 写回每个队列项 (未证明的函数没有指纹，记为全零)
+            for index in indices {
+                let function_name = &self.items[index as usize].cfc_id.function_name;
+                let fingerprint = proven.iter().find(|(name, _)| name == function_name).map(|(_, fp)| *fp);
+                self.update_cft_verification(index, CftVerificationResult {
+                    fingerprint: fingerprint.unwrap_or(CfcFingerprint([0u8; 32])),
+                    in_cft: fingerprint.is_some(),
+                    cft_root: cft_root.clone(),
+                    depth,
+                    merkle_path: None,
+                    source: "GCON.CLEAF".to_string(),
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 按 SDKey 策略授权队列项的消费并在账本中预留额度
+    ///
+    /// 金额取自参数中的 `amount` (缺省为 0，不是非负整数时为 InvalidInput)，收款方取自 `to`。
+    /// 同一账本上先授权的队列项计入后续项的限额与会话计数器，违反策略 (含缺少策略要求的 2FA)
+    /// 的队列项标记为失败。预留按队列项记录: 已授权的项再次授权时直接返回上次结果，不重复预留；
+    /// 项失败时释放预留，End Cap 提交成功后经 `commit_spends` 提交到账本存储。
+    pub fn authorize_spend(
+        &mut self,
+        index: u32,
+        policy: &SdkeyPolicy,
+        timestamp: u64,
+        second_factor: Option<&VerifiedFactor>,
+    ) -> Result<Vec<String>> {
+        let ledger = self.ledger.clone()
+            .ok_or_else(|| PsyGuardError::InvalidInput("队列未配置消费账本".to_string()))?;
+        let item = self.items.get(index as usize)
+            .ok_or_else(|| PsyGuardError::NotFound(format!("队列项 {} 不存在", index)))?;
+        if let Some((_, satisfied)) = self.spends.get(&index) {
+            return Ok(satisfied.clone());
+        }
+        let args: serde_json::Value = serde_json::from_str(&item.args)
+            .map_err(|e| PsyGuardError::InvalidInput(format!("参数解析失败: {}", e)))?;
+        let amount = match args.get("amount") {
+            None => 0,
+            Some(amount) => amount.as_u64().ok_or_else(|| {
+                PsyGuardError::InvalidInput(format!("amount 必须是非负整数，实际为 {}", amount))
+            })?,
+        };
+        let tx = TxContext::new(&item.cfc_id.contract_id, amount, timestamp)
+            .with_function(&item.cfc_id.function_name)
+            .with_recipient(args.get("to").and_then(|v| v.as_str()))
+            .with_second_factor(second_factor);
+
+        match ledger.reserve(policy, &tx) {
+            Ok((reservation, satisfied)) => {
+                self.spends.insert(index, (Some(reservation), satisfied.clone()));
+                Ok(satisfied)
+            }
+            Err(e) => {
+                if let PsyGuardError::SdkeyPolicyViolation(_) = &e {
+                    self.mark_failed(index)?;
+                }
+                Err(e)
+            }
+        }
+    }
+
     /// 标记队列项开始执行
     pub fn mark_executing(&mut self, index: u32) -> Result<()> {
         let item = self.items.get_mut(index as usize)
@@ -105,6 +232,8 @@ impl UpsQueue {
     }
 
     /// 标记队列项执行成功
+    ///
+    /// 本项的消费预留保留到 End Cap 提交 (见 `commit_spends`)，期间仍计入限额。
     pub fn mark_success(&mut self, index: u32, proving_time_ms: u64) -> Result<()> {
         let item = self.items.get_mut(index as usize)
             .ok_or_else(|| PsyGuardError::NotFound(format!("队列项 {} 不存在", index)))?;
@@ -124,6 +253,31 @@ impl UpsQueue {
             .ok_or_else(|| PsyGuardError::NotFound(format!("队列项 {} 不存在", index)))?;
 
         item.status = UpsQueueItemStatus::Failed;
+
+        // 释放尚未提交的预留，重试时重新授权
+        if let Some((Some(id), _)) = self.spends.get(&index) {
+            if let Some(ledger) = &self.ledger {
+                ledger.release_reservation(*id);
+            }
+            self.spends.remove(&index);
+        }
+        Ok(())
+    }
+
+    /// 提交成功项的消费预留 (End Cap 提交成功后调用)
+    ///
+    /// 提交失败时不调用本方法，由 `clear` 释放预留。
+    pub fn commit_spends(&mut self) -> Result<()> {
+        let Some(ledger) = &self.ledger else {
+            return Ok(());
+        };
+        for item in self.items.iter().filter(|item| item.status == UpsQueueItemStatus::Success) {
+            if let Some((reservation, _)) = self.spends.get_mut(&item.index) {
+                if let Some(id) = reservation.take() {
+                    ledger.commit_reservation(id)?;
+                }
+            }
+        }
         Ok(())
     }
 
@@ -173,8 +327,14 @@ impl UpsQueue {
         self.is_all_completed()
     }
 
-    /// 清空队列
+    /// 清空队列 (释放尚未提交的预留)
     pub fn clear(&mut self) {
+        if let Some(ledger) = &self.ledger {
+            for id in self.spends.values().filter_map(|(id, _)| *id) {
+                ledger.release_reservation(id);
+            }
+        }
+        self.spends.clear();
         self.items.clear();
         self.accumulated_info.total_items = 0;
         self.accumulated_info.total_proving_time_ms = 0;
@@ -211,6 +371,7 @@ mod tests {
             requires_2fa: false,
             estimated_gas: 21000,
             error_message: None,
+            policy_rules: vec![],
         }).unwrap();
 
         assert_eq!(queue.get_items()[0].status, UpsQueueItemStatus::PreviewSuccess);
@@ -222,4 +383,203 @@ mod tests {
         assert_eq!(queue.get_success_count(), 1);
         assert!(queue.can_submit_endcap());
     }
+
+    #[test]
+    fn test_queue_items_share_daily_limit() {
+        let store = Arc::new(crate::ledger::MemorySpendStore::new());
+        let ledger = Arc::new(SpendLedger::new(UserId("alice".to_string()), store));
+        let policy = crate::sdkey::SdkeyPolicyBuilder::new().with_daily_limit(1000).build();
+
+        let mut queue = UpsQueue::new([0u8; 32]).with_ledger(ledger.clone());
+        let transfer = CfcId {
+            contract_id: ContractId("token".to_string()),
+            function_name: "transfer".to_string(),
+        };
+        for _ in 0..3 {
+            queue.add_item(transfer.clone(), r#"{"to": "bob", "amount": 400}"#.to_string());
+        }
+
+        // 单笔都未超限，但第三笔使累计超过日限额
+        queue.authorize_spend(0, &policy, 100, None).unwrap();
+        queue.authorize_spend(1, &policy, 100, None).unwrap();
+        assert!(matches!(
+            queue.authorize_spend(2, &policy, 100, None),
+            Err(PsyGuardError::SdkeyPolicyViolation(_))
+        ));
+        assert_eq!(queue.get_items()[2].status, UpsQueueItemStatus::Failed);
+        assert_eq!(ledger.spent(&transfer.contract_id, 100).unwrap(), 800);
+
+        // 金额不是非负整数时拒绝，而不是按 0 放行
+        for amount in [r#""400""#, "-1", "4.5", "1e30"] {
+            let index = queue.add_item(transfer.clone(), format!(r#"{{"to": "bob", "amount": {}}}"#, amount));
+            assert!(matches!(
+                queue.authorize_spend(index, &policy, 100, None),
+                Err(PsyGuardError::InvalidInput(_))
+            ));
+        }
+        assert_eq!(ledger.spent(&transfer.contract_id, 100).unwrap(), 800);
+    }
+
+    #[test]
+    fn test_queue_spend_reservations_follow_item_status() {
+        let store = Arc::new(crate::ledger::MemorySpendStore::new());
+        let ledger = Arc::new(SpendLedger::new(UserId("alice".to_string()), store.clone()));
+        let policy = crate::sdkey::SdkeyPolicyBuilder::new().with_daily_limit(1000).build();
+        let token = ContractId("token".to_string());
+
+        let mut queue = UpsQueue::new([0u8; 32]).with_ledger(ledger.clone());
+        let transfer = CfcId { contract_id: token.clone(), function_name: "transfer".to_string() };
+        for _ in 0..3 {
+            queue.add_item(transfer.clone(), r#"{"to": "bob", "amount": 400}"#.to_string());
+        }
+
+        // 重复授权同一项不重复预留
+        queue.authorize_spend(0, &policy, 100, None).unwrap();
+        queue.authorize_spend(0, &policy, 100, None).unwrap();
+        queue.authorize_spend(1, &policy, 100, None).unwrap();
+        assert_eq!(ledger.spent(&token, 100).unwrap(), 800);
+        assert!(queue.authorize_spend(2, &policy, 100, None).is_err());
+
+        // 失败项的金额回到日限额，之后的项可以使用
+        queue.mark_executing(1).unwrap();
+        queue.mark_failed(1).unwrap();
+        assert_eq!(ledger.spent(&token, 100).unwrap(), 400);
+        queue.authorize_spend(2, &policy, 100, None).unwrap();
+        assert_eq!(ledger.spent(&token, 100).unwrap(), 800);
+
+        // 执行成功的项仍是预留，End Cap 提交成功后才写入存储，之后不再能被释放
+        let stored = || crate::ledger::SpendStore::load(store.as_ref(), &UserId("alice".to_string())).unwrap().len();
+        queue.mark_executing(0).unwrap();
+        queue.mark_success(0, 0).unwrap();
+        assert_eq!(stored(), 0);
+        assert_eq!(ledger.reserved().len(), 2);
+        queue.commit_spends().unwrap();
+        assert_eq!(stored(), 1);
+        assert_eq!(ledger.reserved().len(), 1);
+        queue.mark_failed(0).unwrap();
+        assert_eq!(ledger.spent(&token, 100).unwrap(), 800);
+
+        // 清空队列释放其余预留
+        queue.clear();
+        assert!(ledger.reserved().is_empty());
+        assert_eq!(ledger.spent(&token, 100).unwrap(), 400);
+    }
+
+    /// 记录 GCON 获取次数的网络桩
+    struct CountingNetwork {
+        gcon: crate::smt::SparseMerkleTree,
+        cft_root: CftRoot,
+        leaf_fetches: std::sync::atomic::AtomicUsize,
+    }
+
+    impl CountingNetwork {
+        fn checkpoint_leaf(&self) -> crate::checkpoint::CheckpointLeaf {
+            crate::checkpoint::CheckpointLeaf { block_number: 1, gusr_root: [0u8; 32], gcon_root: self.gcon.root() }
+        }
+    }
+
+    impl crate::traits::NetworkState for CountingNetwork {
+        fn latest_finalized_chkp(&self) -> Result<CheckpointRef> {
+            Ok(self.checkpoint_leaf().checkpoint_ref(&crate::hash::Sha256Hasher))
+        }
+
+        fn fetch_checkpoint_leaf(&self, _chkp: &CheckpointRef) -> Result<crate::checkpoint::CheckpointLeaf> {
+            Ok(self.checkpoint_leaf())
+        }
+
+        fn fetch_user_leaf(&self, _user_id: &UserId, _chkp: &CheckpointRef) -> Result<UserLeafCtx> {
+            Err(PsyGuardError::NotFound("未实现".to_string()))
+        }
+
+        fn fetch_contract_meta(&self, _contract_id: &ContractId, _chkp: &CheckpointRef)
+            -> Result<(CftRoot, CstateHeight)> {
+            Ok((self.cft_root.clone(), 32))
+        }
+
+        fn fetch_contract_leaf(&self, contract_id: &ContractId, _chkp: &CheckpointRef)
+            -> Result<GconLeafProof> {
+            self.leaf_fetches.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(GconLeafProof {
+                cft_root: self.cft_root.clone(),
+                cstate_height: 32,
+                gcon_root: self.gcon.root(),
+                proof: self.gcon.prove(CftRegistry::gcon_index(contract_id))?,
+            })
+        }
+
+        fn fetch_cstate_leaf(&self, _owner: &UserId, _contract_id: &ContractId, _slot: u64,
+            _chkp: &CheckpointRef) -> Result<CstateReadWitness> {
+            Err(PsyGuardError::NotFound("未实现".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_queue_cft_verification_batches_per_contract() {
+        use crate::hash::Sha256Hasher;
+        use std::sync::Arc;
+
+        let functions: Vec<ContractFunction> = ["transfer", "approve", "claim", "mint"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| ContractFunction {
+                name: name.to_string(),
+                fingerprint: CfcFingerprint([i as u8 + 1; 32]),
+            })
+            .collect();
+        let fingerprints: Vec<_> = functions.iter().map(|f| f.fingerprint).collect();
+        let cft_root = crate::cft::CftTree::build(&Sha256Hasher, &fingerprints).unwrap().root();
+
+        let mut gcon = crate::smt::SparseMerkleTree::new(crate::registry::GCON_TREE_HEIGHT).unwrap();
+        for id in ["token", "nft"] {
+            let contract_id = ContractId(id.to_string());
+            let leaf = CftRegistry::contract_leaf_hash(&Sha256Hasher, &contract_id, &cft_root, 32);
+            gcon.set_leaf(CftRegistry::gcon_index(&contract_id), leaf).unwrap();
+        }
+        let network = Arc::new(CountingNetwork {
+            gcon,
+            cft_root,
+            leaf_fetches: Default::default(),
+        });
+
+        let registry = CftRegistry::new(network.clone(), Arc::new(Sha256Hasher));
+        for id in ["token", "nft"] {
+            registry.register_contract(ContractId(id.to_string()), functions.clone()).unwrap();
+        }
+
+        let mut queue = UpsQueue::new([0u8; 32]);
+        for (contract, function) in [
+            ("token", "transfer"),
+            ("token", "approve"),
+            ("nft", "mint"),
+            ("token", "claim"),
+            ("token", "burn"),
+        ] {
+            queue.add_item(
+                CfcId {
+                    contract_id: ContractId(contract.to_string()),
+                    function_name: function.to_string(),
+                },
+                "{}".to_string(),
+            );
+        }
+
+        let chkp = crate::traits::NetworkState::latest_finalized_chkp(network.as_ref()).unwrap();
+        queue.verify_cft(&registry, &chkp).unwrap();
+
+        // 每个合约只获取一次 GCON 叶
+        assert_eq!(network.leaf_fetches.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        let items = queue.get_items();
+        for item in &items[..4] {
+            let result = item.cft_verification.as_ref().unwrap();
+            assert!(result.in_cft, "{:?}", item.cfc_id);
+            assert_eq!(result.depth, 2);
+        }
+        assert_eq!(items[0].cft_verification.as_ref().unwrap().fingerprint, functions[0].fingerprint);
+
+        // 不在 CFT 中的函数
+        let burn = items[4].cft_verification.as_ref().unwrap();
+        assert!(!burn.in_cft);
+        assert_eq!(items[4].status, UpsQueueItemStatus::Failed);
+    }
 }
@@ -0,0 +1,689 @@
+//! CFT 注册表
+//!
+//! 按合约缓存经 GCON 合约叶校验过的 CFT 根，并为 CfcId 提供 CFT 包含证明。
+//! GCON 根取自与会话 CheckpointRef 一致的 CHKP 叶，而非合约叶响应本身。
+//! 函数清单由调用方登记 (合约部署数据)，其 CFT 根必须与链上记录一致。
+//! 参考: 《6-Smart Contracts.md》- CFT 指纹白名单
+//! 参考: 《6-Smart Contracts.md》- 从 GCON 获取合约信息
+
+use crate::types::*;
+use crate::traits::{AsyncNetworkState, NetworkState};
+use crate::error::{PsyGuardError, Result};
+use crate::cft::CftTree;
+use crate::checkpoint::CheckpointLeaf;
+use crate::hash::MerkleHasher;
+use crate::smt::SparseMerkleTree;
+use crate::state::Ucon;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// GCON 树高 (合约叶索引与 UCON 相同，为合约 ID 哈希的前 8 字节)
+pub const GCON_TREE_HEIGHT: u32 = 64;
+
+/// 合约叶哈希域分隔标签
+const CLEAF_DOMAIN: &[u8] = b"psyguard/gcon-cleaf";
+
+/// 某个 CFC 的指纹及其 CFT 包含证明
+#[derive(Debug, Clone)]
+pub struct CfcCftProof {
+    pub fingerprint: CfcFingerprint,
+    pub proof: CftInclusionProof,
+}
+
+/// 同一合约多个 CFC 的指纹及其批量 CFT 证明
+#[derive(Debug, Clone)]
+pub struct ContractCftMultiProof {
+    /// (函数名, 指纹)，与 proof.leaf_indices 一一对应
+    pub functions: Vec<(String, CfcFingerprint)>,
+    pub proof: CftMultiProof,
+}
+
+/// 已通过 GCON 校验的合约
+#[derive(Debug, Clone)]
+struct VerifiedContract {
+    cft_root: CftRoot,
+    cstate_height: CstateHeight,
+    /// 由函数清单构建、根已与 cft_root 比对的 CFT
+    tree: Option<Arc<CftTree>>,
+}
+
+/// 单个 checkpoint 内的缓存
+#[derive(Default)]
+struct RegistryCache {
+    checkpoint: Option<CheckpointRef>,
+    /// 已校验哈希等于 chkp_root 的 CHKP 叶
+    checkpoint_leaf: Option<CheckpointLeaf>,
+    contracts: HashMap<ContractId, VerifiedContract>,
+}
+
+impl RegistryCache {
+    /// 切换到新的 checkpoint 时丢弃旧缓存
+    fn select(&mut self, chkp: &CheckpointRef) {
+        let same = self.checkpoint.as_ref().is_some_and(|current| {
+            current.block_number == chkp.block_number && current.chkp_root == chkp.chkp_root
+        });
+        if !same {
+            *self = RegistryCache {
+                checkpoint: Some(chkp.clone()),
+                ..RegistryCache::default()
+            };
+        }
+    }
+}
+
+/// 获取 GCON 合约叶的网络后端
+enum ContractSource {
+    Sync(Arc<dyn NetworkState>),
+    Async(Arc<dyn AsyncNetworkState>),
+}
+
+/// CFT 注册表
+pub struct CftRegistry {
+    network: ContractSource,
+    hasher: Arc<dyn MerkleHasher>,
+    manifests: Mutex<HashMap<ContractId, Vec<ContractFunction>>>,
+    cache: Mutex<RegistryCache>,
+}
+
+impl CftRegistry {
+    pub fn new(network: Arc<dyn NetworkState>, hasher: Arc<dyn MerkleHasher>) -> Self {
+        Self::with_source(ContractSource::Sync(network), hasher)
+    }
+
+    /// 使用异步网络后端
+    ///
+    /// 同步方法只读取缓存，合约需先经 load_contract 获取并校验。
+    pub fn with_async_network(
+        network: Arc<dyn AsyncNetworkState>,
+        hasher: Arc<dyn MerkleHasher>,
+    ) -> Self {
+        Self::with_source(ContractSource::Async(network), hasher)
+    }
+
+    fn with_source(network: ContractSource, hasher: Arc<dyn MerkleHasher>) -> Self {
+        Self {
+            network,
+            hasher,
+            manifests: Mutex::new(HashMap::new()),
+            cache: Mutex::new(RegistryCache::default()),
+        }
+    }
+
+    /// 注册表使用的哈希函数
+    pub fn hasher(&self) -> &Arc<dyn MerkleHasher> {
+        &self.hasher
+    }
+
+    /// 登记合约的函数清单 (按 CFT 叶顺序)
+    pub fn register_contract(
+        &self,
+        contract_id: ContractId,
+        functions: Vec<ContractFunction>,
+    ) -> Result<()> {
+        if functions.is_empty() {
+            return Err(PsyGuardError::InvalidInput(format!(
+                "合约 {:?} 的函数清单为空",
+                contract_id
+            )));
+        }
+        let mut names = HashSet::new();
+        for function in &functions {
+            if !names.insert(function.name.as_str()) {
+                return Err(PsyGuardError::InvalidInput(format!(
+                    "合约 {:?} 的函数 {} 重复登记",
+                    contract_id, function.name
+                )));
+            }
+        }
+
+        if let Some(verified) = self.cache.lock().unwrap().contracts.get_mut(&contract_id) {
+            verified.tree = None;
+        }
+        self.manifests.lock().unwrap().insert(contract_id, functions);
+        Ok(())
+    }
+
+    /// 获取经 GCON 校验的合约元数据 (CFT 根和 CSTATE 高度)
+    pub fn contract_meta(
+        &self,
+        contract_id: &ContractId,
+        chkp: &CheckpointRef,
+    ) -> Result<(CftRoot, CstateHeight)> {
+        let verified = self.verified_contract(contract_id, chkp)?;
+        Ok((verified.cft_root, verified.cstate_height))
+    }
+
+    /// 获取经校验的 CHKP 叶
+    ///
+    /// 异步网络后端只读取缓存，需先经 load_contract 获取并校验。
+    pub fn checkpoint_leaf(&self, chkp: &CheckpointRef) -> Result<CheckpointLeaf> {
+        if let Some(checkpoint) = self.cached_checkpoint(chkp) {
+            return Ok(checkpoint);
+        }
+        let checkpoint = match &self.network {
+            ContractSource::Sync(network) => network.fetch_checkpoint_leaf(chkp)?,
+            ContractSource::Async(_) => {
+                return Err(PsyGuardError::NetworkError(format!(
+                    "checkpoint {} 的 CHKP 叶尚未加载，异步网络后端需先调用 load_contract",
+                    hex::encode(chkp.chkp_root)
+                )));
+            }
+        };
+        checkpoint.verify(self.hasher.as_ref(), chkp)?;
+
+        let mut cache = self.cache.lock().unwrap();
+        cache.select(chkp);
+        cache.checkpoint_leaf = Some(checkpoint.clone());
+        Ok(checkpoint)
+    }
+
+    /// 获取并校验合约的 GCON 记录 (已缓存时直接返回)
+    pub async fn load_contract(
+        &self,
+        contract_id: &ContractId,
+        chkp: &CheckpointRef,
+    ) -> Result<(CftRoot, CstateHeight)> {
+        if let Some(verified) = self.cached_contract(contract_id, chkp) {
+            return Ok((verified.cft_root, verified.cstate_height));
+        }
+
+        let cached_checkpoint = self.cached_checkpoint(chkp);
+        let (checkpoint, meta, leaf) = match &self.network {
+            ContractSource::Sync(network) => (
+                match cached_checkpoint {
+                    Some(checkpoint) => checkpoint,
+                    None => network.fetch_checkpoint_leaf(chkp)?,
+                },
+                network.fetch_contract_meta(contract_id, chkp)?,
+                network.fetch_contract_leaf(contract_id, chkp)?,
+            ),
+            ContractSource::Async(network) => (
+                match cached_checkpoint {
+                    Some(checkpoint) => checkpoint,
+                    None => network.fetch_checkpoint_leaf(chkp).await?,
+                },
+                network.fetch_contract_meta(contract_id, chkp).await?,
+                network.fetch_contract_leaf(contract_id, chkp).await?,
+            ),
+        };
+        let verified = self.insert_contract(contract_id, chkp, &checkpoint, meta, leaf)?;
+        Ok((verified.cft_root, verified.cstate_height))
+    }
+
+    /// 为 CFC 生成 CFT 包含证明
+    ///
+    /// CFT 根取自已校验的 GCON 合约叶，登记的函数清单必须重建出同一个根。
+    pub fn prove(&self, cfc_id: &CfcId, chkp: &CheckpointRef) -> Result<CfcCftProof> {
+        let label = format!("{}::{}", cfc_id.contract_id.0, cfc_id.function_name);
+        let (functions, tree) = self.contract_tree(&cfc_id.contract_id, &label, chkp)?;
+        let index = functions
+            .iter()
+            .position(|f| f.name == cfc_id.function_name)
+            .ok_or_else(|| PsyGuardError::CftVerificationFailed(label.clone()))?;
+
+        Ok(CfcCftProof {
+            fingerprint: functions[index].fingerprint,
+            proof: tree.prove(index)?,
+        })
+    }
+
+    /// 为同一合约的多个函数生成一份批量 CFT 证明
+    ///
+    /// 不在函数清单中的函数名不会出现在结果里；全部未知时返回 CftVerificationFailed。
+    pub fn prove_multi(
+        &self,
+        contract_id: &ContractId,
+        function_names: &[String],
+        chkp: &CheckpointRef,
+    ) -> Result<ContractCftMultiProof> {
+        let label = format!("{}::{{{}}}", contract_id.0, function_names.join(","));
+        let (functions, tree) = self.contract_tree(contract_id, &label, chkp)?;
+
+        let indices: Vec<usize> = functions
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| function_names.contains(&f.name))
+            .map(|(i, _)| i)
+            .collect();
+        if indices.is_empty() {
+            return Err(PsyGuardError::CftVerificationFailed(label));
+        }
+
+        Ok(ContractCftMultiProof {
+            functions: indices
+                .iter()
+                .map(|i| (functions[*i].name.clone(), functions[*i].fingerprint))
+                .collect(),
+            proof: tree.prove_multi(&indices)?,
+        })
+    }
+
+    /// 取出合约的函数清单及其 CFT (根已与 GCON 记录比对)
+    fn contract_tree(
+        &self,
+        contract_id: &ContractId,
+        label: &str,
+        chkp: &CheckpointRef,
+    ) -> Result<(Vec<ContractFunction>, Arc<CftTree>)> {
+        let verified = self.verified_contract(contract_id, chkp)?;
+
+        let functions = self
+            .manifests
+            .lock()
+            .unwrap()
+            .get(contract_id)
+            .cloned()
+            .ok_or_else(|| PsyGuardError::CftVerificationFailed(format!("{} (合约未登记函数清单)", label)))?;
+
+        let tree = match verified.tree {
+            Some(tree) => tree,
+            None => {
+                let fingerprints: Vec<CfcFingerprint> =
+                    functions.iter().map(|f| f.fingerprint).collect();
+                let tree = Arc::new(CftTree::build(self.hasher.as_ref(), &fingerprints)?);
+                if tree.root().0 != verified.cft_root.0 {
+                    return Err(PsyGuardError::CftVerificationFailed(format!(
+                        "{} (函数清单的 CFT 根 {} 与 GCON 记录 {} 不一致)",
+                        label,
+                        hex::encode(tree.root().0),
+                        hex::encode(verified.cft_root.0)
+                    )));
+                }
+
+                let mut cache = self.cache.lock().unwrap();
+                cache.select(chkp);
+                if let Some(entry) = cache.contracts.get_mut(contract_id) {
+                    entry.tree = Some(tree.clone());
+                }
+                tree
+            }
+        };
+
+        Ok((functions, tree))
+    }
+
+    /// 合约在 GCON 中的叶索引
+    pub fn gcon_index(contract_id: &ContractId) -> u64 {
+        Ucon::contract_index(contract_id)
+    }
+
+    /// 合约叶 (CLEAF) 哈希
+    pub fn contract_leaf_hash(
+        hasher: &dyn MerkleHasher,
+        contract_id: &ContractId,
+        cft_root: &CftRoot,
+        cstate_height: CstateHeight,
+    ) -> Hash {
+        let id = contract_id.0.as_bytes();
+        let mut preimage = Vec::with_capacity(CLEAF_DOMAIN.len() + 4 + id.len() + 40);
+        preimage.extend_from_slice(CLEAF_DOMAIN);
+        preimage.extend_from_slice(&(id.len() as u32).to_le_bytes());
+        preimage.extend_from_slice(id);
+        preimage.extend_from_slice(&cft_root.0);
+        preimage.extend_from_slice(&cstate_height.to_le_bytes());
+        hasher.hash_bytes(&preimage)
+    }
+
+    /// 校验 GCON 合约叶证明与元数据一致，且证明锚定在 CHKP 叶的 GCON 根下
+    ///
+    /// `checkpoint` 须已校验与会话的 CheckpointRef 一致 (见 `CheckpointLeaf::verify`)。
+    pub fn verify_contract_leaf(
+        hasher: &dyn MerkleHasher,
+        contract_id: &ContractId,
+        meta: &(CftRoot, CstateHeight),
+        checkpoint: &CheckpointLeaf,
+        leaf: &GconLeafProof,
+    ) -> Result<()> {
+        if leaf.gcon_root != checkpoint.gcon_root {
+            return Err(PsyGuardError::GconVerificationFailed(format!(
+                "合约 {:?} 的 GCON 根 {} 不是区块 {} 的 GCON 根",
+                contract_id,
+                hex::encode(leaf.gcon_root),
+                checkpoint.block_number
+            )));
+        }
+        if leaf.cft_root.0 != meta.0.0 || leaf.cstate_height != meta.1 {
+            return Err(PsyGuardError::GconVerificationFailed(format!(
+                "合约 {:?} 的元数据与 GCON 叶不一致",
+                contract_id
+            )));
+        }
+
+        let expected_leaf =
+            Self::contract_leaf_hash(hasher, contract_id, &leaf.cft_root, leaf.cstate_height);
+        if leaf.proof.index != Self::gcon_index(contract_id)
+            || leaf.proof.value != expected_leaf
+            || !SparseMerkleTree::verify_proof(hasher, GCON_TREE_HEIGHT, &leaf.gcon_root, &leaf.proof)
+        {
+            return Err(PsyGuardError::GconVerificationFailed(format!(
+                "合约 {:?} 的 CLEAF 不在 GCON 根 {} 下",
+                contract_id,
+                hex::encode(leaf.gcon_root)
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// 取出 (或获取并校验) 合约的 GCON 记录
+    fn verified_contract(
+        &self,
+        contract_id: &ContractId,
+        chkp: &CheckpointRef,
+    ) -> Result<VerifiedContract> {
+        if let Some(verified) = self.cached_contract(contract_id, chkp) {
+            return Ok(verified);
+        }
+
+        let network = match &self.network {
+            ContractSource::Sync(network) => network,
+            ContractSource::Async(_) => {
+                return Err(PsyGuardError::NetworkError(format!(
+                    "合约 {:?} 尚未加载，异步网络后端需先调用 load_contract",
+                    contract_id
+                )));
+            }
+        };
+        let checkpoint = match self.cached_checkpoint(chkp) {
+            Some(checkpoint) => checkpoint,
+            None => network.fetch_checkpoint_leaf(chkp)?,
+        };
+        let meta = network.fetch_contract_meta(contract_id, chkp)?;
+        let leaf = network.fetch_contract_leaf(contract_id, chkp)?;
+        self.insert_contract(contract_id, chkp, &checkpoint, meta, leaf)
+    }
+
+    /// 当前 checkpoint 下已校验的 CHKP 叶
+    fn cached_checkpoint(&self, chkp: &CheckpointRef) -> Option<CheckpointLeaf> {
+        let mut cache = self.cache.lock().unwrap();
+        cache.select(chkp);
+        cache.checkpoint_leaf.clone()
+    }
+
+    /// 当前 checkpoint 下已缓存的合约记录
+    fn cached_contract(&self, contract_id: &ContractId, chkp: &CheckpointRef) -> Option<VerifiedContract> {
+        let mut cache = self.cache.lock().unwrap();
+        cache.select(chkp);
+        cache.contracts.get(contract_id).cloned()
+    }
+
+    /// 校验 CHKP 叶与 GCON 合约叶并写入缓存
+    fn insert_contract(
+        &self,
+        contract_id: &ContractId,
+        chkp: &CheckpointRef,
+        checkpoint: &CheckpointLeaf,
+        meta: (CftRoot, CstateHeight),
+        leaf: GconLeafProof,
+    ) -> Result<VerifiedContract> {
+        checkpoint.verify(self.hasher.as_ref(), chkp)?;
+        Self::verify_contract_leaf(self.hasher.as_ref(), contract_id, &meta, checkpoint, &leaf)?;
+
+        let mut cache = self.cache.lock().unwrap();
+        cache.select(chkp);
+        cache.checkpoint_leaf = Some(checkpoint.clone());
+
+        let verified = VerifiedContract {
+            cft_root: meta.0,
+            cstate_height: meta.1,
+            tree: None,
+        };
+        cache.contracts.insert(contract_id.clone(), verified.clone());
+        Ok(verified)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cft::CftVerifier;
+    use crate::hash::Sha256Hasher;
+    use crate::traits::SyncAdapter;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 持有真实 GCON 树的网络桩
+    struct StubNetwork {
+        gcon: SparseMerkleTree,
+        metas: HashMap<ContractId, (CftRoot, CstateHeight)>,
+        /// 覆盖 fetch_contract_meta 返回的 CFT 根 (模拟不诚实节点)
+        forged_meta: Option<CftRoot>,
+        /// 覆盖 CHKP 叶中的 GCON 根 (合约叶只在桩自己的 GCON 下自洽)
+        checkpoint_gcon: Option<Hash>,
+        meta_fetches: AtomicUsize,
+        checkpoint_fetches: AtomicUsize,
+    }
+
+    impl StubNetwork {
+        fn new(contracts: &[(&str, &[ContractFunction])]) -> Self {
+            let mut gcon = SparseMerkleTree::new(GCON_TREE_HEIGHT).unwrap();
+            let mut metas = HashMap::new();
+            for (id, functions) in contracts {
+                let contract_id = ContractId(id.to_string());
+                let fingerprints: Vec<_> = functions.iter().map(|f| f.fingerprint).collect();
+                let cft_root = CftTree::build(&Sha256Hasher, &fingerprints).unwrap().root();
+                let leaf = CftRegistry::contract_leaf_hash(&Sha256Hasher, &contract_id, &cft_root, 32);
+                gcon.set_leaf(CftRegistry::gcon_index(&contract_id), leaf).unwrap();
+                metas.insert(contract_id, (cft_root, 32));
+            }
+
+            Self {
+                gcon,
+                metas,
+                forged_meta: None,
+                checkpoint_gcon: None,
+                meta_fetches: AtomicUsize::new(0),
+                checkpoint_fetches: AtomicUsize::new(0),
+            }
+        }
+
+        /// 各区块共用同一 GCON
+        fn checkpoint_leaf(&self, block_number: u64) -> CheckpointLeaf {
+            CheckpointLeaf {
+                block_number,
+                gusr_root: [0u8; 32],
+                gcon_root: self.checkpoint_gcon.unwrap_or_else(|| self.gcon.root()),
+            }
+        }
+
+        fn checkpoint(&self, block_number: u64) -> CheckpointRef {
+            self.checkpoint_leaf(block_number).checkpoint_ref(&Sha256Hasher)
+        }
+    }
+
+    impl NetworkState for StubNetwork {
+        fn latest_finalized_chkp(&self) -> Result<CheckpointRef> {
+            Ok(self.checkpoint(1))
+        }
+
+        fn fetch_checkpoint_leaf(&self, chkp: &CheckpointRef) -> Result<CheckpointLeaf> {
+            self.checkpoint_fetches.fetch_add(1, Ordering::SeqCst);
+            Ok(self.checkpoint_leaf(chkp.block_number))
+        }
+
+        fn fetch_user_leaf(&self, _user_id: &UserId, _chkp: &CheckpointRef) -> Result<UserLeafCtx> {
+            Err(PsyGuardError::NotFound("未实现".to_string()))
+        }
+
+        fn fetch_contract_meta(&self, contract_id: &ContractId, _chkp: &CheckpointRef)
+            -> Result<(CftRoot, CstateHeight)> {
+            self.meta_fetches.fetch_add(1, Ordering::SeqCst);
+            let (root, height) = self.metas.get(contract_id).cloned()
+                .ok_or_else(|| PsyGuardError::NotFound(format!("{:?}", contract_id)))?;
+            Ok((self.forged_meta.clone().unwrap_or(root), height))
+        }
+
+        fn fetch_contract_leaf(&self, contract_id: &ContractId, _chkp: &CheckpointRef)
+            -> Result<GconLeafProof> {
+            let (cft_root, cstate_height) = self.metas.get(contract_id).cloned()
+                .ok_or_else(|| PsyGuardError::NotFound(format!("{:?}", contract_id)))?;
+            Ok(GconLeafProof {
+                cft_root,
+                cstate_height,
+                gcon_root: self.gcon.root(),
+                proof: self.gcon.prove(CftRegistry::gcon_index(contract_id))?,
+            })
+        }
+
+        fn fetch_cstate_leaf(&self, _owner: &UserId, _contract_id: &ContractId, _slot: u64,
+            _chkp: &CheckpointRef) -> Result<CstateReadWitness> {
+            Err(PsyGuardError::NotFound("未实现".to_string()))
+        }
+    }
+
+    fn function(name: &str, seed: u8) -> ContractFunction {
+        ContractFunction { name: name.to_string(), fingerprint: CfcFingerprint([seed; 32]) }
+    }
+
+    fn cfc(contract: &str, function: &str) -> CfcId {
+        CfcId {
+            contract_id: ContractId(contract.to_string()),
+            function_name: function.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_registry_proves_registered_functions() {
+        let token = [function("transfer", 1), function("mint", 2), function("burn", 3)];
+        let network = Arc::new(StubNetwork::new(&[("token", &token)]));
+        let registry = CftRegistry::new(network.clone(), Arc::new(Sha256Hasher));
+        registry.register_contract(ContractId("token".to_string()), token.to_vec()).unwrap();
+
+        let chkp = network.checkpoint(1);
+        let proof = registry.prove(&cfc("token", "mint"), &chkp).unwrap();
+        assert_eq!(proof.fingerprint, token[1].fingerprint);
+        assert_eq!(proof.proof.cft_root.0, network.metas[&ContractId("token".to_string())].0.0);
+        assert!(CftVerifier::verify_inclusion(&Sha256Hasher, &proof.fingerprint, &proof.proof).unwrap());
+
+        // 同一 checkpoint 内命中缓存，新 checkpoint 重新获取
+        registry.prove(&cfc("token", "burn"), &chkp).unwrap();
+        assert_eq!(network.meta_fetches.load(Ordering::SeqCst), 1);
+        registry.prove(&cfc("token", "burn"), &network.checkpoint(2)).unwrap();
+        assert_eq!(network.meta_fetches.load(Ordering::SeqCst), 2);
+
+        // 未知函数 / 未登记合约
+        assert!(matches!(
+            registry.prove(&cfc("token", "approve"), &chkp),
+            Err(PsyGuardError::CftVerificationFailed(_))
+        ));
+        assert!(registry.prove(&cfc("nft", "transfer"), &chkp).is_err());
+
+        // 批量证明: 未知函数被略过
+        let names: Vec<String> = ["burn", "approve", "transfer"].iter().map(|s| s.to_string()).collect();
+        let multi = registry.prove_multi(&ContractId("token".to_string()), &names, &chkp).unwrap();
+        assert_eq!(
+            multi.functions.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(),
+            vec!["transfer", "burn"]
+        );
+        let fingerprints: Vec<_> = multi.functions.iter().map(|(_, fp)| *fp).collect();
+        assert!(CftVerifier::verify_multi(&Sha256Hasher, &fingerprints, &multi.proof).unwrap());
+    }
+
+    #[test]
+    fn test_registry_rejects_mismatched_manifest() {
+        let token = [function("transfer", 1), function("mint", 2)];
+        let network = Arc::new(StubNetwork::new(&[("token", &token)]));
+        let chkp = network.checkpoint(1);
+        let registry = CftRegistry::new(network, Arc::new(Sha256Hasher));
+
+        // 清单中混入链上 CFT 之外的函数
+        let forged = vec![function("transfer", 1), function("mint", 9)];
+        registry.register_contract(ContractId("token".to_string()), forged).unwrap();
+        assert!(matches!(
+            registry.prove(&cfc("token", "mint"), &chkp),
+            Err(PsyGuardError::CftVerificationFailed(_))
+        ));
+
+        // 重新登记正确清单后可以证明
+        registry.register_contract(ContractId("token".to_string()), token.to_vec()).unwrap();
+        assert!(registry.prove(&cfc("token", "mint"), &chkp).is_ok());
+
+        assert!(registry.register_contract(ContractId("x".to_string()), vec![]).is_err());
+        assert!(registry
+            .register_contract(ContractId("x".to_string()), vec![function("a", 1), function("a", 2)])
+            .is_err());
+    }
+
+    #[test]
+    fn test_registry_rejects_forged_contract_meta() {
+        let token = [function("transfer", 1)];
+        let mut network = StubNetwork::new(&[("token", &token)]);
+        network.forged_meta = Some(CftTree::build(&Sha256Hasher, &[CfcFingerprint([7u8; 32])]).unwrap().root());
+        let chkp = network.checkpoint(1);
+        let registry = CftRegistry::new(Arc::new(network), Arc::new(Sha256Hasher));
+        registry
+            .register_contract(ContractId("token".to_string()), vec![function("transfer", 7)])
+            .unwrap();
+
+        assert!(matches!(
+            registry.prove(&cfc("token", "transfer"), &chkp),
+            Err(PsyGuardError::GconVerificationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_registry_anchors_gcon_root_to_checkpoint() {
+        let honest = Arc::new(StubNetwork::new(&[
+            ("token", &[function("transfer", 1)]),
+            ("nft", &[function("mint", 2)]),
+        ]));
+        let chkp = honest.checkpoint(1);
+
+        // 伪造的合约叶与元数据在伪造节点自己的 GCON 下自洽，但 CHKP 叶记录的是真实 GCON 根
+        let mut forged = StubNetwork::new(&[("token", &[function("transfer", 7)])]);
+        forged.checkpoint_gcon = Some(honest.gcon.root());
+        let registry = CftRegistry::new(Arc::new(forged), Arc::new(Sha256Hasher));
+        registry
+            .register_contract(ContractId("token".to_string()), vec![function("transfer", 7)])
+            .unwrap();
+        assert!(matches!(
+            registry.prove(&cfc("token", "transfer"), &chkp),
+            Err(PsyGuardError::GconVerificationFailed(_))
+        ));
+
+        // CHKP 叶与会话的 CheckpointRef 不一致
+        let registry = CftRegistry::new(honest.clone(), Arc::new(Sha256Hasher));
+        registry
+            .register_contract(ContractId("token".to_string()), vec![function("transfer", 1)])
+            .unwrap();
+        registry
+            .register_contract(ContractId("nft".to_string()), vec![function("mint", 2)])
+            .unwrap();
+        let stale = CheckpointRef { chkp_root: [1u8; 32], ..chkp.clone() };
+        assert!(matches!(
+            registry.prove(&cfc("token", "transfer"), &stale),
+            Err(PsyGuardError::MerkleProofInvalid(_))
+        ));
+        assert!(registry.prove(&cfc("token", "transfer"), &chkp).is_ok());
+
+        // 同一 checkpoint 下各合约共用已校验的 CHKP 叶
+        let fetches = honest.checkpoint_fetches.load(Ordering::SeqCst);
+        assert!(registry.prove(&cfc("nft", "mint"), &chkp).is_ok());
+        assert_eq!(honest.checkpoint_fetches.load(Ordering::SeqCst), fetches);
+    }
+
+    #[tokio::test]
+    async fn test_registry_async_network_requires_load() {
+        let token = [function("transfer", 1)];
+        let network = Arc::new(StubNetwork::new(&[("token", &token)]));
+        let registry = CftRegistry::with_async_network(
+            Arc::new(SyncAdapter::new(network.clone())),
+            Arc::new(Sha256Hasher),
+        );
+        registry.register_contract(ContractId("token".to_string()), token.to_vec()).unwrap();
+
+        let chkp = network.checkpoint(1);
+        assert!(matches!(
+            registry.prove(&cfc("token", "transfer"), &chkp),
+            Err(PsyGuardError::NetworkError(_))
+        ));
+
+        let token_id = ContractId("token".to_string());
+        let meta = registry.load_contract(&token_id, &chkp).await.unwrap();
+        assert_eq!(meta.0.0, network.metas[&token_id].0.0);
+        assert!(registry.prove(&cfc("token", "transfer"), &chkp).is_ok());
+
+        // 已缓存时不再访问网络
+        registry.load_contract(&token_id, &chkp).await.unwrap();
+        assert_eq!(network.meta_fetches.load(Ordering::SeqCst), 1);
+    }
+}
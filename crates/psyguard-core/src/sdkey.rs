@@ -3,184 +3,184 @@
 //! 参考: 《7-Psy Jargon.md》- SDKey 签名电路
 
 use crate::types::*;
-use crate::traits::SdkeyPolicy;
 use crate::error::{PsyGuardError, Result};
+use crate::ledger::SpendLedger;
+use crate::policy_inputs;
+use crate::second_factor::VerifiedFactor;
+use crate::policy_expr::{self, PolicyCounters, TxContext, DAILY_LIMIT_RULE, REQUIRE_2FA_RULE, TIME_LOCK_RULE, TRUSTED_CONTRACTS_RULE};
+
+/// 签名证明声称满足策略 2FA 要求时的约束标签 (见 `SignatureProof::policy_satisfied`)
+pub const REQUIRE_2FA_CONSTRAINT: &str = "2fa_required";
+
+impl SdkeyPolicy {
+    /// 从 JSON 解析策略，拒绝本版本无法求值的模式与非法规则
+    pub fn from_json(json: &str) -> Result<Self> {
+        let policy: SdkeyPolicy = serde_json::from_str(json)
+            .map_err(|e| PsyGuardError::SerializationError(format!("策略解析失败: {}", e)))?;
+        SdkeyPolicyValidator::check_version(&policy)?;
+        policy_expr::validate_rules(&policy.compiled_rules())?;
+        Ok(policy)
+    }
+
+    /// 固定字段编译出的内置规则，后接组合规则
+    pub fn compiled_rules(&self) -> Vec<PolicyRule> {
+        let mut rules = Vec::new();
+        if let Some(limit) = self.daily_limit {
+            rules.push(PolicyRule {
+                name: DAILY_LIMIT_RULE.to_string(),
+                expr: PolicyExpr::Cmp { field: NumField::DailyAmount, cmp: CmpOp::Le, value: limit },
+            });
+        }
+        if let Some(ref contracts) = self.trusted_contracts {
+            rules.push(PolicyRule {
+                name: TRUSTED_CONTRACTS_RULE.to_string(),
+                expr: PolicyExpr::In {
+                    field: StrField::Contract,
+                    values: contracts.iter().map(|c| c.0.clone()).collect(),
+                },
+            });
+        }
+        if let Some(until) = self.time_lock_until {
+            rules.push(PolicyRule {
+                name: TIME_LOCK_RULE.to_string(),
+                expr: PolicyExpr::Cmp { field: NumField::Timestamp, cmp: CmpOp::Ge, value: until },
+            });
+        }
+        if self.require_2fa {
+            rules.push(PolicyRule { name: REQUIRE_2FA_RULE.to_string(), expr: PolicyExpr::SecondFactor });
+        }
+        rules.extend(self.rules.iter().cloned());
+        rules
+    }
+
+    /// 编码为 JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(|e| PsyGuardError::SerializationError(e.to_string()))
+    }
+}
 
 /// SDKey 策略验证器
+/// 策略的唯一求值入口，预演、签名与 WASM 层都经由这里
 pub struct SdkeyPolicyValidator;
 
 impl SdkeyPolicyValidator {
-    /// 验证交易是否满足 SDKey 策略
-    /// 参考: 《7-Psy Jargon.md》- SDKey 策略约束
-    pub fn validate_transaction(
-        policy: &SdkeyPolicy,
-        tx_amount: u64,
-        contract_id: &ContractId,
-        timestamp: u64,
-    ) -> Result<Vec<String>> {
-        let mut satisfied_policies = Vec::new();
-
-        // 1. 检查日限额
-        if let Some(daily_limit) = policy.daily_limit {
-            if tx_amount > daily_limit {
-                return Err(PsyGuardError::SdkeyPolicyViolation(format!(
-                    "交易金额 {} 超过日限额 {}",
-                    tx_amount, daily_limit
-                )));
-            }
-            satisfied_policies.push(format!("日限额检查通过: {} <= {}", tx_amount, daily_limit));
+    /// 检查策略模式版本受支持
+    pub fn check_version(policy: &SdkeyPolicy) -> Result<()> {
+        if policy.version == 0 || policy.version > SDKEY_POLICY_VERSION {
+            return Err(PsyGuardError::SdkeyPolicyViolation(format!(
+                "不支持的策略版本 {} (当前 {})",
+                policy.version, SDKEY_POLICY_VERSION
+            )));
         }
-
-        // 2. 检查合约白名单
-        if let Some(ref trusted_contracts) = policy.trusted_contracts {
-            if !trusted_contracts.contains(contract_id) {
-                return Err(PsyGuardError::SdkeyPolicyViolation(format!(
-                    "合约 {:?} 不在白名单中",
-                    contract_id
-                )));
-            }
-            satisfied_policies.push(format!("合约白名单检查通过: {:?}", contract_id));
+        if policy.version < 2 && !policy.rules.is_empty() {
+            return Err(PsyGuardError::SdkeyPolicyViolation(
+                "组合规则需要策略版本 2".to_string(),
+            ));
         }
+        if policy.version < 3 && policy.second_factor_key.is_some() {
+            return Err(PsyGuardError::SdkeyPolicyViolation(
+                "登记第二因素凭据需要策略版本 3".to_string(),
+            ));
+        }
+        Ok(())
+    }
 
-        // 3. 检查时间锁
-        if let Some(time_lock_until) = policy.time_lock_until {
-            if timestamp < time_lock_until {
-                return Err(PsyGuardError::SdkeyPolicyViolation(format!(
-                    "时间锁未到期: 当前 {} < 解锁时间 {}",
-                    timestamp, time_lock_until
-                )));
-            }
-            satisfied_policies.push(format!("时间锁检查通过: {} >= {}", timestamp, time_lock_until));
+    /// 检查第二因素是策略登记的凭据 (策略未登记时接受任一凭据)
+    pub fn check_second_factor(policy: &SdkeyPolicy, factor: &VerifiedFactor) -> Result<()> {
+        match policy.second_factor_key {
+            Some(key) if factor.credential_id() != key => Err(PsyGuardError::SdkeyPolicyViolation(
+                "第二因素不是策略登记的凭据".to_string(),
+            )),
+            _ => Ok(()),
         }
+    }
 
-        // 4. 检查 2FA 要求
+    /// 策略启用的约束 (签名证明的 policy_satisfied 标签)
+    pub fn active_constraints(policy: &SdkeyPolicy) -> Result<Vec<String>> {
+        Self::check_version(policy)?;
+        let mut constraints = Vec::new();
+        if policy.daily_limit.is_some() {
+            constraints.push("daily_limit_checked".to_string());
+        }
+        if policy.trusted_contracts.is_some() {
+            constraints.push("contract_whitelist_checked".to_string());
+        }
+        if policy.time_lock_until.is_some() {
+            constraints.push("time_lock_checked".to_string());
+        }
         if policy.require_2fa {
-            // TODO: 实际应该验证 2FA 令牌
-            satisfied_policies.push("2FA 验证 (待实现)".to_string());
+            constraints.push(REQUIRE_2FA_CONSTRAINT.to_string());
+        }
+        if !policy.rules.is_empty() {
+            constraints.push("custom_rules_checked".to_string());
         }
+        Ok(constraints)
+    }
 
-        Ok(satisfied_policies)
+    /// 按策略求值一笔交易，返回逐条规则的结果
+    ///
+    /// 固定字段编译出的内置规则与组合规则一并求值；给出账本时，计数器
+    /// (会话内笔数与金额、窗口内累计消费) 取自账本。未登记的第二因素视为缺失。
+    /// 参考: 《7-Psy Jargon.md》- SDKey 策略约束
+    pub fn evaluate(
+        policy: &SdkeyPolicy,
+        tx: &TxContext,
+        ledger: Option<&SpendLedger>,
+    ) -> Result<PolicyEvaluation> {
+        Self::check_version(policy)?;
+        let counters = ledger
+            .map(|ledger| ledger.counters(tx.contract_id, tx.timestamp))
+            .transpose()?
+            .unwrap_or_default();
+        Self::evaluate_with_counters(policy, tx, counters)
     }
 
-    /// 实时约束检查 (返回详细结果)
-    /// 用于前端展示各项约束的通过/未通过状态
-    pub fn check_constraints(
+    /// 按给定的计数器求值 (账本在持有预留锁时使用)
+    pub(crate) fn evaluate_with_counters(
         policy: &SdkeyPolicy,
-        tx_amount: u64,
-        contract_id: &ContractId,
-        timestamp: u64,
-        twofa_verified: bool,
-    ) -> SdkeyConstraintCheck {
-        // 1. 限额检查
-        let limit_check = if let Some(daily_limit) = policy.daily_limit {
-            if tx_amount > daily_limit {
-                ConstraintCheckResult {
-                    passed: false,
-                    message: format!("超过日限额: {} > {}", tx_amount, daily_limit),
-                }
-            } else {
-                ConstraintCheckResult {
-                    passed: true,
-                    message: format!("通过: {} <= {}", tx_amount, daily_limit),
-                }
-            }
-        } else {
-            ConstraintCheckResult {
-                passed: true,
-                message: "未设置限额".to_string(),
-            }
-        };
-
-        // 2. 白名单检查
-        let whitelist_check = if let Some(ref trusted_contracts) = policy.trusted_contracts {
-            if trusted_contracts.contains(contract_id) {
-                ConstraintCheckResult {
-                    passed: true,
-                    message: format!("合约在白名单中: {:?}", contract_id),
-                }
-            } else {
-                ConstraintCheckResult {
-                    passed: false,
-                    message: format!("合约不在白名单中: {:?}", contract_id),
-                }
-            }
-        } else {
-            ConstraintCheckResult {
-                passed: true,
-                message: "未设置白名单".to_string(),
-            }
-        };
-
-        // 3. 时间锁检查
-        let timelock_check = if let Some(time_lock_until) = policy.time_lock_until {
-            if timestamp >= time_lock_until {
-                ConstraintCheckResult {
-                    passed: true,
-                    message: format!("已解锁: {} >= {}", timestamp, time_lock_until),
-                }
-            } else {
-                ConstraintCheckResult {
-                    passed: false,
-                    message: format!("未解锁: {} < {}", timestamp, time_lock_until),
-                }
-            }
-        } else {
-            ConstraintCheckResult {
-                passed: true,
-                message: "未设置时间锁".to_string(),
-            }
-        };
-
-        // 4. 2FA 检查
-        let twofa_check = if policy.require_2fa {
-            TwoFaCheckResult {
-                required: true,
-                verified: twofa_verified,
-                message: if twofa_verified {
-                    "2FA 已验证".to_string()
-                } else {
-                    "需要 2FA 验证".to_string()
-                },
-            }
-        } else {
-            TwoFaCheckResult {
-                required: false,
-                verified: true,
-                message: "不需要 2FA".to_string(),
-            }
-        };
-
-        SdkeyConstraintCheck {
-            limit_check,
-            whitelist_check,
-            timelock_check,
-            twofa_check,
+        tx: &TxContext,
+        counters: PolicyCounters,
+    ) -> Result<PolicyEvaluation> {
+        Self::check_version(policy)?;
+        let second_factor = tx
+            .second_factor
+            .filter(|factor| Self::check_second_factor(policy, factor).is_ok());
+        let tx = (*tx).with_second_factor(second_factor);
+        Ok(policy_expr::evaluate(&policy.compiled_rules(), &tx, counters))
+    }
+
+    /// 验证交易是否满足 SDKey 策略，返回各规则的通过原因
+    ///
+    /// 任一规则未通过 (含缺少第二因素) 即为策略违规。
+    pub fn validate_transaction(
+        policy: &SdkeyPolicy,
+        tx: &TxContext,
+        ledger: Option<&SpendLedger>,
+    ) -> Result<Vec<String>> {
+        Self::accept(Self::evaluate(policy, tx, ledger)?)
+    }
+
+    /// 全部规则通过时返回各规则的通过原因，否则为策略违规
+    pub(crate) fn accept(evaluation: PolicyEvaluation) -> Result<Vec<String>> {
+        if !evaluation.allowed() {
+            let reasons: Vec<String> = evaluation
+                .violations()
+                .map(|rule| format!("{}: {}", rule.name, rule.reason))
+                .collect();
+            return Err(PsyGuardError::SdkeyPolicyViolation(reasons.join("; ")));
         }
+        Ok(evaluation.rules.into_iter().map(|rule| rule.reason).collect())
     }
 
     /// 计算 SDKey 公钥哈希
+    ///
+    /// 策略的每个字段都编码为签名电路的公共输入，任一字段变化都会改变公钥。
     /// 参考: 《7-Psy Jargon.md》- 公钥 = 签名电路 verifier data 哈希
     pub fn compute_public_key_hash(
         verifier_data: &[u8],
         policy_params: &SdkeyPolicy,
     ) -> Hash {
-        use sha2::{Sha256, Digest};
-
-        let mut hasher = Sha256::new();
-        hasher.update(verifier_data);
-        
-        // 将策略参数也纳入公钥计算
-        if let Some(limit) = policy_params.daily_limit {
-            hasher.update(limit.to_le_bytes());
-        }
-        if let Some(time_lock) = policy_params.time_lock_until {
-            hasher.update(time_lock.to_le_bytes());
-        }
-
-        let result = hasher.finalize();
-        let mut hash = [0u8; 32];
-        hash.copy_from_slice(&result);
-        hash
+        policy_inputs::public_key_hash(verifier_data, &policy_inputs::PolicyPublicInputs::encode(policy_params))
     }
 }
 
@@ -190,14 +190,10 @@ pub struct SdkeyPolicyBuilder {
 }
 
 impl SdkeyPolicyBuilder {
+    /// 从不施加任何约束的策略开始构建
     pub fn new() -> Self {
         Self {
-            policy: SdkeyPolicy {
-                daily_limit: None,
-                trusted_contracts: None,
-                time_lock_until: None,
-                require_2fa: false,
-            },
+            policy: SdkeyPolicy { daily_limit: None, ..SdkeyPolicy::default() },
         }
     }
 
@@ -221,6 +217,16 @@ impl SdkeyPolicyBuilder {
         self
     }
 
+    pub fn with_rule(mut self, rule: PolicyRule) -> Self {
+        self.policy.rules.push(rule);
+        self
+    }
+
+    pub fn with_second_factor_key(mut self, credential_id: Hash) -> Self {
+        self.policy.second_factor_key = Some(credential_id);
+        self
+    }
+
     pub fn build(self) -> SdkeyPolicy {
         self.policy
     }
@@ -247,19 +253,101 @@ mod tests {
         // 应该通过
         let result = SdkeyPolicyValidator::validate_transaction(
             &policy,
-            500,
-            &contract_id,
-            1000000,
+            &TxContext::new(&contract_id, 500, 1000000),
+            None,
         );
         assert!(result.is_ok());
 
         // 应该失败
         let result = SdkeyPolicyValidator::validate_transaction(
             &policy,
-            1500,
-            &contract_id,
-            1000000,
+            &TxContext::new(&contract_id, 1500, 1000000),
+            None,
         );
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_policy_schema_version() {
+        // 未带版本号的旧格式视为 v1，缺省字段不施加约束
+        let legacy = SdkeyPolicy::from_json(r#"{"daily_limit": 10000, "trusted_contracts": ["token"]}"#)
+            .unwrap();
+        assert_eq!(legacy.version, 1);
+        assert_eq!(legacy.trusted_contracts, Some(vec![ContractId("token".to_string())]));
+        assert_eq!(legacy.time_lock_until, None);
+        assert!(!legacy.require_2fa);
+        assert_eq!(SdkeyPolicy::from_json(&legacy.to_json().unwrap()).unwrap(), legacy);
+
+        let future = SdkeyPolicy { version: SDKEY_POLICY_VERSION + 1, ..SdkeyPolicy::default() };
+        assert!(matches!(
+            SdkeyPolicy::from_json(&future.to_json().unwrap()),
+            Err(PsyGuardError::SdkeyPolicyViolation(_))
+        ));
+        assert!(SdkeyPolicyValidator::validate_transaction(&future, &TxContext::new(&ContractId("token".to_string()), 0, 0), None)
+            .is_err());
+
+        // 组合规则需要版本 2，旧求值器不会悄悄忽略它们
+        let json = r#"{"version": 1, "rules": [{"name": "cap", "expr": {"op": "const", "value": false}}]}"#;
+        assert!(SdkeyPolicy::from_json(json).is_err());
+        assert!(SdkeyPolicy::from_json(&json.replace(r#""version": 1"#, r#""version": 2"#)).is_ok());
+    }
+
+    #[test]
+    fn test_fixed_fields_and_rules_evaluate_together() {
+        let policy = SdkeyPolicyBuilder::new()
+            .with_daily_limit(1000)
+            .with_trusted_contracts(vec![ContractId("token".to_string())])
+            .with_rule(policy_expr::parse_rules("rule no_nft: contract != \"nft\"").unwrap().remove(0))
+            .with_2fa()
+            .build();
+        let token = ContractId("token".to_string());
+        let nft = ContractId("nft".to_string());
+
+        let eval = SdkeyPolicyValidator::evaluate(&policy, &TxContext::new(&token, 1200, 0), None).unwrap();
+        let names: Vec<&str> = eval.rules.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, [DAILY_LIMIT_RULE, TRUSTED_CONTRACTS_RULE, REQUIRE_2FA_RULE, "no_nft"]);
+        assert_eq!(eval.rule(DAILY_LIMIT_RULE).unwrap().outcome, RuleOutcome::Failed);
+        assert!(eval.rule(DAILY_LIMIT_RULE).unwrap().reason.contains("daily_amount = 1200"));
+        assert_eq!(eval.rule(REQUIRE_2FA_RULE).unwrap().outcome, RuleOutcome::RequiresSecondFactor);
+
+        let eval = SdkeyPolicyValidator::evaluate(&policy, &TxContext::new(&nft, 1, 0), None).unwrap();
+        assert_eq!(eval.rule(TRUSTED_CONTRACTS_RULE).unwrap().outcome, RuleOutcome::Failed);
+        assert_eq!(eval.rule("no_nft").unwrap().outcome, RuleOutcome::Failed);
+
+        // 缺少第二因素即违规
+        let error = SdkeyPolicyValidator::validate_transaction(&policy, &TxContext::new(&token, 1, 0), None)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(REQUIRE_2FA_RULE), "{}", error);
+        assert_eq!(SdkeyPolicy::from_json(&policy.to_json().unwrap()).unwrap(), policy);
+    }
+
+    #[test]
+    fn test_second_factor_key_binds_credential() {
+        use crate::second_factor::TotpEnrollment;
+
+        let mut enrolled = TotpEnrollment::new(b"enrolled-secret-0123".to_vec()).unwrap();
+        let mut other = TotpEnrollment::new(b"another-secret-4567".to_vec()).unwrap();
+        let policy = SdkeyPolicyBuilder::new()
+            .with_2fa()
+            .with_second_factor_key(enrolled.credential_id())
+            .build();
+        let token = ContractId("token".to_string());
+        let (now, binding) = (1_700_000_000, [9u8; 32]);
+
+        let factor = enrolled.verify(&enrolled.code_at(now), now, &binding).unwrap();
+        let tx = TxContext::new(&token, 1, now).with_second_factor(Some(&factor));
+        assert!(SdkeyPolicyValidator::validate_transaction(&policy, &tx, None).is_ok());
+
+        // 未登记的凭据视为缺少第二因素
+        let stranger = other.verify(&other.code_at(now), now, &binding).unwrap();
+        assert!(SdkeyPolicyValidator::check_second_factor(&policy, &stranger).is_err());
+        let tx = TxContext::new(&token, 1, now).with_second_factor(Some(&stranger));
+        let eval = SdkeyPolicyValidator::evaluate(&policy, &tx, None).unwrap();
+        assert_eq!(eval.rule(REQUIRE_2FA_RULE).unwrap().outcome, RuleOutcome::RequiresSecondFactor);
+
+        // 登记凭据需要版本 3
+        let legacy = SdkeyPolicy { version: 2, ..policy };
+        assert!(SdkeyPolicy::from_json(&legacy.to_json().unwrap()).is_err());
+    }
 }
@@ -0,0 +1,428 @@
+//! SDKey 第二因素认证
+//!
+//! 策略 `require_2fa` 的实际校验，支持两种因素:
+//! - RFC 6238 TOTP (HMAC-SHA1，默认 30 秒步长、6 位数字)
+//! - 挑战-应答: 由登记的第二公钥 (Ed25519，如硬件密钥) 对会话挑战签名
+//!
+//! 两种因素都针对不含 2FA 的会话消息哈希 (`session_binding`) 验证。验证通过得到的
+//! `VerifiedFactor` 只能由本模块构造；其凭证写入签名证明，承诺包含在会话消息中，
+//! 签名证明因此无法声称未经过的 2FA，也不能把 2FA 挪到其他会话。
+//! 参考: 《7-Psy Jargon.md》- SDKey 签名电路 (2FA)
+
+use crate::types::*;
+use crate::codec::{self, WireWriter};
+use crate::error::{PsyGuardError, Result};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// TOTP 默认时间步长 (秒)
+pub const TOTP_STEP_SECS: u64 = 30;
+
+/// TOTP 默认位数
+pub const TOTP_DIGITS: u32 = 6;
+
+/// 默认允许的时钟漂移 (前后各若干时间步)
+pub const TOTP_SKEW_STEPS: u64 = 1;
+
+/// TOTP 密钥最短长度 (RFC 4226 要求至少 128 位)
+pub const TOTP_MIN_SECRET_LEN: usize = 16;
+
+/// TOTP 凭据标识域分隔标签
+pub const TOTP_CREDENTIAL_DOMAIN: &[u8] = b"psyguard/2fa-totp-credential";
+
+/// 挑战域分隔标签
+pub const CHALLENGE_DOMAIN: &[u8] = b"psyguard/2fa-challenge";
+
+/// 挑战-应答凭据标识的域分隔
+pub const CHALLENGE_CREDENTIAL_DOMAIN: &[u8] = b"psyguard/2fa-challenge-credential";
+
+/// 凭证承诺域分隔标签
+pub const ATTESTATION_DOMAIN: &[u8] = b"psyguard/2fa-attestation";
+
+/// 已验证的第二因素
+///
+/// 只能由 `TotpEnrollment::verify` 或 `ChallengeKey::verify` 构造，
+/// 策略求值与会话终结以此代替调用方自报的布尔值。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedFactor {
+    attestation: SecondFactorAttestation,
+}
+
+impl VerifiedFactor {
+    /// 验证凭证
+    pub fn attestation(&self) -> &SecondFactorAttestation {
+        &self.attestation
+    }
+
+    /// 因素类型
+    pub fn method(&self) -> &'static str {
+        self.attestation.method()
+    }
+
+    /// 登记凭据标识
+    pub fn credential_id(&self) -> Hash {
+        self.attestation.credential_id()
+    }
+}
+
+impl SecondFactorAttestation {
+    /// 因素类型
+    pub fn method(&self) -> &'static str {
+        match self {
+            SecondFactorAttestation::Totp { .. } => "totp",
+            SecondFactorAttestation::Challenge { .. } => "challenge",
+        }
+    }
+
+    /// 登记凭据标识 (策略中 `second_factor_key` 的取值)
+    pub fn credential_id(&self) -> Hash {
+        match self {
+            SecondFactorAttestation::Totp { credential_id, .. } => *credential_id,
+            SecondFactorAttestation::Challenge { public_key, .. } => {
+                ChallengeKey::credential_id_of(public_key)
+            }
+        }
+    }
+
+    /// 验证时绑定的会话消息哈希 (不含 2FA)
+    pub fn session_binding(&self) -> &Hash {
+        match self {
+            SecondFactorAttestation::Totp { session_binding, .. }
+            | SecondFactorAttestation::Challenge { session_binding, .. } => session_binding,
+        }
+    }
+
+    /// 凭证承诺 (编码进会话消息)
+    pub fn commitment(&self) -> Hash {
+        let mut w = WireWriter::new();
+        w.put_bytes(ATTESTATION_DOMAIN);
+        codec::put_second_factor(&mut w, self);
+        sha256(&w.into_bytes())
+    }
+
+    /// 校验凭证绑定到给定会话
+    ///
+    /// 挑战-应答凭证可公开复验签名；TOTP 的密钥只在登记方，这里只检查绑定。
+    pub fn check(&self, session_binding: &Hash) -> Result<()> {
+        if self.session_binding() != session_binding {
+            return Err(PsyGuardError::SecondFactorFailed(
+                "凭证未绑定到本会话".to_string(),
+            ));
+        }
+        if let SecondFactorAttestation::Challenge { public_key, signature, .. } = self {
+            ChallengeKey::new(public_key)?.verify_signature(session_binding, signature)?;
+        }
+        Ok(())
+    }
+}
+
+/// TOTP 登记
+///
+/// 密钥由调用方用安全随机源生成 (浏览器中为 `crypto.getRandomValues`)，
+/// 通过 `provisioning_uri` 交给认证器 App。
+pub struct TotpEnrollment {
+    secret: Vec<u8>,
+    digits: u32,
+    step_secs: u64,
+    skew_steps: u64,
+    /// 最近一次验证通过的时间步，拒绝重放同一验证码
+    last_time_step: Option<u64>,
+}
+
+impl TotpEnrollment {
+    pub fn new(secret: Vec<u8>) -> Result<Self> {
+        if secret.len() < TOTP_MIN_SECRET_LEN {
+            return Err(PsyGuardError::InvalidInput(format!(
+                "TOTP 密钥至少 {} 字节，实际 {}",
+                TOTP_MIN_SECRET_LEN,
+                secret.len()
+            )));
+        }
+        Ok(Self {
+            secret,
+            digits: TOTP_DIGITS,
+            step_secs: TOTP_STEP_SECS,
+            skew_steps: TOTP_SKEW_STEPS,
+            last_time_step: None,
+        })
+    }
+
+    /// 验证码位数 (6-8)
+    pub fn with_digits(mut self, digits: u32) -> Self {
+        self.digits = digits.clamp(6, 8);
+        self
+    }
+
+    /// 时间步长 (秒)
+    pub fn with_step(mut self, step_secs: u64) -> Self {
+        self.step_secs = step_secs.max(1);
+        self
+    }
+
+    /// 允许的时钟漂移 (时间步)
+    pub fn with_skew(mut self, skew_steps: u64) -> Self {
+        self.skew_steps = skew_steps;
+        self
+    }
+
+    /// 登记凭据标识 (不泄露密钥)
+    pub fn credential_id(&self) -> Hash {
+        let mut w = WireWriter::new();
+        w.put_bytes(TOTP_CREDENTIAL_DOMAIN);
+        w.put_bytes(&self.secret);
+        sha256(&w.into_bytes())
+    }
+
+    /// 认证器 App 使用的 `otpauth://` 登记链接
+    pub fn provisioning_uri(&self, issuer: &str, account: &str) -> String {
+        format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            percent_encode(issuer),
+            percent_encode(account),
+            base32_encode(&self.secret),
+            percent_encode(issuer),
+            self.digits,
+            self.step_secs
+        )
+    }
+
+    /// 时间戳所在的时间步
+    pub fn time_step(&self, timestamp: u64) -> u64 {
+        timestamp / self.step_secs
+    }
+
+    /// 时间戳对应的验证码
+    pub fn code_at(&self, timestamp: u64) -> String {
+        self.hotp(self.time_step(timestamp))
+    }
+
+    /// 验证 TOTP 验证码并绑定到会话
+    ///
+    /// 接受漂移窗口内的时间步；同一时间步或更早的验证码不能再次使用。
+    pub fn verify(&mut self, code: &str, timestamp: u64, session_binding: &Hash) -> Result<VerifiedFactor> {
+        let current = self.time_step(timestamp);
+        let matched = (current.saturating_sub(self.skew_steps)..=current.saturating_add(self.skew_steps))
+            .find(|&step| constant_time_eq(self.hotp(step).as_bytes(), code.as_bytes()))
+            .ok_or_else(|| PsyGuardError::SecondFactorFailed("TOTP 验证码无效".to_string()))?;
+
+        if self.last_time_step.is_some_and(|last| matched <= last) {
+            return Err(PsyGuardError::SecondFactorFailed("TOTP 验证码已使用".to_string()));
+        }
+        self.last_time_step = Some(matched);
+
+        Ok(VerifiedFactor {
+            attestation: SecondFactorAttestation::Totp {
+                credential_id: self.credential_id(),
+                time_step: matched,
+                session_binding: *session_binding,
+            },
+        })
+    }
+
+    /// RFC 4226 HOTP
+    fn hotp(&self, counter: u64) -> String {
+        let mut mac = Hmac::<Sha1>::new_from_slice(&self.secret).expect("HMAC 接受任意长度密钥");
+        mac.update(&counter.to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+
+        let offset = (digest[19] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        let code = binary % 10u32.pow(self.digits);
+        format!("{:0width$}", code, width = self.digits as usize)
+    }
+}
+
+/// 挑战-应答第二因素 (登记的 Ed25519 公钥)
+pub struct ChallengeKey {
+    key: VerifyingKey,
+}
+
+impl ChallengeKey {
+    pub fn new(public_key: &[u8; 32]) -> Result<Self> {
+        let key = VerifyingKey::from_bytes(public_key)
+            .map_err(|e| PsyGuardError::InvalidInput(format!("第二公钥无效: {}", e)))?;
+        Ok(Self { key })
+    }
+
+    /// 登记的公钥
+    pub fn public_key(&self) -> [u8; 32] {
+        self.key.to_bytes()
+    }
+
+    /// 登记凭据标识
+    pub fn credential_id(&self) -> Hash {
+        Self::credential_id_of(&self.public_key())
+    }
+
+    fn credential_id_of(public_key: &[u8; 32]) -> Hash {
+        let mut w = WireWriter::new();
+        w.put_bytes(CHALLENGE_CREDENTIAL_DOMAIN);
+        w.put_hash(public_key);
+        sha256(&w.into_bytes())
+    }
+
+    /// 会话挑战 (设备需签名的 32 字节)
+    pub fn challenge(session_binding: &Hash) -> Hash {
+        let mut w = WireWriter::new();
+        w.put_bytes(CHALLENGE_DOMAIN);
+        w.put_hash(session_binding);
+        sha256(&w.into_bytes())
+    }
+
+    /// 验证设备对会话挑战的签名
+    pub fn verify(&self, session_binding: &Hash, signature: &[u8]) -> Result<VerifiedFactor> {
+        self.verify_signature(session_binding, signature)?;
+        Ok(VerifiedFactor {
+            attestation: SecondFactorAttestation::Challenge {
+                public_key: self.public_key(),
+                signature: signature.to_vec(),
+                session_binding: *session_binding,
+            },
+        })
+    }
+
+    fn verify_signature(&self, session_binding: &Hash, signature: &[u8]) -> Result<()> {
+        let signature = Signature::from_slice(signature)
+            .map_err(|e| PsyGuardError::SecondFactorFailed(format!("挑战签名格式无效: {}", e)))?;
+        self.key
+            .verify(&Self::challenge(session_binding), &signature)
+            .map_err(|_| PsyGuardError::SecondFactorFailed("挑战签名无效".to_string()))
+    }
+}
+
+fn sha256(bytes: &[u8]) -> Hash {
+    let result = Sha256::digest(bytes);
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&result);
+    hash
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// RFC 4648 base32 (无填充，认证器 App 的密钥格式)
+fn base32_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_totp_rfc6238_vectors() {
+        // RFC 6238 附录 B (SHA1, 8 位)
+        let totp = TotpEnrollment::new(RFC_SECRET.to_vec()).unwrap().with_digits(8);
+        for (timestamp, code) in [
+            (59, "94287082"),
+            (1_111_111_109, "07081804"),
+            (1_111_111_111, "14050471"),
+            (1_234_567_890, "89005924"),
+            (2_000_000_000, "69279037"),
+            (20_000_000_000, "65353130"),
+        ] {
+            assert_eq!(totp.code_at(timestamp), code, "T = {}", timestamp);
+        }
+
+        // RFC 4226 附录 D 的 6 位 HOTP 即时间步 0/1 的 TOTP
+        let totp = TotpEnrollment::new(RFC_SECRET.to_vec()).unwrap();
+        assert_eq!(totp.code_at(0), "755224");
+        assert_eq!(totp.code_at(30), "287082");
+
+        assert_eq!(
+            totp.provisioning_uri("PsyGuard", "alice@example.com"),
+            "otpauth://totp/PsyGuard:alice%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+             &issuer=PsyGuard&algorithm=SHA1&digits=6&period=30"
+        );
+        assert!(TotpEnrollment::new(vec![0u8; 10]).is_err());
+    }
+
+    #[test]
+    fn test_totp_verify_window_and_replay() {
+        let binding = [7u8; 32];
+        let mut totp = TotpEnrollment::new(RFC_SECRET.to_vec()).unwrap();
+        let now = 1_700_000_000;
+
+        // 上一个时间步的验证码仍在漂移窗口内
+        let previous = totp.code_at(now - TOTP_STEP_SECS);
+        let factor = totp.verify(&previous, now, &binding).unwrap();
+        assert_eq!(factor.method(), "totp");
+        assert_eq!(factor.attestation().session_binding(), &binding);
+        factor.attestation().check(&binding).unwrap();
+        assert!(factor.attestation().check(&[8u8; 32]).is_err());
+
+        // 重放与更早的验证码被拒绝，之后的验证码可用
+        assert!(matches!(
+            totp.verify(&previous, now, &binding),
+            Err(PsyGuardError::SecondFactorFailed(_))
+        ));
+        totp.verify(&totp.code_at(now), now, &binding).unwrap();
+
+        // 超出窗口
+        let stale = totp.code_at(now - 3 * TOTP_STEP_SECS);
+        assert!(totp.verify(&stale, now + TOTP_STEP_SECS, &binding).is_err());
+        assert!(totp.verify("000000x", now + TOTP_STEP_SECS, &binding).is_err());
+    }
+
+    #[test]
+    fn test_challenge_response() {
+        let device = SigningKey::from_bytes(&[42u8; 32]);
+        let key = ChallengeKey::new(&device.verifying_key().to_bytes()).unwrap();
+        let binding = [3u8; 32];
+
+        let signature = device.sign(&ChallengeKey::challenge(&binding)).to_bytes();
+        let factor = key.verify(&binding, &signature).unwrap();
+        assert_eq!(factor.method(), "challenge");
+        factor.attestation().check(&binding).unwrap();
+
+        // 签名只对应本会话的挑战
+        assert!(key.verify(&[4u8; 32], &signature).is_err());
+        let other_device = SigningKey::from_bytes(&[43u8; 32]);
+        let forged = other_device.sign(&ChallengeKey::challenge(&binding)).to_bytes();
+        assert!(key.verify(&binding, &forged).is_err());
+
+        // 篡改凭证中的签名后无法通过复验，承诺也随之改变
+        let mut tampered = factor.attestation().clone();
+        if let SecondFactorAttestation::Challenge { signature, .. } = &mut tampered {
+            signature[0] ^= 1;
+        }
+        assert!(tampered.check(&binding).is_err());
+        assert_ne!(tampered.commitment(), factor.attestation().commitment());
+    }
+}
@@ -0,0 +1,316 @@
+//! 稀疏 Merkle 树 (固定高度)
+//!
+//! 参考: 《7-Psy Jargon.md》- Delta Merkle Proof
+//! 参考: 《6-Smart Contracts.md》- contract_state_tree_height 决定 CSTATE 槽位上限
+
+use crate::types::*;
+use crate::error::{PsyGuardError, Result};
+use crate::hash::{MerkleHasher, Sha256Hasher};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 支持的最大树高 (叶索引为 u64)
+pub const MAX_TREE_HEIGHT: u32 = 64;
+
+/// 空叶 / 空子树哈希
+pub const EMPTY_NODE: Hash = [0u8; 32];
+
+/// 固定高度的稀疏 Merkle 树
+///
+/// 叶索引的第 i 位决定第 i 层的左右方向 (0 = 左, 1 = 右)。
+/// 空叶为全零哈希，两个空子节点的父节点也约定为全零，
+/// 因此空树的根恒为全零，只存储非空节点。
+#[derive(Debug, Clone)]
+pub struct SparseMerkleTree {
+    height: u32,
+    hasher: Arc<dyn MerkleHasher>,
+    /// (层, 层内索引) -> 节点哈希，第 0 层为叶
+    nodes: HashMap<(u32, u64), Hash>,
+}
+
+impl SparseMerkleTree {
+    /// 创建指定高度的空树 (SHA-256)
+    pub fn new(height: u32) -> Result<Self> {
+        Self::with_hasher(height, Arc::new(Sha256Hasher))
+    }
+
+    /// 使用指定哈希函数创建空树
+    pub fn with_hasher(height: u32, hasher: Arc<dyn MerkleHasher>) -> Result<Self> {
+        if height == 0 || height > MAX_TREE_HEIGHT {
+            return Err(PsyGuardError::InvalidInput(format!(
+                "树高 {} 超出范围 (1..={})",
+                height, MAX_TREE_HEIGHT
+            )));
+        }
+
+        Ok(Self {
+            height,
+            hasher,
+            nodes: HashMap::new(),
+        })
+    }
+
+    /// 树高
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// 树使用的哈希函数
+    pub fn hasher(&self) -> &Arc<dyn MerkleHasher> {
+        &self.hasher
+    }
+
+    /// 当前根
+    pub fn root(&self) -> Hash {
+        self.node(self.height, 0)
+    }
+
+    /// 读取叶值 (空叶返回全零哈希)
+    pub fn get_leaf(&self, index: u64) -> Hash {
+        self.node(0, index)
+    }
+
+    /// 写入叶值，返回该次写入的 Delta Merkle 证明
+    pub fn set_leaf(&mut self, index: u64, value: Hash) -> Result<DeltaMerkleProof> {
+        self.check_index(index)?;
+
+        let siblings = self.siblings(index);
+        let old_value = self.get_leaf(index);
+
+        let mut current = value;
+        let mut current_index = index;
+        self.put_node(0, index, value);
+        for (level, sibling) in siblings.iter().enumerate() {
+            current = if current_index & 1 == 0 {
+                hash_pair(self.hasher.as_ref(), &current, sibling)
+            } else {
+                hash_pair(self.hasher.as_ref(), sibling, &current)
+            };
+            current_index >>= 1;
+            self.put_node(level as u32 + 1, current_index, current);
+        }
+
+        Ok(DeltaMerkleProof {
+            index,
+            old_value,
+            new_value: value,
+            siblings,
+        })
+    }
+
+    /// 生成叶的包含证明
+    pub fn prove(&self, index: u64) -> Result<MerkleProof> {
+        self.check_index(index)?;
+
+        Ok(MerkleProof {
+            index,
+            value: self.get_leaf(index),
+            siblings: self.siblings(index),
+        })
+    }
+
+    /// 从叶值和兄弟路径计算根
+    pub fn compute_root(
+        hasher: &dyn MerkleHasher,
+        index: u64,
+        value: &Hash,
+        siblings: &[Hash],
+    ) -> Hash {
+        let mut current = *value;
+        let mut current_index = index;
+        for sibling in siblings {
+            current = if current_index & 1 == 0 {
+                hash_pair(hasher, &current, sibling)
+            } else {
+                hash_pair(hasher, sibling, &current)
+            };
+            current_index >>= 1;
+        }
+        current
+    }
+
+    /// 校验包含证明
+    ///
+    /// 路径长度必须等于树高，否则截短的路径可把内部节点冒充为叶。
+    pub fn verify_proof(hasher: &dyn MerkleHasher, height: u32, root: &Hash, proof: &MerkleProof) -> bool {
+        Self::path_fits(proof.index, &proof.siblings, height)
+            && Self::compute_root(hasher, proof.index, &proof.value, &proof.siblings) == *root
+    }
+
+    /// 校验单叶 Delta 证明: 旧叶在 old_root 下成立，新叶在同一路径下得到 new_root
+    pub fn verify_delta(
+        hasher: &dyn MerkleHasher,
+        height: u32,
+        old_root: &Hash,
+        new_root: &Hash,
+        proof: &DeltaMerkleProof,
+    ) -> bool {
+        Self::path_fits(proof.index, &proof.siblings, height)
+            && Self::compute_root(hasher, proof.index, &proof.old_value, &proof.siblings) == *old_root
+            && Self::compute_root(hasher, proof.index, &proof.new_value, &proof.siblings) == *new_root
+    }
+
+    /// 依次校验一串 Delta 证明，确认根从 old_root 过渡到 new_root
+    pub fn verify_delta_chain(
+        hasher: &dyn MerkleHasher,
+        height: u32,
+        old_root: &Hash,
+        new_root: &Hash,
+        proofs: &[DeltaMerkleProof],
+    ) -> bool {
+        let mut current_root = *old_root;
+        for proof in proofs {
+            let next_root = Self::compute_root(hasher, proof.index, &proof.new_value, &proof.siblings);
+            if !Self::verify_delta(hasher, height, &current_root, &next_root, proof) {
+                return false;
+            }
+            current_root = next_root;
+        }
+        current_root == *new_root
+    }
+
+    /// 读取任意层节点 (第 0 层为叶)
+    pub fn node(&self, level: u32, index: u64) -> Hash {
+        self.nodes
+            .get(&(level, index))
+            .copied()
+            .unwrap_or(EMPTY_NODE)
+    }
+
+    fn put_node(&mut self, level: u32, index: u64, hash: Hash) {
+        if hash == EMPTY_NODE {
+            self.nodes.remove(&(level, index));
+        } else {
+            self.nodes.insert((level, index), hash);
+        }
+    }
+
+    fn siblings(&self, index: u64) -> Vec<Hash> {
+        (0..self.height)
+            .map(|level| self.node(level, (index >> level) ^ 1))
+            .collect()
+    }
+
+    fn check_index(&self, index: u64) -> Result<()> {
+        if !Self::index_fits(index, self.height as usize) {
+            return Err(PsyGuardError::InvalidInput(format!(
+                "叶索引 {} 超出树高 {} 的容量",
+                index, self.height
+            )));
+        }
+        Ok(())
+    }
+
+    fn path_fits(index: u64, siblings: &[Hash], height: u32) -> bool {
+        siblings.len() == height as usize && Self::index_fits(index, height as usize)
+    }
+
+    fn index_fits(index: u64, height: usize) -> bool {
+        height >= MAX_TREE_HEIGHT as usize || index >> height == 0
+    }
+}
+
+/// 计算两个哈希的父节点 (两个空子节点的父节点仍为空)
+fn hash_pair(hasher: &dyn MerkleHasher, left: &Hash, right: &Hash) -> Hash {
+    if *left == EMPTY_NODE && *right == EMPTY_NODE {
+        return EMPTY_NODE;
+    }
+
+    hasher.hash_pair(left, right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::HashAlgorithm;
+
+    #[test]
+    fn test_smt_inclusion_and_delta() {
+        let mut tree = SparseMerkleTree::new(8).unwrap();
+        let hasher = tree.hasher().clone();
+        let empty_root = tree.root();
+        assert_eq!(empty_root, EMPTY_NODE);
+
+        let delta = tree.set_leaf(5, [7u8; 32]).unwrap();
+        assert_eq!(delta.old_value, [0u8; 32]);
+        assert!(SparseMerkleTree::verify_delta(hasher.as_ref(), 8, &empty_root, &tree.root(), &delta));
+
+        let proof = tree.prove(5).unwrap();
+        assert!(SparseMerkleTree::verify_proof(hasher.as_ref(), 8, &tree.root(), &proof));
+
+        // 空叶也可证明
+        let empty_proof = tree.prove(6).unwrap();
+        assert!(SparseMerkleTree::verify_proof(hasher.as_ref(), 8, &tree.root(), &empty_proof));
+
+        // 超出容量
+        assert!(tree.set_leaf(256, [1u8; 32]).is_err());
+    }
+
+    #[test]
+    fn test_smt_delta_chain() {
+        let mut tree = SparseMerkleTree::new(16).unwrap();
+        let hasher = tree.hasher().clone();
+        let start_root = tree.root();
+
+        let proofs = vec![
+            tree.set_leaf(1, [1u8; 32]).unwrap(),
+            tree.set_leaf(300, [2u8; 32]).unwrap(),
+            tree.set_leaf(1, [3u8; 32]).unwrap(),
+        ];
+
+        assert!(SparseMerkleTree::verify_delta_chain(hasher.as_ref(), 16, &start_root, &tree.root(), &proofs));
+        assert!(!SparseMerkleTree::verify_delta_chain(hasher.as_ref(), 16, &start_root, &tree.root(), &proofs[..2]));
+    }
+
+    #[test]
+    fn test_smt_rejects_truncated_proof() {
+        let mut tree = SparseMerkleTree::new(8).unwrap();
+        let hasher = tree.hasher().clone();
+        tree.set_leaf(4, [1u8; 32]).unwrap();
+        tree.set_leaf(5, [2u8; 32]).unwrap();
+        let root = tree.root();
+
+        // 去掉最底层兄弟，把叶 4、5 的父节点当作索引 2 处的 "叶"
+        let full = tree.prove(5).unwrap();
+        let forged = MerkleProof {
+            index: 2,
+            value: tree.node(1, 2),
+            siblings: full.siblings[1..].to_vec(),
+        };
+        assert_eq!(SparseMerkleTree::compute_root(hasher.as_ref(), forged.index, &forged.value, &forged.siblings), root);
+        assert!(!SparseMerkleTree::verify_proof(hasher.as_ref(), 8, &root, &forged));
+        assert!(!SparseMerkleTree::verify_proof(hasher.as_ref(), 7, &root, &full));
+
+        let delta = DeltaMerkleProof {
+            index: forged.index,
+            old_value: forged.value,
+            new_value: [9u8; 32],
+            siblings: forged.siblings.clone(),
+        };
+        let new_root = SparseMerkleTree::compute_root(hasher.as_ref(), 2, &delta.new_value, &delta.siblings);
+        assert!(!SparseMerkleTree::verify_delta(hasher.as_ref(), 8, &root, &new_root, &delta));
+    }
+
+    #[test]
+    fn test_smt_hasher_selects_roots() {
+        let mut roots = Vec::new();
+        for algorithm in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Keccak256,
+            HashAlgorithm::PoseidonGoldilocks,
+        ] {
+            let hasher = algorithm.hasher();
+            let mut tree = SparseMerkleTree::with_hasher(16, hasher.clone()).unwrap();
+            tree.set_leaf(3, [5u8; 32]).unwrap();
+
+            let proof = tree.prove(3).unwrap();
+            assert!(SparseMerkleTree::verify_proof(hasher.as_ref(), 16, &tree.root(), &proof));
+            roots.push((tree.root(), proof));
+        }
+
+        // 不同哈希产生不同的根，证明不能跨哈希函数复用
+        assert_ne!(roots[0].0, roots[1].0);
+        assert_ne!(roots[0].0, roots[2].0);
+        assert!(!SparseMerkleTree::verify_proof(&Sha256Hasher, 16, &roots[2].0, &roots[2].1));
+    }
+}
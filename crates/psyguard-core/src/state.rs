@@ -3,11 +3,21 @@
 //! 参考: 《5-Local Proving (UPS).md》- PARTH 状态模型
 
 use crate::types::*;
-use crate::error::Result;
+use crate::error::{PsyGuardError, Result};
+use crate::hash::{HashAlgorithm, MerkleHasher, Sha256Hasher};
+use crate::checkpoint::CheckpointLeaf;
+use crate::guta::GutaConfig;
+use crate::smt::{SparseMerkleTree, EMPTY_NODE};
 use std::collections::HashMap;
+use std::sync::Arc;
+
+/// UCON 树高 (合约叶索引为合约 ID 哈希的前 8 字节)
+pub const UCON_TREE_HEIGHT: u32 = 64;
 
 /// UCON (User Container) - 用户容器
-/// 每个用户的所有合约状态聚合
+/// 每个用户的所有合约状态聚合，以合约 ID 哈希为索引的稀疏 Merkle 树
+/// 参考: 《3-How a Block is Made.md》- UCON 叶为 contract_id 对应的 CSTATE 根
+#[derive(Clone)]
 pub struct Ucon {
     /// 用户 ID
     pub user_id: UserId,
@@ -15,21 +25,42 @@ pub struct Ucon {
     pub contract_states: HashMap<ContractId, Hash>,
     /// UCON 根哈希
     pub root: Hash,
+    /// 合约 Merkle 树
+    tree: SparseMerkleTree,
+    /// 本地保存的合约 CSTATE (执行 CFC 时在其上写入)
+    cstates: HashMap<ContractId, Cstate>,
 }
 
 impl Ucon {
     pub fn new(user_id: UserId) -> Self {
+        Self::with_hasher(user_id, Arc::new(Sha256Hasher))
+    }
+
+    /// 使用指定哈希函数创建 (需与网络配置一致)
+    pub fn with_hasher(user_id: UserId, hasher: Arc<dyn MerkleHasher>) -> Self {
+        let tree = SparseMerkleTree::with_hasher(UCON_TREE_HEIGHT, hasher).expect("UCON 树高有效");
+
         Self {
             user_id,
             contract_states: HashMap::new(),
-            root: [0u8; 32],
+            root: tree.root(),
+            tree,
+            cstates: HashMap::new(),
         }
     }
 
-    /// 更新合约状态根
+    /// UCON 树使用的哈希函数
+    pub fn hasher(&self) -> &Arc<dyn MerkleHasher> {
+        self.tree.hasher()
+    }
+
+    /// 更新合约状态根 (只记录根；与之不符的本地 CSTATE 被丢弃)
     pub fn update_contract_state(&mut self, contract_id: ContractId, new_root: Hash) {
-        self.contract_states.insert(contract_id, new_root);
-        self.recompute_root();
+        if self.cstates.get(&contract_id).is_some_and(|cstate| cstate.root != new_root) {
+            self.cstates.remove(&contract_id);
+        }
+        self.set_contract_root(contract_id, new_root)
+            .expect("UCON 树高覆盖全部 u64 索引");
     }
 
     /// 获取合约状态根
@@ -37,28 +68,214 @@ impl Ucon {
         self.contract_states.get(contract_id)
     }
 
-    /// 重新计算 UCON 根
-    fn recompute_root(&mut self) {
+    /// 本地保存的合约 CSTATE
+    pub fn cstate(&self, contract_id: &ContractId) -> Option<&Cstate> {
+        self.cstates.get(contract_id)
+    }
+
+    /// 登记合约的完整 CSTATE，合约叶设为其根 (之后的 Delta 从该根开始)
+    pub fn insert_cstate(&mut self, mut cstate: Cstate) -> Result<()> {
+        if cstate.hasher().name() != self.hasher().name() {
+            return Err(PsyGuardError::InvalidInput(format!(
+                "合约 {:?} 的 CSTATE 使用 {}，UCON 使用 {}",
+                cstate.contract_id,
+                cstate.hasher().name(),
+                self.hasher().name()
+            )));
+        }
+        cstate.take_delta_proof();
+        self.set_contract_root(cstate.contract_id.clone(), cstate.root)?;
+        self.cstates.insert(cstate.contract_id.clone(), cstate);
+        Ok(())
+    }
+
+    /// 合约当前 CSTATE 的见证 (见 `apply_writes` 对缺少本地 CSTATE 的约定)
+    pub fn cstate_witness(&self, contract_id: &ContractId, height: CstateHeight) -> Result<CstateWitness> {
+        self.working_cstate(contract_id, height)?.witness()
+    }
+
+    /// 在合约 CSTATE 上依次写入槽位，返回携带逐叶证明的 UCON Delta 证明
+    ///
+    /// 合约叶为空时按 `height` 新建 CSTATE；非空的合约叶必须有本地 CSTATE。
+    /// 参考: 《5-Local Proving (UPS).md》- 证明 CSTATE 根从旧到新的过渡
+    pub fn apply_writes(
+        &mut self,
+        contract_id: &ContractId,
+        height: CstateHeight,
+        writes: &[(u64, Vec<u8>)],
+    ) -> Result<UconDeltaProof> {
+        let mut cstate = self.working_cstate(contract_id, height)?;
+        for (slot, value) in writes {
+            cstate.write_slot(*slot, value.clone())?;
+        }
+        let proof = self.apply_update(contract_id, cstate.take_delta_proof())?;
+        self.cstates.insert(contract_id.clone(), cstate);
+        Ok(proof)
+    }
+
+    /// 导出可持久化的快照 (含全部本地 CSTATE)
+    pub fn snapshot(&self) -> Result<UconSnapshot> {
+        let name = self.hasher().name();
+        let hash_algorithm = HashAlgorithm::from_name(name)
+            .ok_or_else(|| PsyGuardError::InvalidInput(format!("哈希 {} 无法写入快照", name)))?;
+        let mut contracts = self
+            .contract_states
+            .iter()
+            .map(|(contract_id, root)| {
+                Ok(ContractSnapshot {
+                    contract_id: contract_id.clone(),
+                    cstate_root: *root,
+                    cstate: self.cstates.get(contract_id).map(Cstate::witness).transpose()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        contracts.sort_unstable_by(|a, b| a.contract_id.0.cmp(&b.contract_id.0));
+
+        Ok(UconSnapshot { user_id: self.user_id.clone(), hash_algorithm, contracts })
+    }
+
+    /// 由快照恢复；每个 CSTATE 重建后的根必须等于记录的合约根
+    pub fn from_snapshot(snapshot: &UconSnapshot) -> Result<Self> {
+        let mut ucon = Self::with_hasher(snapshot.user_id.clone(), snapshot.hash_algorithm.hasher());
+        for contract in &snapshot.contracts {
+            match &contract.cstate {
+                Some(witness) => {
+                    if witness.hash_algorithm != snapshot.hash_algorithm {
+                        return Err(PsyGuardError::InvalidInput(format!(
+                            "合约 {:?} 的 CSTATE 哈希与 UCON 不一致",
+                            contract.contract_id
+                        )));
+                    }
+                    let cstate = Cstate::from_witness(contract.contract_id.clone(), witness)?;
+                    if cstate.root != contract.cstate_root {
+                        return Err(PsyGuardError::InvalidStateTransition(format!(
+                            "合约 {:?} 的 CSTATE 内容与记录的根不一致",
+                            contract.contract_id
+                        )));
+                    }
+                    ucon.insert_cstate(cstate)?;
+                }
+                None => ucon.update_contract_state(contract.contract_id.clone(), contract.cstate_root),
+            }
+        }
+        Ok(ucon)
+    }
+
+    /// 合约在 UCON 树中的叶索引
+    pub fn contract_index(contract_id: &ContractId) -> u64 {
         use sha2::{Sha256, Digest};
 
-        let mut hasher = Sha256::new();
-        
-        // 按合约 ID 排序以保证确定性
-        let mut sorted: Vec<_> = self.contract_states.iter().collect();
-        sorted.sort_by_key(|(id, _)| &id.0);
+        let digest = Sha256::digest(contract_id.0.as_bytes());
+        let mut index = [0u8; 8];
+        index.copy_from_slice(&digest[..8]);
+        u64::from_be_bytes(index)
+    }
+
+    /// 生成合约 CSTATE 根在 UCON 中的包含证明
+    pub fn prove_contract(&self, contract_id: &ContractId) -> Result<MerkleProof> {
+        self.tree.prove(Self::contract_index(contract_id))
+    }
+
+    /// 校验合约 CSTATE 根在给定 UCON 根下成立 (未写入过的合约为零叶)
+    pub fn verify_contract(
+        hasher: &dyn MerkleHasher,
+        ucon_root: &Hash,
+        contract_id: &ContractId,
+        cstate_root: &Hash,
+        proof: &MerkleProof,
+    ) -> bool {
+        proof.index == Self::contract_index(contract_id)
+            && proof.value == *cstate_root
+            && SparseMerkleTree::verify_proof(hasher, UCON_TREE_HEIGHT, ucon_root, proof)
+    }
+
+    /// 应用一次合约状态过渡，返回 UCON Delta 证明
+    /// 参考: 《3-How a Block is Made.md》- UCON 中 contract_id 叶从旧 CSTATE 根变为新 CSTATE 根
+    pub fn apply_update(
+        &mut self,
+        contract_id: &ContractId,
+        cstate_delta: CstateDeltaProof,
+    ) -> Result<UconDeltaProof> {
+        let current = self.tree.get_leaf(Self::contract_index(contract_id));
+        if current != cstate_delta.old_root {
+            return Err(PsyGuardError::InvalidStateTransition(format!(
+                "合约 {:?} 的 CSTATE 根与 UCON 记录不一致",
+                contract_id
+            )));
+        }
+
+        let old_root = self.root;
+        let ucon_leaf_proof = self.set_contract_root(contract_id.clone(), cstate_delta.new_root)?;
+
+        Ok(UconDeltaProof {
+            old_root,
+            new_root: self.root,
+            contract_id: contract_id.clone(),
+            ucon_leaf_proof,
+            cstate_delta,
+        })
+    }
+
+    /// 校验 UCON Delta 证明
+    /// 合约叶的过渡必须与内部 CSTATE Delta 的起止根一致；`cstate_height` 取自合约元数据
+    pub fn verify_delta_proof(
+        hasher: &dyn MerkleHasher,
+        cstate_height: CstateHeight,
+        proof: &UconDeltaProof,
+    ) -> Result<bool> {
+        let leaf = &proof.ucon_leaf_proof;
 
-        for (contract_id, state_root) in sorted {
-            hasher.update(contract_id.0.as_bytes());
-            hasher.update(state_root);
+        Ok(leaf.index == Self::contract_index(&proof.contract_id)
+            && leaf.old_value == proof.cstate_delta.old_root
+            && leaf.new_value == proof.cstate_delta.new_root
+            && SparseMerkleTree::verify_delta(hasher, UCON_TREE_HEIGHT, &proof.old_root, &proof.new_root, leaf)
+            && Cstate::verify_delta_proof(hasher, cstate_height, &proof.cstate_delta)?)
+    }
+
+    /// 本地 CSTATE 的副本；未写入过的合约新建空 CSTATE
+    fn working_cstate(&self, contract_id: &ContractId, height: CstateHeight) -> Result<Cstate> {
+        let cstate = match self.cstates.get(contract_id) {
+            Some(cstate) => cstate.clone(),
+            None if self.tree.get_leaf(Self::contract_index(contract_id)) == EMPTY_NODE => {
+                Cstate::with_hasher(contract_id.clone(), height, self.hasher().clone())?
+            }
+            None => {
+                return Err(PsyGuardError::InvalidStateTransition(format!(
+                    "缺少合约 {:?} 的本地 CSTATE",
+                    contract_id
+                )))
+            }
+        };
+        if cstate.height() != height {
+            return Err(PsyGuardError::InvalidStateTransition(format!(
+                "合约 {:?} 的本地 CSTATE 树高 {} 与合约元数据 {} 不一致",
+                contract_id,
+                cstate.height(),
+                height
+            )));
         }
+        Ok(cstate)
+    }
 
-        let result = hasher.finalize();
-        self.root.copy_from_slice(&result);
+    fn set_contract_root(
+        &mut self,
+        contract_id: ContractId,
+        new_root: Hash,
+    ) -> Result<DeltaMerkleProof> {
+        let delta = self.tree.set_leaf(Self::contract_index(&contract_id), new_root)?;
+        self.contract_states.insert(contract_id, new_root);
+        self.root = self.tree.root();
+        Ok(delta)
     }
 }
 
+/// 默认 CSTATE 树高
+/// 参考: 《6-Smart Contracts.md》- contract_state_tree_height
+pub const DEFAULT_CSTATE_HEIGHT: CstateHeight = 32;
+
 /// CSTATE (Contract State) - 合约状态
-/// 每个合约的键值存储
+/// 每个合约的键值存储，以槽位为索引的固定高度稀疏 Merkle 树
+#[derive(Clone)]
 pub struct Cstate {
     /// 合约 ID
     pub contract_id: ContractId,
@@ -66,21 +283,85 @@ pub struct Cstate {
     pub slots: HashMap<u64, Vec<u8>>,
     /// CSTATE 根哈希
     pub root: Hash,
+    /// 槽位 Merkle 树 (叶 = 槽位值哈希)
+    tree: SparseMerkleTree,
+    /// 上次提交 Delta 时的根
+    delta_base_root: Hash,
+    /// 自上次提交以来的写入证明
+    pending_deltas: Vec<DeltaMerkleProof>,
 }
 
 impl Cstate {
     pub fn new(contract_id: ContractId) -> Self {
-        Self {
+        Self::with_height(contract_id, DEFAULT_CSTATE_HEIGHT)
+            .expect("默认 CSTATE 树高有效")
+    }
+
+    /// 按合约声明的 CSTATE 高度创建
+    pub fn with_height(contract_id: ContractId, height: CstateHeight) -> Result<Self> {
+        Self::with_hasher(contract_id, height, Arc::new(Sha256Hasher))
+    }
+
+    /// 按合约声明的 CSTATE 高度和网络哈希函数创建
+    pub fn with_hasher(
+        contract_id: ContractId,
+        height: CstateHeight,
+        hasher: Arc<dyn MerkleHasher>,
+    ) -> Result<Self> {
+        let height = u32::try_from(height)
+            .map_err(|_| PsyGuardError::InvalidInput(format!("CSTATE 树高 {} 无效", height)))?;
+        let tree = SparseMerkleTree::with_hasher(height, hasher)?;
+        let root = tree.root();
+
+        Ok(Self {
             contract_id,
             slots: HashMap::new(),
-            root: [0u8; 32],
+            root,
+            tree,
+            delta_base_root: root,
+            pending_deltas: vec![],
+        })
+    }
+
+    /// 树高
+    pub fn height(&self) -> CstateHeight {
+        self.tree.height() as CstateHeight
+    }
+
+    /// CSTATE 树使用的哈希函数
+    pub fn hasher(&self) -> &Arc<dyn MerkleHasher> {
+        self.tree.hasher()
+    }
+
+    /// 由见证重建 CSTATE，Delta 从重建后的根开始
+    pub fn from_witness(contract_id: ContractId, witness: &CstateWitness) -> Result<Self> {
+        let mut cstate = Self::with_hasher(contract_id, witness.height, witness.hash_algorithm.hasher())?;
+        for (slot, value) in &witness.slots {
+            cstate.write_slot(*slot, value.clone())?;
         }
+        cstate.take_delta_proof();
+        Ok(cstate)
+    }
+
+    /// 导出为 CFC 见证
+    pub fn witness(&self) -> Result<CstateWitness> {
+        let name = self.hasher().name();
+        let hash_algorithm = HashAlgorithm::from_name(name)
+            .ok_or_else(|| PsyGuardError::InvalidInput(format!("哈希 {} 无法写入见证", name)))?;
+        let mut slots: Vec<(u64, Vec<u8>)> =
+            self.slots.iter().map(|(slot, value)| (*slot, value.clone())).collect();
+        slots.sort_unstable_by_key(|(slot, _)| *slot);
+        Ok(CstateWitness { height: self.height(), hash_algorithm, slots })
     }
 
     /// 写入槽位
-    pub fn write_slot(&mut self, slot: u64, value: Vec<u8>) {
+    pub fn write_slot(&mut self, slot: u64, value: Vec<u8>) -> Result<()> {
+        let leaf = Self::hash_value(self.tree.hasher().as_ref(), &value);
+        let delta = self.tree.set_leaf(slot, leaf)?;
         self.slots.insert(slot, value);
-        self.recompute_root();
+        self.root = self.tree.root();
+        self.pending_deltas.push(delta);
+        Ok(())
     }
 
     /// 读取槽位
@@ -88,46 +369,150 @@ impl Cstate {
         self.slots.get(&slot)
     }
 
-    /// 重新计算 CSTATE 根
-    fn recompute_root(&mut self) {
-        use sha2::{Sha256, Digest};
+    /// 生成槽位包含证明 (空槽位证明其为零叶)
+    pub fn prove_slot(&self, slot: u64) -> Result<MerkleProof> {
+        self.tree.prove(slot)
+    }
+
+    /// 校验槽位值在给定 CSTATE 根下成立
+    pub fn verify_slot(
+        hasher: &dyn MerkleHasher,
+        height: CstateHeight,
+        root: &Hash,
+        value: Option<&[u8]>,
+        proof: &MerkleProof,
+    ) -> bool {
+        let expected = value.map(|v| Self::hash_value(hasher, v)).unwrap_or([0u8; 32]);
+        proof.value == expected
+            && u32::try_from(height).is_ok_and(|height| SparseMerkleTree::verify_proof(hasher, height, root, proof))
+    }
 
-        let mut hasher = Sha256::new();
-        
-        // 按槽位排序以保证确定性
-        let mut sorted: Vec<_> = self.slots.iter().collect();
-        sorted.sort_by_key(|(slot, _)| *slot);
+    /// 计算槽位叶哈希
+    pub fn hash_value(hasher: &dyn MerkleHasher, value: &[u8]) -> Hash {
+        hasher.hash_bytes(value)
+    }
 
-        for (slot, value) in sorted {
-            hasher.update(slot.to_le_bytes());
-            hasher.update(value);
+    /// 生成自上次提交以来的 Delta 证明
+    pub fn generate_delta_proof(&self) -> CstateDeltaProof {
+        CstateDeltaProof {
+            old_root: self.delta_base_root,
+            new_root: self.root,
+            leaf_proofs: self.pending_deltas.clone(),
         }
+    }
 
-        let result = hasher.finalize();
-        self.root.copy_from_slice(&result);
+    /// 取出 Delta 证明并以当前根作为下一段的起点
+    pub fn take_delta_proof(&mut self) -> CstateDeltaProof {
+        let proof = self.generate_delta_proof();
+        self.delta_base_root = self.root;
+        self.pending_deltas.clear();
+        proof
     }
 
-    /// 生成 Delta 证明
-    pub fn generate_delta_proof(&self, old_root: Hash) -> CstateDeltaProof {
-        let modified_leaves: Vec<_> = self.slots
-            .iter()
-            .map(|(slot, value)| {
-                use sha2::{Sha256, Digest};
-                let mut hasher = Sha256::new();
-                hasher.update(value);
-                let result = hasher.finalize();
-                let mut hash = [0u8; 32];
-                hash.copy_from_slice(&result);
-                (*slot, hash)
-            })
-            .collect();
+    /// 校验 Delta 证明: 按顺序重放每个叶的过渡，根必须从 old_root 走到 new_root
+    pub fn verify_delta_proof(
+        hasher: &dyn MerkleHasher,
+        height: CstateHeight,
+        proof: &CstateDeltaProof,
+    ) -> Result<bool> {
+        let height = u32::try_from(height)
+            .map_err(|_| PsyGuardError::InvalidInput(format!("CSTATE 树高 {} 无效", height)))?;
+        Ok(SparseMerkleTree::verify_delta_chain(
+            hasher,
+            height,
+            &proof.old_root,
+            &proof.new_root,
+            &proof.leaf_proofs,
+        ))
+    }
 
-        CstateDeltaProof {
-            old_root,
-            new_root: self.root,
-            merkle_path: vec![], // TODO: 实现真实的 Merkle 路径
-            modified_leaves,
+    /// 校验 Delta 证明恰好覆盖提交的槽位变更
+    /// 参考: 《5-Local Proving (UPS).md》- 提交时附带的 CSTATE 叶需与证明一致
+    pub fn verify_against_delta(
+        hasher: &dyn MerkleHasher,
+        height: CstateHeight,
+        proof: &CstateDeltaProof,
+        delta: &CstateDelta,
+    ) -> Result<bool> {
+        if !Self::verify_delta_proof(hasher, height, proof)? {
+            return Ok(false);
+        }
+
+        // 每个槽位的最终值
+        let mut final_values: HashMap<u64, Hash> = HashMap::new();
+        for leaf in &proof.leaf_proofs {
+            final_values.insert(leaf.index, leaf.new_value);
+        }
+
+        let mut submitted: HashMap<u64, Hash> = HashMap::new();
+        for (slot, value) in &delta.modified_slots {
+            if submitted.insert(*slot, Self::hash_value(hasher, value)).is_some() {
+                return Err(PsyGuardError::InvalidStateTransition(format!(
+                    "槽位 {} 在 Delta 中重复出现",
+                    slot
+                )));
+            }
+        }
+
+        Ok(final_values == submitted)
+    }
+}
+
+impl CstateReadWitness {
+    /// 校验历史读沿持有者 UCON 与 ULEAF 锚定到 CHKP 叶的 GUSR 根
+    ///
+    /// `checkpoint` 须已校验与会话的 CheckpointRef 一致 (见 `CheckpointLeaf::verify`)，
+    /// `cstate_height` 取自已校验的 GCON 合约叶。
+    pub fn verify(
+        &self,
+        guta: &GutaConfig,
+        checkpoint: &CheckpointLeaf,
+        cstate_height: CstateHeight,
+    ) -> Result<()> {
+        let hasher = guta.hash_algorithm.hasher();
+        let hasher = hasher.as_ref();
+        let label = format!("{:?} 持有的合约 {:?} 槽位 {}", self.owner.0, self.contract_id.0, self.slot);
+        let leaf = &self.owner_leaf;
+
+        let uleaf_hash = guta.user_leaf_hash(
+            &leaf.public_key_hash,
+            leaf.balance,
+            leaf.nonce,
+            self.owner_checkpoint_id,
+            &leaf.ucon_root,
+        );
+        if leaf.uleaf_hash != uleaf_hash
+            || self.owner_leaf_proof.index != guta.user_index(&self.owner)
+            || self.owner_leaf_proof.value != uleaf_hash
+            || !SparseMerkleTree::verify_proof(hasher, guta.gusr_height, &checkpoint.gusr_root, &self.owner_leaf_proof)
+        {
+            return Err(PsyGuardError::MerkleProofInvalid(format!(
+                "{} 的持有者用户叶不在 GUSR 根 {} 下",
+                label,
+                hex::encode(checkpoint.gusr_root)
+            )));
+        }
+
+        if !Ucon::verify_contract(hasher, &leaf.ucon_root, &self.contract_id, &self.cstate_root, &self.ucon_proof) {
+            return Err(PsyGuardError::MerkleProofInvalid(format!(
+                "{} 的 CSTATE 根不在持有者的 UCON 中",
+                label
+            )));
+        }
+
+        let slot_proof = MerkleProof {
+            index: self.slot,
+            value: self.value.as_deref().map_or(EMPTY_NODE, |v| Cstate::hash_value(hasher, v)),
+            siblings: self.siblings.clone(),
+        };
+        if !Cstate::verify_slot(hasher, cstate_height, &self.cstate_root, self.value.as_deref(), &slot_proof) {
+            return Err(PsyGuardError::MerkleProofInvalid(format!(
+                "{} 的值不在 CSTATE 根 {} 下",
+                label,
+                hex::encode(self.cstate_root)
+            )));
         }
+        Ok(())
     }
 }
 
@@ -150,24 +535,26 @@ impl ParthTransfer {
     pub fn send(cstate: &mut Cstate, transfer: &ParthTransfer) -> Result<()> {
         // 在 sent_to_others 槽位记录
         let slot = 1000 + transfer.timestamp; // 简化的槽位分配
-        let value = serde_json::to_vec(transfer).unwrap();
-        cstate.write_slot(slot, value);
-        Ok(())
+        cstate.write_slot(slot, crate::codec::encode(transfer))
     }
 
     /// 接收阶段: B 读历史并写入自己的 CSTATE
     pub fn claim(cstate: &mut Cstate, transfer: &ParthTransfer) -> Result<()> {
         // 在 claimed_from_others 槽位记录
         let slot = 2000 + transfer.timestamp; // 简化的槽位分配
-        let value = serde_json::to_vec(transfer).unwrap();
-        cstate.write_slot(slot, value);
-        Ok(())
+        cstate.write_slot(slot, crate::codec::encode(transfer))
+    }
+
+    /// 从槽位值解码收件箱记录
+    pub fn from_slot_value(value: &[u8]) -> Result<ParthTransfer> {
+        crate::codec::decode(value)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hash::HashAlgorithm;
 
     #[test]
     fn test_ucon_update() {
@@ -180,6 +567,69 @@ mod tests {
         ucon.update_contract_state(contract_id.clone(), state_root);
 
         assert_eq!(ucon.get_contract_state(&contract_id), Some(&state_root));
+
+        let hasher = ucon.hasher().clone();
+        let proof = ucon.prove_contract(&contract_id).unwrap();
+        assert!(Ucon::verify_contract(hasher.as_ref(), &ucon.root, &contract_id, &state_root, &proof));
+        assert!(!Ucon::verify_contract(hasher.as_ref(), &ucon.root, &contract_id, &[2u8; 32], &proof));
+
+        let other = ContractId("contract2".to_string());
+        assert!(!Ucon::verify_contract(hasher.as_ref(), &ucon.root, &other, &state_root, &proof));
+    }
+
+    #[test]
+    fn test_ucon_delta_proof() {
+        let contract_id = ContractId("token".to_string());
+        let mut ucon = Ucon::new(UserId("alice".to_string()));
+        ucon.update_contract_state(ContractId("other".to_string()), [9u8; 32]);
+
+        let mut cstate = Cstate::new(contract_id.clone());
+        cstate.write_slot(0, vec![42]).unwrap();
+
+        let old_ucon_root = ucon.root;
+        let delta = ucon.apply_update(&contract_id, cstate.take_delta_proof()).unwrap();
+        assert_eq!(delta.old_root, old_ucon_root);
+        assert_eq!(delta.new_root, ucon.root);
+        assert!(Ucon::verify_delta_proof(&Sha256Hasher, DEFAULT_CSTATE_HEIGHT, &delta).unwrap());
+
+        // 新根不匹配
+        let mut forged = delta.clone();
+        forged.new_root = [7u8; 32];
+        assert!(!Ucon::verify_delta_proof(&Sha256Hasher, DEFAULT_CSTATE_HEIGHT, &forged).unwrap());
+
+        // CSTATE 起点与 UCON 记录不一致
+        cstate.write_slot(1, vec![1]).unwrap();
+        let mut stale = cstate.take_delta_proof();
+        stale.old_root = [3u8; 32];
+        assert!(ucon.apply_update(&contract_id, stale).is_err());
+    }
+
+    #[test]
+    fn test_ucon_snapshot_round_trip() {
+        let token = ContractId("token".to_string());
+        let mut ucon = Ucon::with_hasher(UserId("alice".to_string()), HashAlgorithm::Keccak256.hasher());
+        ucon.apply_writes(&token, 16, &[(3, vec![4]), (1, vec![2])]).unwrap();
+        ucon.update_contract_state(ContractId("other".to_string()), [9u8; 32]);
+
+        let json = serde_json::to_string(&ucon.snapshot().unwrap()).unwrap();
+        let snapshot: UconSnapshot = serde_json::from_str(&json).unwrap();
+        let mut restored = Ucon::from_snapshot(&snapshot).unwrap();
+        assert_eq!(restored.root, ucon.root);
+        assert_eq!(restored.hasher().name(), "keccak256");
+        assert_eq!(restored.cstate(&token).unwrap().read_slot(3), Some(&vec![4]));
+
+        // 恢复后可继续写入，Delta 从记录的根开始
+        let delta = restored.apply_writes(&token, 16, &[(5, vec![6])]).unwrap();
+        assert_eq!(delta.old_root, ucon.root);
+
+        // CSTATE 内容与记录的根不符
+        let mut tampered = snapshot.clone();
+        let token_entry = tampered.contracts.iter_mut().find(|c| c.contract_id == token).unwrap();
+        token_entry.cstate.as_mut().unwrap().slots[0].1 = vec![0];
+        assert!(matches!(
+            Ucon::from_snapshot(&tampered),
+            Err(PsyGuardError::InvalidStateTransition(_))
+        ));
     }
 
     #[test]
@@ -187,7 +637,80 @@ mod tests {
         let contract_id = ContractId("contract1".to_string());
         let mut cstate = Cstate::new(contract_id);
 
-        cstate.write_slot(0, vec![1, 2, 3]);
+        cstate.write_slot(0, vec![1, 2, 3]).unwrap();
         assert_eq!(cstate.read_slot(0), Some(&vec![1, 2, 3]));
+
+        let proof = cstate.prove_slot(0).unwrap();
+        assert!(Cstate::verify_slot(&Sha256Hasher, DEFAULT_CSTATE_HEIGHT, &cstate.root, Some(&[1, 2, 3]), &proof));
+        assert!(!Cstate::verify_slot(&Sha256Hasher, DEFAULT_CSTATE_HEIGHT, &cstate.root, Some(&[9]), &proof));
+    }
+
+    #[test]
+    fn test_state_with_poseidon() {
+        let hasher = HashAlgorithm::PoseidonGoldilocks.hasher();
+        let contract_id = ContractId("token".to_string());
+        let mut cstate =
+            Cstate::with_hasher(contract_id.clone(), DEFAULT_CSTATE_HEIGHT, hasher.clone()).unwrap();
+        cstate.write_slot(0, vec![42]).unwrap();
+
+        let proof = cstate.prove_slot(0).unwrap();
+        assert!(Cstate::verify_slot(hasher.as_ref(), DEFAULT_CSTATE_HEIGHT, &cstate.root, Some(&[42]), &proof));
+        assert!(!Cstate::verify_slot(&Sha256Hasher, DEFAULT_CSTATE_HEIGHT, &cstate.root, Some(&[42]), &proof));
+
+        let mut ucon = Ucon::with_hasher(UserId("alice".to_string()), hasher.clone());
+        let delta = ucon.apply_update(&contract_id, cstate.take_delta_proof()).unwrap();
+        assert!(Ucon::verify_delta_proof(hasher.as_ref(), DEFAULT_CSTATE_HEIGHT, &delta).unwrap());
+        assert!(!Ucon::verify_delta_proof(&Sha256Hasher, DEFAULT_CSTATE_HEIGHT, &delta).unwrap());
+    }
+
+    #[test]
+    fn test_parth_transfer_slot_encoding() {
+        let mut cstate = Cstate::new(ContractId("token".to_string()));
+        let transfer = ParthTransfer {
+            from: UserId("alice".to_string()),
+            to: UserId("bob".to_string()),
+            amount: 100,
+            timestamp: 7,
+        };
+
+        ParthTransfer::send(&mut cstate, &transfer).unwrap();
+        let stored = ParthTransfer::from_slot_value(cstate.read_slot(1007).unwrap()).unwrap();
+        assert_eq!(stored.to, transfer.to);
+        assert_eq!(stored.amount, 100);
+    }
+
+    #[test]
+    fn test_cstate_delta_proof() {
+        let contract_id = ContractId("contract1".to_string());
+        let mut cstate = Cstate::new(contract_id.clone());
+
+        cstate.write_slot(3, vec![1]).unwrap();
+        let first = cstate.take_delta_proof();
+        assert!(Cstate::verify_delta_proof(&Sha256Hasher, DEFAULT_CSTATE_HEIGHT, &first).unwrap());
+
+        cstate.write_slot(3, vec![2]).unwrap();
+        cstate.write_slot(7, vec![3]).unwrap();
+        let proof = cstate.generate_delta_proof();
+        assert_eq!(proof.old_root, first.new_root);
+        assert!(Cstate::verify_delta_proof(&Sha256Hasher, DEFAULT_CSTATE_HEIGHT, &proof).unwrap());
+
+        let delta = CstateDelta {
+            contract_id: contract_id.clone(),
+            modified_slots: vec![(3, vec![2]), (7, vec![3])],
+        };
+        assert!(Cstate::verify_against_delta(&Sha256Hasher, DEFAULT_CSTATE_HEIGHT, &proof, &delta).unwrap());
+
+        // 漏报或篡改槽位都应失败
+        let partial = CstateDelta {
+            contract_id: contract_id.clone(),
+            modified_slots: vec![(3, vec![2])],
+        };
+        assert!(!Cstate::verify_against_delta(&Sha256Hasher, DEFAULT_CSTATE_HEIGHT, &proof, &partial).unwrap());
+
+        let tampered = CstateDelta {
+            contract_id,
+            modified_slots: vec![(3, vec![1]), (7, vec![3])],
+        };
+        assert!(!Cstate::verify_against_delta(&Sha256Hasher, DEFAULT_CSTATE_HEIGHT, &proof, &tampered).unwrap());
     }
 }
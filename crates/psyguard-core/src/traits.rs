@@ -3,7 +3,10 @@
 //! 参考: 教程第3步 - 定义核心接口
 
 use crate::types::*;
+use crate::checkpoint::CheckpointLeaf;
 use crate::error::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
 
 /// 网络状态接口
 /// 负责从 Realm/Coordinator/DA 获取全局状态
@@ -12,18 +15,33 @@ pub trait NetworkState: Send + Sync {
     /// 参考: 《3-How a Block is Made.md》
     fn latest_finalized_chkp(&self) -> Result<CheckpointRef>;
 
+    /// 获取 checkpoint 的 CHKP 叶 (全局树根)，调用方须校验其哈希等于 chkp_root
+    /// 参考: 《3-How a Block is Made.md》- PsyCheckpointLeaf 含新的全局根
+    fn fetch_checkpoint_leaf(&self, chkp: &CheckpointRef) -> Result<CheckpointLeaf>;
+
     /// 获取用户叶上下文 (带 Merkle 证明)
     /// 参考: 《5-Local Proving (UPS).md》- 从 GUSR 中取回用户上下文
     fn fetch_user_leaf(&self, user_id: &UserId, chkp: &CheckpointRef) -> Result<UserLeafCtx>;
 
-    /// 获取合约元数据 (CFT 根和 CSTATE 高度)
+    /// 获取合约在 chkp 所在区块的元数据 (CFT 根和 CSTATE 高度)
     /// 参考: 《6-Smart Contracts.md》- 从 GCON 获取合约信息
-    fn fetch_contract_meta(&self, contract_id: &ContractId) -> Result<(CftRoot, CstateHeight)>;
+    fn fetch_contract_meta(&self, contract_id: &ContractId, chkp: &CheckpointRef)
+        -> Result<(CftRoot, CstateHeight)>;
+
+    /// 获取合约在 GCON 中的叶 (CLEAF) 及其 Merkle 证明
+    /// 参考: 《6-Smart Contracts.md》- GCON 合约叶记录 CFT 根与 CSTATE 高度
+    fn fetch_contract_leaf(&self, contract_id: &ContractId, chkp: &CheckpointRef)
+        -> Result<GconLeafProof>;
 
-    /// 获取历史 CSTATE 叶值 (带 Merkle 证明，用于只读)
+    /// 获取 owner 持有的历史 CSTATE 叶值 (带 Merkle 证明，用于只读)
     /// 参考: 《2-Miners & Roles on Psy.md》- DA Miners 提供历史读
-    fn fetch_cstate_leaf(&self, contract_id: &ContractId, slot: u64, chkp: &CheckpointRef) 
-        -> Result<(Vec<u8>, Vec<Hash>)>;
+    fn fetch_cstate_leaf(
+        &self,
+        owner: &UserId,
+        contract_id: &ContractId,
+        slot: u64,
+        chkp: &CheckpointRef,
+    ) -> Result<CstateReadWitness>;
 }
 
 /// 证明器接口
@@ -31,28 +49,17 @@ pub trait NetworkState: Send + Sync {
 pub trait Prover: Send + Sync {
     /// 证明 CFC 执行
     /// 参考: 《5-Local Proving (UPS).md》- CFC 本地执行与证明
-    fn prove_cfc(
-        &self,
-        cfc: &CfcId,
-        inputs: &CfcInputs,
-        start_cstate_root: Hash,
-    ) -> Result<(CfcProof, TxEndCtx)>;
+    fn prove_cfc(&self, witness: &CfcWitness) -> Result<(CfcProof, TxEndCtx)>;
 
     /// UPS 集成步骤 (递归合并)
     /// 参考: 《5-Local Proving (UPS).md》- UPS 集成校验 CFT & UCON/CSTATE 过渡
-    fn ups_integrate_step(
-        &self,
-        prev: &UpsStepProof,
-        cfc_proof: &CfcProof,
-        cft_proof: &CftInclusionProof,
-        ucon_delta: &UconDeltaProof,
-        debts_delta: &DebtDeltaProof,
-    ) -> Result<UpsStepProof>;
+    fn ups_integrate_step(&self, witness: &UpsStepWitness) -> Result<UpsStepProof>;
 
-    /// 终结 End Cap
+    /// 终结 End Cap (封装会话头部、最后一步与签名证明)
     /// 参考: 《5-Local Proving (UPS).md》- End Cap 终结电路
     fn finalize_endcap(
         &self,
+        header: &UpsHeader,
         last_step: &UpsStepProof,
         sdkey_sig: &SignatureProof,
     ) -> Result<EndCapProof>;
@@ -64,6 +71,24 @@ pub trait Prover: Send + Sync {
         message: &[u8],
         policy: &SdkeyPolicy,
     ) -> Result<SignatureProof>;
+
+    /// 校验 End Cap 的递归证明与签名证明
+    /// 参考: 《4_Global User Tree Aggregation (GUTA).md》- Realm 验证 End Cap
+    fn verify_endcap(&self, endcap: &EndCapProof) -> Result<bool>;
+}
+
+/// 证明校验接口
+/// 校验由他方 (如委托的证明服务) 生成的证明，只依赖公开输入
+/// 参考: 《5-Local Proving (UPS).md》- 不信任证明者，只验证明
+pub trait ProofVerifier: Send + Sync {
+    /// 校验 CFC 证明与其见证一致
+    fn verify_cfc(&self, witness: &CfcWitness, proof: &CfcProof) -> Result<bool>;
+
+    /// 校验 UPS 步骤证明由上一步与本步的 CFC、CFT、UCON/Debts 变更递归合并而来
+    fn verify_step(&self, witness: &UpsStepWitness, step: &UpsStepProof) -> Result<bool>;
+
+    /// 校验 End Cap 的递归证明与签名证明
+    fn verify_endcap(&self, endcap: &EndCapProof) -> Result<bool>;
 }
 
 /// 提交器接口
@@ -78,16 +103,174 @@ pub trait Submitter: Send + Sync {
     ) -> Result<SubmitReceipt>;
 }
 
-/// SDKey 策略
-/// 参考: 《7-Psy Jargon.md》- SDKey 可编程策略
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct SdkeyPolicy {
-    /// 日限额 (可选)
-    pub daily_limit: Option<u64>,
-    /// 受信合约白名单 (可选)
-    pub trusted_contracts: Option<Vec<ContractId>>,
-    /// 时间锁 (Unix 时间戳，可选)
-    pub time_lock_until: Option<u64>,
-    /// 需要 2FA (可选)
-    pub require_2fa: bool,
+/// 异步网络状态接口
+/// 与 NetworkState 一一对应，供浏览器 (fetch) 与 tokio 中继等不能阻塞线程的环境使用
+#[async_trait]
+pub trait AsyncNetworkState: Send + Sync {
+    /// 获取最新的 finalized checkpoint
+    async fn latest_finalized_chkp(&self) -> Result<CheckpointRef>;
+
+    /// 获取 checkpoint 的 CHKP 叶 (全局树根)，调用方须校验其哈希等于 chkp_root
+    async fn fetch_checkpoint_leaf(&self, chkp: &CheckpointRef) -> Result<CheckpointLeaf>;
+
+    /// 获取用户叶上下文 (带 Merkle 证明)
+    async fn fetch_user_leaf(&self, user_id: &UserId, chkp: &CheckpointRef) -> Result<UserLeafCtx>;
+
+    /// 获取合约在 chkp 所在区块的元数据 (CFT 根和 CSTATE 高度)
+    async fn fetch_contract_meta(&self, contract_id: &ContractId, chkp: &CheckpointRef)
+        -> Result<(CftRoot, CstateHeight)>;
+
+    /// 获取合约在 GCON 中的叶 (CLEAF) 及其 Merkle 证明
+    async fn fetch_contract_leaf(&self, contract_id: &ContractId, chkp: &CheckpointRef)
+        -> Result<GconLeafProof>;
+
+    /// 获取 owner 持有的历史 CSTATE 叶值 (带 Merkle 证明，用于只读)
+    async fn fetch_cstate_leaf(
+        &self,
+        owner: &UserId,
+        contract_id: &ContractId,
+        slot: u64,
+        chkp: &CheckpointRef,
+    ) -> Result<CstateReadWitness>;
+}
+
+/// 异步证明器接口
+/// 与 Prover 一一对应；证明耗时较长，异步版本在等待期间让出执行权
+#[async_trait]
+pub trait AsyncProver: Send + Sync {
+    /// 证明 CFC 执行
+    async fn prove_cfc(&self, witness: &CfcWitness) -> Result<(CfcProof, TxEndCtx)>;
+
+    /// UPS 集成步骤 (递归合并)
+    async fn ups_integrate_step(&self, witness: &UpsStepWitness) -> Result<UpsStepProof>;
+
+    /// 终结 End Cap
+    async fn finalize_endcap(
+        &self,
+        header: &UpsHeader,
+        last_step: &UpsStepProof,
+        sdkey_sig: &SignatureProof,
+    ) -> Result<EndCapProof>;
+
+    /// 生成 SDKey 签名证明
+    async fn sign_with_sdkey(
+        &self,
+        message: &[u8],
+        policy: &SdkeyPolicy,
+    ) -> Result<SignatureProof>;
+
+    /// 校验 End Cap 的递归证明与签名证明
+    async fn verify_endcap(&self, endcap: &EndCapProof) -> Result<bool>;
+}
+
+/// 异步提交器接口
+#[async_trait]
+pub trait AsyncSubmitter: Send + Sync {
+    /// 提交 End Cap 和状态变更
+    async fn submit_endcap(
+        &self,
+        endcap: &EndCapProof,
+        state_deltas: Vec<CstateDelta>,
+    ) -> Result<SubmitReceipt>;
+}
+
+/// 同步实现到异步接口的适配器
+///
+/// 同步调用在第一次 poll 时直接完成，期间不会让出执行权，
+/// 适合内存中的 Mock 或本地后端；耗时的实现应直接实现异步接口。
+pub struct SyncAdapter<T: ?Sized>(Arc<T>);
+
+impl<T: ?Sized> SyncAdapter<T> {
+    pub fn new(inner: Arc<T>) -> Self {
+        Self(inner)
+    }
+
+    /// 被适配的同步实现
+    pub fn inner(&self) -> &Arc<T> {
+        &self.0
+    }
+}
+
+impl<T: ?Sized> Clone for SyncAdapter<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+#[async_trait]
+impl<T: NetworkState + ?Sized> AsyncNetworkState for SyncAdapter<T> {
+    async fn latest_finalized_chkp(&self) -> Result<CheckpointRef> {
+        self.0.latest_finalized_chkp()
+    }
+
+    async fn fetch_checkpoint_leaf(&self, chkp: &CheckpointRef) -> Result<CheckpointLeaf> {
+        self.0.fetch_checkpoint_leaf(chkp)
+    }
+
+    async fn fetch_user_leaf(&self, user_id: &UserId, chkp: &CheckpointRef) -> Result<UserLeafCtx> {
+        self.0.fetch_user_leaf(user_id, chkp)
+    }
+
+    async fn fetch_contract_meta(&self, contract_id: &ContractId, chkp: &CheckpointRef)
+        -> Result<(CftRoot, CstateHeight)> {
+        self.0.fetch_contract_meta(contract_id, chkp)
+    }
+
+    async fn fetch_contract_leaf(&self, contract_id: &ContractId, chkp: &CheckpointRef)
+        -> Result<GconLeafProof> {
+        self.0.fetch_contract_leaf(contract_id, chkp)
+    }
+
+    async fn fetch_cstate_leaf(
+        &self,
+        owner: &UserId,
+        contract_id: &ContractId,
+        slot: u64,
+        chkp: &CheckpointRef,
+    ) -> Result<CstateReadWitness> {
+        self.0.fetch_cstate_leaf(owner, contract_id, slot, chkp)
+    }
+}
+
+#[async_trait]
+impl<T: Prover + ?Sized> AsyncProver for SyncAdapter<T> {
+    async fn prove_cfc(&self, witness: &CfcWitness) -> Result<(CfcProof, TxEndCtx)> {
+        self.0.prove_cfc(witness)
+    }
+
+    async fn ups_integrate_step(&self, witness: &UpsStepWitness) -> Result<UpsStepProof> {
+        self.0.ups_integrate_step(witness)
+    }
+
+    async fn finalize_endcap(
+        &self,
+        header: &UpsHeader,
+        last_step: &UpsStepProof,
+        sdkey_sig: &SignatureProof,
+    ) -> Result<EndCapProof> {
+        self.0.finalize_endcap(header, last_step, sdkey_sig)
+    }
+
+    async fn sign_with_sdkey(
+        &self,
+        message: &[u8],
+        policy: &SdkeyPolicy,
+    ) -> Result<SignatureProof> {
+        self.0.sign_with_sdkey(message, policy)
+    }
+
+    async fn verify_endcap(&self, endcap: &EndCapProof) -> Result<bool> {
+        self.0.verify_endcap(endcap)
+    }
+}
+
+#[async_trait]
+impl<T: Submitter + ?Sized> AsyncSubmitter for SyncAdapter<T> {
+    async fn submit_endcap(
+        &self,
+        endcap: &EndCapProof,
+        state_deltas: Vec<CstateDelta>,
+    ) -> Result<SubmitReceipt> {
+        self.0.submit_endcap(endcap, state_deltas)
+    }
 }
@@ -3,6 +3,7 @@
 //! 严格对应 Psy 协议文档中的数据结构
 
 use serde::{Deserialize, Serialize};
+use crate::hash::HashAlgorithm;
 
 /// 哈希值类型 (32 字节)
 pub type Hash = [u8; 32];
@@ -24,7 +25,7 @@ pub struct CfcId {
 
 /// Checkpoint 引用
 /// 参考: 《5-Local Proving (UPS).md》- UPS 绑定的全局历史根
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CheckpointRef {
     /// CHKP 根哈希
     pub chkp_root: Hash,
@@ -34,22 +35,35 @@ pub struct CheckpointRef {
 
 /// 用户叶上下文
 /// 参考: 《5-Local Proving (UPS).md》- UPS 启动时需要的用户上下文
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UserLeafCtx {
     /// 用户叶哈希
     pub uleaf_hash: Hash,
     /// UCON (User Container) 根
     pub ucon_root: Hash,
+    /// 已登记的 SDKey 公钥哈希 (用户叶原像的一部分)
+    pub public_key_hash: Hash,
     /// 余额
     pub balance: u64,
     /// Nonce
     pub nonce: u64,
 }
 
-/// CFC 函数指纹 (32 字节十六进制)
+/// CFC 函数指纹 (CFC 验证数据的 32 字节哈希，序列化为十六进制)
 /// 参考: 《6-Smart Contracts.md》- CFT 指纹白名单
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct CfcFingerprint(pub String);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CfcFingerprint(pub Hash);
+
+/// CFC 电路验证数据
+/// 参考: 《6-Smart Contracts.md》- 函数指纹为 CFC 验证数据的哈希
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CfcVerifierData {
+    /// 电路常量承诺根
+    pub constants_root: Hash,
+    /// 电路摘要
+    pub circuit_digest: Hash,
+}
 
 /// CFT (Contract Function Tree) 根
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,23 +73,78 @@ pub struct CftRoot(pub Hash);
 /// 参考: 《6-Smart Contracts.md》- 函数指纹在 CFT 的包含证明
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CftInclusionProof {
-    /// Merkle 路径
+    /// 叶索引 (第 i 位决定第 i 层的左右方向)
+    pub leaf_index: u64,
+    /// Merkle 路径 (从叶到根)
     pub merkle_path: Vec<Hash>,
     /// CFT 根
     pub cft_root: CftRoot,
 }
 
+/// CFT 批量包含证明 (多个叶共享内部节点)
+/// 参考: 《6-Smart Contracts.md》- 函数指纹在 CFT 的包含证明
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CftMultiProof {
+    /// 被证明的叶索引 (严格递增)
+    pub leaf_indices: Vec<u64>,
+    /// 树深度
+    pub depth: u32,
+    /// 无法由被证明叶推出的兄弟节点 (逐层自叶向根，层内按索引递增)
+    pub nodes: Vec<Hash>,
+    /// CFT 根
+    pub cft_root: CftRoot,
+}
+
 /// CSTATE (Contract State) 高度
 pub type CstateHeight = u64;
 
+/// 合约函数 (CFT 叶按声明顺序排列)
+/// 参考: 《6-Smart Contracts.md》- 部署时生成 CFT
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractFunction {
+    pub name: String,
+    pub fingerprint: CfcFingerprint,
+}
+
+/// GCON 中合约叶 (CLEAF) 的包含证明
+/// 参考: 《6-Smart Contracts.md》- 从 GCON 获取合约信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GconLeafProof {
+    /// 叶中记录的 CFT 根
+    pub cft_root: CftRoot,
+    /// 叶中记录的 CSTATE 高度
+    pub cstate_height: CstateHeight,
+    /// 该 checkpoint 的 GCON 根
+    pub gcon_root: Hash,
+    /// 叶在 GCON 中的证明 (value 为 CLEAF 哈希)
+    pub proof: MerkleProof,
+}
+
+/// Merkle 包含证明 (稀疏 Merkle 树)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub index: u64,
+    pub value: Hash,
+    pub siblings: Vec<Hash>, // 自叶向根
+}
+
+/// 单叶 Delta Merkle 证明
+/// 参考: 《7-Psy Jargon.md》- Delta Merkle Proof
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeltaMerkleProof {
+    pub index: u64,
+    pub old_value: Hash,
+    pub new_value: Hash,
+    pub siblings: Vec<Hash>, // 自叶向根
+}
+
 /// CSTATE Delta 证明
 /// 参考: 《5-Local Proving (UPS).md》- 证明 CSTATE 根从旧到新的过渡
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CstateDeltaProof {
     pub old_root: Hash,
     pub new_root: Hash,
-    pub merkle_path: Vec<Hash>,
-    pub modified_leaves: Vec<(u64, Hash)>, // (index, new_value)
+    pub leaf_proofs: Vec<DeltaMerkleProof>, // 按写入顺序
 }
 
 /// UCON Delta 证明
@@ -85,6 +154,7 @@ pub struct UconDeltaProof {
     pub old_root: Hash,
     pub new_root: Hash,
     pub contract_id: ContractId,
+    pub ucon_leaf_proof: DeltaMerkleProof, // 合约叶: 旧 CSTATE 根 -> 新 CSTATE 根
     pub cstate_delta: CstateDeltaProof,
 }
 
@@ -101,6 +171,103 @@ pub struct CfcInputs {
     pub function_args: Vec<u8>,
     pub caller: UserId,
     pub contract_state_root: Hash,
+    /// 执行中读取的合约共享状态槽位 (按 checkpoint 的历史读)
+    #[serde(default)]
+    pub read_slots: Vec<u64>,
+    /// 历史读所在 CSTATE 的持有者，read_slots 非空时必须给出
+    #[serde(default)]
+    pub read_owner: Option<UserId>,
+}
+
+/// CSTATE 历史读见证
+///
+/// 证明链: 槽位 -> CSTATE 根 -> 持有者 UCON 根 -> 持有者 ULEAF -> GUSR 根 (CHKP 叶)。
+/// 参考: 《6-Smart Contracts.md》- 合约读取 checkpoint 时的历史状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CstateReadWitness {
+    pub contract_id: ContractId,
+    pub slot: u64,
+    /// 槽位值 (空槽位为 None)
+    pub value: Option<Vec<u8>>,
+    /// 槽位在 CSTATE 中的兄弟路径 (自叶向根)
+    pub siblings: Vec<Hash>,
+    /// 槽位证明所锚定的 CSTATE 根
+    pub cstate_root: Hash,
+    /// CSTATE 的持有者
+    pub owner: UserId,
+    /// CSTATE 根在持有者 UCON 中的包含证明
+    pub ucon_proof: MerkleProof,
+    /// 持有者在该 checkpoint 的用户叶
+    pub owner_leaf: UserLeafCtx,
+    /// 持有者用户叶记录的最近同步 checkpoint (ULEAF 原像的一部分)
+    pub owner_checkpoint_id: u64,
+    /// 持有者用户叶在 GUSR 中的包含证明
+    pub owner_leaf_proof: MerkleProof,
+}
+
+/// 调用者 CSTATE 的当前内容 (私有见证)
+/// CFC 在其上执行写入，结束根须等于写入后的树根
+/// 参考: 《5-Local Proving (UPS).md》- 证明 CSTATE 根从旧到新的过渡
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CstateWitness {
+    pub height: CstateHeight,
+    pub hash_algorithm: HashAlgorithm,
+    /// 非空槽位 (按槽位排序)
+    pub slots: Vec<(u64, Vec<u8>)>,
+}
+
+/// UCON 中单个合约的本地状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractSnapshot {
+    pub contract_id: ContractId,
+    pub cstate_root: Hash,
+    /// 完整 CSTATE 内容 (仅记录根的合约为 None，之后无法在其上写入)
+    pub cstate: Option<CstateWitness>,
+}
+
+/// 可持久化的 UCON，会话结束后导出、下次会话启动时恢复
+/// 参考: 《5-Local Proving (UPS).md》- UPS 启动时的本地用户状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UconSnapshot {
+    pub user_id: UserId,
+    pub hash_algorithm: HashAlgorithm,
+    /// 按合约 ID 排序
+    pub contracts: Vec<ContractSnapshot>,
+}
+
+/// CFC 证明的完整输入
+/// 参考: 《5-Local Proving (UPS).md》- CFC 本地执行与证明
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CfcWitness {
+    pub cfc: CfcId,
+    pub inputs: CfcInputs,
+    /// 历史读所针对的 checkpoint
+    pub checkpoint_ref: CheckpointRef,
+    /// 调用者 CSTATE 的起始根
+    pub start_cstate_root: Hash,
+    /// 起始根对应的 CSTATE 内容
+    #[serde(default)]
+    pub cstate: CstateWitness,
+    /// 调用前的 UCON 根
+    pub caller_ucon_root: Hash,
+    /// 该合约叶在调用者 UCON 中的路径 (叶值为 start_cstate_root)
+    pub caller_ucon_proof: MerkleProof,
+    /// inputs.read_slots 对应的历史读
+    pub state_reads: Vec<CstateReadWitness>,
+    /// 调用前的 Debts
+    pub debts: Vec<(ContractId, u64)>,
+    pub cft_proof: CftInclusionProof,
+}
+
+/// UPS 集成步骤的完整输入
+/// 参考: 《5-Local Proving (UPS).md》- UPS 集成校验 CFT & UCON/CSTATE 过渡
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpsStepWitness {
+    pub prev: UpsStepProof,
+    pub cfc_proof: CfcProof,
+    pub cft_proof: CftInclusionProof,
+    pub ucon_delta: UconDeltaProof,
+    pub debts_delta: DebtDeltaProof,
 }
 
 /// CFC 证明
@@ -118,11 +285,12 @@ pub struct TxEndCtx {
     pub gas_used: u64,
     pub success: bool,
     pub return_data: Vec<u8>,
+    pub modified_slots: Vec<(u64, Vec<u8>)>, // (slot_index, new_value)
 }
 
 /// UPS 头部
 /// 参考: 《5-Local Proving (UPS).md》- UPS Header
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UpsHeader {
     pub user_id: UserId,
     pub checkpoint_ref: CheckpointRef,
@@ -132,7 +300,7 @@ pub struct UpsHeader {
 
 /// UPS 步骤证明
 /// 参考: 《5-Local Proving (UPS).md》- UPS 步骤证明
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UpsStepProof {
     pub step_number: u32,
     pub accumulated_proof: Vec<u8>,
@@ -142,11 +310,37 @@ pub struct UpsStepProof {
 
 /// 签名证明 (SDKey)
 /// 参考: 《7-Psy Jargon.md》- SDKey 签名电路
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SignatureProof {
     pub proof_data: Vec<u8>,
     pub public_key_hash: Hash,
+    pub message_hash: Hash, // 签名覆盖的会话消息哈希
     pub policy_satisfied: Vec<String>,
+    /// 会话终结前验证的第二因素 (其承诺已包含在 message_hash 中)
+    #[serde(default)]
+    pub second_factor: Option<SecondFactorAttestation>,
+}
+
+/// 第二因素验证凭证
+///
+/// 由 `second_factor` 模块在验证通过后生成，绑定到不含 2FA 的会话消息哈希。
+/// 参考: 《7-Psy Jargon.md》- SDKey 签名电路 (2FA)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SecondFactorAttestation {
+    /// RFC 6238 TOTP
+    Totp {
+        /// 登记凭据标识 (密钥的哈希)
+        credential_id: Hash,
+        /// 验证通过的时间步
+        time_step: u64,
+        session_binding: Hash,
+    },
+    /// 第二公钥 (Ed25519) 对会话挑战的签名
+    Challenge {
+        public_key: [u8; 32],
+        signature: Vec<u8>,
+        session_binding: Hash,
+    },
 }
 
 /// End Cap 证明
@@ -207,6 +401,9 @@ pub struct ReadOnlyPreviewResult {
     pub requires_2fa: bool,
     pub estimated_gas: u64,
     pub error_message: Option<String>,
+    /// 逐条策略规则的求值结果
+    #[serde(default)]
+    pub policy_rules: Vec<RuleResult>,
 }
 
 /// 槽位修改信息
@@ -259,26 +456,153 @@ pub struct UpsAccumulatedInfo {
     pub new_ucon_root: Hash,
 }
 
-/// SDKey 约束检查结果
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SdkeyConstraintCheck {
-    pub limit_check: ConstraintCheckResult,
-    pub whitelist_check: ConstraintCheckResult,
-    pub timelock_check: ConstraintCheckResult,
-    pub twofa_check: TwoFaCheckResult,
+/// SDKey 策略模式版本
+pub const SDKEY_POLICY_VERSION: u32 = 3;
+
+/// SDKey 策略
+/// 预演、签名与 WASM 层共用，由 `sdkey::SdkeyPolicyValidator` 统一求值
+/// 参考: 《7-Psy Jargon.md》- SDKey 可编程策略
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SdkeyPolicy {
+    /// 策略模式版本 (缺省视为 1)
+    #[serde(default = "sdkey_policy_v1")]
+    pub version: u32,
+    /// 日限额 (可选)
+    #[serde(default)]
+    pub daily_limit: Option<u64>,
+    /// 受信合约白名单 (None 表示不限制，空列表表示不信任任何合约)
+    #[serde(default)]
+    pub trusted_contracts: Option<Vec<ContractId>>,
+    /// 时间锁 (Unix 时间戳，可选)
+    #[serde(default)]
+    pub time_lock_until: Option<u64>,
+    /// 需要 2FA
+    #[serde(default)]
+    pub require_2fa: bool,
+    /// 组合规则 (策略版本 2 起)
+    #[serde(default)]
+    pub rules: Vec<PolicyRule>,
+    /// 登记的第二因素凭据标识 (策略版本 3 起；None 表示接受任一已验证因素)
+    #[serde(default)]
+    pub second_factor_key: Option<Hash>,
 }
 
-/// 约束检查结果
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ConstraintCheckResult {
-    pub passed: bool,
-    pub message: String,
+fn sdkey_policy_v1() -> u32 {
+    1
 }
 
-/// 2FA 检查结果
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TwoFaCheckResult {
-    pub required: bool,
-    pub verified: bool,
-    pub message: String,
+/// 默认日限额
+pub const DEFAULT_DAILY_LIMIT: u64 = 10000;
+
+/// 默认策略: 仅有日限额 (DEFAULT_DAILY_LIMIT)，不限制合约、不设时间锁
+impl Default for SdkeyPolicy {
+    fn default() -> Self {
+        Self {
+            version: SDKEY_POLICY_VERSION,
+            daily_limit: Some(DEFAULT_DAILY_LIMIT),
+            trusted_contracts: None,
+            time_lock_until: None,
+            require_2fa: false,
+            rules: vec![],
+            second_factor_key: None,
+        }
+    }
+}
+
+/// 一条具名策略规则，交易须使其表达式成立
+/// 参考: policy_expr - 文本形式与求值
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyRule {
+    pub name: String,
+    pub expr: PolicyExpr,
+}
+
+/// 策略表达式
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum PolicyExpr {
+    Const { value: bool },
+    And { args: Vec<PolicyExpr> },
+    Or { args: Vec<PolicyExpr> },
+    Not { arg: Box<PolicyExpr> },
+    /// `when` 成立时要求 `then` 成立
+    Implies { when: Box<PolicyExpr>, then: Box<PolicyExpr> },
+    /// 数值比较
+    Cmp { field: NumField, cmp: CmpOp, value: u64 },
+    /// 数值落在 `[start, end)` 内
+    InRange { field: NumField, start: u64, end: u64 },
+    /// 字符串相等
+    Is { field: StrField, value: String },
+    /// 字符串属于列表
+    In { field: StrField, values: Vec<String> },
+    /// 已验证第二因素
+    SecondFactor,
+}
+
+/// 数值字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NumField {
+    /// 本笔金额
+    Amount,
+    /// Unix 时间戳 (秒)
+    Timestamp,
+    /// UTC 小时 (0-23)
+    Hour,
+    /// UTC 星期 (0 = 周一)
+    Weekday,
+    /// 会话内第几笔交易 (含本笔)
+    TxCount,
+    /// 会话内本合约的累计金额 (含本笔)
+    SessionAmount,
+    /// 24 小时窗口内本合约的累计金额 (含本笔)
+    DailyAmount,
+}
+
+/// 字符串字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StrField {
+    /// 调用的函数名
+    Function,
+    /// 合约 ID
+    Contract,
+    /// 收款方 (参数中的 `to`)
+    Recipient,
+}
+
+/// 比较运算
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// 规则求值结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleOutcome {
+    Passed,
+    /// 验证第二因素后即可通过
+    RequiresSecondFactor,
+    Failed,
+}
+
+/// 单条规则的求值结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleResult {
+    pub name: String,
+    pub outcome: RuleOutcome,
+    pub reason: String,
+}
+
+/// 策略求值结果 (逐条规则，前端展示各项约束的通过/未通过状态)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyEvaluation {
+    pub rules: Vec<RuleResult>,
 }
@@ -5,25 +5,69 @@
 use crate::types::*;
 use crate::traits::*;
 use crate::error::{PsyGuardError, Result};
+use crate::state::Ucon;
+use crate::guta::GutaConfig;
+use crate::hash::HashAlgorithm;
+use crate::message::SessionMessage;
+use crate::registry::{CfcCftProof, CftRegistry};
+use crate::cft::CftVerifier;
+use crate::second_factor::VerifiedFactor;
+use crate::sdkey::SdkeyPolicyValidator;
 use std::sync::Arc;
 
+/// 会话使用的证明后端
+#[derive(Clone)]
+enum ProverBackend {
+    Sync(Arc<dyn Prover>),
+    Async(Arc<dyn AsyncProver>),
+}
+
+/// 会话使用的网络后端 (CSTATE 历史读)
+#[derive(Clone)]
+enum NetworkBackend {
+    Sync(Arc<dyn NetworkState>),
+    Async(Arc<dyn AsyncNetworkState>),
+}
+
 /// UPS 会话
+///
+/// 同步构造的会话可以同时使用同步与异步方法；
+/// 以异步后端构造的会话只能使用 `*_async` 方法。
 pub struct UpsSession {
     header: UpsHeader,
     current_step: UpsStepProof,
     step_count: u32,
-    network: Arc<dyn NetworkState>,
-    prover: Arc<dyn Prover>,
+    prover: ProverBackend,
+    network: NetworkBackend,
     state_deltas: Vec<CstateDelta>,
+    /// 用户 UCON (合约 ID -> CSTATE 根)
+    ucon: Ucon,
+    /// 经 GCON 校验的 CFT 根与函数清单
+    cft_registry: Arc<CftRegistry>,
+    /// 终结前验证的第二因素
+    second_factor: Option<VerifiedFactor>,
 }
 
 impl UpsSession {
-    /// 初始化新的 UPS 会话
+    /// 初始化新的 UPS 会话 (用户尚无合约状态)
     /// 参考: 《5-Local Proving (UPS).md》- UPS 启动
     pub fn new(
         user_id: UserId,
         network: Arc<dyn NetworkState>,
         prover: Arc<dyn Prover>,
+    ) -> Result<Self> {
+        let ucon = Ucon::new(user_id.clone());
+        Self::with_ucon(user_id, ucon, network, prover)
+    }
+
+    /// 以本地保存的 UCON 初始化 UPS 会话
+    /// UCON 根必须与 GUSR 用户叶中的 ucon_root 一致
+    /// 参考: 《5-Local Proving (UPS).md》- UPS 启动
+    pub fn with_ucon(
+        user_id: UserId,
+        ucon: Ucon,
+        network: Arc<dyn NetworkState>,
+        prover: Arc<dyn Prover>,
     ) -> Result<Self> {
         // 1. 获取最新 finalized checkpoint
         let checkpoint_ref = network.latest_finalized_chkp()?;
@@ -31,6 +75,77 @@ impl UpsSession {
         // 2. 获取用户叶上下文 (带 Merkle 证明)
         let user_leaf_ctx = network.fetch_user_leaf(&user_id, &checkpoint_ref)?;
 
+        let cft_registry = Arc::new(CftRegistry::new(network.clone(), ucon.hasher().clone()));
+        Self::start(
+            user_id,
+            ucon,
+            checkpoint_ref,
+            user_leaf_ctx,
+            cft_registry,
+            ProverBackend::Sync(prover),
+            NetworkBackend::Sync(network),
+        )
+    }
+
+    /// 以异步后端初始化新的 UPS 会话
+    pub async fn new_async(
+        user_id: UserId,
+        network: Arc<dyn AsyncNetworkState>,
+        prover: Arc<dyn AsyncProver>,
+    ) -> Result<Self> {
+        let ucon = Ucon::new(user_id.clone());
+        Self::with_ucon_async(user_id, ucon, network, prover).await
+    }
+
+    /// 以本地保存的 UCON 和异步后端初始化 UPS 会话
+    pub async fn with_ucon_async(
+        user_id: UserId,
+        ucon: Ucon,
+        network: Arc<dyn AsyncNetworkState>,
+        prover: Arc<dyn AsyncProver>,
+    ) -> Result<Self> {
+        let checkpoint_ref = network.latest_finalized_chkp().await?;
+        let user_leaf_ctx = network.fetch_user_leaf(&user_id, &checkpoint_ref).await?;
+
+        let cft_registry = Arc::new(CftRegistry::with_async_network(
+            network.clone(),
+            ucon.hasher().clone(),
+        ));
+        Self::start(
+            user_id,
+            ucon,
+            checkpoint_ref,
+            user_leaf_ctx,
+            cft_registry,
+            ProverBackend::Async(prover),
+            NetworkBackend::Async(network),
+        )
+    }
+
+    /// 校验 UCON 与用户叶一致，并构建会话初始状态
+    fn start(
+        user_id: UserId,
+        ucon: Ucon,
+        checkpoint_ref: CheckpointRef,
+        user_leaf_ctx: UserLeafCtx,
+        cft_registry: Arc<CftRegistry>,
+        prover: ProverBackend,
+        network: NetworkBackend,
+    ) -> Result<Self> {
+        if ucon.user_id != user_id {
+            return Err(PsyGuardError::UpsSessionError(format!(
+                "UCON 属于 {:?}，不属于 {:?}",
+                ucon.user_id, user_id
+            )));
+        }
+        if ucon.root != user_leaf_ctx.ucon_root {
+            return Err(PsyGuardError::InvalidStateTransition(format!(
+                "本地 UCON 根 {} 与用户叶 ucon_root {} 不一致",
+                hex::encode(ucon.root),
+                hex::encode(user_leaf_ctx.ucon_root)
+            )));
+        }
+
         // 3. 构建 UPS Header
         let header = UpsHeader {
             user_id,
@@ -51,69 +166,76 @@ impl UpsSession {
             header,
             current_step,
             step_count: 0,
-            network,
             prover,
+            network,
             state_deltas: vec![],
+            second_factor: None,
+            ucon,
+            cft_registry,
         })
     }
 
+    /// 使用共享的 CFT 注册表 (哈希函数需与 UCON 一致)
+    pub fn with_cft_registry(mut self, cft_registry: Arc<CftRegistry>) -> Self {
+        self.cft_registry = cft_registry;
+        self
+    }
+
     /// 执行一个 CFC 并集成到 UPS
     /// 参考: 《5-Local Proving (UPS).md》- UPS 集成步骤
     pub fn execute_cfc(
         &mut self,
         cfc_id: &CfcId,
         inputs: &CfcInputs,
-        cft_proof: &CftInclusionProof,
     ) -> Result<TxEndCtx> {
-        // 1. 获取合约当前状态根
-        let start_cstate_root = self.get_contract_state_root(&cfc_id.contract_id)?;
+        let prover = self.sync_prover("execute_cfc_async")?;
 
-        // 2. 生成 CFC 证明
-        let (cfc_proof, tx_end_ctx) = self.prover.prove_cfc(
-            cfc_id,
-            inputs,
-            start_cstate_root,
-        )?;
+        // 0-1. CFT 包含证明与合约当前状态根
+        let (cft_proof, start_cstate_root) = self.prepare_cfc(cfc_id)?;
 
-        // 3. 构建 UCON Delta 证明
-        let ucon_delta = UconDeltaProof {
-            old_root: self.current_step.current_ucon_root,
-            new_root: tx_end_ctx.end_contract_state_root,
-            contract_id: cfc_id.contract_id.clone(),
-            cstate_delta: CstateDeltaProof {
-                old_root: start_cstate_root,
-                new_root: tx_end_ctx.end_contract_state_root,
-                merkle_path: vec![],
-                modified_leaves: vec![],
-            },
-        };
+        // 2. CSTATE 历史读并组装 CFC 见证
+        let state_reads = self.fetch_state_reads(cfc_id, inputs)?;
+        let witness = self.cfc_witness(cfc_id, inputs, cft_proof, start_cstate_root, state_reads)?;
 
-        // 4. 构建 Debts Delta (简化版)
-        let debts_delta = DebtDeltaProof {
-            old_debts: self.current_step.current_debts.clone(),
-            new_debts: self.current_step.current_debts.clone(),
-        };
+        // 3. 生成 CFC 证明
+        let (cfc_proof, tx_end_ctx) = prover.prove_cfc(&witness)?;
 
-        // 5. UPS 集成步骤 (递归合并)
-        let next_step = self.prover.ups_integrate_step(
-            &self.current_step,
-            &cfc_proof,
-            cft_proof,
-            &ucon_delta,
-            &debts_delta,
-        )?;
+        // 4-5. UCON / Debts Delta
+        let (next_ucon, ucon_delta, debts_delta) = self.build_deltas(cfc_id, &tx_end_ctx)?;
 
-        // 6. 更新当前步骤
-        self.step_count += 1;
-        self.current_step = next_step;
+        // 6. UPS 集成步骤 (递归合并)
+        let step_witness = self.step_witness(witness.cft_proof, cfc_proof, ucon_delta, debts_delta);
+        let next_step = prover.ups_integrate_step(&step_witness)?;
 
-        // 7. 记录状态变更 (用于提交)
-        self.state_deltas.push(CstateDelta {
-            contract_id: cfc_id.contract_id.clone(),
-            modified_slots: vec![],
-        });
+        self.commit_step(cfc_id, next_step, next_ucon, &step_witness.ucon_delta, tx_end_ctx)
+    }
 
-        Ok(tx_end_ctx)
+    /// 异步执行一个 CFC 并集成到 UPS
+    ///
+    /// 步骤与 execute_cfc 相同；网络获取与证明期间让出执行权。
+    pub async fn execute_cfc_async(
+        &mut self,
+        cfc_id: &CfcId,
+        inputs: &CfcInputs,
+    ) -> Result<TxEndCtx> {
+        let prover = self.async_prover();
+
+        self.cft_registry
+            .load_contract(&cfc_id.contract_id, &self.header.checkpoint_ref)
+            .await?;
+        let (cft_proof, start_cstate_root) = self.prepare_cfc(cfc_id)?;
+
+        let state_reads = self.fetch_state_reads_async(cfc_id, inputs).await?;
+        let witness = self.cfc_witness(cfc_id, inputs, cft_proof, start_cstate_root, state_reads)?;
+
+        let (cfc_proof, tx_end_ctx) = prover.prove_cfc(&witness).await?;
+
+        let (next_ucon, ucon_delta, debts_delta) = self.build_deltas(cfc_id, &tx_end_ctx)?;
+
+        let step_witness = self.step_witness(witness.cft_proof, cfc_proof, ucon_delta, debts_delta);
+        let next_step = prover.ups_integrate_step(&step_witness).await?;
+
+        self.commit_step(cfc_id, next_step, next_ucon, &step_witness.ucon_delta, tx_end_ctx)
     }
 
     /// 终结会话并生成 End Cap
@@ -122,16 +244,48 @@ impl UpsSession {
         &self,
         sdkey_policy: &SdkeyPolicy,
     ) -> Result<EndCapProof> {
-        // 1. 生成 SDKey 签名证明
-        let message = self.compute_session_message();
-        let signature_proof = self.prover.sign_with_sdkey(&message, sdkey_policy)?;
+        let prover = self.sync_prover("finalize_async")?;
+
+        // 1. 生成 SDKey 签名证明 (消息承诺第二因素)
+        let (message, second_factor) = self.signing_message(sdkey_policy)?;
+        let mut signature_proof = prover.sign_with_sdkey(&message, sdkey_policy)?;
+        signature_proof.second_factor = second_factor;
 
         // 2. 生成 End Cap
-        let endcap = self.prover.finalize_endcap(&self.current_step, &signature_proof)?;
+        let endcap = prover.finalize_endcap(&self.header, &self.current_step, &signature_proof)?;
 
         Ok(endcap)
     }
 
+    /// 异步终结会话并生成 End Cap
+    pub async fn finalize_async(
+        &self,
+        sdkey_policy: &SdkeyPolicy,
+    ) -> Result<EndCapProof> {
+        let prover = self.async_prover();
+
+        let (message, second_factor) = self.signing_message(sdkey_policy)?;
+        let mut signature_proof = prover.sign_with_sdkey(&message, sdkey_policy).await?;
+        signature_proof.second_factor = second_factor;
+
+        prover.finalize_endcap(&self.header, &self.current_step, &signature_proof).await
+    }
+
+    /// 第二因素需绑定的会话消息哈希 (不含 2FA)
+    ///
+    /// TOTP 验证以此为 `session_binding`；挑战-应答设备签名
+    /// `ChallengeKey::challenge(&binding)`。执行完全部 CFC 后再取。
+    pub fn second_factor_binding(&self) -> Hash {
+        SessionMessage::new(&self.header, &self.current_step, &self.state_deltas).hash()
+    }
+
+    /// 附加已验证的第二因素，须绑定到当前会话
+    pub fn attach_second_factor(&mut self, factor: VerifiedFactor) -> Result<()> {
+        factor.attestation().check(&self.second_factor_binding())?;
+        self.second_factor = Some(factor);
+        Ok(())
+    }
+
     /// 获取会话头部
     pub fn header(&self) -> &UpsHeader {
         &self.header
@@ -147,25 +301,915 @@ impl UpsSession {
         &self.state_deltas
     }
 
-    /// 获取合约状态根 (从当前 UCON 中)
-    fn get_contract_state_root(&self, _contract_id: &ContractId) -> Result<Hash> {
-        // TODO: 从 UCON 树中查找对应合约的 CSTATE 根
-        Ok([0u8; 32])
+    /// 获取当前 UCON
+    pub fn ucon(&self) -> &Ucon {
+        &self.ucon
+    }
+
+    /// 获取 CFT 注册表 (用于登记合约函数清单)
+    pub fn cft_registry(&self) -> &Arc<CftRegistry> {
+        &self.cft_registry
+    }
+
+    /// 同步方法使用的证明器；异步后端的会话返回错误并提示对应的异步方法
+    fn sync_prover(&self, async_method: &str) -> Result<Arc<dyn Prover>> {
+        match &self.prover {
+            ProverBackend::Sync(prover) => Ok(prover.clone()),
+            ProverBackend::Async(_) => Err(PsyGuardError::UpsSessionError(format!(
+                "会话使用异步证明器，请改用 {}",
+                async_method
+            ))),
+        }
+    }
+
+    /// 异步方法使用的证明器 (同步证明器经 SyncAdapter 适配)
+    fn async_prover(&self) -> Arc<dyn AsyncProver> {
+        match &self.prover {
+            ProverBackend::Sync(prover) => Arc::new(SyncAdapter::new(prover.clone())),
+            ProverBackend::Async(prover) => prover.clone(),
+        }
+    }
+
+    /// 取得 CFC 的 CFT 包含证明 (证明前独立校验) 及合约当前状态根
+    /// 参考: 《6-Smart Contracts.md》- 函数指纹 Merkle 校验
+    fn prepare_cfc(&self, cfc_id: &CfcId) -> Result<(CftInclusionProof, Hash)> {
+        let cft = self.cft_registry.prove(cfc_id, &self.header.checkpoint_ref)?;
+        self.verify_cft_proof(cfc_id, &cft)?;
+        let start_cstate_root = self.get_contract_state_root(&cfc_id.contract_id)?;
+        Ok((cft.proof, start_cstate_root))
+    }
+
+    /// 历史读所在 CSTATE 的持有者 (无历史读时为 None)
+    fn read_owner(inputs: &CfcInputs) -> Result<Option<&UserId>> {
+        match (&inputs.read_owner, inputs.read_slots.is_empty()) {
+            (_, true) => Ok(None),
+            (Some(owner), false) => Ok(Some(owner)),
+            (None, false) => Err(PsyGuardError::InvalidInput(
+                "历史读需要指定 CSTATE 持有者 (read_owner)".to_string(),
+            )),
+        }
+    }
+
+    /// 按 inputs.read_slots 在会话 checkpoint 上读取 read_owner 的合约 CSTATE (带 Merkle 路径)
+    /// 参考: 《6-Smart Contracts.md》- 合约读取 checkpoint 时的历史状态
+    fn fetch_state_reads(&self, cfc_id: &CfcId, inputs: &CfcInputs) -> Result<Vec<CstateReadWitness>> {
+        let Some(owner) = Self::read_owner(inputs)? else {
+            return Ok(vec![]);
+        };
+        let network = match &self.network {
+            NetworkBackend::Sync(network) => network,
+            NetworkBackend::Async(_) => {
+                return Err(PsyGuardError::UpsSessionError(
+                    "会话使用异步网络后端，请改用 execute_cfc_async".to_string(),
+                ))
+            }
+        };
+        let reads = inputs
+            .read_slots
+            .iter()
+            .map(|&slot| {
+                network.fetch_cstate_leaf(owner, &cfc_id.contract_id, slot, &self.header.checkpoint_ref)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.verify_state_reads(cfc_id, owner, &inputs.read_slots, &reads)?;
+        Ok(reads)
+    }
+
+    /// fetch_state_reads 的异步版本 (同步网络后端经 SyncAdapter 适配)
+    async fn fetch_state_reads_async(
+        &self,
+        cfc_id: &CfcId,
+        inputs: &CfcInputs,
+    ) -> Result<Vec<CstateReadWitness>> {
+        let Some(owner) = Self::read_owner(inputs)? else {
+            return Ok(vec![]);
+        };
+        let network: Arc<dyn AsyncNetworkState> = match &self.network {
+            NetworkBackend::Sync(network) => Arc::new(SyncAdapter::new(network.clone())),
+            NetworkBackend::Async(network) => network.clone(),
+        };
+        let mut reads = Vec::with_capacity(inputs.read_slots.len());
+        for &slot in &inputs.read_slots {
+            let read = network
+                .fetch_cstate_leaf(owner, &cfc_id.contract_id, slot, &self.header.checkpoint_ref)
+                .await?;
+            reads.push(read);
+        }
+        self.verify_state_reads(cfc_id, owner, &inputs.read_slots, &reads)?;
+        Ok(reads)
+    }
+
+    /// 校验历史读是所请求持有者、合约与槽位的值，且证明链锚定到会话 checkpoint
+    fn verify_state_reads(
+        &self,
+        cfc_id: &CfcId,
+        owner: &UserId,
+        slots: &[u64],
+        reads: &[CstateReadWitness],
+    ) -> Result<()> {
+        let checkpoint = self.cft_registry.checkpoint_leaf(&self.header.checkpoint_ref)?;
+        let cstate_height = self.cstate_height(cfc_id)?;
+        let name = self.cft_registry.hasher().name();
+        let guta = GutaConfig {
+            hash_algorithm: HashAlgorithm::from_name(name)
+                .ok_or_else(|| PsyGuardError::InvalidInput(format!("哈希 {} 无法校验历史读", name)))?,
+            ..GutaConfig::default()
+        };
+
+        for (read, &slot) in reads.iter().zip(slots) {
+            if read.owner != *owner || read.contract_id != cfc_id.contract_id || read.slot != slot {
+                return Err(PsyGuardError::MerkleProofInvalid(format!(
+                    "请求 {:?} 持有的合约 {:?} 槽位 {}，网络返回 {:?} 持有的合约 {:?} 槽位 {}",
+                    owner.0, cfc_id.contract_id.0, slot, read.owner.0, read.contract_id.0, read.slot
+                )));
+            }
+            read.verify(&guta, &checkpoint, cstate_height)?;
+        }
+        Ok(())
+    }
+
+    /// 组装 CFC 见证：调用者 UCON 路径、历史读、当前 Debts 与 CFT 证明
+    fn cfc_witness(
+        &self,
+        cfc_id: &CfcId,
+        inputs: &CfcInputs,
+        cft_proof: CftInclusionProof,
+        start_cstate_root: Hash,
+        state_reads: Vec<CstateReadWitness>,
+    ) -> Result<CfcWitness> {
+        Ok(CfcWitness {
+            cfc: cfc_id.clone(),
+            inputs: inputs.clone(),
+            checkpoint_ref: self.header.checkpoint_ref.clone(),
+            start_cstate_root,
+            cstate: self.ucon.cstate_witness(&cfc_id.contract_id, self.cstate_height(cfc_id)?)?,
+            caller_ucon_root: self.ucon.root,
+            caller_ucon_proof: self.ucon.prove_contract(&cfc_id.contract_id)?,
+            state_reads,
+            debts: self.current_step.current_debts.clone(),
+            cft_proof,
+        })
     }
 
-    /// 计算会话消息 (用于签名)
-    fn compute_session_message(&self) -> Vec<u8> {
-        // TODO: 序列化会话关键信息
-        vec![]
+    /// 组装 UPS 集成步骤见证
+    fn step_witness(
+        &self,
+        cft_proof: CftInclusionProof,
+        cfc_proof: CfcProof,
+        ucon_delta: UconDeltaProof,
+        debts_delta: DebtDeltaProof,
+    ) -> UpsStepWitness {
+        UpsStepWitness {
+            prev: self.current_step.clone(),
+            cfc_proof,
+            cft_proof,
+            ucon_delta,
+            debts_delta,
+        }
+    }
+
+    /// 构建 UCON Delta 证明 (合约叶: 旧 CSTATE 根 -> 新 CSTATE 根) 与 Debts Delta
+    /// CFC 的槽位写入在会话持有的 CSTATE 上重放，逐叶证明随 CSTATE Delta 提交；
+    /// 在临时副本上更新，集成失败时会话状态保持不变
+    fn build_deltas(
+        &self,
+        cfc_id: &CfcId,
+        tx_end_ctx: &TxEndCtx,
+    ) -> Result<(Ucon, UconDeltaProof, DebtDeltaProof)> {
+        let mut next_ucon = self.ucon.clone();
+        let ucon_delta = next_ucon.apply_writes(
+            &cfc_id.contract_id,
+            self.cstate_height(cfc_id)?,
+            &tx_end_ctx.modified_slots,
+        )?;
+
+        // 在会话持有的 CSTATE 上重放写入，结果必须与证明声明的结束根一致
+        if ucon_delta.cstate_delta.new_root != tx_end_ctx.end_contract_state_root {
+            return Err(PsyGuardError::InvalidStateTransition(format!(
+                "合约 {:?} 的槽位写入得到的 CSTATE 根与 TxEndCtx 不一致",
+                cfc_id.contract_id
+            )));
+        }
+
+        // Debts Delta (简化版)
+        let debts_delta = DebtDeltaProof {
+            old_debts: self.current_step.current_debts.clone(),
+            new_debts: self.current_step.current_debts.clone(),
+        };
+
+        Ok((next_ucon, ucon_delta, debts_delta))
+    }
+
+    /// 接受集成步骤并记录状态变更
+    fn commit_step(
+        &mut self,
+        cfc_id: &CfcId,
+        next_step: UpsStepProof,
+        next_ucon: Ucon,
+        ucon_delta: &UconDeltaProof,
+        tx_end_ctx: TxEndCtx,
+    ) -> Result<TxEndCtx> {
+        // 6. 新步骤必须以本次 UCON 过渡的新根结束，下一步从这里接续
+        if next_step.current_ucon_root != ucon_delta.new_root {
+            return Err(PsyGuardError::InvalidStateTransition(format!(
+                "步骤 {} 的 UCON 根与 Delta 证明不一致",
+                next_step.step_number
+            )));
+        }
+
+        // 7. 更新当前步骤
+        self.step_count += 1;
+        self.current_step = next_step;
+        self.ucon = next_ucon;
+
+        // 8. 记录状态变更 (用于提交)
+        self.state_deltas.push(CstateDelta {
+            contract_id: cfc_id.contract_id.clone(),
+            modified_slots: tx_end_ctx.modified_slots.clone(),
+        });
+
+        Ok(tx_end_ctx)
+    }
+
+
+    /// 校验 CFC 指纹在合约经 GCON 校验的 CFT 根下成立
+    fn verify_cft_proof(&self, cfc_id: &CfcId, cft: &CfcCftProof) -> Result<()> {
+        let (cft_root, _) = self
+            .cft_registry
+            .contract_meta(&cfc_id.contract_id, &self.header.checkpoint_ref)?;
+
+        let in_cft = cft.proof.cft_root.0 == cft_root.0
+            && CftVerifier::verify_inclusion(self.ucon.hasher().as_ref(), &cft.fingerprint, &cft.proof)?;
+        if !in_cft {
+            return Err(PsyGuardError::CftVerificationFailed(format!(
+                "{} ({}::{})",
+                cft.fingerprint, cfc_id.contract_id.0, cfc_id.function_name
+            )));
+        }
+        Ok(())
+    }
+
+    /// 合约经 GCON 校验的 CSTATE 树高
+    fn cstate_height(&self, cfc_id: &CfcId) -> Result<CstateHeight> {
+        let (_, height) = self
+            .cft_registry
+            .contract_meta(&cfc_id.contract_id, &self.header.checkpoint_ref)?;
+        Ok(height)
+    }
+
+    /// 获取合约状态根 (从当前 UCON 中，未写入过的合约为空根)
+    fn get_contract_state_root(&self, contract_id: &ContractId) -> Result<Hash> {
+        Ok(self.ucon.get_contract_state(contract_id).copied().unwrap_or([0u8; 32]))
+    }
+
+    /// 计算待签名的会话消息及其承诺的第二因素
+    ///
+    /// 策略要求 2FA 时必须已附加第二因素；附加之后又执行了 CFC 的凭证已失效。
+    /// 参考: message::SessionMessage - 规范化会话承诺
+    fn signing_message(
+        &self,
+        sdkey_policy: &SdkeyPolicy,
+    ) -> Result<(Vec<u8>, Option<SecondFactorAttestation>)> {
+        let message = SessionMessage::new(&self.header, &self.current_step, &self.state_deltas);
+        let Some(factor) = &self.second_factor else {
+            if sdkey_policy.require_2fa {
+                return Err(PsyGuardError::SdkeyPolicyViolation(
+                    "策略要求 2FA，请先附加已验证的第二因素".to_string(),
+                ));
+            }
+            return Ok((message.encode(), None));
+        };
+        SdkeyPolicyValidator::check_second_factor(sdkey_policy, factor)?;
+
+        let attestation = factor.attestation();
+        if attestation.session_binding() != &message.hash() {
+            return Err(PsyGuardError::UpsSessionError(
+                "附加第二因素后会话已变化，请重新验证".to_string(),
+            ));
+        }
+        Ok((message.with_second_factor(attestation).encode(), Some(attestation.clone())))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cft::CftTree;
+    use crate::checkpoint::CheckpointLeaf;
+    use crate::hash::Sha256Hasher;
+    use crate::smt::SparseMerkleTree;
+    use crate::registry::GCON_TREE_HEIGHT;
+    use crate::state::Cstate;
+    use crate::endcap::{self, EndCapExpectations};
+    use crate::sdkey::{SdkeyPolicyBuilder, REQUIRE_2FA_CONSTRAINT};
+    use crate::second_factor::TotpEnrollment;
+    use std::sync::Mutex;
+
+    /// 每个合约只有一个 transfer 函数
+    fn transfer_function() -> ContractFunction {
+        ContractFunction { name: "transfer".to_string(), fingerprint: CfcFingerprint([1u8; 32]) }
+    }
+
+    fn transfer_cft_root() -> CftRoot {
+        CftTree::build(&Sha256Hasher, &[transfer_function().fingerprint]).unwrap().root()
+    }
+
+    struct StubNetwork {
+        ucon_root: Hash,
+        gcon: SparseMerkleTree,
+        /// 只有 bob 登记在 GUSR 中，其 UCON 持有 token 的 CSTATE (槽位 3、5 有值)
+        gusr: SparseMerkleTree,
+        bob_leaf: UserLeafCtx,
+        bob_ucon: Ucon,
+        /// 篡改历史读返回的槽位值
+        forge_read: bool,
+    }
+
+    impl StubNetwork {
+        fn new(ucon_root: Hash) -> Self {
+            let mut gcon = SparseMerkleTree::new(GCON_TREE_HEIGHT).unwrap();
+            for id in ["token", "nft"] {
+                let contract_id = ContractId(id.to_string());
+                let leaf = CftRegistry::contract_leaf_hash(&Sha256Hasher, &contract_id, &transfer_cft_root(), 32);
+                gcon.set_leaf(CftRegistry::gcon_index(&contract_id), leaf).unwrap();
+            }
+
+            let bob = UserId("bob".to_string());
+            let mut cstate = Cstate::with_height(ContractId("token".to_string()), 32).unwrap();
+            for slot in [3u64, 5] {
+                cstate.write_slot(slot, slot.to_le_bytes().to_vec()).unwrap();
+            }
+            let mut bob_ucon = Ucon::new(bob.clone());
+            bob_ucon.insert_cstate(cstate).unwrap();
+
+            let guta = GutaConfig::default();
+            let uleaf_hash = guta.user_leaf_hash(&[0u8; 32], 1000, 0, 1, &bob_ucon.root);
+            let mut gusr = SparseMerkleTree::new(guta.gusr_height).unwrap();
+            gusr.set_leaf(guta.user_index(&bob), uleaf_hash).unwrap();
+            let bob_leaf = UserLeafCtx {
+                uleaf_hash,
+                ucon_root: bob_ucon.root,
+                public_key_hash: [0u8; 32],
+                balance: 1000,
+                nonce: 0,
+            };
+
+            Self { ucon_root, gcon, gusr, bob_leaf, bob_ucon, forge_read: false }
+        }
+
+        fn checkpoint_leaf(&self) -> CheckpointLeaf {
+            CheckpointLeaf { block_number: 1, gusr_root: self.gusr.root(), gcon_root: self.gcon.root() }
+        }
+    }
+
+    impl NetworkState for StubNetwork {
+        fn latest_finalized_chkp(&self) -> Result<CheckpointRef> {
+            Ok(self.checkpoint_leaf().checkpoint_ref(&Sha256Hasher))
+        }
+
+        fn fetch_checkpoint_leaf(&self, _chkp: &CheckpointRef) -> Result<CheckpointLeaf> {
+            Ok(self.checkpoint_leaf())
+        }
+
+        fn fetch_user_leaf(&self, _user_id: &UserId, _chkp: &CheckpointRef) -> Result<UserLeafCtx> {
+            Ok(UserLeafCtx {
+                uleaf_hash: [0u8; 32],
+                ucon_root: self.ucon_root,
+                public_key_hash: [0u8; 32],
+                balance: 1000,
+                nonce: 0,
+            })
+        }
+
+        fn fetch_contract_meta(&self, _contract_id: &ContractId, _chkp: &CheckpointRef)
+            -> Result<(CftRoot, CstateHeight)> {
+            Ok((transfer_cft_root(), 32))
+        }
+
+        fn fetch_contract_leaf(&self, contract_id: &ContractId, _chkp: &CheckpointRef)
+            -> Result<GconLeafProof> {
+            Ok(GconLeafProof {
+                cft_root: transfer_cft_root(),
+                cstate_height: 32,
+                gcon_root: self.gcon.root(),
+                proof: self.gcon.prove(CftRegistry::gcon_index(contract_id))?,
+            })
+        }
+
+        /// 不论请求哪个持有者都返回 bob 的 CSTATE
+        fn fetch_cstate_leaf(&self, owner: &UserId, contract_id: &ContractId, slot: u64,
+            _chkp: &CheckpointRef) -> Result<CstateReadWitness> {
+            let cstate = self.bob_ucon.cstate(contract_id).unwrap();
+            let value = match self.forge_read {
+                true => Some(b"forged".to_vec()),
+                false => cstate.read_slot(slot).cloned(),
+            };
+            Ok(CstateReadWitness {
+                contract_id: contract_id.clone(),
+                slot,
+                value,
+                siblings: cstate.prove_slot(slot)?.siblings,
+                cstate_root: cstate.root,
+                owner: owner.clone(),
+                ucon_proof: self.bob_ucon.prove_contract(contract_id)?,
+                owner_leaf: self.bob_leaf.clone(),
+                owner_checkpoint_id: 1,
+                owner_leaf_proof: self.gusr.prove(GutaConfig::default().user_index(&UserId("bob".to_string())))?,
+            })
+        }
+    }
+
+    /// 记录每次 CFC 的起始 CSTATE 根、完整见证与集成步骤见证
+    #[derive(Default)]
+    struct StubProver {
+        start_roots: Mutex<Vec<Hash>>,
+        witnesses: Mutex<Vec<CfcWitness>>,
+        step_witnesses: Mutex<Vec<UpsStepWitness>>,
+        /// 声明一个与槽位写入不符的结束根
+        forge_end_root: bool,
+    }
+
+    impl Prover for StubProver {
+        fn prove_cfc(&self, witness: &CfcWitness) -> Result<(CfcProof, TxEndCtx)> {
+            let (cfc, inputs, start_cstate_root) =
+                (&witness.cfc, &witness.inputs, witness.start_cstate_root);
+            self.start_roots.lock().unwrap().push(start_cstate_root);
+            self.witnesses.lock().unwrap().push(witness.clone());
+
+            // 槽位 0 记录调用参数
+            let modified_slots = vec![(0, inputs.function_args.clone())];
+            let mut cstate = Cstate::from_witness(cfc.contract_id.clone(), &witness.cstate)?;
+            assert_eq!(cstate.root, start_cstate_root);
+            for (slot, value) in &modified_slots {
+                cstate.write_slot(*slot, value.clone())?;
+            }
+            let end_contract_state_root = if self.forge_end_root { [9u8; 32] } else { cstate.root };
+            let tx_end_ctx = TxEndCtx {
+                end_contract_state_root,
+                gas_used: 0,
+                success: true,
+                return_data: vec![],
+                modified_slots,
+            };
+            let proof = CfcProof {
+                proof_data: cfc.function_name.clone().into_bytes(),
+                tx_end_ctx: tx_end_ctx.clone(),
+            };
+            Ok((proof, tx_end_ctx))
+        }
+
+        fn ups_integrate_step(&self, witness: &UpsStepWitness) -> Result<UpsStepProof> {
+            self.step_witnesses.lock().unwrap().push(witness.clone());
+            Ok(UpsStepProof {
+                step_number: witness.prev.step_number + 1,
+                accumulated_proof: vec![],
+                current_ucon_root: witness.ucon_delta.new_root,
+                current_debts: vec![],
+            })
+        }
+
+        fn finalize_endcap(&self, header: &UpsHeader, last_step: &UpsStepProof,
+            sdkey_sig: &SignatureProof) -> Result<EndCapProof> {
+            Ok(EndCapProof {
+                ups_header: header.clone(),
+                final_step: last_step.clone(),
+                signature_proof: sdkey_sig.clone(),
+                timestamp: 0,
+            })
+        }
+
+        /// 签名不含密码学证明，只按策略给出公钥与约束标签
+        fn sign_with_sdkey(&self, message: &[u8], policy: &SdkeyPolicy) -> Result<SignatureProof> {
+            Ok(SignatureProof {
+                proof_data: vec![],
+                public_key_hash: SdkeyPolicyValidator::compute_public_key_hash(STUB_SDKEY_VERIFIER_DATA, policy),
+                message_hash: SessionMessage::hash_bytes(message),
+                policy_satisfied: SdkeyPolicyValidator::active_constraints(policy)?,
+                second_factor: None,
+            })
+        }
+
+        fn verify_endcap(&self, _endcap: &EndCapProof) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    /// 桩签名电路的 verifier data
+    const STUB_SDKEY_VERIFIER_DATA: &[u8] = b"stub-sdkey-verifier-data";
+
+    /// 能签名并终结 End Cap 的空会话
+    fn signing_session() -> UpsSession {
+        let network = Arc::new(StubNetwork::new([0u8; 32]));
+        UpsSession::new(UserId("alice".to_string()), network, Arc::new(StubProver::default())).unwrap()
+    }
+
+    fn register(session: &UpsSession) {
+        for id in ["token", "nft"] {
+            session.cft_registry()
+                .register_contract(ContractId(id.to_string()), vec![transfer_function()])
+                .unwrap();
+        }
+    }
+
+    fn call(session: &mut UpsSession, contract: &str, args: &[u8]) -> Result<TxEndCtx> {
+        call_function(session, contract, "transfer", args)
+    }
+
+    fn call_function(session: &mut UpsSession, contract: &str, function: &str, args: &[u8])
+        -> Result<TxEndCtx> {
+        let cfc_id = CfcId {
+            contract_id: ContractId(contract.to_string()),
+            function_name: function.to_string(),
+        };
+        let inputs = CfcInputs {
+            function_args: args.to_vec(),
+            caller: session.header().user_id.clone(),
+            contract_state_root: [0u8; 32],
+            read_slots: vec![],
+            read_owner: None,
+        };
+        session.execute_cfc(&cfc_id, &inputs)
+    }
+
+    #[test]
+    fn test_ups_session_chains_contract_roots() {
+        let network = Arc::new(StubNetwork::new([0u8; 32]));
+        let prover = Arc::new(StubProver::default());
+        let mut session = UpsSession::new(UserId("alice".to_string()), network, prover.clone())
+            .unwrap();
+        register(&session);
+
+        let first = call(&mut session, "token", b"a").unwrap();
+        call(&mut session, "nft", b"b").unwrap();
+        let third = call(&mut session, "token", b"c").unwrap();
+
+        // 第二次调用 token 从第一次的结束根开始
+        let start_roots = prover.start_roots.lock().unwrap().clone();
+        assert_eq!(start_roots[0], [0u8; 32]);
+        assert_eq!(start_roots[2], first.end_contract_state_root);
+
+        let token = ContractId("token".to_string());
+        assert_eq!(session.ucon().get_contract_state(&token), Some(&third.end_contract_state_root));
+        assert_eq!(session.current_step().current_ucon_root, session.ucon().root);
+        assert_eq!(session.current_step().step_number, 3);
+
+        let deltas = session.state_deltas();
+        assert_eq!(deltas.len(), 3);
+        assert_eq!(deltas[2].modified_slots, vec![(0, b"c".to_vec())]);
+
+        // 每一步的 UCON Delta 都携带逐叶证明，可独立校验
+        let steps = prover.step_witnesses.lock().unwrap().clone();
+        assert_eq!(steps.len(), 3);
+        for (step, delta) in steps.iter().zip(deltas) {
+            let cstate_delta = &step.ucon_delta.cstate_delta;
+            assert_eq!(cstate_delta.leaf_proofs.len(), 1);
+            assert_ne!(cstate_delta.old_root, cstate_delta.new_root);
+            assert!(Ucon::verify_delta_proof(&Sha256Hasher, 32, &step.ucon_delta).unwrap());
+            assert!(Cstate::verify_against_delta(&Sha256Hasher, 32, cstate_delta, delta).unwrap());
+        }
+        assert_eq!(session.ucon().cstate(&token).unwrap().read_slot(0), Some(&b"c".to_vec()));
+    }
+
+    #[test]
+    fn test_ups_session_rejects_mismatched_ucon() {
+        let network = Arc::new(StubNetwork::new([5u8; 32]));
+        let prover = Arc::new(StubProver::default());
+
+        let result = UpsSession::new(UserId("alice".to_string()), network, prover);
+        assert!(matches!(result, Err(PsyGuardError::InvalidStateTransition(_))));
+    }
+
+    #[test]
+    fn test_ups_session_rejects_end_root_not_matching_writes() {
+        let network = Arc::new(StubNetwork::new([0u8; 32]));
+        let prover = Arc::new(StubProver { forge_end_root: true, ..Default::default() });
+        let mut session = UpsSession::new(UserId("alice".to_string()), network, prover.clone())
+            .unwrap();
+        register(&session);
+
+        let ucon_root = session.ucon().root;
+        assert!(matches!(
+            call(&mut session, "token", b"a"),
+            Err(PsyGuardError::InvalidStateTransition(_))
+        ));
+        assert_eq!(session.current_step().step_number, 0);
+        assert_eq!(session.ucon().root, ucon_root);
+        assert!(prover.step_witnesses.lock().unwrap().is_empty());
+    }
+
+    /// 已有 token 合约状态 (槽位 7) 的 UCON
+    fn existing_ucon(user_id: &UserId) -> Ucon {
+        let mut cstate = Cstate::new(ContractId("token".to_string()));
+        cstate.write_slot(7, vec![8]).unwrap();
+        let mut ucon = Ucon::new(user_id.clone());
+        ucon.insert_cstate(cstate).unwrap();
+        ucon
+    }
+
+    #[test]
+    fn test_ups_session_with_existing_ucon() {
+        let user_id = UserId("alice".to_string());
+        let ucon = existing_ucon(&user_id);
+        let token = ContractId("token".to_string());
+        let start_root = *ucon.get_contract_state(&token).unwrap();
+
+        let network = Arc::new(StubNetwork::new(ucon.root));
+        let prover = Arc::new(StubProver::default());
+        let mut session = UpsSession::with_ucon(user_id.clone(), ucon, network, prover.clone()).unwrap();
+        register(&session);
+
+        call(&mut session, "token", b"x").unwrap();
+        assert_eq!(prover.start_roots.lock().unwrap()[0], start_root);
+        let cstate = session.ucon().cstate(&token).unwrap();
+        assert_eq!(cstate.read_slot(7), Some(&vec![8]));
+        assert_eq!(cstate.read_slot(0), Some(&b"x".to_vec()));
+
+        // 导出的 UCON 可在下一次会话中恢复并继续写入
+        let snapshot = session.ucon().snapshot().unwrap();
+        let restored = Ucon::from_snapshot(&snapshot).unwrap();
+        let network = Arc::new(StubNetwork::new(restored.root));
+        let mut next = UpsSession::with_ucon(user_id.clone(), restored, network, prover.clone()).unwrap();
+        register(&next);
+        let end = call(&mut next, "token", b"y").unwrap();
+        assert_eq!(prover.start_roots.lock().unwrap()[1], snapshot.contracts[0].cstate_root);
+        assert_eq!(next.ucon().cstate(&token).unwrap().read_slot(7), Some(&vec![8]));
+        assert_eq!(next.ucon().get_contract_state(&token), Some(&end.end_contract_state_root));
+
+        // 只有根、没有本地 CSTATE 的合约无法在其上写入
+        let mut bare = Ucon::new(user_id.clone());
+        bare.update_contract_state(token, start_root);
+        let network = Arc::new(StubNetwork::new(bare.root));
+        let mut session = UpsSession::with_ucon(user_id, bare, network, prover.clone()).unwrap();
+        register(&session);
+        assert!(matches!(
+            call(&mut session, "token", b"x"),
+            Err(PsyGuardError::InvalidStateTransition(_))
+        ));
+    }
+
+    #[test]
+    fn test_ups_session_assembles_cfc_witness() {
+        let user_id = UserId("alice".to_string());
+        let token = ContractId("token".to_string());
+        let ucon = existing_ucon(&user_id);
+        let ucon_root = ucon.root;
+
+        let network = Arc::new(StubNetwork::new(ucon_root));
+        let prover = Arc::new(StubProver::default());
+        let mut session = UpsSession::with_ucon(user_id, ucon, network.clone(), prover.clone()).unwrap();
+        register(&session);
+
+        let cfc_id = CfcId { contract_id: token.clone(), function_name: "transfer".to_string() };
+        let inputs = CfcInputs {
+            function_args: b"x".to_vec(),
+            caller: session.header().user_id.clone(),
+            contract_state_root: [0u8; 32],
+            read_slots: vec![3, 5],
+            read_owner: Some(UserId("bob".to_string())),
+        };
+        session.execute_cfc(&cfc_id, &inputs).unwrap();
+
+        let witness = prover.witnesses.lock().unwrap()[0].clone();
+        assert_eq!(witness.checkpoint_ref.chkp_root, network.latest_finalized_chkp().unwrap().chkp_root);
+        assert_eq!(witness.caller_ucon_root, ucon_root);
+        assert!(Ucon::verify_contract(
+            &Sha256Hasher,
+            &witness.caller_ucon_root,
+            &token,
+            &witness.start_cstate_root,
+            &witness.caller_ucon_proof,
+        ));
+
+        // 历史读按 read_slots 顺序携带值与路径
+        let reads: Vec<_> = witness.state_reads.iter().map(|r| (r.slot, r.value.clone())).collect();
+        assert_eq!(reads, vec![(3, Some(3u64.to_le_bytes().to_vec())), (5, Some(5u64.to_le_bytes().to_vec()))]);
+        assert_eq!(witness.state_reads[1].siblings.len(), 32);
+        assert_eq!(witness.cft_proof.cft_root.0, transfer_cft_root().0);
+    }
+
+    #[test]
+    fn test_ups_session_rejects_unproven_state_reads() {
+        let token = CfcId { contract_id: ContractId("token".to_string()), function_name: "transfer".to_string() };
+        let inputs = |owner: &str| CfcInputs {
+            function_args: b"x".to_vec(),
+            caller: UserId("alice".to_string()),
+            contract_state_root: [0u8; 32],
+            read_slots: vec![3, 5],
+            read_owner: Some(UserId(owner.to_string())),
+        };
+
+        // 篡改的槽位值不在持有者的 CSTATE 根下
+        let mut network = StubNetwork::new([0u8; 32]);
+        network.forge_read = true;
+        let prover = Arc::new(StubProver::default());
+        let mut session = UpsSession::new(UserId("alice".to_string()), Arc::new(network), prover.clone()).unwrap();
+        register(&session);
+        assert!(matches!(
+            session.execute_cfc(&token, &inputs("bob")),
+            Err(PsyGuardError::MerkleProofInvalid(_))
+        ));
+
+        // 把 bob 的 CSTATE 冒充为 carol 持有的也不成立
+        let network = Arc::new(StubNetwork::new([0u8; 32]));
+        let mut session = UpsSession::new(UserId("alice".to_string()), network, prover.clone()).unwrap();
+        register(&session);
+        assert!(matches!(
+            session.execute_cfc(&token, &inputs("carol")),
+            Err(PsyGuardError::MerkleProofInvalid(_))
+        ));
+        assert!(prover.witnesses.lock().unwrap().is_empty());
+
+        session.execute_cfc(&token, &inputs("bob")).unwrap();
+        assert_eq!(prover.witnesses.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_ups_session_refuses_unproven_cfc() {
+        let network = Arc::new(StubNetwork::new([0u8; 32]));
+        let prover = Arc::new(StubProver::default());
+        let mut session = UpsSession::new(UserId("alice".to_string()), network, prover.clone())
+            .unwrap();
+
+        // 未登记函数清单
+        assert!(matches!(
+            call(&mut session, "token", b"a"),
+            Err(PsyGuardError::CftVerificationFailed(_))
+        ));
+
+        // 函数不在 CFT 中
+        register(&session);
+        assert!(matches!(
+            call_function(&mut session, "token", "mint", b"a"),
+            Err(PsyGuardError::CftVerificationFailed(_))
+        ));
+
+        // 拒绝发生在证明之前，会话状态不变
+        assert!(prover.start_roots.lock().unwrap().is_empty());
+        assert_eq!(session.current_step().step_number, 0);
+        assert!(session.state_deltas().is_empty());
+    }
 
     #[test]
-    fn test_ups_session_creation() {
-        // TODO: 添加测试
+    fn test_ups_session_verifies_cft_inclusion() {
+        let network = Arc::new(StubNetwork::new([0u8; 32]));
+        let prover = Arc::new(StubProver::default());
+        let session = UpsSession::new(UserId("alice".to_string()), network, prover).unwrap();
+        register(&session);
+
+        let cfc_id = CfcId {
+            contract_id: ContractId("token".to_string()),
+            function_name: "transfer".to_string(),
+        };
+        let cft = session.cft_registry().prove(&cfc_id, &session.header().checkpoint_ref).unwrap();
+        assert!(session.verify_cft_proof(&cfc_id, &cft).is_ok());
+
+        // 指纹被替换
+        let mut other_fingerprint = cft.clone();
+        other_fingerprint.fingerprint = CfcFingerprint([2u8; 32]);
+        assert!(matches!(
+            session.verify_cft_proof(&cfc_id, &other_fingerprint),
+            Err(PsyGuardError::CftVerificationFailed(_))
+        ));
+
+        // 证明自洽但指向另一个 CFT 根
+        let forged_tree = CftTree::build(&Sha256Hasher, &[CfcFingerprint([2u8; 32])]).unwrap();
+        let forged = CfcCftProof {
+            fingerprint: CfcFingerprint([2u8; 32]),
+            proof: forged_tree.prove(0).unwrap(),
+        };
+        assert!(CftVerifier::verify_inclusion(&Sha256Hasher, &forged.fingerprint, &forged.proof).unwrap());
+        assert!(matches!(
+            session.verify_cft_proof(&cfc_id, &forged),
+            Err(PsyGuardError::CftVerificationFailed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ups_session_async_backends() {
+        let network = Arc::new(SyncAdapter::new(Arc::new(StubNetwork::new([0u8; 32]))));
+        let stub_prover = Arc::new(StubProver::default());
+        let prover = Arc::new(SyncAdapter::new(stub_prover.clone()));
+        let mut session = UpsSession::new_async(UserId("alice".to_string()), network, prover)
+            .await
+            .unwrap();
+        register(&session);
+
+        let cfc_id = CfcId {
+            contract_id: ContractId("token".to_string()),
+            function_name: "transfer".to_string(),
+        };
+        let inputs = CfcInputs {
+            function_args: b"a".to_vec(),
+            caller: session.header().user_id.clone(),
+            contract_state_root: [0u8; 32],
+            read_slots: vec![],
+            read_owner: None,
+        };
+        let first = session.execute_cfc_async(&cfc_id, &inputs).await.unwrap();
+        session.execute_cfc_async(&cfc_id, &inputs).await.unwrap();
+
+        assert_eq!(stub_prover.start_roots.lock().unwrap()[1], first.end_contract_state_root);
+        assert_eq!(session.current_step().step_number, 2);
+        assert_eq!(session.current_step().current_ucon_root, session.ucon().root);
+
+        // 异步后端的会话拒绝同步调用，且不改变状态
+        assert!(matches!(
+            session.execute_cfc(&cfc_id, &inputs),
+            Err(PsyGuardError::UpsSessionError(_))
+        ));
+        assert_eq!(session.current_step().step_number, 2);
+    }
+
+    #[tokio::test]
+    async fn test_ups_session_sync_backends_support_async_calls() {
+        let network = Arc::new(StubNetwork::new([0u8; 32]));
+        let prover = Arc::new(StubProver::default());
+        let mut session = UpsSession::new(UserId("alice".to_string()), network, prover.clone())
+            .unwrap();
+        register(&session);
+
+        call(&mut session, "token", b"a").unwrap();
+        let cfc_id = CfcId {
+            contract_id: ContractId("nft".to_string()),
+            function_name: "transfer".to_string(),
+        };
+        let inputs = CfcInputs {
+            function_args: b"b".to_vec(),
+            caller: session.header().user_id.clone(),
+            contract_state_root: [0u8; 32],
+            read_slots: vec![],
+            read_owner: None,
+        };
+        session.execute_cfc_async(&cfc_id, &inputs).await.unwrap();
+
+        assert_eq!(session.current_step().step_number, 2);
+        assert_eq!(session.state_deltas().len(), 2);
+    }
+
+    #[test]
+    fn test_ups_session_binds_second_factor() {
+        let mut totp = TotpEnrollment::new(b"12345678901234567890".to_vec()).unwrap();
+        let policy = SdkeyPolicyBuilder::new().with_2fa().build();
+        let mut first = signing_session();
+
+        // 未验证第二因素不能签名
+        assert!(matches!(first.finalize(&policy), Err(PsyGuardError::SdkeyPolicyViolation(_))));
+
+        // 绑定到其他会话的因素不能附加
+        let now = 1_700_000_000;
+        let elsewhere = totp.verify(&totp.code_at(now - 30), now, &[0u8; 32]).unwrap();
+        assert!(matches!(
+            first.attach_second_factor(elsewhere),
+            Err(PsyGuardError::SecondFactorFailed(_))
+        ));
+
+        let binding = first.second_factor_binding();
+        let factor = totp.verify(&totp.code_at(now), now, &binding).unwrap();
+        first.attach_second_factor(factor.clone()).unwrap();
+        let endcap = first.finalize(&policy).unwrap();
+        assert_eq!(endcap.signature_proof.second_factor.as_ref(), Some(factor.attestation()));
+        assert!(endcap.signature_proof.policy_satisfied.contains(&REQUIRE_2FA_CONSTRAINT.to_string()));
+
+        let checkpoint = first.header().checkpoint_ref.clone();
+        let expected = EndCapExpectations::for_policy(checkpoint.clone(), STUB_SDKEY_VERIFIER_DATA, &policy);
+        endcap::verify(&endcap, first.state_deltas(), &expected, &StubProver::default()).unwrap();
+
+        // 去掉第二因素: 消息哈希不再匹配；按无 2FA 重新计算哈希则不能声称满足 2FA
+        let mut stripped = endcap.clone();
+        stripped.signature_proof.second_factor = None;
+        assert!(endcap::verify(&stripped, first.state_deltas(), &expected, &StubProver::default()).is_err());
+        stripped.signature_proof.message_hash = binding;
+        assert!(matches!(
+            endcap::verify(&stripped, first.state_deltas(), &expected, &StubProver::default()),
+            Err(PsyGuardError::EndCapVerificationFailed(_))
+        ));
+
+        // 凭据须是策略登记的第二因素
+        let enrolled = SdkeyPolicyBuilder::new().with_2fa().build();
+        let enrolled = SdkeyPolicy { second_factor_key: Some(totp.credential_id()), ..enrolled };
+        let mut second = signing_session();
+        let factor = totp.verify(&totp.code_at(now + 30), now + 30, &second.second_factor_binding()).unwrap();
+        second.attach_second_factor(factor).unwrap();
+        let endcap = second.finalize(&enrolled).unwrap();
+        let expected = EndCapExpectations::for_policy(checkpoint.clone(), STUB_SDKEY_VERIFIER_DATA, &enrolled);
+        endcap::verify(&endcap, second.state_deltas(), &expected, &StubProver::default()).unwrap();
+        let other_credential = EndCapExpectations { second_factor_key: Some([7u8; 32]), ..expected };
+        assert!(matches!(
+            endcap::verify(&endcap, second.state_deltas(), &other_credential, &StubProver::default()),
+            Err(PsyGuardError::EndCapVerificationFailed(_))
+        ));
+
+        // 策略要求 2FA 时，未声称 2FA 的签名被拒绝
+        let lax = SdkeyPolicyBuilder::new().build();
+        let third = signing_session();
+        let endcap = third.finalize(&lax).unwrap();
+        let expected = EndCapExpectations {
+            require_2fa: true,
+            ..EndCapExpectations::for_policy(checkpoint, STUB_SDKEY_VERIFIER_DATA, &lax)
+        };
+        assert!(matches!(
+            endcap::verify(&endcap, third.state_deltas(), &expected, &StubProver::default()),
+            Err(PsyGuardError::EndCapVerificationFailed(_))
+        ));
     }
 }
@@ -0,0 +1,80 @@
+//! REST 请求
+//!
+//! 统一拼接 `/v1` 路径、映射状态码与解析 JSON。
+
+use crate::schema::*;
+use psyguard_core::*;
+use reqwest::{Client, RequestBuilder, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// 指向某个接口根地址的 HTTP 客户端
+#[derive(Debug, Clone)]
+pub(crate) struct ApiClient {
+    base_url: Url,
+    client: Client,
+}
+
+impl ApiClient {
+    pub(crate) fn new(base_url: &str, client: Client) -> Result<Self> {
+        let base_url = Url::parse(base_url)
+            .map_err(|e| PsyGuardError::InvalidInput(format!("接口地址 {} 无效: {}", base_url, e)))?;
+        if base_url.cannot_be_a_base() {
+            return Err(PsyGuardError::InvalidInput(format!("接口地址 {} 无效", base_url)));
+        }
+        Ok(Self { base_url, client })
+    }
+
+    pub(crate) fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    /// GET `{base_url}/v1/<segments>` 并解析 JSON
+    pub(crate) async fn get<T: DeserializeOwned>(&self, segments: &[&str]) -> Result<T> {
+        let url = self.url(segments);
+        self.send("GET", &url, self.client.get(url.clone())).await
+    }
+
+    /// POST JSON 到 `{base_url}/v1/<segments>` 并解析 JSON
+    pub(crate) async fn post<B: Serialize, T: DeserializeOwned>(&self, segments: &[&str], body: &B) -> Result<T> {
+        let url = self.url(segments);
+        self.send("POST", &url, self.client.post(url.clone()).json(body)).await
+    }
+
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("构造时已检查")
+            .pop_if_empty()
+            .push(API_PREFIX.trim_start_matches('/'))
+            .extend(segments);
+        url
+    }
+
+    async fn send<T: DeserializeOwned>(&self, method: &str, url: &Url, request: RequestBuilder) -> Result<T> {
+        log::debug!("{} {}", method, url);
+        let response = request
+            .send()
+            .await
+            .map_err(|e| PsyGuardError::NetworkError(format!("{} {} 失败: {}", method, url, e)))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let reason = response
+                .json::<ErrorResponse>()
+                .await
+                .map(|body| body.error)
+                .unwrap_or_else(|_| status.to_string());
+            return Err(match status {
+                StatusCode::NOT_FOUND => PsyGuardError::NotFound(reason),
+                StatusCode::BAD_REQUEST => PsyGuardError::InvalidInput(reason),
+                _ => PsyGuardError::NetworkError(format!("{} {} 返回 {}: {}", method, url, status, reason)),
+            });
+        }
+
+        response
+            .json::<T>()
+            .await
+            .map_err(|e| PsyGuardError::SerializationError(format!("{} {} 响应无效: {}", method, url, e)))
+    }
+}
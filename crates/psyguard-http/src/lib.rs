@@ -0,0 +1,13 @@
+//! PsyGuard HTTP - Realm/DA REST 客户端
+//!
+//! 按 [`schema`] 中记录的 REST 接口获取全局状态，并在信任前校验每个 Merkle 证明。
+
+pub mod schema;
+pub mod verify;
+pub mod network;
+pub mod submitter;
+mod client;
+
+pub use network::HttpNetworkState;
+pub use submitter::HttpSubmitter;
+pub use verify::ResponseVerifier;
@@ -0,0 +1,113 @@
+//! 基于 REST 接口的网络状态
+//!
+//! 参考: 《2-Miners & Roles on Psy.md》- Realm/Coordinator 提供 CHKP 与用户叶，DA Miners 提供历史读
+
+use crate::client::ApiClient;
+use crate::schema::*;
+use crate::verify::ResponseVerifier;
+use async_trait::async_trait;
+use psyguard_core::checkpoint::CheckpointLeaf;
+use psyguard_core::profile::NetworkProfile;
+use psyguard_core::*;
+use reqwest::{Client, Url};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// 通过 HTTP/JSON 访问 Realm/DA 的网络状态
+///
+/// 响应中的 Merkle 证明在返回前全部校验，已校验的 CHKP 叶按区块号缓存。
+pub struct HttpNetworkState {
+    api: ApiClient,
+    verifier: ResponseVerifier,
+    checkpoints: Mutex<HashMap<u64, CheckpointLeaf>>,
+}
+
+impl HttpNetworkState {
+    /// base_url 为接口根地址，如 `http://127.0.0.1:8080`
+    pub fn new(base_url: &str, profile: &NetworkProfile) -> Result<Self> {
+        Self::with_client(base_url, profile, Client::new())
+    }
+
+    /// 使用自定义的 HTTP 客户端 (超时、代理等)
+    pub fn with_client(base_url: &str, profile: &NetworkProfile, client: Client) -> Result<Self> {
+        Ok(Self {
+            api: ApiClient::new(base_url, client)?,
+            verifier: ResponseVerifier::new(profile),
+            checkpoints: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 接口根地址
+    pub fn base_url(&self) -> &Url {
+        self.api.base_url()
+    }
+
+    /// 取得与 chkp 一致的 CHKP 叶 (已缓存时不访问网络)
+    async fn checkpoint_leaf(&self, chkp: &CheckpointRef) -> Result<CheckpointLeaf> {
+        if let Some(leaf) = self.checkpoints.lock().unwrap().get(&chkp.block_number) {
+            if leaf.verify(self.verifier.hasher(), chkp).is_ok() {
+                return Ok(leaf.clone());
+            }
+        }
+
+        let block = chkp.block_number.to_string();
+        let response: CheckpointResponse = self.api.get(&["checkpoints", &block]).await?;
+        let leaf = self.verifier.verify_checkpoint(&response, Some(chkp))?;
+        self.checkpoints.lock().unwrap().insert(leaf.block_number, leaf.clone());
+        Ok(leaf)
+    }
+}
+
+#[async_trait]
+impl AsyncNetworkState for HttpNetworkState {
+    async fn latest_finalized_chkp(&self) -> Result<CheckpointRef> {
+        let response: CheckpointResponse = self.api.get(&["checkpoints", "latest"]).await?;
+        let leaf = self.verifier.verify_checkpoint(&response, None)?;
+        self.checkpoints.lock().unwrap().insert(leaf.block_number, leaf);
+        Ok(response.checkpoint_ref())
+    }
+
+    async fn fetch_checkpoint_leaf(&self, chkp: &CheckpointRef) -> Result<CheckpointLeaf> {
+        self.checkpoint_leaf(chkp).await
+    }
+
+    async fn fetch_user_leaf(&self, user_id: &UserId, chkp: &CheckpointRef) -> Result<UserLeafCtx> {
+        let checkpoint = self.checkpoint_leaf(chkp).await?;
+        let block = chkp.block_number.to_string();
+        let response: UserLeafResponse = self.api.get(&["checkpoints", &block, "users", &user_id.0]).await?;
+        self.verifier.verify_user_leaf(&checkpoint, user_id, &response)
+    }
+
+    async fn fetch_contract_meta(&self, contract_id: &ContractId, chkp: &CheckpointRef)
+        -> Result<(CftRoot, CstateHeight)> {
+        let leaf = self.fetch_contract_leaf(contract_id, chkp).await?;
+        Ok((leaf.cft_root, leaf.cstate_height))
+    }
+
+    async fn fetch_contract_leaf(&self, contract_id: &ContractId, chkp: &CheckpointRef)
+        -> Result<GconLeafProof> {
+        let checkpoint = self.checkpoint_leaf(chkp).await?;
+        let block = chkp.block_number.to_string();
+        let response: ContractLeafResponse =
+            self.api.get(&["checkpoints", &block, "contracts", &contract_id.0]).await?;
+        self.verifier.verify_contract_leaf(&checkpoint, contract_id, &response)
+    }
+
+    async fn fetch_cstate_leaf(
+        &self,
+        owner: &UserId,
+        contract_id: &ContractId,
+        slot: u64,
+        chkp: &CheckpointRef,
+    ) -> Result<CstateReadWitness> {
+        let checkpoint = self.checkpoint_leaf(chkp).await?;
+        let contract = self.fetch_contract_leaf(contract_id, chkp).await?;
+        let block = chkp.block_number.to_string();
+        let slot_segment = slot.to_string();
+        let response: CstateLeafResponse = self
+            .api
+            .get(&["checkpoints", &block, "contracts", &contract_id.0, "slots", &slot_segment])
+            .await?;
+        self.verifier.verify_cstate_leaf(&checkpoint, &contract, owner, contract_id, slot, &response)
+    }
+}
@@ -0,0 +1,221 @@
+//! Realm/DA REST 接口模式 (v1)
+//!
+//! 所有哈希与字节串均为小写十六进制字符串 (无 `0x` 前缀)，Merkle 兄弟路径自叶向根。
+//! 路径中的用户 ID 与合约 ID 需做 URL 百分号编码。
+//! 失败时返回 `{"error": "<原因>"}`，不存在的资源使用 404，请求本身无效时使用 400。
+//! 提交 End Cap 的请求体沿用 psyguard-core 类型的 serde 表示。
+//!
+//! | 方法 | 路径 | 响应 |
+//! |------|------|------|
+//! | GET | `/v1/checkpoints/latest` | [`CheckpointResponse`] |
+//! | GET | `/v1/checkpoints/{block_number}` | [`CheckpointResponse`] |
+//! | GET | `/v1/checkpoints/{block_number}/users/{user_id}` | [`UserLeafResponse`] |
+//! | GET | `/v1/checkpoints/{block_number}/contracts/{contract_id}` | [`ContractLeafResponse`] |
+//! | GET | `/v1/checkpoints/{block_number}/contracts/{contract_id}/slots/{slot}` | [`CstateLeafResponse`] |
+//! | POST | `/v1/endcaps` ([`SubmitEndcapRequest`]) | `SubmitReceipt` |
+//!
+//! 信任链: `chkp_root` 是 CHKP 叶 (区块号、GUSR 根、GCON 根) 的哈希；
+//! 用户叶证明锚定 GUSR 根，合约叶证明锚定 GCON 根，
+//! CSTATE 槽位证明经持有者的 UCON 证明与用户叶证明锚定 GUSR 根。
+//! 参考: 《2-Miners & Roles on Psy.md》- Realm 提供用户叶，DA Miners 提供历史读
+
+use psyguard_core::checkpoint::CheckpointLeaf;
+use psyguard_core::{CheckpointRef, CstateDelta, EndCapProof, Hash, MerkleProof};
+use serde::{Deserialize, Serialize};
+
+/// 接口版本前缀
+pub const API_PREFIX: &str = "/v1";
+
+/// CHKP 叶及其哈希
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointResponse {
+    pub block_number: u64,
+    #[serde(with = "hex_hash")]
+    pub chkp_root: Hash,
+    #[serde(with = "hex_hash")]
+    pub gusr_root: Hash,
+    #[serde(with = "hex_hash")]
+    pub gcon_root: Hash,
+}
+
+impl CheckpointResponse {
+    pub fn leaf(&self) -> CheckpointLeaf {
+        CheckpointLeaf {
+            block_number: self.block_number,
+            gusr_root: self.gusr_root,
+            gcon_root: self.gcon_root,
+        }
+    }
+
+    pub fn checkpoint_ref(&self) -> CheckpointRef {
+        CheckpointRef {
+            chkp_root: self.chkp_root,
+            block_number: self.block_number,
+        }
+    }
+}
+
+/// 单叶 Merkle 证明
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProofBody {
+    pub index: u64,
+    #[serde(with = "hex_hash")]
+    pub value: Hash,
+    #[serde(with = "hex_hashes")]
+    pub siblings: Vec<Hash>,
+}
+
+impl From<MerkleProof> for MerkleProofBody {
+    fn from(proof: MerkleProof) -> Self {
+        Self {
+            index: proof.index,
+            value: proof.value,
+            siblings: proof.siblings,
+        }
+    }
+}
+
+impl From<MerkleProofBody> for MerkleProof {
+    fn from(body: MerkleProofBody) -> Self {
+        Self {
+            index: body.index,
+            value: body.value,
+            siblings: body.siblings,
+        }
+    }
+}
+
+/// GUSR 用户叶 (ULEAF) 原像及其证明
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserLeafResponse {
+    pub user_id: String,
+    #[serde(with = "hex_hash")]
+    pub public_key_hash: Hash,
+    pub balance: u64,
+    pub nonce: u64,
+    /// 用户最近一次同步的 checkpoint
+    pub last_checkpoint_id: u64,
+    #[serde(with = "hex_hash")]
+    pub ucon_root: Hash,
+    /// ULEAF 在 GUSR 中的证明
+    pub proof: MerkleProofBody,
+}
+
+/// GCON 合约叶 (CLEAF) 及其证明
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractLeafResponse {
+    pub contract_id: String,
+    #[serde(with = "hex_hash")]
+    pub cft_root: Hash,
+    pub cstate_height: u64,
+    /// CLEAF 在 GCON 中的证明
+    pub proof: MerkleProofBody,
+}
+
+/// CSTATE 槽位的历史读
+///
+/// 合约共享状态保存在其持有者 (owner) 的 CSTATE 中
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CstateLeafResponse {
+    pub contract_id: String,
+    pub slot: u64,
+    /// 槽位值；空槽位为 null
+    #[serde(with = "hex_bytes_opt")]
+    pub value: Option<Vec<u8>>,
+    #[serde(with = "hex_hash")]
+    pub cstate_root: Hash,
+    /// 槽位在 CSTATE 中的证明
+    pub slot_proof: MerkleProofBody,
+    /// CSTATE 根在持有者 UCON 中的证明
+    pub ucon_proof: MerkleProofBody,
+    /// 持有者的用户叶
+    pub owner: UserLeafResponse,
+}
+
+/// 提交 End Cap 及其状态变更
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitEndcapRequest {
+    pub endcap: EndCapProof,
+    pub state_deltas: Vec<CstateDelta>,
+}
+
+/// 错误响应
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+mod hex_hash {
+    use psyguard_core::Hash;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(hash: &Hash, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(hash))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Hash, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        parse(&s).map_err(D::Error::custom)
+    }
+
+    pub fn parse(s: &str) -> Result<Hash, String> {
+        let bytes = hex::decode(s).map_err(|e| format!("十六进制无效: {}", e))?;
+        bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| format!("哈希应为 32 字节，实际 {} 字节", bytes.len()))
+    }
+}
+
+mod hex_hashes {
+    use psyguard_core::Hash;
+    use serde::{de::Error, ser::SerializeSeq, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(hashes: &[Hash], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(hashes.len()))?;
+        for hash in hashes {
+            seq.serialize_element(&hex::encode(hash))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Hash>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| super::hex_hash::parse(s).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+mod hex_bytes_opt {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => serializer.serialize_some(&hex::encode(bytes)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| hex::decode(s).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_uses_hex_strings() {
+        let body = MerkleProofBody { index: 3, value: [0xab; 32], siblings: vec![[1u8; 32]] };
+        let json = serde_json::to_value(&body).unwrap();
+        assert_eq!(json["value"], "ab".repeat(32));
+        assert_eq!(json["siblings"][0], "01".repeat(32));
+        assert_eq!(serde_json::from_value::<MerkleProofBody>(json).unwrap(), body);
+
+        let short = serde_json::json!({ "index": 0, "value": "abcd", "siblings": [] });
+        assert!(serde_json::from_value::<MerkleProofBody>(short).is_err());
+    }
+}
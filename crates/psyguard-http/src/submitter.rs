@@ -0,0 +1,41 @@
+//! 基于 REST 接口的 End Cap 提交
+//!
+//! 参考: 《5-Local Proving (UPS).md》- End Cap 提交给 Realm
+
+use crate::client::ApiClient;
+use crate::schema::SubmitEndcapRequest;
+use async_trait::async_trait;
+use psyguard_core::*;
+use reqwest::{Client, Url};
+
+/// 通过 `POST /v1/endcaps` 提交 End Cap
+pub struct HttpSubmitter {
+    api: ApiClient,
+}
+
+impl HttpSubmitter {
+    pub fn new(base_url: &str) -> Result<Self> {
+        Self::with_client(base_url, Client::new())
+    }
+
+    pub fn with_client(base_url: &str, client: Client) -> Result<Self> {
+        Ok(Self { api: ApiClient::new(base_url, client)? })
+    }
+
+    /// 接口根地址
+    pub fn base_url(&self) -> &Url {
+        self.api.base_url()
+    }
+}
+
+#[async_trait]
+impl AsyncSubmitter for HttpSubmitter {
+    async fn submit_endcap(
+        &self,
+        endcap: &EndCapProof,
+        state_deltas: Vec<CstateDelta>,
+    ) -> Result<SubmitReceipt> {
+        let request = SubmitEndcapRequest { endcap: endcap.clone(), state_deltas };
+        self.api.post(&["endcaps"], &request).await
+    }
+}
@@ -0,0 +1,270 @@
+//! REST 响应校验
+//!
+//! 每个响应中的 Merkle 证明都必须沿信任链回到已校验的 CHKP 叶，否则整个响应被拒绝。
+//! 参考: 《3-How a Block is Made.md》- ULEAF 的 Merkle 证明锚定到 CHKP 根
+
+use crate::schema::*;
+use psyguard_core::checkpoint::CheckpointLeaf;
+use psyguard_core::guta::GutaConfig;
+use psyguard_core::hash::MerkleHasher;
+use psyguard_core::profile::NetworkProfile;
+use psyguard_core::registry::CftRegistry;
+use psyguard_core::smt::SparseMerkleTree;
+use psyguard_core::*;
+use std::sync::Arc;
+
+/// 按网络配置校验 REST 响应
+#[derive(Debug, Clone)]
+pub struct ResponseVerifier {
+    hasher: Arc<dyn MerkleHasher>,
+    guta: GutaConfig,
+}
+
+impl ResponseVerifier {
+    pub fn new(profile: &NetworkProfile) -> Self {
+        Self {
+            hasher: profile.hasher(),
+            guta: profile.guta_config(),
+        }
+    }
+
+    /// 树哈希函数
+    pub fn hasher(&self) -> &dyn MerkleHasher {
+        self.hasher.as_ref()
+    }
+
+    /// 校验 CHKP 叶哈希；给定 expected 时还需与其一致
+    pub fn verify_checkpoint(
+        &self,
+        response: &CheckpointResponse,
+        expected: Option<&CheckpointRef>,
+    ) -> Result<CheckpointLeaf> {
+        let leaf = response.leaf();
+        leaf.verify(self.hasher.as_ref(), &response.checkpoint_ref())?;
+        if let Some(expected) = expected {
+            leaf.verify(self.hasher.as_ref(), expected)?;
+        }
+        Ok(leaf)
+    }
+
+    /// 校验用户叶原像及其在 GUSR 中的证明
+    pub fn verify_user_leaf(
+        &self,
+        checkpoint: &CheckpointLeaf,
+        user_id: &UserId,
+        response: &UserLeafResponse,
+    ) -> Result<UserLeafCtx> {
+        if response.user_id != user_id.0 {
+            return Err(PsyGuardError::MerkleProofInvalid(format!(
+                "请求用户 {:?}，响应为 {:?}",
+                user_id.0, response.user_id
+            )));
+        }
+
+        let uleaf_hash = self.guta.user_leaf_hash(
+            &response.public_key_hash,
+            response.balance,
+            response.nonce,
+            response.last_checkpoint_id,
+            &response.ucon_root,
+        );
+        let proof = MerkleProof::from(response.proof.clone());
+        self.check_proof(
+            &format!("用户 {:?} 的 ULEAF", user_id.0),
+            &checkpoint.gusr_root,
+            &proof,
+            self.guta.user_index(user_id),
+            &uleaf_hash,
+            self.guta.gusr_height,
+        )?;
+
+        Ok(UserLeafCtx {
+            uleaf_hash,
+            ucon_root: response.ucon_root,
+            public_key_hash: response.public_key_hash,
+            balance: response.balance,
+            nonce: response.nonce,
+        })
+    }
+
+    /// 校验合约叶在 GCON 中的证明
+    pub fn verify_contract_leaf(
+        &self,
+        checkpoint: &CheckpointLeaf,
+        contract_id: &ContractId,
+        response: &ContractLeafResponse,
+    ) -> Result<GconLeafProof> {
+        if response.contract_id != contract_id.0 {
+            return Err(PsyGuardError::MerkleProofInvalid(format!(
+                "请求合约 {:?}，响应为 {:?}",
+                contract_id.0, response.contract_id
+            )));
+        }
+
+        let leaf = GconLeafProof {
+            cft_root: CftRoot(response.cft_root),
+            cstate_height: response.cstate_height,
+            gcon_root: checkpoint.gcon_root,
+            proof: response.proof.clone().into(),
+        };
+        CftRegistry::verify_contract_leaf(
+            self.hasher.as_ref(),
+            contract_id,
+            &(leaf.cft_root.clone(), leaf.cstate_height),
+            checkpoint,
+            &leaf,
+        )?;
+        Ok(leaf)
+    }
+
+    /// 校验 CSTATE 槽位: 槽位 -> CSTATE 根 -> 持有者 UCON 根 -> GUSR 根
+    ///
+    /// 持有者由调用方指定，响应中其他用户的 CSTATE 即使证明成立也会被拒绝。
+    pub fn verify_cstate_leaf(
+        &self,
+        checkpoint: &CheckpointLeaf,
+        contract: &GconLeafProof,
+        owner: &UserId,
+        contract_id: &ContractId,
+        slot: u64,
+        response: &CstateLeafResponse,
+    ) -> Result<CstateReadWitness> {
+        let label = format!("合约 {:?} 槽位 {}", contract_id.0, slot);
+        if response.contract_id != contract_id.0 || response.slot != slot {
+            return Err(PsyGuardError::MerkleProofInvalid(format!(
+                "{} 的响应指向合约 {:?} 槽位 {}",
+                label, response.contract_id, response.slot
+            )));
+        }
+        if response.owner.user_id != owner.0 {
+            return Err(PsyGuardError::MerkleProofInvalid(format!(
+                "请求 {:?} 持有的{}，响应的持有者为 {:?}",
+                owner.0, label, response.owner.user_id
+            )));
+        }
+
+        let slot_proof = MerkleProof::from(response.slot_proof.clone());
+        if slot_proof.index != slot {
+            return Err(PsyGuardError::MerkleProofInvalid(format!(
+                "{} 的证明指向槽位 {}",
+                label, slot_proof.index
+            )));
+        }
+
+        let witness = CstateReadWitness {
+            contract_id: contract_id.clone(),
+            slot,
+            value: response.value.clone(),
+            siblings: slot_proof.siblings,
+            cstate_root: response.cstate_root,
+            owner: owner.clone(),
+            ucon_proof: response.ucon_proof.clone().into(),
+            owner_leaf: self.verify_user_leaf(checkpoint, owner, &response.owner)?,
+            owner_checkpoint_id: response.owner.last_checkpoint_id,
+            owner_leaf_proof: response.owner.proof.clone().into(),
+        };
+        witness.verify(&self.guta, checkpoint, contract.cstate_height)?;
+        Ok(witness)
+    }
+
+    /// 校验证明的索引、叶值、路径长度与根
+    fn check_proof(
+        &self,
+        label: &str,
+        root: &Hash,
+        proof: &MerkleProof,
+        index: u64,
+        value: &Hash,
+        height: u32,
+    ) -> Result<()> {
+        if proof.index != index
+            || proof.value != *value
+            || !SparseMerkleTree::verify_proof(self.hasher.as_ref(), height, root, proof)
+        {
+            return Err(PsyGuardError::MerkleProofInvalid(format!(
+                "{} 不在根 {} 下",
+                label,
+                hex::encode(root)
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_tree(profile: &NetworkProfile, response: &mut UserLeafResponse) -> CheckpointLeaf {
+        let guta = profile.guta_config();
+        let user_id = UserId(response.user_id.clone());
+        let mut gusr = SparseMerkleTree::with_hasher(guta.gusr_height, profile.hasher()).unwrap();
+        let leaf = guta.user_leaf_hash(
+            &response.public_key_hash,
+            response.balance,
+            response.nonce,
+            response.last_checkpoint_id,
+            &response.ucon_root,
+        );
+        gusr.set_leaf(guta.user_index(&user_id), leaf).unwrap();
+        response.proof = gusr.prove(guta.user_index(&user_id)).unwrap().into();
+
+        CheckpointLeaf { block_number: 1, gusr_root: gusr.root(), gcon_root: [0u8; 32] }
+    }
+
+    #[test]
+    fn test_verify_user_leaf_rejects_tampering() {
+        let profile = NetworkProfile::local();
+        let verifier = ResponseVerifier::new(&profile);
+        let mut response = UserLeafResponse {
+            user_id: "alice".to_string(),
+            public_key_hash: [7u8; 32],
+            balance: 500,
+            nonce: 2,
+            last_checkpoint_id: 1,
+            ucon_root: [9u8; 32],
+            proof: MerkleProofBody { index: 0, value: [0u8; 32], siblings: vec![] },
+        };
+        let checkpoint = user_tree(&profile, &mut response);
+        let alice = UserId("alice".to_string());
+
+        let ctx = verifier.verify_user_leaf(&checkpoint, &alice, &response).unwrap();
+        assert_eq!(ctx.balance, 500);
+        assert_eq!(ctx.ucon_root, [9u8; 32]);
+
+        // 篡改余额、换成别的用户、截短路径都会被拒绝
+        let mut richer = response.clone();
+        richer.balance = 1_000_000;
+        let mut truncated = response.clone();
+        truncated.proof.siblings.pop();
+        for (user, forged) in [(&alice, &richer), (&alice, &truncated), (&UserId("bob".to_string()), &response)] {
+            assert!(matches!(
+                verifier.verify_user_leaf(&checkpoint, user, forged),
+                Err(PsyGuardError::MerkleProofInvalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_verify_checkpoint_against_expected() {
+        let profile = NetworkProfile::local();
+        let verifier = ResponseVerifier::new(&profile);
+        let leaf = CheckpointLeaf { block_number: 3, gusr_root: [1u8; 32], gcon_root: [2u8; 32] };
+        let chkp = leaf.checkpoint_ref(profile.hasher().as_ref());
+        let response = CheckpointResponse {
+            block_number: 3,
+            chkp_root: chkp.chkp_root,
+            gusr_root: leaf.gusr_root,
+            gcon_root: leaf.gcon_root,
+        };
+
+        assert_eq!(verifier.verify_checkpoint(&response, Some(&chkp)).unwrap(), leaf);
+
+        let mut forged = response.clone();
+        forged.gusr_root = [5u8; 32];
+        assert!(verifier.verify_checkpoint(&forged, None).is_err());
+
+        let other = CheckpointRef { chkp_root: [0u8; 32], block_number: 3 };
+        assert!(verifier.verify_checkpoint(&response, Some(&other)).is_err());
+    }
+}
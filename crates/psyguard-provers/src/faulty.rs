@@ -0,0 +1,563 @@
+//! 故障注入装饰器 - 用于韧性测试
+//!
+//! `FaultyProver` / `FaultyNetworkState` 包装任意证明器或网络后端，按配置注入
+//! 错误、延迟、被篡改的根、过期 checkpoint 和缺失用户，用来检验 UpsSession 与
+//! UpsQueue 在批次中途失败时能否保持状态一致并继续。
+//!
+//! 调用按类别从 1 开始计数；`*_at(k)` 只影响第 k 次调用，之后的重试照常转发，
+//! 以此模拟瞬时故障。
+
+use psyguard_core::*;
+use psyguard_core::checkpoint::CheckpointLeaf;
+use async_trait::async_trait;
+use futures_timer::Delay;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 篡改哈希 (翻转最后一个字节)
+fn corrupt(hash: &mut Hash) {
+    hash[31] ^= 0xff;
+}
+
+fn injected(what: &str, call: u64) -> PsyGuardError {
+    PsyGuardError::ProofGenerationFailed(format!("注入故障: 第 {} 次{}失败", call, what))
+}
+
+/// 故障注入证明器
+pub struct FaultyProver<P> {
+    inner: P,
+    latency: Option<Duration>,
+    fail_cfc_at: Option<u64>,
+    fail_step_at: Option<u64>,
+    corrupt_root_at: Option<u64>,
+    fail_finalize: bool,
+    cfc_calls: AtomicU64,
+    step_calls: AtomicU64,
+}
+
+impl<P> FaultyProver<P> {
+    /// 包装证明器，默认不注入任何故障
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            latency: None,
+            fail_cfc_at: None,
+            fail_step_at: None,
+            corrupt_root_at: None,
+            fail_finalize: false,
+            cfc_calls: AtomicU64::new(0),
+            step_calls: AtomicU64::new(0),
+        }
+    }
+
+    /// 每次证明前的额外延迟 (同步接口阻塞线程，异步接口让出执行权)
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// 第 k 次 CFC 证明返回错误
+    pub fn fail_cfc_at(mut self, call: u64) -> Self {
+        self.fail_cfc_at = Some(call);
+        self
+    }
+
+    /// 第 k 次 UPS 集成步骤返回错误
+    pub fn fail_step_at(mut self, call: u64) -> Self {
+        self.fail_step_at = Some(call);
+        self
+    }
+
+    /// 第 k 次 UPS 集成步骤返回篡改过的 UCON 根
+    pub fn corrupt_root_at(mut self, call: u64) -> Self {
+        self.corrupt_root_at = Some(call);
+        self
+    }
+
+    /// End Cap 终结总是失败
+    pub fn fail_finalize(mut self) -> Self {
+        self.fail_finalize = true;
+        self
+    }
+
+    /// 被包装的证明器
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// 已收到的 CFC 证明调用次数
+    pub fn cfc_calls(&self) -> u64 {
+        self.cfc_calls.load(Ordering::SeqCst)
+    }
+
+    /// 已收到的集成步骤调用次数
+    pub fn step_calls(&self) -> u64 {
+        self.step_calls.load(Ordering::SeqCst)
+    }
+
+    fn block_for_latency(&self) {
+        if let Some(latency) = self.latency {
+            std::thread::sleep(latency);
+        }
+    }
+
+    async fn wait_for_latency(&self) {
+        if let Some(latency) = self.latency {
+            Delay::new(latency).await;
+        }
+    }
+
+    /// 计数一次 CFC 证明，命中时返回注入的错误
+    fn before_cfc(&self) -> Result<()> {
+        let call = self.cfc_calls.fetch_add(1, Ordering::SeqCst) + 1;
+        if self.fail_cfc_at == Some(call) {
+            return Err(injected("CFC 证明", call));
+        }
+        Ok(())
+    }
+
+    /// 计数一次集成步骤，返回调用序号
+    fn before_step(&self) -> Result<u64> {
+        let call = self.step_calls.fetch_add(1, Ordering::SeqCst) + 1;
+        if self.fail_step_at == Some(call) {
+            return Err(injected("UPS 集成步骤", call));
+        }
+        Ok(call)
+    }
+
+    fn after_step(&self, call: u64, mut step: UpsStepProof) -> UpsStepProof {
+        if self.corrupt_root_at == Some(call) {
+            corrupt(&mut step.current_ucon_root);
+        }
+        step
+    }
+
+    fn before_finalize(&self) -> Result<()> {
+        if self.fail_finalize {
+            return Err(PsyGuardError::ProofGenerationFailed("注入故障: End Cap 终结失败".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl<P: Prover> Prover for FaultyProver<P> {
+    fn prove_cfc(&self, witness: &CfcWitness) -> Result<(CfcProof, TxEndCtx)> {
+        self.block_for_latency();
+        self.before_cfc()?;
+        self.inner.prove_cfc(witness)
+    }
+
+    fn ups_integrate_step(&self, witness: &UpsStepWitness) -> Result<UpsStepProof> {
+        self.block_for_latency();
+        let call = self.before_step()?;
+        let step = self.inner.ups_integrate_step(witness)?;
+        Ok(self.after_step(call, step))
+    }
+
+    fn finalize_endcap(
+        &self,
+        header: &UpsHeader,
+        last_step: &UpsStepProof,
+        sdkey_sig: &SignatureProof,
+    ) -> Result<EndCapProof> {
+        self.block_for_latency();
+        self.before_finalize()?;
+        self.inner.finalize_endcap(header, last_step, sdkey_sig)
+    }
+
+    fn sign_with_sdkey(&self, message: &[u8], policy: &SdkeyPolicy) -> Result<SignatureProof> {
+        self.inner.sign_with_sdkey(message, policy)
+    }
+
+    fn verify_endcap(&self, endcap: &EndCapProof) -> Result<bool> {
+        self.inner.verify_endcap(endcap)
+    }
+}
+
+#[async_trait]
+impl<P: AsyncProver> AsyncProver for FaultyProver<P> {
+    async fn prove_cfc(&self, witness: &CfcWitness) -> Result<(CfcProof, TxEndCtx)> {
+        self.wait_for_latency().await;
+        self.before_cfc()?;
+        self.inner.prove_cfc(witness).await
+    }
+
+    async fn ups_integrate_step(&self, witness: &UpsStepWitness) -> Result<UpsStepProof> {
+        self.wait_for_latency().await;
+        let call = self.before_step()?;
+        let step = self.inner.ups_integrate_step(witness).await?;
+        Ok(self.after_step(call, step))
+    }
+
+    async fn finalize_endcap(
+        &self,
+        header: &UpsHeader,
+        last_step: &UpsStepProof,
+        sdkey_sig: &SignatureProof,
+    ) -> Result<EndCapProof> {
+        self.wait_for_latency().await;
+        self.before_finalize()?;
+        self.inner.finalize_endcap(header, last_step, sdkey_sig).await
+    }
+
+    async fn sign_with_sdkey(&self, message: &[u8], policy: &SdkeyPolicy) -> Result<SignatureProof> {
+        self.inner.sign_with_sdkey(message, policy).await
+    }
+
+    async fn verify_endcap(&self, endcap: &EndCapProof) -> Result<bool> {
+        self.inner.verify_endcap(endcap).await
+    }
+}
+
+/// 故障注入网络状态
+pub struct FaultyNetworkState<N> {
+    inner: N,
+    latency: Option<Duration>,
+    stale_checkpoint: Option<CheckpointRef>,
+    corrupt_roots: bool,
+    missing_users: HashSet<UserId>,
+}
+
+impl<N> FaultyNetworkState<N> {
+    /// 包装网络后端，默认不注入任何故障
+    pub fn new(inner: N) -> Self {
+        Self {
+            inner,
+            latency: None,
+            stale_checkpoint: None,
+            corrupt_roots: false,
+            missing_users: HashSet::new(),
+        }
+    }
+
+    /// 每次查询前的额外延迟
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// latest_finalized_chkp 固定返回给定的旧 checkpoint
+    pub fn with_stale_checkpoint(mut self, checkpoint: CheckpointRef) -> Self {
+        self.stale_checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// 篡改返回的用户叶 ucon_root 与 GCON 根
+    pub fn corrupt_roots(mut self) -> Self {
+        self.corrupt_roots = true;
+        self
+    }
+
+    /// 该用户的叶查询返回 NotFound
+    pub fn with_missing_user(mut self, user_id: UserId) -> Self {
+        self.missing_users.insert(user_id);
+        self
+    }
+
+    /// 被包装的网络后端
+    pub fn inner(&self) -> &N {
+        &self.inner
+    }
+
+    fn block_for_latency(&self) {
+        if let Some(latency) = self.latency {
+            std::thread::sleep(latency);
+        }
+    }
+
+    async fn wait_for_latency(&self) {
+        if let Some(latency) = self.latency {
+            Delay::new(latency).await;
+        }
+    }
+
+    fn check_user(&self, user_id: &UserId) -> Result<()> {
+        if self.missing_users.contains(user_id) {
+            return Err(PsyGuardError::NotFound(format!("注入故障: 用户 {:?} 不存在", user_id)));
+        }
+        Ok(())
+    }
+
+    fn user_leaf(&self, mut leaf: UserLeafCtx) -> UserLeafCtx {
+        if self.corrupt_roots {
+            corrupt(&mut leaf.ucon_root);
+        }
+        leaf
+    }
+
+    fn contract_leaf(&self, mut leaf: GconLeafProof) -> GconLeafProof {
+        if self.corrupt_roots {
+            corrupt(&mut leaf.gcon_root);
+        }
+        leaf
+    }
+}
+
+impl<N: NetworkState> NetworkState for FaultyNetworkState<N> {
+    fn latest_finalized_chkp(&self) -> Result<CheckpointRef> {
+        self.block_for_latency();
+        match &self.stale_checkpoint {
+            Some(checkpoint) => Ok(checkpoint.clone()),
+            None => self.inner.latest_finalized_chkp(),
+        }
+    }
+
+    fn fetch_user_leaf(&self, user_id: &UserId, chkp: &CheckpointRef) -> Result<UserLeafCtx> {
+        self.block_for_latency();
+        self.check_user(user_id)?;
+        Ok(self.user_leaf(self.inner.fetch_user_leaf(user_id, chkp)?))
+    }
+
+    fn fetch_checkpoint_leaf(&self, chkp: &CheckpointRef) -> Result<CheckpointLeaf> {
+        self.block_for_latency();
+        self.inner.fetch_checkpoint_leaf(chkp)
+    }
+
+    fn fetch_contract_meta(&self, contract_id: &ContractId, chkp: &CheckpointRef)
+        -> Result<(CftRoot, CstateHeight)> {
+        self.block_for_latency();
+        self.inner.fetch_contract_meta(contract_id, chkp)
+    }
+
+    fn fetch_contract_leaf(&self, contract_id: &ContractId, chkp: &CheckpointRef) -> Result<GconLeafProof> {
+        self.block_for_latency();
+        Ok(self.contract_leaf(self.inner.fetch_contract_leaf(contract_id, chkp)?))
+    }
+
+    fn fetch_cstate_leaf(
+        &self,
+        owner: &UserId,
+        contract_id: &ContractId,
+        slot: u64,
+        chkp: &CheckpointRef,
+    ) -> Result<CstateReadWitness> {
+        self.block_for_latency();
+        self.inner.fetch_cstate_leaf(owner, contract_id, slot, chkp)
+    }
+}
+
+#[async_trait]
+impl<N: AsyncNetworkState> AsyncNetworkState for FaultyNetworkState<N> {
+    async fn latest_finalized_chkp(&self) -> Result<CheckpointRef> {
+        self.wait_for_latency().await;
+        match &self.stale_checkpoint {
+            Some(checkpoint) => Ok(checkpoint.clone()),
+            None => self.inner.latest_finalized_chkp().await,
+        }
+    }
+
+    async fn fetch_user_leaf(&self, user_id: &UserId, chkp: &CheckpointRef) -> Result<UserLeafCtx> {
+        self.wait_for_latency().await;
+        self.check_user(user_id)?;
+        Ok(self.user_leaf(self.inner.fetch_user_leaf(user_id, chkp).await?))
+    }
+
+    async fn fetch_checkpoint_leaf(&self, chkp: &CheckpointRef) -> Result<CheckpointLeaf> {
+        self.wait_for_latency().await;
+        self.inner.fetch_checkpoint_leaf(chkp).await
+    }
+
+    async fn fetch_contract_meta(&self, contract_id: &ContractId, chkp: &CheckpointRef)
+        -> Result<(CftRoot, CstateHeight)> {
+        self.wait_for_latency().await;
+        self.inner.fetch_contract_meta(contract_id, chkp).await
+    }
+
+    async fn fetch_contract_leaf(&self, contract_id: &ContractId, chkp: &CheckpointRef)
+        -> Result<GconLeafProof> {
+        self.wait_for_latency().await;
+        Ok(self.contract_leaf(self.inner.fetch_contract_leaf(contract_id, chkp).await?))
+    }
+
+    async fn fetch_cstate_leaf(
+        &self,
+        owner: &UserId,
+        contract_id: &ContractId,
+        slot: u64,
+        chkp: &CheckpointRef,
+    ) -> Result<CstateReadWitness> {
+        self.wait_for_latency().await;
+        self.inner.fetch_cstate_leaf(owner, contract_id, slot, chkp).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MockNetworkState, MockProver, MockVerifier};
+    use crate::mock::MOCK_SDKEY_VERIFIER_DATA;
+    use psyguard_core::endcap::{self, EndCapExpectations};
+    use psyguard_core::queue::UpsQueue;
+    use psyguard_core::sdkey::SdkeyPolicyBuilder;
+    use psyguard_core::ups::UpsSession;
+    use std::sync::Arc;
+
+    fn transfer_function() -> ContractFunction {
+        ContractFunction { name: "transfer".to_string(), fingerprint: CfcFingerprint([1u8; 32]) }
+    }
+
+    fn alice() -> UserId {
+        UserId("alice".to_string())
+    }
+
+    fn network() -> MockNetworkState {
+        let network = MockNetworkState::new();
+        network.add_user(alice(), 1000);
+        network.add_contract_functions(ContractId("token".to_string()), &[transfer_function()]).unwrap();
+        network
+    }
+
+    fn session(network: Arc<dyn NetworkState>, prover: Arc<dyn Prover>) -> Result<UpsSession> {
+        let session = UpsSession::new(alice(), network, prover)?;
+        session.cft_registry().register_contract(ContractId("token".to_string()), vec![transfer_function()])?;
+        Ok(session)
+    }
+
+    fn transfer(amount: u64) -> (CfcId, CfcInputs) {
+        let cfc_id = CfcId {
+            contract_id: ContractId("token".to_string()),
+            function_name: "transfer".to_string(),
+        };
+        let inputs = CfcInputs {
+            function_args: amount.to_le_bytes().to_vec(),
+            caller: alice(),
+            contract_state_root: [0u8; 32],
+            read_slots: vec![],
+            read_owner: None,
+        };
+        (cfc_id, inputs)
+    }
+
+    /// 依次执行队列中的待执行项，失败的项标记为失败后继续
+    fn drain(queue: &mut UpsQueue, session: &mut UpsSession, amounts: &[u64]) {
+        for index in 0..queue.get_items().len() as u32 {
+            let status = &queue.get_items()[index as usize].status;
+            if *status == UpsQueueItemStatus::Success {
+                continue;
+            }
+            queue.mark_executing(index).unwrap();
+            let (cfc_id, inputs) = transfer(amounts[index as usize]);
+            match session.execute_cfc(&cfc_id, &inputs) {
+                Ok(_) => {
+                    queue.mark_success(index, 0).unwrap();
+                    queue.update_ucon_root(session.current_step().current_ucon_root);
+                }
+                Err(_) => queue.mark_failed(index).unwrap(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_session_and_queue_recover_from_mid_batch_failure() {
+        let prover = Arc::new(FaultyProver::new(MockProver::new()).fail_step_at(2));
+        let mut session = session(Arc::new(network()), prover.clone()).unwrap();
+
+        let amounts = [10, 20, 30];
+        let mut queue = UpsQueue::new(session.ucon().root);
+        for amount in amounts {
+            queue.add_item(transfer(amount).0, amount.to_string());
+        }
+
+        drain(&mut queue, &mut session, &amounts);
+        let statuses: Vec<_> = queue.get_items().iter().map(|item| item.status.clone()).collect();
+        assert_eq!(
+            statuses,
+            vec![UpsQueueItemStatus::Success, UpsQueueItemStatus::Failed, UpsQueueItemStatus::Success]
+        );
+
+        // 失败的步骤没有留下痕迹: 步骤、UCON 与状态变更仍然连续
+        assert_eq!(session.current_step().step_number, 2);
+        assert_eq!(session.state_deltas().len(), 2);
+        assert_eq!(session.current_step().current_ucon_root, session.ucon().root);
+        assert_eq!(queue.get_accumulated_info().new_ucon_root, session.ucon().root);
+        assert!(queue.can_submit_endcap());
+
+        // 瞬时故障: 重试失败项即可补齐批次
+        drain(&mut queue, &mut session, &amounts);
+        assert_eq!(queue.get_failed_count(), 0);
+        assert_eq!(session.current_step().step_number, 3);
+        assert_eq!(prover.step_calls(), 4);
+
+        let policy = SdkeyPolicyBuilder::new().build();
+        let endcap = session.finalize(&policy).unwrap();
+        let expected = EndCapExpectations::for_policy(session.header().checkpoint_ref.clone(), MOCK_SDKEY_VERIFIER_DATA, &policy);
+        endcap::verify(&endcap, session.state_deltas(), &expected, &MockProver::new()).unwrap();
+        assert!(MockVerifier.verify_endcap(&endcap).unwrap());
+    }
+
+    #[test]
+    fn test_session_rejects_corrupted_step_root() {
+        let prover = Arc::new(FaultyProver::new(MockProver::new()).corrupt_root_at(1).fail_cfc_at(2));
+        let mut session = session(Arc::new(network()), prover).unwrap();
+        let (cfc_id, inputs) = transfer(10);
+
+        assert!(matches!(
+            session.execute_cfc(&cfc_id, &inputs),
+            Err(PsyGuardError::InvalidStateTransition(_))
+        ));
+        assert!(matches!(
+            session.execute_cfc(&cfc_id, &inputs),
+            Err(PsyGuardError::ProofGenerationFailed(_))
+        ));
+        assert_eq!(session.current_step().step_number, 0);
+        assert!(session.state_deltas().is_empty());
+
+        session.execute_cfc(&cfc_id, &inputs).unwrap();
+        assert_eq!(session.current_step().step_number, 1);
+        assert_eq!(session.current_step().current_ucon_root, session.ucon().root);
+    }
+
+    #[test]
+    fn test_faulty_network_state() {
+        let missing = FaultyNetworkState::new(network()).with_missing_user(alice());
+        assert!(matches!(
+            session(Arc::new(missing), Arc::new(MockProver::new())),
+            Err(PsyGuardError::NotFound(_))
+        ));
+
+        let corrupted = FaultyNetworkState::new(network()).corrupt_roots();
+        assert!(matches!(
+            session(Arc::new(corrupted), Arc::new(MockProver::new())),
+            Err(PsyGuardError::InvalidStateTransition(_))
+        ));
+
+        // 基于过期 checkpoint 的 End Cap 被拒绝
+        let inner = network();
+        let stale_chkp = inner.latest_finalized_chkp().unwrap();
+        inner.add_contract(ContractId("nft".to_string()), CftRoot([2u8; 32]));
+        let latest = inner.latest_finalized_chkp().unwrap();
+        let stale = FaultyNetworkState::new(inner).with_stale_checkpoint(stale_chkp);
+        let mut session = session(Arc::new(stale), Arc::new(MockProver::new())).unwrap();
+        let (cfc_id, inputs) = transfer(10);
+        session.execute_cfc(&cfc_id, &inputs).unwrap();
+
+        let policy = SdkeyPolicyBuilder::new().build();
+        let endcap = session.finalize(&policy).unwrap();
+        let expected = EndCapExpectations::for_policy(latest, MOCK_SDKEY_VERIFIER_DATA, &policy);
+        assert!(matches!(
+            endcap::verify(&endcap, session.state_deltas(), &expected, &MockProver::new()),
+            Err(PsyGuardError::EndCapVerificationFailed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_faulty_latency_does_not_block_async_session() {
+        let network = Arc::new(FaultyNetworkState::new(SyncAdapter::new(Arc::new(network())))
+            .with_latency(Duration::from_millis(5)));
+        let prover = Arc::new(FaultyProver::new(MockProver::new()).with_latency(Duration::from_secs(60)));
+        let mut session = UpsSession::new_async(alice(), network, prover).await.unwrap();
+        session.cft_registry().register_contract(ContractId("token".to_string()), vec![transfer_function()])
+            .unwrap();
+
+        // 异步延迟让出执行权，超时可以触发且会话保持不变
+        let (cfc_id, inputs) = transfer(10);
+        let pending = tokio::time::timeout(
+            Duration::from_millis(20),
+            session.execute_cfc_async(&cfc_id, &inputs),
+        )
+        .await;
+        assert!(pending.is_err());
+        assert_eq!(session.current_step().step_number, 0);
+    }
+}
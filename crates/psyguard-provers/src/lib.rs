@@ -1,7 +1,13 @@
 //! PsyGuard Provers - 证明器实现
 //! 
-//! 包含 Mock 和真实证明器实现
+//! 包含 Mock、委托 (远程)、故障注入和真实证明器实现
 
 pub mod mock;
+pub mod remote;
+pub mod faulty;
 
-pub use mock::{MockProver, MockNetworkState, MockSubmitter};
+pub use mock::{MockProver, MockVerifier, MockNetworkState, MockSubmitter};
+pub use faulty::{FaultyNetworkState, FaultyProver};
+pub use remote::{ProvingRequest, ProvingResponse, ProvingTransport, RemoteProver};
+#[cfg(not(target_arch = "wasm32"))]
+pub use remote::HttpProvingTransport;
@@ -3,11 +3,42 @@
 //! 参考: 教程第1步 - 采用接口驱动 + Mock
 
 use psyguard_core::*;
+use psyguard_core::checkpoint::CheckpointLeaf;
+use psyguard_core::message::SessionMessage;
+use psyguard_core::codec::WireWriter;
+use psyguard_core::guta::{GutaConfig, GutaSimulator};
+use psyguard_core::cft::CftTree;
+use psyguard_core::hash::Sha256Hasher;
+use psyguard_core::registry::{CftRegistry, GCON_TREE_HEIGHT};
+use psyguard_core::sdkey::{SdkeyPolicyBuilder, SdkeyPolicyValidator};
+use psyguard_core::smt::SparseMerkleTree;
+use psyguard_core::state::{Cstate, DEFAULT_CSTATE_HEIGHT};
+use async_trait::async_trait;
+use futures_timer::Delay;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Mock SDKey 签名电路的 verifier data
+pub const MOCK_SDKEY_VERIFIER_DATA: &[u8] = b"mock_sdkey_circuit";
+
+/// Mock CFC 记录调用次数的槽位
+pub const MOCK_CALL_COUNT_SLOT: u64 = 0;
+
+/// Mock CFC 证明域分隔标签
+const MOCK_CFC_DOMAIN: &[u8] = b"psyguard/mock-cfc-proof";
+
+/// Mock UPS 步骤证明域分隔标签
+const MOCK_STEP_DOMAIN: &[u8] = b"psyguard/mock-ups-step";
+
+/// Mock UPS 链头域分隔标签
+const MOCK_STEP_HEAD_DOMAIN: &[u8] = b"psyguard/mock-ups-step-head";
+
+/// Mock SDKey 签名证明域分隔标签
+const MOCK_SDKEY_DOMAIN: &[u8] = b"psyguard/mock-sdkey-signature";
 
 /// Mock 证明器
-/// 不生成真实 ZK 证明，仅模拟流程
+/// 不生成真实 ZK 证明，以确定性哈希链模拟流程，可由 MockVerifier 校验
 pub struct MockProver {
     /// 模拟延迟 (毫秒)
     pub delay_ms: u64,
@@ -21,6 +52,28 @@ impl MockProver {
     pub fn with_delay(delay_ms: u64) -> Self {
         Self { delay_ms }
     }
+
+    /// Mock SDKey 公钥哈希 (签名电路 verifier data + 策略参数)
+    pub fn sdkey_public_key_hash(policy: &SdkeyPolicy) -> Hash {
+        psyguard_core::sdkey::SdkeyPolicyValidator::compute_public_key_hash(
+            MOCK_SDKEY_VERIFIER_DATA,
+            policy,
+        )
+    }
+
+    /// 同步接口的模拟延迟 (阻塞当前线程)
+    fn block_for_delay(&self) {
+        if self.delay_ms > 0 {
+            std::thread::sleep(Duration::from_millis(self.delay_ms));
+        }
+    }
+
+    /// 异步接口的模拟延迟 (让出执行权)
+    async fn wait_for_delay(&self) {
+        if self.delay_ms > 0 {
+            Delay::new(Duration::from_millis(self.delay_ms)).await;
+        }
+    }
 }
 
 impl Default for MockProver {
@@ -29,108 +82,249 @@ impl Default for MockProver {
     }
 }
 
-impl Prover for MockProver {
-    fn prove_cfc(
-        &self,
-        cfc: &CfcId,
-        inputs: &CfcInputs,
-        start_cstate_root: Hash,
-    ) -> Result<(CfcProof, TxEndCtx)> {
-        log::info!("Mock: 证明 CFC {:?}", cfc);
-
-        // 模拟延迟
-        if self.delay_ms > 0 {
-            std::thread::sleep(std::time::Duration::from_millis(self.delay_ms));
-        }
+/// 带域分隔的承诺哈希，字段按 codec 线格式规范化编码
+fn commit(domain: &[u8], encode: impl FnOnce(&mut WireWriter)) -> Vec<u8> {
+    let mut w = WireWriter::new();
+    w.put_bytes(domain);
+    encode(&mut w);
+    SessionMessage::hash_bytes(&w.into_bytes()).to_vec()
+}
 
-        // 生成 Mock 证明
-        let proof_data = format!(
-            "mock_cfc_proof_{}_{}", 
-            cfc.contract_id.0, 
-            cfc.function_name
-        ).into_bytes();
+fn put_debts(w: &mut WireWriter, debts: &[(ContractId, u64)]) {
+    w.put_len(debts.len());
+    for (contract_id, amount) in debts {
+        w.put_str(&contract_id.0);
+        w.put_u64(*amount);
+    }
+}
 
-        // 模拟状态变更
-        let mut new_root = start_cstate_root;
-        new_root[0] = new_root[0].wrapping_add(1);
+/// 证明逻辑 (不含模拟延迟)
+///
+/// Mock 证明是见证的确定性哈希链: CFC 证明承诺完整见证与执行结果，
+/// 步骤 N 承诺步骤 N-1、本步 CFC 证明与 UCON Delta。
+impl MockProver {
+    fn cfc_proof(witness: &CfcWitness) -> Result<(CfcProof, TxEndCtx)> {
+        log::info!("Mock: 证明 CFC {:?}", witness.cfc);
+
+        // 模拟状态变更: 槽位 0 记录调用次数
+        let mut cstate = Cstate::from_witness(witness.cfc.contract_id.clone(), &witness.cstate)?;
+        if cstate.root != witness.start_cstate_root {
+            return Err(PsyGuardError::ProofGenerationFailed(
+                "CSTATE 见证与起始根不一致".to_string(),
+            ));
+        }
+        let calls = cstate
+            .read_slot(MOCK_CALL_COUNT_SLOT)
+            .and_then(|value| <[u8; 8]>::try_from(value.as_slice()).ok())
+            .map_or(0, u64::from_le_bytes);
+        let modified_slots = vec![(MOCK_CALL_COUNT_SLOT, (calls + 1).to_le_bytes().to_vec())];
+        for (slot, value) in &modified_slots {
+            cstate.write_slot(*slot, value.clone())?;
+        }
 
         let tx_end_ctx = TxEndCtx {
-            end_contract_state_root: new_root,
+            end_contract_state_root: cstate.root,
             gas_used: 21000,
             success: true,
             return_data: vec![],
+            modified_slots,
         };
 
         let cfc_proof = CfcProof {
-            proof_data,
+            proof_data: Self::cfc_commitment(witness, &tx_end_ctx),
             tx_end_ctx: tx_end_ctx.clone(),
         };
 
         Ok((cfc_proof, tx_end_ctx))
     }
 
-    fn ups_integrate_step(
-        &self,
-        prev: &UpsStepProof,
-        cfc_proof: &CfcProof,
-        cft_proof: &CftInclusionProof,
-        ucon_delta: &UconDeltaProof,
-        _debts_delta: &DebtDeltaProof,
-    ) -> Result<UpsStepProof> {
+    fn cfc_commitment(witness: &CfcWitness, tx_end_ctx: &TxEndCtx) -> Vec<u8> {
+        commit(MOCK_CFC_DOMAIN, |w| {
+            w.put_str(&witness.cfc.contract_id.0);
+            w.put_str(&witness.cfc.function_name);
+            w.put_bytes(&witness.inputs.function_args);
+            w.put_str(&witness.inputs.caller.0);
+            w.put_hash(&witness.checkpoint_ref.chkp_root);
+            w.put_u64(witness.checkpoint_ref.block_number);
+            w.put_hash(&witness.start_cstate_root);
+            w.put_hash(&witness.caller_ucon_root);
+            w.put_len(witness.state_reads.len());
+            for read in &witness.state_reads {
+                w.put_str(&read.contract_id.0);
+                w.put_u64(read.slot);
+                w.put_str(&read.owner.0);
+                w.put_hash(&read.cstate_root);
+                w.put_bool(read.value.is_some());
+                w.put_bytes(read.value.as_deref().unwrap_or_default());
+            }
+            put_debts(w, &witness.debts);
+            w.put_hash(&witness.cft_proof.cft_root.0);
+            w.put_u64(witness.cft_proof.leaf_index);
+            w.put_hash(&tx_end_ctx.end_contract_state_root);
+            w.put_len(tx_end_ctx.modified_slots.len());
+            for (slot, value) in &tx_end_ctx.modified_slots {
+                w.put_u64(*slot);
+                w.put_bytes(value);
+            }
+            w.put_u64(tx_end_ctx.gas_used);
+            w.put_bool(tx_end_ctx.success);
+            w.put_bytes(&tx_end_ctx.return_data);
+        })
+    }
+
+    fn integrate_step(witness: &UpsStepWitness) -> Result<UpsStepProof> {
+        let prev = &witness.prev;
         log::info!("Mock: UPS 集成步骤 {}", prev.step_number + 1);
 
-        // 模拟延迟
-        if self.delay_ms > 0 {
-            std::thread::sleep(std::time::Duration::from_millis(self.delay_ms));
-        }
+        Self::check_step_links(witness)?;
+
+        let step_number = prev.step_number + 1;
+        let current_ucon_root = witness.ucon_delta.new_root;
+        let current_debts = witness.debts_delta.new_debts.clone();
 
-        // 生成 Mock 递归证明
-        let mut accumulated_proof = prev.accumulated_proof.clone();
-        accumulated_proof.extend_from_slice(&cfc_proof.proof_data);
-        accumulated_proof.extend_from_slice(&cft_proof.cft_root.0);
+        // 累积证明 = 哈希链 || 链头，链头把本步公开的状态绑定到哈希链上
+        let mut accumulated_proof = Self::step_commitment(witness, step_number);
+        let head = Self::step_head(step_number, &accumulated_proof, &current_ucon_root, &current_debts);
+        accumulated_proof.extend_from_slice(&head);
 
         Ok(UpsStepProof {
-            step_number: prev.step_number + 1,
+            step_number,
             accumulated_proof,
-            current_ucon_root: ucon_delta.new_root,
-            current_debts: prev.current_debts.clone(),
+            current_ucon_root,
+            current_debts,
         })
     }
 
-    fn finalize_endcap(
-        &self,
+    /// 递归电路的连接约束: UCON 过渡从上一步的根接续，合约叶的新根来自本步 CFC
+    fn check_step_links(witness: &UpsStepWitness) -> Result<()> {
+        if witness.ucon_delta.old_root != witness.prev.current_ucon_root {
+            return Err(PsyGuardError::InvalidStateTransition(format!(
+                "步骤 {} 的 UCON Delta 未从上一步的根开始",
+                witness.prev.step_number + 1
+            )));
+        }
+        if witness.ucon_delta.cstate_delta.new_root != witness.cfc_proof.tx_end_ctx.end_contract_state_root {
+            return Err(PsyGuardError::InvalidStateTransition(format!(
+                "步骤 {} 的 CSTATE 新根与 CFC 结束根不一致",
+                witness.prev.step_number + 1
+            )));
+        }
+        Ok(())
+    }
+
+    fn step_commitment(witness: &UpsStepWitness, step_number: u32) -> Vec<u8> {
+        commit(MOCK_STEP_DOMAIN, |w| {
+            w.put_u32(step_number);
+            w.put_bytes(&witness.prev.accumulated_proof);
+            w.put_hash(&witness.prev.current_ucon_root);
+            w.put_bytes(&witness.cfc_proof.proof_data);
+            w.put_hash(&witness.cft_proof.cft_root.0);
+            w.put_str(&witness.ucon_delta.contract_id.0);
+            w.put_hash(&witness.ucon_delta.old_root);
+            w.put_hash(&witness.ucon_delta.new_root);
+            put_debts(w, &witness.debts_delta.old_debts);
+            put_debts(w, &witness.debts_delta.new_debts);
+        })
+    }
+
+    /// 链头: 步数、UCON 根与 Debts 对哈希链的承诺，End Cap 校验时可由最后一步重算
+    fn step_head(
+        step_number: u32,
+        chain: &[u8],
+        current_ucon_root: &Hash,
+        current_debts: &[(ContractId, u64)],
+    ) -> Vec<u8> {
+        commit(MOCK_STEP_HEAD_DOMAIN, |w| {
+            w.put_u32(step_number);
+            w.put_bytes(chain);
+            w.put_hash(current_ucon_root);
+            put_debts(w, current_debts);
+        })
+    }
+
+    fn endcap(
+        header: &UpsHeader,
         last_step: &UpsStepProof,
         sdkey_sig: &SignatureProof,
     ) -> Result<EndCapProof> {
         log::info!("Mock: 终结 End Cap");
 
-        // 模拟延迟
-        if self.delay_ms > 0 {
-            std::thread::sleep(std::time::Duration::from_millis(self.delay_ms));
-        }
-
-        // 创建 Mock End Cap
-        let endcap = EndCapProof {
-            ups_header: UpsHeader {
-                user_id: UserId("mock_user".to_string()),
-                checkpoint_ref: CheckpointRef {
-                    chkp_root: [0u8; 32],
-                    block_number: 1,
-                },
-                user_leaf_ctx: UserLeafCtx {
-                    uleaf_hash: [0u8; 32],
-                    ucon_root: [0u8; 32],
-                    balance: 1000,
-                    nonce: 0,
-                },
-                session_id: "mock_session".to_string(),
-            },
+        Ok(EndCapProof {
+            ups_header: header.clone(),
             final_step: last_step.clone(),
             signature_proof: sdkey_sig.clone(),
             timestamp: chrono::Utc::now().timestamp() as u64,
+        })
+    }
+
+    fn signature(message: &[u8], policy: &SdkeyPolicy) -> Result<SignatureProof> {
+        log::info!("Mock: SDKey 签名");
+
+        let public_key_hash = Self::sdkey_public_key_hash(policy);
+        let message_hash = SessionMessage::hash_bytes(message);
+
+        let mut policy_satisfied = vec!["mock_signature".to_string()];
+        policy_satisfied.extend(SdkeyPolicyValidator::active_constraints(policy)?);
+
+        Ok(SignatureProof {
+            proof_data: Self::signature_commitment(&message_hash, &public_key_hash),
+            public_key_hash,
+            message_hash,
+            policy_satisfied,
+            second_factor: None,
+        })
+    }
+
+    fn signature_commitment(message_hash: &Hash, public_key_hash: &Hash) -> Vec<u8> {
+        commit(MOCK_SDKEY_DOMAIN, |w| {
+            w.put_bytes(MOCK_SDKEY_VERIFIER_DATA);
+            w.put_hash(message_hash);
+            w.put_hash(public_key_hash);
+        })
+    }
+
+    fn check_endcap(endcap: &EndCapProof) -> bool {
+        log::info!("Mock: 校验 End Cap");
+
+        // 空会话没有递归证明，最后一步即初始 UCON；
+        // 否则为 32 字节哈希链加 32 字节链头，链头须承诺最后一步的步数、UCON 根与 Debts
+        let step = &endcap.final_step;
+        let steps_proven = if step.step_number == 0 {
+            step.accumulated_proof.is_empty()
+                && step.current_ucon_root == endcap.ups_header.user_leaf_ctx.ucon_root
+        } else if step.accumulated_proof.len() == 64 {
+            let (chain, head) = step.accumulated_proof.split_at(32);
+            head == Self::step_head(step.step_number, chain, &step.current_ucon_root, &step.current_debts)
+        } else {
+            false
         };
+        let signature = &endcap.signature_proof;
+        let signed = signature.proof_data
+            == Self::signature_commitment(&signature.message_hash, &signature.public_key_hash);
 
-        Ok(endcap)
+        steps_proven && signed
+    }
+}
+
+impl Prover for MockProver {
+    fn prove_cfc(&self, witness: &CfcWitness) -> Result<(CfcProof, TxEndCtx)> {
+        self.block_for_delay();
+        Self::cfc_proof(witness)
+    }
+
+    fn ups_integrate_step(&self, witness: &UpsStepWitness) -> Result<UpsStepProof> {
+        self.block_for_delay();
+        Self::integrate_step(witness)
+    }
+
+    fn finalize_endcap(
+        &self,
+        header: &UpsHeader,
+        last_step: &UpsStepProof,
+        sdkey_sig: &SignatureProof,
+    ) -> Result<EndCapProof> {
+        self.block_for_delay();
+        Self::endcap(header, last_step, sdkey_sig)
     }
 
     fn sign_with_sdkey(
@@ -138,75 +332,182 @@ impl Prover for MockProver {
         message: &[u8],
         policy: &SdkeyPolicy,
     ) -> Result<SignatureProof> {
-        log::info!("Mock: SDKey 签名");
+        self.block_for_delay();
+        Self::signature(message, policy)
+    }
 
-        // 模拟延迟
-        if self.delay_ms > 0 {
-            std::thread::sleep(std::time::Duration::from_millis(self.delay_ms));
-        }
+    fn verify_endcap(&self, endcap: &EndCapProof) -> Result<bool> {
+        Ok(Self::check_endcap(endcap))
+    }
+}
 
-        use sha2::{Sha256, Digest};
-        let mut hasher = Sha256::new();
-        hasher.update(message);
-        let public_key_hash_vec = hasher.finalize();
-        let mut public_key_hash = [0u8; 32];
-        public_key_hash.copy_from_slice(&public_key_hash_vec);
+/// 异步版本以计时器模拟延迟，不阻塞线程 (浏览器与 tokio 中继可用)
+#[async_trait]
+impl AsyncProver for MockProver {
+    async fn prove_cfc(&self, witness: &CfcWitness) -> Result<(CfcProof, TxEndCtx)> {
+        self.wait_for_delay().await;
+        Self::cfc_proof(witness)
+    }
 
-        let mut policy_satisfied = vec!["mock_signature".to_string()];
-        
-        if policy.daily_limit.is_some() {
-            policy_satisfied.push("daily_limit_checked".to_string());
-        }
-        if policy.trusted_contracts.is_some() {
-            policy_satisfied.push("contract_whitelist_checked".to_string());
+    async fn ups_integrate_step(&self, witness: &UpsStepWitness) -> Result<UpsStepProof> {
+        self.wait_for_delay().await;
+        Self::integrate_step(witness)
+    }
+
+    async fn finalize_endcap(
+        &self,
+        header: &UpsHeader,
+        last_step: &UpsStepProof,
+        sdkey_sig: &SignatureProof,
+    ) -> Result<EndCapProof> {
+        self.wait_for_delay().await;
+        Self::endcap(header, last_step, sdkey_sig)
+    }
+
+    async fn sign_with_sdkey(
+        &self,
+        message: &[u8],
+        policy: &SdkeyPolicy,
+    ) -> Result<SignatureProof> {
+        self.wait_for_delay().await;
+        Self::signature(message, policy)
+    }
+
+    async fn verify_endcap(&self, endcap: &EndCapProof) -> Result<bool> {
+        Ok(Self::check_endcap(endcap))
+    }
+}
+
+/// Mock 证明校验器
+/// Mock 证明是见证的确定性函数，重新计算后比对即可发现篡改
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MockVerifier;
+
+impl ProofVerifier for MockVerifier {
+    fn verify_cfc(&self, witness: &CfcWitness, proof: &CfcProof) -> Result<bool> {
+        let (expected, _) = MockProver::cfc_proof(witness)?;
+        Ok(proof.proof_data == expected.proof_data
+            && proof.proof_data == MockProver::cfc_commitment(witness, &proof.tx_end_ctx))
+    }
+
+    fn verify_step(&self, witness: &UpsStepWitness, step: &UpsStepProof) -> Result<bool> {
+        if MockProver::check_step_links(witness).is_err() {
+            return Ok(false);
         }
+        let expected = MockProver::integrate_step(witness)?;
+        Ok(step.step_number == expected.step_number
+            && step.accumulated_proof == expected.accumulated_proof
+            && step.current_ucon_root == expected.current_ucon_root
+            && step.current_debts == expected.current_debts)
+    }
 
-        Ok(SignatureProof {
-            proof_data: b"mock_sdkey_signature".to_vec(),
-            public_key_hash,
-            policy_satisfied,
-        })
+    fn verify_endcap(&self, endcap: &EndCapProof) -> Result<bool> {
+        Ok(MockProver::check_endcap(endcap))
     }
 }
 
 /// Mock 网络状态
 /// 模拟从 Realm/Coordinator/DA 获取数据
+///
+/// 每次部署合约都产生一个新区块；CHKP 叶承诺该区块的 GCON 根 (SHA-256)，
+/// 合约元数据与合约叶证明按会话 checkpoint 所在区块的 GCON 快照给出。
 pub struct MockNetworkState {
-    checkpoints: Arc<Mutex<HashMap<u64, CheckpointRef>>>,
     user_leaves: Arc<Mutex<HashMap<UserId, UserLeafCtx>>>,
-    contract_metas: Arc<Mutex<HashMap<ContractId, (CftRoot, CstateHeight)>>>,
+    /// 各区块的快照 (下标 = 区块号 - 1)
+    blocks: Arc<Mutex<Vec<MockBlock>>>,
+}
+
+/// Mock 区块: CHKP 叶、GCON 合约树及当时的合约元数据
+#[derive(Clone)]
+struct MockBlock {
+    leaf: CheckpointLeaf,
+    gcon: SparseMerkleTree,
+    contracts: HashMap<ContractId, (CftRoot, CstateHeight)>,
 }
 
 impl MockNetworkState {
     pub fn new() -> Self {
-        let mut checkpoints = HashMap::new();
-        checkpoints.insert(1, CheckpointRef {
-            chkp_root: [1u8; 32],
-            block_number: 1,
-        });
-
-        Self {
-            checkpoints: Arc::new(Mutex::new(checkpoints)),
+        let network = Self {
             user_leaves: Arc::new(Mutex::new(HashMap::new())),
-            contract_metas: Arc::new(Mutex::new(HashMap::new())),
-        }
+            blocks: Arc::new(Mutex::new(Vec::new())),
+        };
+        network.seal_block(
+            SparseMerkleTree::new(GCON_TREE_HEIGHT).expect("GCON 树高有效"),
+            HashMap::new(),
+        );
+        network
+    }
+
+    /// 以新的 GCON 树出块
+    fn seal_block(&self, gcon: SparseMerkleTree, contracts: HashMap<ContractId, (CftRoot, CstateHeight)>) {
+        let mut blocks = self.blocks.lock().unwrap();
+        let leaf = CheckpointLeaf {
+            block_number: blocks.len() as u64 + 1,
+            gusr_root: [0u8; 32],
+            gcon_root: gcon.root(),
+        };
+        blocks.push(MockBlock { leaf, gcon, contracts });
+    }
+
+    /// 与 `chkp` 一致的区块
+    fn block(&self, chkp: &CheckpointRef) -> Result<MockBlock> {
+        let blocks = self.blocks.lock().unwrap();
+        chkp.block_number
+            .checked_sub(1)
+            .and_then(|index| blocks.get(index as usize))
+            .filter(|block| block.leaf.verify(&Sha256Hasher, chkp).is_ok())
+            .cloned()
+            .ok_or_else(|| PsyGuardError::NotFound(format!("checkpoint {:?} not found", chkp)))
     }
 
-    /// 添加 Mock 用户
+    /// 添加 Mock 用户 (尚无合约状态，登记默认策略的 SDKey)
     pub fn add_user(&self, user_id: UserId, balance: u64) {
+        self.add_user_with_ucon_root(user_id, balance, [0u8; 32]);
+    }
+
+    /// 添加已有合约状态的 Mock 用户
+    pub fn add_user_with_ucon_root(&self, user_id: UserId, balance: u64, ucon_root: Hash) {
         let ctx = UserLeafCtx {
             uleaf_hash: [0u8; 32],
-            ucon_root: [0u8; 32],
+            ucon_root,
+            public_key_hash: MockProver::sdkey_public_key_hash(&SdkeyPolicyBuilder::new().build()),
             balance,
             nonce: 0,
         };
         self.user_leaves.lock().unwrap().insert(user_id, ctx);
     }
 
-    /// 添加 Mock 合约
+    /// 为用户登记按 `policy` 派生的 SDKey 公钥哈希
+    pub fn register_sdkey(&self, user_id: &UserId, policy: &SdkeyPolicy) -> Result<()> {
+        let mut user_leaves = self.user_leaves.lock().unwrap();
+        let ctx = user_leaves
+            .get_mut(user_id)
+            .ok_or_else(|| PsyGuardError::NotFound(format!("user {:?} not found", user_id)))?;
+        ctx.public_key_hash = MockProver::sdkey_public_key_hash(policy);
+        Ok(())
+    }
+
+    /// 添加 Mock 合约 (默认 CSTATE 树高)
     pub fn add_contract(&self, contract_id: ContractId, cft_root: CftRoot) {
-        self.contract_metas.lock().unwrap()
-            .insert(contract_id, (cft_root, 0));
+        let leaf = CftRegistry::contract_leaf_hash(&Sha256Hasher, &contract_id, &cft_root, DEFAULT_CSTATE_HEIGHT);
+        let MockBlock { mut gcon, mut contracts, .. } =
+            self.blocks.lock().unwrap().last().expect("创世区块").clone();
+        gcon.set_leaf(CftRegistry::gcon_index(&contract_id), leaf)
+            .expect("GCON 覆盖全部 u64 索引");
+        contracts.insert(contract_id, (cft_root, DEFAULT_CSTATE_HEIGHT));
+        self.seal_block(gcon, contracts);
+    }
+
+    /// 按函数清单部署 Mock 合约，返回其 CFT 根
+    pub fn add_contract_functions(
+        &self,
+        contract_id: ContractId,
+        functions: &[ContractFunction],
+    ) -> Result<CftRoot> {
+        let fingerprints: Vec<CfcFingerprint> = functions.iter().map(|f| f.fingerprint).collect();
+        let cft_root = CftTree::build(&Sha256Hasher, &fingerprints)?.root();
+        self.add_contract(contract_id, cft_root.clone());
+        Ok(cft_root)
     }
 }
 
@@ -218,10 +519,12 @@ impl Default for MockNetworkState {
 
 impl NetworkState for MockNetworkState {
     fn latest_finalized_chkp(&self) -> Result<CheckpointRef> {
-        let checkpoints = self.checkpoints.lock().unwrap();
-        checkpoints.get(&1)
-            .cloned()
-            .ok_or_else(|| PsyGuardError::NotFound("checkpoint not found".to_string()))
+        let blocks = self.blocks.lock().unwrap();
+        Ok(blocks.last().expect("创世区块").leaf.checkpoint_ref(&Sha256Hasher))
+    }
+
+    fn fetch_checkpoint_leaf(&self, chkp: &CheckpointRef) -> Result<CheckpointLeaf> {
+        Ok(self.block(chkp)?.leaf)
     }
 
     fn fetch_user_leaf(&self, user_id: &UserId, _chkp: &CheckpointRef) -> Result<UserLeafCtx> {
@@ -231,21 +534,38 @@ impl NetworkState for MockNetworkState {
             .ok_or_else(|| PsyGuardError::NotFound(format!("user {:?} not found", user_id)))
     }
 
-    fn fetch_contract_meta(&self, contract_id: &ContractId) -> Result<(CftRoot, CstateHeight)> {
-        let contract_metas = self.contract_metas.lock().unwrap();
-        contract_metas.get(contract_id)
+    fn fetch_contract_meta(&self, contract_id: &ContractId, chkp: &CheckpointRef)
+        -> Result<(CftRoot, CstateHeight)> {
+        self.block(chkp)?
+            .contracts
+            .get(contract_id)
             .cloned()
             .ok_or_else(|| PsyGuardError::NotFound(format!("contract {:?} not found", contract_id)))
     }
 
+    fn fetch_contract_leaf(&self, contract_id: &ContractId, chkp: &CheckpointRef) -> Result<GconLeafProof> {
+        let (cft_root, cstate_height) = self.fetch_contract_meta(contract_id, chkp)?;
+        let gcon = self.block(chkp)?.gcon;
+        Ok(GconLeafProof {
+            cft_root,
+            cstate_height,
+            gcon_root: gcon.root(),
+            proof: gcon.prove(CftRegistry::gcon_index(contract_id))?,
+        })
+    }
+
     fn fetch_cstate_leaf(
         &self,
-        _contract_id: &ContractId,
-        _slot: u64,
+        owner: &UserId,
+        contract_id: &ContractId,
+        slot: u64,
         _chkp: &CheckpointRef,
-    ) -> Result<(Vec<u8>, Vec<Hash>)> {
-        // Mock 返回空值和空证明
-        Ok((vec![0u8; 32], vec![]))
+    ) -> Result<CstateReadWitness> {
+        // Mock 网络不保存用户的 CSTATE，无法给出可校验的历史读
+        Err(PsyGuardError::NotFound(format!(
+            "Mock 网络不提供 {:?} 在合约 {:?} 槽位 {} 的历史读",
+            owner, contract_id, slot
+        )))
     }
 }
 
@@ -280,14 +600,14 @@ impl Submitter for MockSubmitter {
     ) -> Result<SubmitReceipt> {
         log::info!("Mock: 提交 End Cap, {} 个状态变更", state_deltas.len());
 
+        // 每次提交视为一个单独的区块，在全新的 GUSR 上做本地 GUTA 聚合
+        let mut simulator = GutaSimulator::new(GutaConfig::default())?;
+        let mut block = simulator.aggregate(&endcap.ups_header.checkpoint_ref, std::slice::from_ref(endcap))?;
+
         let receipt = SubmitReceipt {
             receipt_id: format!("receipt_{}", endcap.timestamp),
             timestamp: chrono::Utc::now().timestamp() as u64,
-            guta_path: Some(GutaPath {
-                realm_segment: "realm_mock".to_string(),
-                coordinator_segment: "coordinator_mock".to_string(),
-                global_root: [0u8; 32],
-            }),
+            guta_path: block.paths.remove(&endcap.ups_header.user_id),
         };
 
         self.receipts.lock().unwrap().push(receipt.clone());
@@ -300,21 +620,165 @@ impl Submitter for MockSubmitter {
 mod tests {
     use super::*;
 
+    fn witness() -> CfcWitness {
+        CfcWitness {
+            cfc: CfcId {
+                contract_id: ContractId("test".to_string()),
+                function_name: "transfer".to_string(),
+            },
+            inputs: CfcInputs {
+                function_args: vec![],
+                caller: UserId("alice".to_string()),
+                contract_state_root: [0u8; 32],
+                read_slots: vec![],
+                read_owner: None,
+            },
+            checkpoint_ref: CheckpointRef { chkp_root: [1u8; 32], block_number: 1 },
+            start_cstate_root: [0u8; 32],
+            cstate: CstateWitness { height: 32, ..CstateWitness::default() },
+            caller_ucon_root: [0u8; 32],
+            caller_ucon_proof: MerkleProof { index: 0, value: [0u8; 32], siblings: vec![] },
+            state_reads: vec![],
+            debts: vec![],
+            cft_proof: CftInclusionProof {
+                leaf_index: 0,
+                merkle_path: vec![],
+                cft_root: CftRoot([0u8; 32]),
+            },
+        }
+    }
+
     #[test]
     fn test_mock_prover() {
         let prover = MockProver::new();
-        let cfc_id = CfcId {
-            contract_id: ContractId("test".to_string()),
-            function_name: "transfer".to_string(),
-        };
-        let inputs = CfcInputs {
-            function_args: vec![],
-            caller: UserId("alice".to_string()),
-            contract_state_root: [0u8; 32],
+        let result = Prover::prove_cfc(&prover, &witness());
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_mock_prover_async_delay_does_not_block() {
+        let prover = MockProver::with_delay(60_000);
+        let witness = witness();
+
+        // 阻塞式延迟会让超时无法触发
+        let pending = tokio::time::timeout(
+            Duration::from_millis(10),
+            AsyncProver::prove_cfc(&prover, &witness),
+        )
+        .await;
+        assert!(pending.is_err());
+
+        // 异步与同步接口产生相同的证明
+        let prover = MockProver::new();
+        let (proof, _) = AsyncProver::prove_cfc(&prover, &witness).await.unwrap();
+        let (expected, _) = Prover::prove_cfc(&prover, &witness).unwrap();
+        assert_eq!(proof.proof_data, expected.proof_data);
+    }
+
+    /// 以 witness() 为起点集成一步
+    fn step_witness(prev: &UpsStepProof, cfc: &CfcWitness) -> UpsStepWitness {
+        let (cfc_proof, tx_end_ctx) = MockProver::cfc_proof(cfc).unwrap();
+        let mut ucon = psyguard_core::state::Ucon::new(UserId("alice".to_string()));
+        let ucon_delta = ucon
+            .apply_writes(&cfc.cfc.contract_id, cfc.cstate.height, &tx_end_ctx.modified_slots)
+            .unwrap();
+        UpsStepWitness {
+            prev: prev.clone(),
+            cfc_proof,
+            cft_proof: cfc.cft_proof.clone(),
+            ucon_delta,
+            debts_delta: DebtDeltaProof { old_debts: vec![], new_debts: vec![] },
+        }
+    }
+
+    #[test]
+    fn test_mock_verifier_detects_tampering() {
+        let prover = MockProver::new();
+        let verifier = MockVerifier;
+        let cfc = witness();
+
+        // 相同见证得到相同证明
+        let (proof, _) = Prover::prove_cfc(&prover, &cfc).unwrap();
+        assert_eq!(proof.proof_data, Prover::prove_cfc(&prover, &cfc).unwrap().0.proof_data);
+        assert!(verifier.verify_cfc(&cfc, &proof).unwrap());
+
+        let mut forged = proof.clone();
+        forged.tx_end_ctx.end_contract_state_root = [0xee; 32];
+        assert!(!verifier.verify_cfc(&cfc, &forged).unwrap());
+        let mut other = cfc.clone();
+        other.inputs.function_args = b"other".to_vec();
+        assert!(!verifier.verify_cfc(&other, &proof).unwrap());
+
+        let genesis = UpsStepProof {
+            step_number: 0,
+            accumulated_proof: vec![],
+            current_ucon_root: [0u8; 32],
+            current_debts: vec![],
         };
+        let first = step_witness(&genesis, &cfc);
+        let step = Prover::ups_integrate_step(&prover, &first).unwrap();
+        assert_eq!(step.accumulated_proof.len(), 64);
+        assert!(verifier.verify_step(&first, &step).unwrap());
+
+        // 步骤承诺上一步：改动上一步的证明会改变本步
+        let mut rewritten = first.clone();
+        rewritten.prev.accumulated_proof = vec![1];
+        assert!(!verifier.verify_step(&rewritten, &step).unwrap());
+
+        // UCON Delta 必须从上一步的根接续
+        let mut unlinked = first.clone();
+        unlinked.prev.current_ucon_root = [9u8; 32];
+        assert!(!verifier.verify_step(&unlinked, &step).unwrap());
+        assert!(matches!(
+            Prover::ups_integrate_step(&prover, &unlinked),
+            Err(PsyGuardError::InvalidStateTransition(_))
+        ));
+
+        let mut forged = step.clone();
+        forged.current_ucon_root = [7u8; 32];
+        assert!(!verifier.verify_step(&first, &forged).unwrap());
+    }
 
-        let result = prover.prove_cfc(&cfc_id, &inputs, [0u8; 32]);
-        assert!(result.is_ok());
+    #[test]
+    fn test_mock_verifier_rejects_tampered_final_step() {
+        let prover = MockProver::new();
+        let genesis = UpsStepProof {
+            step_number: 0,
+            accumulated_proof: vec![],
+            current_ucon_root: [0u8; 32],
+            current_debts: vec![],
+        };
+        let step = Prover::ups_integrate_step(&prover, &step_witness(&genesis, &witness())).unwrap();
+        let header = UpsHeader {
+            user_id: UserId("alice".to_string()),
+            checkpoint_ref: CheckpointRef { chkp_root: [1u8; 32], block_number: 1 },
+            user_leaf_ctx: UserLeafCtx {
+                uleaf_hash: [0u8; 32],
+                ucon_root: [0u8; 32],
+                public_key_hash: [0u8; 32],
+                balance: 1000,
+                nonce: 0,
+            },
+            session_id: "ups_test".to_string(),
+        };
+        let sig = Prover::sign_with_sdkey(&prover, b"message", &SdkeyPolicy::default()).unwrap();
+        let endcap = Prover::finalize_endcap(&prover, &header, &step, &sig).unwrap();
+        assert!(MockVerifier.verify_endcap(&endcap).unwrap());
+
+        // 改动最后一步的任一公开字段或链本身，链头都不再成立
+        let tampers: [fn(&mut UpsStepProof); 5] = [
+            |step| step.step_number += 1,
+            |step| step.current_ucon_root = [7u8; 32],
+            |step| step.current_debts.push((ContractId("token".to_string()), 1)),
+            |step| step.accumulated_proof[0] ^= 1,
+            |step| step.accumulated_proof.truncate(32),
+        ];
+        for tamper in tampers {
+            let mut forged = endcap.clone();
+            tamper(&mut forged.final_step);
+            assert!(!MockVerifier.verify_endcap(&forged).unwrap());
+            assert!(!Prover::verify_endcap(&prover, &forged).unwrap());
+        }
     }
 
     #[test]
@@ -328,5 +792,67 @@ mod tests {
         let user_leaf = network.fetch_user_leaf(&user_id, &chkp).unwrap();
         
         assert_eq!(user_leaf.balance, 1000);
+
+        let contract_id = ContractId("token".to_string());
+        let functions = vec![ContractFunction {
+            name: "transfer".to_string(),
+            fingerprint: CfcFingerprint([1u8; 32]),
+        }];
+        network.add_contract_functions(contract_id.clone(), &functions).unwrap();
+
+        // 部署产生新区块；旧 checkpoint 的 GCON 与合约元数据中尚无该合约
+        let latest = network.latest_finalized_chkp().unwrap();
+        assert_eq!(latest.block_number, chkp.block_number + 1);
+        assert_eq!(network.fetch_checkpoint_leaf(&chkp).unwrap().block_number, chkp.block_number);
+        assert!(matches!(
+            network.fetch_contract_meta(&contract_id, &chkp),
+            Err(PsyGuardError::NotFound(_))
+        ));
+        assert!(network.fetch_contract_meta(&contract_id, &latest).is_ok());
+
+        let registry = CftRegistry::new(Arc::new(network), Arc::new(Sha256Hasher));
+        registry.register_contract(contract_id.clone(), functions).unwrap();
+        let cfc_id = CfcId { contract_id, function_name: "transfer".to_string() };
+        assert!(matches!(
+            registry.prove(&cfc_id, &chkp),
+            Err(PsyGuardError::NotFound(_))
+        ));
+        assert!(registry.prove(&cfc_id, &latest).is_ok());
+    }
+
+    #[test]
+    fn test_mock_submitter_guta_path() {
+        let prover = MockProver::new();
+        let submitter = MockSubmitter::new();
+
+        let step = UpsStepProof {
+            step_number: 0,
+            accumulated_proof: vec![],
+            current_ucon_root: [0u8; 32],
+            current_debts: vec![],
+        };
+        let policy = SdkeyPolicy::default();
+        let header = UpsHeader {
+            user_id: UserId("alice".to_string()),
+            checkpoint_ref: CheckpointRef { chkp_root: [1u8; 32], block_number: 1 },
+            user_leaf_ctx: UserLeafCtx {
+                uleaf_hash: [0u8; 32],
+                ucon_root: [0u8; 32],
+                public_key_hash: [0u8; 32],
+                balance: 1000,
+                nonce: 0,
+            },
+            session_id: "ups_test".to_string(),
+        };
+        let sig = Prover::sign_with_sdkey(&prover, b"message", &policy).unwrap();
+        let endcap = Prover::finalize_endcap(&prover, &header, &step, &sig).unwrap();
+        assert_eq!(endcap.ups_header.user_id, header.user_id);
+        assert!(MockVerifier.verify_endcap(&endcap).unwrap());
+
+        let receipt = submitter.submit_endcap(&endcap, vec![]).unwrap();
+        let path = receipt.guta_path.unwrap();
+        assert_ne!(path.global_root, [0u8; 32]);
+        assert_eq!(path.nca_count, 0);
+        assert_eq!(path.height, endcap.ups_header.checkpoint_ref.block_number + 1);
     }
 }
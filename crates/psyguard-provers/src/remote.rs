@@ -0,0 +1,516 @@
+//! 委托证明 - 将证明请求发给证明服务
+//!
+//! 证明服务不受信任: 返回的每个证明都在本地由 ProofVerifier 校验后才使用。
+//! 请求超时或服务不可达时改用本地证明器；SDKey 签名始终在本地完成，密钥不出设备。
+//! 参考: 《5-Local Proving (UPS).md》- 上链只验证明，不信任证明者
+
+use async_trait::async_trait;
+use futures::future::{self, Either};
+use futures_timer::Delay;
+use psyguard_core::*;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 证明服务接口路径
+pub const PROVE_PATH: &str = "/v1/prove";
+
+/// 默认的远程证明超时
+pub const DEFAULT_REMOTE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 证明请求 (只在单次请求内存在，不为变体大小差异装箱)
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum ProvingRequest {
+    ProveCfc(CfcWitness),
+    UpsIntegrateStep(UpsStepWitness),
+    FinalizeEndcap {
+        header: UpsHeader,
+        last_step: UpsStepProof,
+        sdkey_sig: SignatureProof,
+    },
+}
+
+/// 证明响应，与请求一一对应
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "proof", rename_all = "snake_case")]
+pub enum ProvingResponse {
+    Cfc(CfcProof),
+    Step(UpsStepProof),
+    EndCap(EndCapProof),
+}
+
+impl ProvingResponse {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Cfc(_) => "CFC",
+            Self::Step(_) => "UPS 步骤",
+            Self::EndCap(_) => "End Cap",
+        }
+    }
+}
+
+impl ProvingRequest {
+    /// 由证明器处理请求 (证明服务端使用)
+    pub async fn prove(self, prover: &dyn AsyncProver) -> Result<ProvingResponse> {
+        match self {
+            Self::ProveCfc(witness) => {
+                let (proof, _) = prover.prove_cfc(&witness).await?;
+                Ok(ProvingResponse::Cfc(proof))
+            }
+            Self::UpsIntegrateStep(witness) => {
+                let step = prover.ups_integrate_step(&witness).await?;
+                Ok(ProvingResponse::Step(step))
+            }
+            Self::FinalizeEndcap { header, last_step, sdkey_sig } => {
+                let endcap = prover.finalize_endcap(&header, &last_step, &sdkey_sig).await?;
+                Ok(ProvingResponse::EndCap(endcap))
+            }
+        }
+    }
+}
+
+/// 与证明服务通信的传输层
+#[async_trait]
+pub trait ProvingTransport: Send + Sync {
+    async fn send(&self, request: &ProvingRequest) -> Result<ProvingResponse>;
+}
+
+/// 通过 `POST {base_url}/v1/prove` 发送 JSON 请求
+#[cfg(not(target_arch = "wasm32"))]
+pub struct HttpProvingTransport {
+    url: String,
+    client: reqwest::Client,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl HttpProvingTransport {
+    pub fn new(base_url: &str) -> Self {
+        Self::with_client(base_url, reqwest::Client::new())
+    }
+
+    pub fn with_client(base_url: &str, client: reqwest::Client) -> Self {
+        Self {
+            url: format!("{}{}", base_url.trim_end_matches('/'), PROVE_PATH),
+            client,
+        }
+    }
+}
+
+/// 错误响应体
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProvingError {
+    pub error: String,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait]
+impl ProvingTransport for HttpProvingTransport {
+    async fn send(&self, request: &ProvingRequest) -> Result<ProvingResponse> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| PsyGuardError::NetworkError(format!("POST {} 失败: {}", self.url, e)))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let reason = response
+                .json::<ProvingError>()
+                .await
+                .map(|body| body.error)
+                .unwrap_or_else(|_| status.to_string());
+            let message = format!("证明服务返回 {}: {}", status, reason);
+            // 5xx 视为服务不可用 (可回退到本地证明)；4xx 为请求本身被拒绝
+            return Err(if status.is_server_error() {
+                PsyGuardError::NetworkError(message)
+            } else {
+                PsyGuardError::ProofGenerationFailed(message)
+            });
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| PsyGuardError::SerializationError(format!("证明服务响应无效: {}", e)))
+    }
+}
+
+/// 委托证明器
+///
+/// 超时、网络错误或服务端 5xx 时回退到本地证明器；服务拒绝请求 (4xx) 或返回的证明
+/// 未通过校验时报错，不回退。
+pub struct RemoteProver {
+    transport: Arc<dyn ProvingTransport>,
+    verifier: Arc<dyn ProofVerifier>,
+    local: Arc<dyn AsyncProver>,
+    timeout: Duration,
+    fallbacks: AtomicU64,
+}
+
+impl RemoteProver {
+    pub fn new(
+        transport: Arc<dyn ProvingTransport>,
+        verifier: Arc<dyn ProofVerifier>,
+        local: Arc<dyn AsyncProver>,
+    ) -> Self {
+        Self {
+            transport,
+            verifier,
+            local,
+            timeout: DEFAULT_REMOTE_TIMEOUT,
+            fallbacks: AtomicU64::new(0),
+        }
+    }
+
+    /// 设置单个远程请求的超时
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 已回退到本地证明的次数
+    pub fn fallbacks(&self) -> u64 {
+        self.fallbacks.load(Ordering::Relaxed)
+    }
+
+    /// 发送请求；超时、服务不可达或服务端 5xx 时返回 None
+    async fn delegate(&self, request: &ProvingRequest) -> Result<Option<ProvingResponse>> {
+        let send = self.transport.send(request);
+        let timeout = Delay::new(self.timeout);
+        let outcome = match future::select(send, timeout).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => Err(PsyGuardError::NetworkError(format!(
+                "证明服务 {} 毫秒内未响应",
+                self.timeout.as_millis()
+            ))),
+        };
+
+        match outcome {
+            Ok(response) => Ok(Some(response)),
+            Err(PsyGuardError::NetworkError(reason)) => {
+                log::warn!("委托证明失败，改用本地证明: {}", reason);
+                self.fallbacks.fetch_add(1, Ordering::Relaxed);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn rejected(what: &str) -> PsyGuardError {
+        PsyGuardError::ProofGenerationFailed(format!("证明服务返回的{}未通过本地校验", what))
+    }
+
+    fn unexpected(response: &ProvingResponse) -> PsyGuardError {
+        PsyGuardError::ProofGenerationFailed(format!("证明服务返回了不匹配的响应 ({})", response.kind()))
+    }
+}
+
+#[async_trait]
+impl AsyncProver for RemoteProver {
+    async fn prove_cfc(&self, witness: &CfcWitness) -> Result<(CfcProof, TxEndCtx)> {
+        let request = ProvingRequest::ProveCfc(witness.clone());
+        match self.delegate(&request).await? {
+            None => self.local.prove_cfc(witness).await,
+            Some(ProvingResponse::Cfc(proof)) => {
+                if !self.verifier.verify_cfc(witness, &proof)? {
+                    return Err(Self::rejected("CFC 证明"));
+                }
+                let tx_end_ctx = proof.tx_end_ctx.clone();
+                Ok((proof, tx_end_ctx))
+            }
+            Some(other) => Err(Self::unexpected(&other)),
+        }
+    }
+
+    async fn ups_integrate_step(&self, witness: &UpsStepWitness) -> Result<UpsStepProof> {
+        let request = ProvingRequest::UpsIntegrateStep(witness.clone());
+        match self.delegate(&request).await? {
+            None => self.local.ups_integrate_step(witness).await,
+            Some(ProvingResponse::Step(step)) => {
+                if !self.verifier.verify_step(witness, &step)? {
+                    return Err(Self::rejected("UPS 步骤证明"));
+                }
+                Ok(step)
+            }
+            Some(other) => Err(Self::unexpected(&other)),
+        }
+    }
+
+    async fn finalize_endcap(
+        &self,
+        header: &UpsHeader,
+        last_step: &UpsStepProof,
+        sdkey_sig: &SignatureProof,
+    ) -> Result<EndCapProof> {
+        let request = ProvingRequest::FinalizeEndcap {
+            header: header.clone(),
+            last_step: last_step.clone(),
+            sdkey_sig: sdkey_sig.clone(),
+        };
+        match self.delegate(&request).await? {
+            None => self.local.finalize_endcap(header, last_step, sdkey_sig).await,
+            Some(ProvingResponse::EndCap(endcap)) => {
+                // End Cap 必须原样封装本地给出的会话头部、最后一步与签名
+                let wraps_inputs = endcap.ups_header == *header
+                    && endcap.final_step == *last_step
+                    && endcap.signature_proof == *sdkey_sig;
+                if !wraps_inputs || !self.verifier.verify_endcap(&endcap)? {
+                    return Err(Self::rejected("End Cap"));
+                }
+                Ok(endcap)
+            }
+            Some(other) => Err(Self::unexpected(&other)),
+        }
+    }
+
+    async fn sign_with_sdkey(
+        &self,
+        message: &[u8],
+        policy: &SdkeyPolicy,
+    ) -> Result<SignatureProof> {
+        self.local.sign_with_sdkey(message, policy).await
+    }
+
+    async fn verify_endcap(&self, endcap: &EndCapProof) -> Result<bool> {
+        self.verifier.verify_endcap(endcap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MockProver, MockVerifier};
+    use std::sync::Mutex;
+
+    /// 进程内的证明服务，可篡改响应或挂起
+    struct InProcessTransport {
+        prover: MockProver,
+        tamper: Option<fn(&mut ProvingResponse)>,
+        hang: bool,
+        requests: Mutex<Vec<ProvingRequest>>,
+    }
+
+    impl InProcessTransport {
+        fn new(tamper: Option<fn(&mut ProvingResponse)>, hang: bool) -> Arc<Self> {
+            Arc::new(Self { prover: MockProver::new(), tamper, hang, requests: Mutex::new(vec![]) })
+        }
+    }
+
+    fn forge_cfc_root(response: &mut ProvingResponse) {
+        if let ProvingResponse::Cfc(proof) = response {
+            proof.tx_end_ctx.end_contract_state_root = [0xee; 32];
+        }
+    }
+
+    #[async_trait]
+    impl ProvingTransport for InProcessTransport {
+        async fn send(&self, request: &ProvingRequest) -> Result<ProvingResponse> {
+            self.requests.lock().unwrap().push(request.clone());
+            if self.hang {
+                future::pending::<()>().await;
+            }
+            let mut response = request.clone().prove(&self.prover).await?;
+            if let Some(tamper) = self.tamper {
+                tamper(&mut response);
+            }
+            Ok(response)
+        }
+    }
+
+    fn remote(transport: Arc<InProcessTransport>) -> RemoteProver {
+        RemoteProver::new(transport, Arc::new(MockVerifier), Arc::new(MockProver::new()))
+            .with_timeout(Duration::from_millis(50))
+    }
+
+    fn cfc() -> CfcWitness {
+        CfcWitness {
+            cfc: CfcId {
+                contract_id: ContractId("token".to_string()),
+                function_name: "transfer".to_string(),
+            },
+            inputs: CfcInputs {
+                function_args: b"10".to_vec(),
+                caller: UserId("alice".to_string()),
+                contract_state_root: [0u8; 32],
+                read_slots: vec![],
+                read_owner: None,
+            },
+            checkpoint_ref: CheckpointRef { chkp_root: [1u8; 32], block_number: 1 },
+            start_cstate_root: [0u8; 32],
+            cstate: CstateWitness { height: 32, ..CstateWitness::default() },
+            caller_ucon_root: [0u8; 32],
+            caller_ucon_proof: MerkleProof { index: 0, value: [0u8; 32], siblings: vec![] },
+            state_reads: vec![],
+            debts: vec![],
+            cft_proof: CftInclusionProof {
+                leaf_index: 0,
+                merkle_path: vec![],
+                cft_root: CftRoot([0u8; 32]),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_prover_verifies_delegated_proofs() {
+        let witness = cfc();
+
+        let honest = InProcessTransport::new(None, false);
+        let prover = remote(honest.clone());
+        let (proof, ctx) = AsyncProver::prove_cfc(&prover, &witness).await.unwrap();
+        assert_eq!(ctx.end_contract_state_root, proof.tx_end_ctx.end_contract_state_root);
+        assert_eq!(honest.requests.lock().unwrap().len(), 1);
+        assert_eq!(prover.fallbacks(), 0);
+
+        // 被篡改的证明不会被接受，也不会静默回退
+        let prover = remote(InProcessTransport::new(Some(forge_cfc_root), false));
+        assert!(matches!(
+            AsyncProver::prove_cfc(&prover, &witness).await,
+            Err(PsyGuardError::ProofGenerationFailed(_))
+        ));
+        assert_eq!(prover.fallbacks(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_remote_prover_rejects_rewritten_endcaps() {
+        let policy = SdkeyPolicy::default();
+        let header = UpsHeader {
+            user_id: UserId("alice".to_string()),
+            checkpoint_ref: CheckpointRef { chkp_root: [1u8; 32], block_number: 1 },
+            user_leaf_ctx: UserLeafCtx {
+                uleaf_hash: [0u8; 32],
+                ucon_root: [0u8; 32],
+                public_key_hash: MockProver::sdkey_public_key_hash(&policy),
+                balance: 1000,
+                nonce: 0,
+            },
+            session_id: "ups_remote".to_string(),
+        };
+        let step = UpsStepProof {
+            step_number: 0,
+            accumulated_proof: vec![],
+            current_ucon_root: [0u8; 32],
+            current_debts: vec![],
+        };
+        let sig = Prover::sign_with_sdkey(&MockProver::new(), b"message", &policy).unwrap();
+
+        let honest = remote(InProcessTransport::new(None, false));
+        let endcap = AsyncProver::finalize_endcap(&honest, &header, &step, &sig).await.unwrap();
+        assert_eq!(endcap.signature_proof, sig);
+
+        // 服务改写本地给出的任一字段都会被拒绝 (即使 End Cap 本身仍能通过校验)
+        let tampers: [fn(&mut ProvingResponse); 4] = [
+            |response| if let ProvingResponse::EndCap(endcap) = response {
+                endcap.signature_proof.policy_satisfied.clear();
+            },
+            |response| if let ProvingResponse::EndCap(endcap) = response {
+                endcap.final_step.current_debts.push((ContractId("token".to_string()), 1));
+            },
+            |response| if let ProvingResponse::EndCap(endcap) = response {
+                endcap.ups_header.user_leaf_ctx.balance = 1_000_000;
+            },
+            |response| if let ProvingResponse::EndCap(endcap) = response {
+                endcap.ups_header.user_leaf_ctx.nonce = 7;
+            },
+        ];
+        for tamper in tampers {
+            let mut rewritten = ProvingResponse::EndCap(endcap.clone());
+            tamper(&mut rewritten);
+            if let ProvingResponse::EndCap(rewritten) = &rewritten {
+                assert!(MockVerifier.verify_endcap(rewritten).unwrap());
+            }
+
+            let prover = remote(InProcessTransport::new(Some(tamper), false));
+            assert!(matches!(
+                AsyncProver::finalize_endcap(&prover, &header, &step, &sig).await,
+                Err(PsyGuardError::ProofGenerationFailed(_))
+            ));
+            assert_eq!(prover.fallbacks(), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_prover_falls_back_on_timeout() {
+        let witness = cfc();
+        let prover = remote(InProcessTransport::new(None, true));
+
+        let (proof, _) = AsyncProver::prove_cfc(&prover, &witness).await.unwrap();
+        let (expected, _) = Prover::prove_cfc(&MockProver::new(), &witness).unwrap();
+        assert_eq!(proof.proof_data, expected.proof_data);
+        assert_eq!(prover.fallbacks(), 1);
+    }
+
+    /// 对每个请求都返回给定状态码的 HTTP 服务
+    async fn status_server(status: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                // 读完请求头与请求体后再响应
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                let body_start = loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break pos + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&request[..body_start]).to_lowercase();
+                let length: usize = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .map(|v| v.trim().parse().unwrap())
+                    .unwrap_or(0);
+                while request.len() < body_start + length {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+
+                let body = r#"{"error":"overloaded"}"#;
+                let response = format!(
+                    "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_remote_prover_falls_back_on_server_error() {
+        let witness = cfc();
+        let remote = |url: &str| {
+            RemoteProver::new(
+                Arc::new(HttpProvingTransport::new(url)),
+                Arc::new(MockVerifier),
+                Arc::new(MockProver::new()),
+            )
+        };
+
+        let unavailable = remote(&status_server("503 Service Unavailable").await);
+        let (proof, _) = AsyncProver::prove_cfc(&unavailable, &witness).await.unwrap();
+        let (expected, _) = Prover::prove_cfc(&MockProver::new(), &witness).unwrap();
+        assert_eq!(proof.proof_data, expected.proof_data);
+        assert_eq!(unavailable.fallbacks(), 1);
+
+        // 4xx: 请求被拒绝，不回退
+        let rejecting = remote(&status_server("422 Unprocessable Entity").await);
+        assert!(matches!(
+            AsyncProver::prove_cfc(&rejecting, &witness).await,
+            Err(PsyGuardError::ProofGenerationFailed(_))
+        ));
+        assert_eq!(rejecting.fallbacks(), 0);
+    }
+}
@@ -0,0 +1,61 @@
+//! 测试夹具
+//!
+//! 夹具描述某个区块结束时的全局状态: 用户叶原像、各用户持有的 CSTATE 槽位，
+//! 以及合约的函数清单 (CFT 叶顺序) 与 CSTATE 高度。哈希与槽位值均为十六进制字符串。
+
+use psyguard_core::state::DEFAULT_CSTATE_HEIGHT;
+use psyguard_core::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 内置的演示夹具
+pub const DEMO_FIXTURES: &str = include_str!("../fixtures/demo.json");
+
+/// 单个区块的全局状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixtures {
+    pub block_number: u64,
+    pub users: Vec<UserFixture>,
+    pub contracts: Vec<ContractFixture>,
+}
+
+impl Fixtures {
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| PsyGuardError::SerializationError(format!("夹具无效: {}", e)))
+    }
+
+    /// 内置的演示夹具
+    pub fn demo() -> Self {
+        Self::from_json(DEMO_FIXTURES).expect("内置夹具有效")
+    }
+}
+
+/// 用户叶原像及其 CSTATE
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserFixture {
+    pub user_id: String,
+    pub public_key_hash: String,
+    pub balance: u64,
+    pub nonce: u64,
+    pub last_checkpoint_id: u64,
+    /// 合约 ID -> (槽位 -> 值)
+    #[serde(default)]
+    pub states: BTreeMap<String, BTreeMap<u64, String>>,
+}
+
+/// 合约叶
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractFixture {
+    pub contract_id: String,
+    pub functions: Vec<ContractFunction>,
+    #[serde(default = "default_cstate_height")]
+    pub cstate_height: CstateHeight,
+    /// 保存合约共享状态的用户；为空时不提供槽位历史读
+    #[serde(default)]
+    pub state_owner: Option<String>,
+}
+
+fn default_cstate_height() -> CstateHeight {
+    DEFAULT_CSTATE_HEIGHT
+}
@@ -0,0 +1,123 @@
+//! PsyGuard Stub Server - 由夹具驱动的 Realm/DA REST 桩服务器
+//!
+//! 提供与 psyguard_http::schema 相同的接口，供 HttpNetworkState 与前端在本地联调。
+
+pub mod fixtures;
+pub mod state;
+pub mod server;
+
+pub use fixtures::Fixtures;
+pub use server::{router, StubServer};
+pub use state::StubChain;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyguard_core::profile::NetworkProfile;
+    use psyguard_core::sdkey::SdkeyPolicyBuilder;
+    use psyguard_core::ups::UpsSession;
+    use psyguard_core::*;
+    use psyguard_http::{HttpNetworkState, HttpSubmitter};
+    use psyguard_provers::MockProver;
+    use std::sync::Arc;
+
+    async fn serve() -> (Arc<StubChain>, StubServer) {
+        let chain = Arc::new(StubChain::build(&Fixtures::demo(), &NetworkProfile::local()).unwrap());
+        let server = StubServer::spawn(chain.clone()).await.unwrap();
+        (chain, server)
+    }
+
+    #[tokio::test]
+    async fn test_http_network_state_verifies_stub_responses() {
+        let (chain, server) = serve().await;
+        let network = HttpNetworkState::new(&server.url(), &NetworkProfile::local()).unwrap();
+
+        let chkp = network.latest_finalized_chkp().await.unwrap();
+        assert_eq!(chkp.chkp_root, chain.checkpoint_ref().chkp_root);
+
+        let alice = network.fetch_user_leaf(&UserId("alice".to_string()), &chkp).await.unwrap();
+        assert_eq!(alice.balance, 1000);
+        assert_eq!(alice.ucon_root, chain.ucon("alice").unwrap().root);
+
+        let nft = ContractId("nft".to_string());
+        let (_, height) = network.fetch_contract_meta(&nft, &chkp).await.unwrap();
+        assert_eq!(height, 16);
+
+        let alice_id = UserId("alice".to_string());
+        let token = ContractId("token".to_string());
+        let read = network.fetch_cstate_leaf(&alice_id, &token, 1, &chkp).await.unwrap();
+        assert_eq!(read.value.as_deref(), Some(&b"alice"[..]));
+        assert_eq!(read.siblings.len(), 32);
+        assert_eq!(read.cstate_root, chain.ucon("alice").unwrap().contract_states[&token]);
+        let empty = network.fetch_cstate_leaf(&alice_id, &token, 9, &chkp).await.unwrap();
+        assert!(empty.value.is_none());
+
+        // token 的共享状态由 alice 持有，按 bob 读取时拒绝 (即使证明本身成立)
+        assert!(matches!(
+            network.fetch_cstate_leaf(&UserId("bob".to_string()), &token, 1, &chkp).await,
+            Err(PsyGuardError::MerkleProofInvalid(_))
+        ));
+
+        // 不存在的资源为 NotFound；按另一网络的哈希校验时证明不成立
+        assert!(matches!(
+            network.fetch_user_leaf(&UserId("mallory".to_string()), &chkp).await,
+            Err(PsyGuardError::NotFound(_))
+        ));
+        let stale = CheckpointRef { block_number: 6, ..chkp.clone() };
+        assert!(matches!(
+            network.fetch_user_leaf(&UserId("alice".to_string()), &stale).await,
+            Err(PsyGuardError::NotFound(_))
+        ));
+        // 合约元数据也按指定的 checkpoint 获取，不会取最新区块
+        assert!(matches!(
+            network.fetch_contract_meta(&nft, &stale).await,
+            Err(PsyGuardError::NotFound(_))
+        ));
+        let psy = HttpNetworkState::new(&server.url(), &NetworkProfile::psy()).unwrap();
+        assert!(matches!(
+            psy.latest_finalized_chkp().await,
+            Err(PsyGuardError::MerkleProofInvalid(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_async_session_over_http() {
+        let (chain, server) = serve().await;
+        let network = Arc::new(HttpNetworkState::new(&server.url(), &NetworkProfile::local()).unwrap());
+        let alice = UserId("alice".to_string());
+        let ucon = chain.ucon("alice").unwrap().clone();
+
+        let mut session = UpsSession::with_ucon_async(alice.clone(), ucon, network, Arc::new(MockProver::new()))
+            .await
+            .unwrap();
+        let fixtures = Fixtures::demo();
+        let token = &fixtures.contracts[0];
+        session
+            .cft_registry()
+            .register_contract(ContractId(token.contract_id.clone()), token.functions.clone())
+            .unwrap();
+
+        let cfc_id = CfcId {
+            contract_id: ContractId("token".to_string()),
+            function_name: "mint".to_string(),
+        };
+        let inputs = CfcInputs {
+            function_args: b"10".to_vec(),
+            caller: alice,
+            contract_state_root: [0u8; 32],
+            read_slots: vec![],
+            read_owner: None,
+        };
+        session.execute_cfc_async(&cfc_id, &inputs).await.unwrap();
+        assert_eq!(session.current_step().step_number, 1);
+
+        let endcap = session.finalize_async(&SdkeyPolicyBuilder::new().build()).await.unwrap();
+        let submitter = HttpSubmitter::new(&server.url()).unwrap();
+        let receipt = submitter
+            .submit_endcap(&endcap, session.state_deltas().to_vec())
+            .await
+            .unwrap();
+        assert_eq!(receipt.receipt_id, "stub_7_0");
+        assert_eq!(chain.submissions().len(), 1);
+    }
+}
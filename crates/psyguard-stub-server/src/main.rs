@@ -0,0 +1,30 @@
+//! 桩服务器命令行
+//!
+//! 用法: `psyguard-stub-server [夹具.json] [监听地址]`，默认使用内置演示夹具并监听 127.0.0.1:8080。
+
+use psyguard_core::profile::NetworkProfile;
+use psyguard_stub_server::{Fixtures, StubChain, StubServer};
+use std::sync::Arc;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let fixtures = match args.next() {
+        Some(path) => Fixtures::from_json(&std::fs::read_to_string(path)?)?,
+        None => Fixtures::demo(),
+    };
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:8080".to_string()).parse()?;
+
+    let chain = StubChain::build(&fixtures, &NetworkProfile::local())?;
+    let checkpoint = chain.checkpoint_ref();
+    let server = StubServer::bind(Arc::new(chain), addr).await?;
+    println!(
+        "监听 {} (区块 {}，chkp_root {})",
+        server.url(),
+        checkpoint.block_number,
+        hex::encode(checkpoint.chkp_root)
+    );
+
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
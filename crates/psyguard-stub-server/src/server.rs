@@ -0,0 +1,129 @@
+//! REST 路由
+//!
+//! 路径与响应格式见 psyguard_http::schema。
+
+use crate::state::StubChain;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use psyguard_core::{PsyGuardError, SubmitReceipt};
+use psyguard_http::schema::*;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+type Chain = State<Arc<StubChain>>;
+
+/// 错误响应: NotFound 为 404，InvalidInput 为 400，其余为 500
+struct ApiError(PsyGuardError);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self.0 {
+            PsyGuardError::NotFound(_) => StatusCode::NOT_FOUND,
+            PsyGuardError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(ErrorResponse { error: self.0.to_string() })).into_response()
+    }
+}
+
+type ApiResult<T> = std::result::Result<Json<T>, ApiError>;
+
+fn respond<T>(result: psyguard_core::Result<T>) -> ApiResult<T> {
+    result.map(Json).map_err(ApiError)
+}
+
+/// 按 schema 构建路由
+pub fn router(chain: Arc<StubChain>) -> Router {
+    Router::new()
+        .route("/v1/checkpoints/latest", get(latest_checkpoint))
+        .route("/v1/checkpoints/{block_number}", get(checkpoint))
+        .route("/v1/checkpoints/{block_number}/users/{user_id}", get(user_leaf))
+        .route("/v1/checkpoints/{block_number}/contracts/{contract_id}", get(contract_leaf))
+        .route(
+            "/v1/checkpoints/{block_number}/contracts/{contract_id}/slots/{slot}",
+            get(cstate_leaf),
+        )
+        .route("/v1/endcaps", post(submit_endcap))
+        .with_state(chain)
+}
+
+async fn latest_checkpoint(State(chain): Chain) -> Json<CheckpointResponse> {
+    Json(chain.latest_checkpoint())
+}
+
+async fn checkpoint(State(chain): Chain, Path(block_number): Path<u64>) -> ApiResult<CheckpointResponse> {
+    respond(chain.checkpoint(block_number))
+}
+
+async fn user_leaf(
+    State(chain): Chain,
+    Path((block_number, user_id)): Path<(u64, String)>,
+) -> ApiResult<UserLeafResponse> {
+    respond(chain.user_leaf(block_number, &user_id))
+}
+
+async fn contract_leaf(
+    State(chain): Chain,
+    Path((block_number, contract_id)): Path<(u64, String)>,
+) -> ApiResult<ContractLeafResponse> {
+    respond(chain.contract_leaf(block_number, &contract_id))
+}
+
+async fn cstate_leaf(
+    State(chain): Chain,
+    Path((block_number, contract_id, slot)): Path<(u64, String, u64)>,
+) -> ApiResult<CstateLeafResponse> {
+    respond(chain.cstate_leaf(block_number, &contract_id, slot))
+}
+
+async fn submit_endcap(
+    State(chain): Chain,
+    Json(request): Json<SubmitEndcapRequest>,
+) -> ApiResult<SubmitReceipt> {
+    respond(chain.submit_endcap(request))
+}
+
+/// 在后台任务中运行的桩服务器，drop 时停止
+pub struct StubServer {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl StubServer {
+    /// 监听 127.0.0.1 的随机端口
+    pub async fn spawn(chain: Arc<StubChain>) -> std::io::Result<Self> {
+        Self::bind(chain, SocketAddr::from(([127, 0, 0, 1], 0))).await
+    }
+
+    pub async fn bind(chain: Arc<StubChain>, addr: SocketAddr) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        let app = router(chain);
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                log::error!("桩服务器退出: {}", e);
+            }
+        });
+        Ok(Self { addr, task })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// 接口根地址，如 `http://127.0.0.1:34567`
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+
+impl Drop for StubServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,305 @@
+//! 由夹具构建的链状态
+//!
+//! 按与 Realm/DA 相同的规则构建 CSTATE、UCON、GUSR、GCON 与 CHKP 叶，
+//! 因此响应中的证明可以通过 psyguard-http 的完整校验。
+
+use crate::fixtures::Fixtures;
+use psyguard_core::cft::CftTree;
+use psyguard_core::checkpoint::CheckpointLeaf;
+use psyguard_core::guta::GutaConfig;
+use psyguard_core::hash::MerkleHasher;
+use psyguard_core::profile::NetworkProfile;
+use psyguard_core::registry::{CftRegistry, GCON_TREE_HEIGHT};
+use psyguard_core::smt::SparseMerkleTree;
+use psyguard_core::state::{Cstate, Ucon};
+use psyguard_core::*;
+use psyguard_http::schema::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+struct UserEntry {
+    leaf: UserLeafResponse,
+    ucon: Ucon,
+    index: u64,
+}
+
+struct ContractEntry {
+    cft_root: CftRoot,
+    cstate_height: CstateHeight,
+    state_owner: Option<String>,
+}
+
+/// 单个区块的全局状态
+pub struct StubChain {
+    checkpoint: CheckpointLeaf,
+    chkp_root: Hash,
+    gcon: SparseMerkleTree,
+    users: HashMap<String, UserEntry>,
+    contracts: HashMap<String, ContractEntry>,
+    submissions: Mutex<Vec<SubmitEndcapRequest>>,
+}
+
+impl StubChain {
+    pub fn build(fixtures: &Fixtures, profile: &NetworkProfile) -> Result<Self> {
+        let hasher = profile.hasher();
+        let guta = profile.guta_config();
+
+        let mut gcon = SparseMerkleTree::with_hasher(GCON_TREE_HEIGHT, hasher.clone())?;
+        let mut contracts = HashMap::new();
+        for contract in &fixtures.contracts {
+            let contract_id = ContractId(contract.contract_id.clone());
+            let fingerprints: Vec<CfcFingerprint> =
+                contract.functions.iter().map(|f| f.fingerprint).collect();
+            let cft_root = CftTree::build(hasher.as_ref(), &fingerprints)?.root();
+            let leaf = CftRegistry::contract_leaf_hash(
+                hasher.as_ref(),
+                &contract_id,
+                &cft_root,
+                contract.cstate_height,
+            );
+            gcon.set_leaf(CftRegistry::gcon_index(&contract_id), leaf)?;
+
+            let entry = ContractEntry {
+                cft_root,
+                cstate_height: contract.cstate_height,
+                state_owner: contract.state_owner.clone(),
+            };
+            if contracts.insert(contract.contract_id.clone(), entry).is_some() {
+                return Err(PsyGuardError::InvalidInput(format!(
+                    "合约 {:?} 重复定义",
+                    contract.contract_id
+                )));
+            }
+        }
+
+        let mut gusr = SparseMerkleTree::with_hasher(guta.gusr_height, hasher.clone())?;
+        let mut users = HashMap::new();
+        for user in &fixtures.users {
+            let entry = Self::build_user(user, &guta, hasher.clone(), &contracts)?;
+            if users.values().any(|other: &UserEntry| other.index == entry.index) {
+                return Err(PsyGuardError::InvalidInput(format!(
+                    "用户 {:?} 的 GUSR 索引与其他用户冲突",
+                    user.user_id
+                )));
+            }
+            gusr.set_leaf(entry.index, entry.leaf.proof.value)?;
+            users.insert(user.user_id.clone(), entry);
+        }
+        for user in users.values_mut() {
+            user.leaf.proof = gusr.prove(user.index)?.into();
+        }
+
+        for (contract_id, contract) in &contracts {
+            if let Some(owner) = &contract.state_owner {
+                if !users.contains_key(owner) {
+                    return Err(PsyGuardError::InvalidInput(format!(
+                        "合约 {:?} 的状态持有者 {:?} 不存在",
+                        contract_id, owner
+                    )));
+                }
+            }
+        }
+
+        let checkpoint = CheckpointLeaf {
+            block_number: fixtures.block_number,
+            gusr_root: gusr.root(),
+            gcon_root: gcon.root(),
+        };
+        let chkp_root = checkpoint.hash(hasher.as_ref());
+
+        Ok(Self {
+            checkpoint,
+            chkp_root,
+            gcon,
+            users,
+            contracts,
+            submissions: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn build_user(
+        user: &crate::fixtures::UserFixture,
+        guta: &GutaConfig,
+        hasher: Arc<dyn MerkleHasher>,
+        contracts: &HashMap<String, ContractEntry>,
+    ) -> Result<UserEntry> {
+        let user_id = UserId(user.user_id.clone());
+        let public_key_hash = parse_hash(&user.public_key_hash)
+            .map_err(|e| PsyGuardError::InvalidInput(format!("用户 {:?} 的公钥哈希{}", user.user_id, e)))?;
+
+        let mut ucon = Ucon::with_hasher(user_id.clone(), hasher.clone());
+        for (contract_id, slots) in &user.states {
+            let contract = contracts.get(contract_id).ok_or_else(|| {
+                PsyGuardError::InvalidInput(format!(
+                    "用户 {:?} 持有未定义合约 {:?} 的状态",
+                    user.user_id, contract_id
+                ))
+            })?;
+            let mut cstate = Cstate::with_hasher(
+                ContractId(contract_id.clone()),
+                contract.cstate_height,
+                hasher.clone(),
+            )?;
+            for (slot, value) in slots {
+                let value = hex::decode(value).map_err(|e| {
+                    PsyGuardError::InvalidInput(format!("合约 {:?} 槽位 {} 的值无效: {}", contract_id, slot, e))
+                })?;
+                cstate.write_slot(*slot, value)?;
+            }
+            ucon.insert_cstate(cstate)?;
+        }
+
+        let uleaf_hash = guta.user_leaf_hash(
+            &public_key_hash,
+            user.balance,
+            user.nonce,
+            user.last_checkpoint_id,
+            &ucon.root,
+        );
+        let index = guta.user_index(&user_id);
+        let leaf = UserLeafResponse {
+            user_id: user.user_id.clone(),
+            public_key_hash,
+            balance: user.balance,
+            nonce: user.nonce,
+            last_checkpoint_id: user.last_checkpoint_id,
+            ucon_root: ucon.root,
+            // GUSR 建成后替换为真实证明
+            proof: MerkleProofBody { index, value: uleaf_hash, siblings: vec![] },
+        };
+
+        Ok(UserEntry { leaf, ucon, index })
+    }
+
+    /// 该区块的 CheckpointRef
+    pub fn checkpoint_ref(&self) -> CheckpointRef {
+        CheckpointRef {
+            chkp_root: self.chkp_root,
+            block_number: self.checkpoint.block_number,
+        }
+    }
+
+    /// 用户的 UCON (会话以此为本地状态)
+    pub fn ucon(&self, user_id: &str) -> Option<&Ucon> {
+        self.users.get(user_id).map(|user| &user.ucon)
+    }
+
+    /// 最新 CHKP 叶
+    pub fn latest_checkpoint(&self) -> CheckpointResponse {
+        CheckpointResponse {
+            block_number: self.checkpoint.block_number,
+            chkp_root: self.chkp_root,
+            gusr_root: self.checkpoint.gusr_root,
+            gcon_root: self.checkpoint.gcon_root,
+        }
+    }
+
+    /// 指定区块的 CHKP 叶
+    pub fn checkpoint(&self, block_number: u64) -> Result<CheckpointResponse> {
+        self.check_block(block_number)?;
+        Ok(self.latest_checkpoint())
+    }
+
+    /// 用户叶及其 GUSR 证明
+    pub fn user_leaf(&self, block_number: u64, user_id: &str) -> Result<UserLeafResponse> {
+        self.check_block(block_number)?;
+        self.users
+            .get(user_id)
+            .map(|user| user.leaf.clone())
+            .ok_or_else(|| PsyGuardError::NotFound(format!("用户 {:?} 不存在", user_id)))
+    }
+
+    /// 合约叶及其 GCON 证明
+    pub fn contract_leaf(&self, block_number: u64, contract_id: &str) -> Result<ContractLeafResponse> {
+        self.check_block(block_number)?;
+        let contract = self.contract(contract_id)?;
+        let proof = self.gcon.prove(CftRegistry::gcon_index(&ContractId(contract_id.to_string())))?;
+
+        Ok(ContractLeafResponse {
+            contract_id: contract_id.to_string(),
+            cft_root: contract.cft_root.0,
+            cstate_height: contract.cstate_height,
+            proof: proof.into(),
+        })
+    }
+
+    /// 合约共享状态的槽位，附带到 GUSR 的完整证明链
+    pub fn cstate_leaf(&self, block_number: u64, contract_id: &str, slot: u64) -> Result<CstateLeafResponse> {
+        self.check_block(block_number)?;
+        let contract = self.contract(contract_id)?;
+        let owner_id = contract.state_owner.as_ref().ok_or_else(|| {
+            PsyGuardError::NotFound(format!("合约 {:?} 没有共享状态", contract_id))
+        })?;
+        let owner = &self.users[owner_id];
+        let id = ContractId(contract_id.to_string());
+
+        // 持有者尚未写入时为空 CSTATE
+        let empty;
+        let cstate = match owner.ucon.cstate(&id) {
+            Some(cstate) => cstate,
+            None => {
+                empty = Cstate::with_hasher(id.clone(), contract.cstate_height, owner.ucon.hasher().clone())?;
+                &empty
+            }
+        };
+
+        Ok(CstateLeafResponse {
+            contract_id: contract_id.to_string(),
+            slot,
+            value: cstate.read_slot(slot).cloned(),
+            cstate_root: cstate.root,
+            slot_proof: cstate.prove_slot(slot)?.into(),
+            ucon_proof: owner.ucon.prove_contract(&id)?.into(),
+            owner: owner.leaf.clone(),
+        })
+    }
+
+    /// 记录一次 End Cap 提交 (只检查其绑定本区块且用户存在)
+    pub fn submit_endcap(&self, request: SubmitEndcapRequest) -> Result<SubmitReceipt> {
+        let header = &request.endcap.ups_header;
+        if header.checkpoint_ref.chkp_root != self.chkp_root {
+            return Err(PsyGuardError::InvalidInput(format!(
+                "End Cap 绑定的 checkpoint {} 不是区块 {}",
+                hex::encode(header.checkpoint_ref.chkp_root),
+                self.checkpoint.block_number
+            )));
+        }
+        if !self.users.contains_key(&header.user_id.0) {
+            return Err(PsyGuardError::InvalidInput(format!("用户 {:?} 不存在", header.user_id.0)));
+        }
+
+        let mut submissions = self.submissions.lock().unwrap();
+        let receipt = SubmitReceipt {
+            receipt_id: format!("stub_{}_{}", self.checkpoint.block_number, submissions.len()),
+            timestamp: request.endcap.timestamp,
+            guta_path: None,
+        };
+        submissions.push(request);
+        Ok(receipt)
+    }
+
+    /// 已收到的 End Cap 提交
+    pub fn submissions(&self) -> Vec<SubmitEndcapRequest> {
+        self.submissions.lock().unwrap().clone()
+    }
+
+    fn contract(&self, contract_id: &str) -> Result<&ContractEntry> {
+        self.contracts
+            .get(contract_id)
+            .ok_or_else(|| PsyGuardError::NotFound(format!("合约 {:?} 不存在", contract_id)))
+    }
+
+    fn check_block(&self, block_number: u64) -> Result<()> {
+        if block_number != self.checkpoint.block_number {
+            return Err(PsyGuardError::NotFound(format!("区块 {} 不存在", block_number)));
+        }
+        Ok(())
+    }
+}
+
+fn parse_hash(s: &str) -> std::result::Result<Hash, String> {
+    let bytes = hex::decode(s).map_err(|e| format!("不是十六进制: {}", e))?;
+    bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| format!("应为 32 字节，实际 {} 字节", bytes.len()))
+}
@@ -4,9 +4,6 @@
 //! 参考: 教程第4步 - WASM 绑定与前端调用面
 
 use wasm_bindgen::prelude::*;
-use psyguard_core::*;
-use psyguard_provers::{MockProver, MockNetworkState, MockSubmitter};
-use std::sync::Arc;
 
 mod session;
 mod utils;
@@ -32,6 +29,21 @@ pub fn version() -> String {
     env!("CARGO_PKG_VERSION").to_string()
 }
 
+/// 解码线格式 End Cap 并返回其内容哈希 (十六进制)
+#[wasm_bindgen]
+pub fn endcap_wire_hash(bytes: &[u8]) -> std::result::Result<String, JsValue> {
+    let endcap: psyguard_core::EndCapProof = psyguard_core::codec::decode(bytes)
+        .map_err(to_js_error)?;
+    Ok(hex::encode(psyguard_core::codec::wire_hash(&endcap)))
+}
+
+/// 将文本形式的 SDKey 策略规则解析为 JSON (可填入策略的 `rules` 字段)
+#[wasm_bindgen]
+pub fn parse_policy_rules(source: &str) -> std::result::Result<String, JsValue> {
+    let rules = psyguard_core::policy_expr::parse_rules(source).map_err(to_js_error)?;
+    serde_json::to_string(&rules).map_err(to_js_error)
+}
+
 /// 测试函数
 #[wasm_bindgen]
 pub fn test_connection() -> String {
@@ -1,171 +1,471 @@
 //! UPS 会话的 WASM 绑定
 //! 
 //! 参考: 教程第4步 - WASM API 设计
+//!
+//! 证明相关的方法返回 Promise，由异步证明器驱动，不阻塞浏览器主线程。
 
 use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+use js_sys::{Promise, Uint8Array};
 use psyguard_core::*;
+use psyguard_core::cft::CftTree;
+use psyguard_core::hash::Sha256Hasher;
+use psyguard_core::registry::CftRegistry;
+use psyguard_core::second_factor::ChallengeKey;
 use psyguard_provers::{MockProver, MockNetworkState, MockSubmitter};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
 use std::sync::Arc;
 use crate::utils::to_js_error;
 
+/// 会话槽位；异步操作期间会话被借出，槽位为空
+type SessionSlot = Rc<RefCell<Option<ups::UpsSession>>>;
+
+/// 从槽位借出的会话，结束 (或 Promise 被丢弃) 时归还
+struct SessionLease {
+    slot: SessionSlot,
+    session: Option<ups::UpsSession>,
+}
+
+impl SessionLease {
+    fn take(slot: &SessionSlot) -> std::result::Result<Self, JsValue> {
+        let session = slot.borrow_mut().take().ok_or_else(session_busy)?;
+        Ok(Self { slot: slot.clone(), session: Some(session) })
+    }
+}
+
+impl Deref for SessionLease {
+    type Target = ups::UpsSession;
+
+    fn deref(&self) -> &Self::Target {
+        self.session.as_ref().expect("会话在归还前始终存在")
+    }
+}
+
+impl DerefMut for SessionLease {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.session.as_mut().expect("会话在归还前始终存在")
+    }
+}
+
+impl Drop for SessionLease {
+    fn drop(&mut self) {
+        *self.slot.borrow_mut() = self.session.take();
+    }
+}
+
+fn session_busy() -> JsValue {
+    to_js_error("会话正忙，请等待上一个操作完成")
+}
+
+fn parse_policy(policy_json: &str) -> std::result::Result<SdkeyPolicy, JsValue> {
+    SdkeyPolicy::from_json(policy_json).map_err(to_js_error)
+}
+
+fn to_js<T: Serialize>(value: &T) -> std::result::Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(value).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// `exec_cfc` 的结果 (哈希为十六进制)
+#[derive(Debug, Serialize)]
+struct CfcResult {
+    success: bool,
+    gas_used: u64,
+    state_root: String,
+}
+
+impl From<&TxEndCtx> for CfcResult {
+    fn from(ctx: &TxEndCtx) -> Self {
+        Self {
+            success: ctx.success,
+            gas_used: ctx.gas_used,
+            state_root: hex::encode(ctx.end_contract_state_root),
+        }
+    }
+}
+
+/// `finalize_endcap` 的结果
+#[derive(Debug, Serialize)]
+struct EndCapSummary {
+    session_id: String,
+    step_count: u32,
+    timestamp: u64,
+    ucon_root: String,
+}
+
+impl From<&EndCapProof> for EndCapSummary {
+    fn from(endcap: &EndCapProof) -> Self {
+        Self {
+            session_id: endcap.ups_header.session_id.clone(),
+            step_count: endcap.final_step.step_number,
+            timestamp: endcap.timestamp,
+            ucon_root: hex::encode(endcap.final_step.current_ucon_root),
+        }
+    }
+}
+
+/// `submit_endcap` 的结果
+#[derive(Debug, Serialize)]
+struct ReceiptView {
+    receipt_id: String,
+    timestamp: u64,
+    guta_path: Option<GutaPathView>,
+}
+
+#[derive(Debug, Serialize)]
+struct GutaPathView {
+    realm_segment: String,
+    coordinator_segment: String,
+    global_root: String,
+    nca_count: u32,
+    proof_summary: String,
+    height: u64,
+}
+
+impl From<&SubmitReceipt> for ReceiptView {
+    fn from(receipt: &SubmitReceipt) -> Self {
+        Self {
+            receipt_id: receipt.receipt_id.clone(),
+            timestamp: receipt.timestamp,
+            guta_path: receipt.guta_path.as_ref().map(|path| GutaPathView {
+                realm_segment: path.realm_segment.clone(),
+                coordinator_segment: path.coordinator_segment.clone(),
+                global_root: hex::encode(path.global_root),
+                nca_count: path.nca_count,
+                proof_summary: path.proof_summary.clone(),
+                height: path.height,
+            }),
+        }
+    }
+}
+
+/// `get_session_info` 的结果
+#[derive(Debug, Serialize)]
+struct SessionInfo {
+    user_id: String,
+    session_id: String,
+    block_number: u64,
+    step_count: u32,
+    balance: u64,
+    nonce: u64,
+}
+
 /// WASM UPS 会话包装器
 #[wasm_bindgen]
 pub struct WasmUpsSession {
-    session: ups::UpsSession,
-    network: Arc<MockNetworkState>,
-    prover: Arc<MockProver>,
-    submitter: Arc<MockSubmitter>,
+    session: SessionSlot,
+    cft_registry: Arc<CftRegistry>,
+    submitter: Arc<dyn AsyncSubmitter>,
 }
 
 #[wasm_bindgen]
 impl WasmUpsSession {
-    /// 初始化新会话
+    /// 初始化新会话 (异步)
+    /// ucon_json: 上次会话 `export_ucon` 导出的 UCON；省略时用户尚无合约状态
+    /// contracts_json: 会话开始前部署的 Mock 合约及其函数清单，
+    /// `{"token": [{"name": "transfer", "fingerprint": "<64 位十六进制>"}]}`；
+    /// 会话绑定部署后的 checkpoint，其后无法再部署
     /// 参考: 《5-Local Proving (UPS).md》- UPS 启动
-    #[wasm_bindgen(constructor)]
-    pub fn new(user_id: String) -> std::result::Result<WasmUpsSession, JsValue> {
+    #[wasm_bindgen]
+    pub async fn create(
+        user_id: String,
+        ucon_json: Option<String>,
+        contracts_json: Option<String>,
+    ) -> std::result::Result<WasmUpsSession, JsValue> {
         log::info!("初始化 UPS 会话: {}", user_id);
+        let ucon = match ucon_json {
+            Some(json) => {
+                let snapshot: UconSnapshot = serde_json::from_str(&json)
+                    .map_err(|e| JsValue::from_str(&format!("UCON 解析失败: {}", e)))?;
+                state::Ucon::from_snapshot(&snapshot).map_err(to_js_error)?
+            }
+            None => state::Ucon::new(UserId(user_id.clone())),
+        };
 
         // 创建 Mock 后端
         let network = Arc::new(MockNetworkState::new());
         let prover = Arc::new(MockProver::new());
-        let submitter = Arc::new(MockSubmitter::new());
+        let submitter = Arc::new(SyncAdapter::new(Arc::new(MockSubmitter::new())));
 
-        // 添加测试用户
-        network.add_user(UserId(user_id.clone()), 10000);
+        // 添加测试用户 (用户叶记录本地 UCON 的根)
+        network.add_user_with_ucon_root(UserId(user_id.clone()), 10000, ucon.root);
+
+        // 部署合约；每次部署出一个新区块，会话随后绑定最新的 checkpoint
+        let contracts: BTreeMap<String, Vec<ContractFunction>> = match contracts_json {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| JsValue::from_str(&format!("合约清单解析失败: {}", e)))?,
+            None => BTreeMap::new(),
+        };
+        for (contract_id, functions) in &contracts {
+            network
+                .add_contract_functions(ContractId(contract_id.clone()), functions)
+                .map_err(to_js_error)?;
+        }
 
         // 创建会话
-        let session = ups::UpsSession::new(
+        let session = ups::UpsSession::with_ucon_async(
             UserId(user_id),
-            network.clone(),
-            prover.clone(),
-        ).map_err(to_js_error)?;
+            ucon,
+            Arc::new(SyncAdapter::new(network)),
+            prover,
+        ).await.map_err(to_js_error)?;
+        let cft_registry = session.cft_registry().clone();
+        for (contract_id, functions) in contracts {
+            cft_registry
+                .register_contract(ContractId(contract_id), functions)
+                .map_err(to_js_error)?;
+        }
 
         Ok(WasmUpsSession {
-            session,
-            network,
-            prover,
+            session: Rc::new(RefCell::new(Some(session))),
+            cft_registry,
             submitter,
         })
     }
 
-    /// 执行 CFC (合约函数调用)
+    /// 登记已部署合约的函数清单，返回清单对应的 CFT 根
+    /// 清单须与会话 checkpoint 中的合约叶一致，否则后续 `exec_cfc` 被拒绝
+    /// functions_json: `[{"name": "transfer", "fingerprint": "<64 位十六进制>"}]`
+    /// 参考: 《6-Smart Contracts.md》- CFT 指纹白名单
+    #[wasm_bindgen]
+    pub fn register_contract(
+        &self,
+        contract_id: String,
+        functions_json: String,
+    ) -> std::result::Result<String, JsValue> {
+        let functions: Vec<ContractFunction> = serde_json::from_str(&functions_json)
+            .map_err(|e| JsValue::from_str(&format!("函数清单解析失败: {}", e)))?;
+        let fingerprints: Vec<CfcFingerprint> = functions.iter().map(|f| f.fingerprint).collect();
+        let cft_root = CftTree::build(&Sha256Hasher, &fingerprints).map_err(to_js_error)?.root();
+        self.cft_registry
+            .register_contract(ContractId(contract_id), functions)
+            .map_err(to_js_error)?;
+
+        Ok(hex::encode(cft_root.0))
+    }
+
+    /// 执行 CFC (合约函数调用)，返回 Promise
     /// 参考: 《5-Local Proving (UPS).md》- CFC 执行与集成
     #[wasm_bindgen]
     pub fn exec_cfc(
-        &mut self,
+        &self,
         contract_id: String,
         function_name: String,
         args_json: String,
-    ) -> std::result::Result<JsValue, JsValue> {
+    ) -> Promise {
         log::info!("执行 CFC: {}::{}", contract_id, function_name);
+        let slot = self.session.clone();
 
-        let cfc_id = CfcId {
-            contract_id: ContractId(contract_id.clone()),
-            function_name: function_name.clone(),
-        };
+        future_to_promise(async move {
+            let mut session = SessionLease::take(&slot)?;
 
-        let inputs = CfcInputs {
-            function_args: args_json.into_bytes(),
-            caller: self.session.header().user_id.clone(),
-            contract_state_root: [0u8; 32],
-        };
+            let cfc_id = CfcId {
+                contract_id: ContractId(contract_id),
+                function_name,
+            };
 
-        // 构建 Mock CFT 证明
-        let cft_proof = CftInclusionProof {
-            merkle_path: vec![],
-            cft_root: CftRoot([0u8; 32]),
-        };
-
-        // 执行 CFC
-        let tx_end_ctx = self.session
-            .execute_cfc(&cfc_id, &inputs, &cft_proof)
-            .map_err(to_js_error)?;
+            let inputs = CfcInputs {
+                function_args: args_json.into_bytes(),
+                caller: session.header().user_id.clone(),
+                contract_state_root: [0u8; 32],
+                read_slots: vec![],
+                read_owner: None,
+            };
 
-        // 返回执行结果
-        let result = serde_json::json!({
-            "success": tx_end_ctx.success,
-            "gas_used": tx_end_ctx.gas_used,
-            "state_root": hex::encode(tx_end_ctx.end_contract_state_root),
-        });
+            // 执行 CFC (CFT 包含证明由会话的注册表生成并校验)
+            let tx_end_ctx = session
+                .execute_cfc_async(&cfc_id, &inputs)
+                .await
+                .map_err(to_js_error)?;
 
-        serde_wasm_bindgen::to_value(&result).map_err(|e| JsValue::from_str(&e.to_string()))
+            to_js(&CfcResult::from(&tx_end_ctx))
+        })
     }
 
-    /// 终结会话并生成 End Cap
+    /// 终结会话并生成 End Cap，返回 Promise
     /// 参考: 《5-Local Proving (UPS).md》- End Cap 终结
     #[wasm_bindgen]
-    pub fn finalize_endcap(&self, policy_json: String) -> std::result::Result<JsValue, JsValue> {
+    pub fn finalize_endcap(&self, policy_json: String) -> Promise {
         log::info!("终结 End Cap");
+        let slot = self.session.clone();
 
-        // 解析策略
-        let policy: SdkeyPolicy = serde_json::from_str(&policy_json)
-            .map_err(|e| to_js_error(format!("策略解析失败: {}", e)))?;
-
-        // 生成 End Cap
-        let endcap = self.session
-            .finalize(&policy)
-            .map_err(to_js_error)?;
+        future_to_promise(async move {
+            let policy = parse_policy(&policy_json)?;
+            let session = SessionLease::take(&slot)?;
 
-        // 返回 End Cap 信息
-        let result = serde_json::json!({
-            "session_id": endcap.ups_header.session_id,
-            "step_count": endcap.final_step.step_number,
-            "timestamp": endcap.timestamp,
-            "ucon_root": hex::encode(endcap.final_step.current_ucon_root),
-        });
+            let endcap = session
+                .finalize_async(&policy)
+                .await
+                .map_err(to_js_error)?;
 
-        serde_wasm_bindgen::to_value(&result).map_err(|e| JsValue::from_str(&e.to_string()))
+            to_js(&EndCapSummary::from(&endcap))
+        })
     }
 
-    /// 提交 End Cap
+    /// 提交 End Cap，返回 Promise
     /// 参考: 《5-Local Proving (UPS).md》- End Cap 提交
     #[wasm_bindgen]
-    pub fn submit_endcap(&self, policy_json: String) -> std::result::Result<JsValue, JsValue> {
+    pub fn submit_endcap(&self, policy_json: String) -> Promise {
         log::info!("提交 End Cap");
+        let slot = self.session.clone();
+        let submitter = self.submitter.clone();
 
-        // 解析策略
-        let policy: SdkeyPolicy = serde_json::from_str(&policy_json)
-            .map_err(|e| to_js_error(format!("策略解析失败: {}", e)))?;
+        future_to_promise(async move {
+            let policy = parse_policy(&policy_json)?;
+            let session = SessionLease::take(&slot)?;
 
-        // 生成 End Cap
-        let endcap = self.session
-            .finalize(&policy)
-            .map_err(to_js_error)?;
+            // 生成 End Cap
+            let endcap = session
+                .finalize_async(&policy)
+                .await
+                .map_err(to_js_error)?;
+
+            // 提交
+            let receipt = submitter
+                .submit_endcap(&endcap, session.state_deltas().to_vec())
+                .await
+                .map_err(to_js_error)?;
 
-        // 提交
-        let receipt = self.submitter
-            .submit_endcap(&endcap, self.session.state_deltas().to_vec())
+            to_js(&ReceiptView::from(&receipt))
+        })
+    }
+
+    /// 终结会话并以二进制线格式导出 End Cap，Promise 结果为 Uint8Array
+    /// 参考: codec - 与中继之间的传输格式
+    #[wasm_bindgen]
+    pub fn export_endcap(&self, policy_json: String) -> Promise {
+        let slot = self.session.clone();
+
+        future_to_promise(async move {
+            let policy = parse_policy(&policy_json)?;
+            let session = SessionLease::take(&slot)?;
+
+            let endcap = session
+                .finalize_async(&policy)
+                .await
+                .map_err(to_js_error)?;
+
+            Ok(Uint8Array::from(codec::encode(&endcap).as_slice()).into())
+        })
+    }
+
+    /// 第二因素挑战 (十六进制)，由登记的硬件密钥 (Ed25519) 签名
+    /// 执行完全部 CFC 后再取；之后执行的 CFC 会使挑战失效
+    #[wasm_bindgen]
+    pub fn second_factor_challenge(&self) -> std::result::Result<String, JsValue> {
+        self.with_session(|session| hex::encode(ChallengeKey::challenge(&session.second_factor_binding())))
+    }
+
+    /// 附加挑战-应答第二因素 (策略 `require_2fa` 时终结前必需)
+    #[wasm_bindgen]
+    pub fn attach_challenge_response(
+        &self,
+        public_key_hex: String,
+        signature_hex: String,
+    ) -> std::result::Result<(), JsValue> {
+        let public_key: [u8; 32] = hex::decode(&public_key_hex)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| JsValue::from_str("第二公钥应为 64 位十六进制"))?;
+        let signature = hex::decode(&signature_hex)
+            .map_err(|e| JsValue::from_str(&format!("签名解析失败: {}", e)))?;
+        let key = ChallengeKey::new(&public_key).map_err(to_js_error)?;
+
+        let mut slot = self.session.borrow_mut();
+        let session = slot.as_mut().ok_or_else(session_busy)?;
+        let factor = key
+            .verify(&session.second_factor_binding(), &signature)
             .map_err(to_js_error)?;
+        session.attach_second_factor(factor).map_err(to_js_error)
+    }
 
-        // 返回收据
-        let result = serde_json::json!({
-            "receipt_id": receipt.receipt_id,
-            "timestamp": receipt.timestamp,
-            "guta_path": receipt.guta_path.as_ref().map(|p| serde_json::json!({
-                "realm_segment": p.realm_segment,
-                "coordinator_segment": p.coordinator_segment,
-                "global_root": hex::encode(p.global_root),
-            })),
-        });
+    /// 导出会话当前的 UCON (JSON)，终结后保存以便下次 `create` 恢复
+    #[wasm_bindgen]
+    pub fn export_ucon(&self) -> std::result::Result<String, JsValue> {
+        let snapshot = self.with_session(|session| session.ucon().snapshot())?.map_err(to_js_error)?;
+        serde_json::to_string(&snapshot).map_err(to_js_error)
+    }
 
-        serde_wasm_bindgen::to_value(&result).map_err(|e| JsValue::from_str(&e.to_string()))
+    /// 以二进制线格式导出状态变更
+    #[wasm_bindgen]
+    pub fn export_state_deltas(&self) -> std::result::Result<Vec<u8>, JsValue> {
+        self.with_session(|session| codec::encode(&session.state_deltas().to_vec()))
     }
 
     /// 获取会话信息
     #[wasm_bindgen]
     pub fn get_session_info(&self) -> std::result::Result<JsValue, JsValue> {
-        let header = self.session.header();
-        let step = self.session.current_step();
-
-        let result = serde_json::json!({
-            "user_id": header.user_id.0,
-            "session_id": header.session_id,
-            "block_number": header.checkpoint_ref.block_number,
-            "step_count": step.step_number,
-            "balance": header.user_leaf_ctx.balance,
-            "nonce": header.user_leaf_ctx.nonce,
-        });
-
-        serde_wasm_bindgen::to_value(&result).map_err(|e| JsValue::from_str(&e.to_string()))
+        let info = self.with_session(|session| {
+            let header = session.header();
+            SessionInfo {
+                user_id: header.user_id.0.clone(),
+                session_id: header.session_id.clone(),
+                block_number: header.checkpoint_ref.block_number,
+                step_count: session.current_step().step_number,
+                balance: header.user_leaf_ctx.balance,
+                nonce: header.user_leaf_ctx.nonce,
+            }
+        })?;
+
+        to_js(&info)
+    }
+}
+
+impl WasmUpsSession {
+    /// 读取当前会话；异步操作进行中时返回错误
+    fn with_session<T>(&self, f: impl FnOnce(&ups::UpsSession) -> T) -> std::result::Result<T, JsValue> {
+        let slot = self.session.borrow();
+        let session = slot.as_ref().ok_or_else(session_busy)?;
+        Ok(f(session))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    const CONTRACTS_JSON: &str =
+        r#"{"token": [{"name": "transfer", "fingerprint": "0101010101010101010101010101010101010101010101010101010101010101"}]}"#;
+
+    fn contracts() -> Option<String> {
+        Some(CONTRACTS_JSON.to_string())
+    }
+
+    async fn transfer(session: &WasmUpsSession, args: &[u8]) -> TxEndCtx {
+        let mut session = SessionLease::take(&session.session).unwrap();
+        let cfc_id = CfcId {
+            contract_id: ContractId("token".to_string()),
+            function_name: "transfer".to_string(),
+        };
+        let inputs = CfcInputs {
+            function_args: args.to_vec(),
+            caller: session.header().user_id.clone(),
+            contract_state_root: [0u8; 32],
+            read_slots: vec![],
+            read_owner: None,
+        };
+        session.execute_cfc_async(&cfc_id, &inputs).await.unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_create_with_existing_ucon() {
+        let first = WasmUpsSession::create("alice".to_string(), None, contracts()).await.unwrap();
+        transfer(&first, b"1").await;
+        let ucon_json = first.export_ucon().unwrap();
+
+        // 以导出的 UCON 开启下一次会话，并在已有的 CSTATE 上继续写入
+        let second = WasmUpsSession::create("alice".to_string(), Some(ucon_json), contracts()).await.unwrap();
+        let start_root = second.with_session(|session| session.ucon().root).unwrap();
+        let end = transfer(&second, b"2").await;
+
+        second.with_session(|session| {
+            let token = ContractId("token".to_string());
+            assert_ne!(session.ucon().root, start_root);
+            assert_eq!(session.ucon().get_contract_state(&token), Some(&end.end_contract_state_root));
+        }).unwrap();
     }
 }
@@ -0,0 +1,163 @@
+//! PsyGuard Proving Server - 委托证明的参考服务
+//!
+//! 按 psyguard_provers::remote 的协议接收 `POST /v1/prove` 请求，由任意 AsyncProver 生成证明。
+//! 参考实现包装 MockProver，可离线测试完整的委托流程。
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use psyguard_core::AsyncProver;
+use psyguard_provers::remote::{ProvingError, PROVE_PATH};
+use psyguard_provers::{ProvingRequest, ProvingResponse};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// 构建证明服务路由
+pub fn router(prover: Arc<dyn AsyncProver>) -> Router {
+    Router::new()
+        .route(PROVE_PATH, post(prove))
+        .with_state(prover)
+}
+
+async fn prove(
+    State(prover): State<Arc<dyn AsyncProver>>,
+    Json(request): Json<ProvingRequest>,
+) -> Response {
+    match request.prove(prover.as_ref()).await {
+        Ok(response) => Json::<ProvingResponse>(response).into_response(),
+        Err(e) => {
+            log::warn!("证明失败: {}", e);
+            (StatusCode::UNPROCESSABLE_ENTITY, Json(ProvingError { error: e.to_string() })).into_response()
+        }
+    }
+}
+
+/// 在后台任务中运行的证明服务，drop 时停止
+pub struct ProvingServer {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl ProvingServer {
+    /// 监听 127.0.0.1 的随机端口
+    pub async fn spawn(prover: Arc<dyn AsyncProver>) -> std::io::Result<Self> {
+        Self::bind(prover, SocketAddr::from(([127, 0, 0, 1], 0))).await
+    }
+
+    pub async fn bind(prover: Arc<dyn AsyncProver>, addr: SocketAddr) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        let app = router(prover);
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                log::error!("证明服务退出: {}", e);
+            }
+        });
+        Ok(Self { addr, task })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// 服务根地址，如 `http://127.0.0.1:34567`
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+
+impl Drop for ProvingServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyguard_core::sdkey::SdkeyPolicyBuilder;
+    use psyguard_core::ups::UpsSession;
+    use psyguard_core::*;
+    use psyguard_provers::{HttpProvingTransport, MockNetworkState, MockProver, MockVerifier, RemoteProver};
+    use std::time::Duration;
+
+    async fn delegated_session(prover: Arc<RemoteProver>) -> UpsSession {
+        let network = Arc::new(MockNetworkState::new());
+        network.add_user(UserId("alice".to_string()), 1000);
+        let functions = vec![ContractFunction {
+            name: "transfer".to_string(),
+            fingerprint: CfcFingerprint([1u8; 32]),
+        }];
+        network.add_contract_functions(ContractId("token".to_string()), &functions).unwrap();
+
+        let session = UpsSession::new_async(
+            UserId("alice".to_string()),
+            Arc::new(SyncAdapter::new(network)),
+            prover,
+        )
+        .await
+        .unwrap();
+        session.cft_registry().register_contract(ContractId("token".to_string()), functions).unwrap();
+        session
+    }
+
+    async fn transfer(session: &mut UpsSession) -> Result<TxEndCtx> {
+        let cfc_id = CfcId {
+            contract_id: ContractId("token".to_string()),
+            function_name: "transfer".to_string(),
+        };
+        let inputs = CfcInputs {
+            function_args: b"10".to_vec(),
+            caller: session.header().user_id.clone(),
+            contract_state_root: [0u8; 32],
+            read_slots: vec![],
+            read_owner: None,
+        };
+        session.execute_cfc_async(&cfc_id, &inputs).await
+    }
+
+    fn remote(url: &str, timeout: Duration) -> Arc<RemoteProver> {
+        let transport = Arc::new(HttpProvingTransport::new(url));
+        Arc::new(
+            RemoteProver::new(transport, Arc::new(MockVerifier), Arc::new(MockProver::new()))
+                .with_timeout(timeout),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_delegated_session_end_to_end() {
+        let server = ProvingServer::spawn(Arc::new(MockProver::new())).await.unwrap();
+        let prover = remote(&server.url(), Duration::from_secs(5));
+        let mut session = delegated_session(prover.clone()).await;
+
+        transfer(&mut session).await.unwrap();
+        transfer(&mut session).await.unwrap();
+        let endcap = session.finalize_async(&SdkeyPolicyBuilder::new().build()).await.unwrap();
+
+        assert_eq!(endcap.final_step.step_number, 2);
+        assert!(AsyncProver::verify_endcap(prover.as_ref(), &endcap).await.unwrap());
+        assert_eq!(prover.fallbacks(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_delegated_session_falls_back_when_service_is_slow_or_down() {
+        let slow = ProvingServer::spawn(Arc::new(MockProver::with_delay(60_000))).await.unwrap();
+        let prover = remote(&slow.url(), Duration::from_millis(50));
+        let mut session = delegated_session(prover.clone()).await;
+        transfer(&mut session).await.unwrap();
+        // CFC 证明与 UPS 集成各超时一次
+        assert_eq!(prover.fallbacks(), 2);
+
+        let down = slow.url();
+        drop(slow);
+        tokio::task::yield_now().await;
+        let prover = remote(&down, Duration::from_secs(5));
+        let mut session = delegated_session(prover.clone()).await;
+        transfer(&mut session).await.unwrap();
+        assert_eq!(prover.fallbacks(), 2);
+    }
+}
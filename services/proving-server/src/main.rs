@@ -0,0 +1,26 @@
+//! 证明服务入口
+//!
+//! `PSYGUARD_PROVER_LISTEN` 为监听地址 (默认 127.0.0.1:8788)，
+//! `PSYGUARD_PROVER_DELAY_MS` 为 MockProver 的模拟证明耗时 (默认 0)。
+
+use psyguard_provers::MockProver;
+use psyguard_proving_server::ProvingServer;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let addr: SocketAddr = std::env::var("PSYGUARD_PROVER_LISTEN")
+        .unwrap_or_else(|_| "127.0.0.1:8788".to_string())
+        .parse()?;
+    let delay_ms: u64 = match std::env::var("PSYGUARD_PROVER_DELAY_MS") {
+        Ok(delay) => delay.parse()?,
+        Err(_) => 0,
+    };
+
+    let server = ProvingServer::bind(Arc::new(MockProver::with_delay(delay_ms)), addr).await?;
+    println!("证明服务监听 {} (MockProver，延迟 {} 毫秒)", server.url(), delay_ms);
+
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
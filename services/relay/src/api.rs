@@ -0,0 +1,524 @@
+//! 中继接口
+//!
+//! | 方法 | 路径 | 响应 |
+//! |------|------|------|
+//! | GET | `/v1/checkpoints/latest` | `CheckpointRef` |
+//! | GET | `/v1/users/{user_id}/leaf` | `UserLeafCtx` |
+//! | GET | `/v1/contracts/{contract_id}/meta` | [`ContractMeta`] |
+//! | GET | `/v1/users/{user_id}/contracts/{contract_id}/slots/{slot}` | `CstateReadWitness` |
+//! | POST | `/v1/endcaps` ([`SubmitRequest`] 或线格式帧) | `SubmitReceipt` |
+//!
+//! 用户叶、合约元数据与槽位接口可用 `?block_number=&chkp_root=` 指定 checkpoint，缺省为最新 finalized checkpoint。
+//! 槽位接口读取 `user_id` 持有的 CSTATE，上游返回其他用户的 CSTATE 时报错。
+//! 请求与响应沿用 psyguard-core 类型的 serde 表示，错误为 `{"error": "<原因>"}`。
+//! End Cap 也可以 `application/octet-stream` 提交: 请求体为 `codec` 编码的 End Cap 帧
+//! (即 WASM `export_endcap` 的输出)，有状态变更时其后紧接状态变更帧 (`export_state_deltas`)；
+//! SDKey 策略 (JSON) 放在 [`POLICY_HEADER`] 头中。
+//! 用户叶查询与 End Cap 提交按用户 ID 限流，超限返回 429 与 `Retry-After`；
+//! End Cap 在通过校验后才计入提交者的配额。
+
+use crate::limiter::RateLimiter;
+use crate::upstream::Upstream;
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use psyguard_core::codec;
+use psyguard_core::endcap::{self, EndCapExpectations};
+use psyguard_core::*;
+use psyguard_http::schema::ErrorResponse;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 二进制提交时携带 SDKey 策略 (JSON) 的请求头
+pub const POLICY_HEADER: &str = "x-psyguard-sdkey-policy";
+
+/// 中继状态
+pub struct Relay {
+    upstream: Upstream,
+    /// 校验 End Cap 的证明后端
+    verifier: Arc<dyn Prover>,
+    /// SDKey 签名电路的 verifier data，用于由策略导出公钥哈希
+    sdkey_verifier_data: Vec<u8>,
+    limiter: RateLimiter,
+}
+
+impl Relay {
+    pub fn new(
+        upstream: Upstream,
+        verifier: Arc<dyn Prover>,
+        sdkey_verifier_data: Vec<u8>,
+        limiter: RateLimiter,
+    ) -> Self {
+        Self { upstream, verifier, sdkey_verifier_data, limiter }
+    }
+
+    /// 解析 checkpoint 查询参数，缺省时取最新 finalized checkpoint
+    async fn checkpoint(&self, query: CheckpointQuery) -> RelayResult<CheckpointRef> {
+        match (query.block_number, query.chkp_root) {
+            (None, None) => Ok(self.upstream.network.latest_finalized_chkp().await?),
+            (Some(block_number), Some(chkp_root)) => {
+                let root = hex::decode(&chkp_root)
+                    .ok()
+                    .and_then(|bytes| Hash::try_from(bytes).ok())
+                    .ok_or_else(|| PsyGuardError::InvalidInput(format!("chkp_root {:?} 无效", chkp_root)))?;
+                Ok(CheckpointRef { chkp_root: root, block_number })
+            }
+            _ => Err(PsyGuardError::InvalidInput(
+                "block_number 与 chkp_root 需同时给出".to_string(),
+            )
+            .into()),
+        }
+    }
+
+    fn rate_limit(&self, user_id: &UserId) -> RelayResult<()> {
+        self.limiter.check(&user_id.0).map_err(|retry_after| RelayError::RateLimited {
+            user_id: user_id.0.clone(),
+            retry_after,
+        })
+    }
+
+    /// 转发前校验 End Cap
+    ///
+    /// End Cap 必须绑定最新 finalized checkpoint，头部的用户叶须与上游一致；
+    /// 提交的策略须导出上游用户叶登记的 SDKey 公钥哈希，2FA 要求与登记凭据取自该策略，
+    /// 其余检查 (步骤数、签名消息、2FA、SDKey、证明) 由 endcap::verify 完成。
+    async fn validate(&self, request: &SubmitRequest) -> RelayResult<()> {
+        let header = &request.endcap.ups_header;
+        let checkpoint = self.upstream.network.latest_finalized_chkp().await?;
+        let user_leaf = self.upstream.network.fetch_user_leaf(&header.user_id, &checkpoint).await?;
+        let claimed = &header.user_leaf_ctx;
+        if claimed.uleaf_hash != user_leaf.uleaf_hash
+            || claimed.ucon_root != user_leaf.ucon_root
+            || claimed.public_key_hash != user_leaf.public_key_hash
+            || claimed.balance != user_leaf.balance
+            || claimed.nonce != user_leaf.nonce
+        {
+            return Err(PsyGuardError::EndCapVerificationFailed(format!(
+                "用户 {:?} 的用户叶与 checkpoint {} 不一致",
+                header.user_id.0, checkpoint.block_number
+            ))
+            .into());
+        }
+
+        let expected = EndCapExpectations::for_policy(checkpoint, &self.sdkey_verifier_data, &request.policy);
+        if expected.sdkey_public_key_hash != user_leaf.public_key_hash {
+            return Err(PsyGuardError::EndCapVerificationFailed(format!(
+                "策略与用户 {:?} 登记的 SDKey 公钥不一致",
+                header.user_id.0
+            ))
+            .into());
+        }
+        endcap::verify(&request.endcap, &request.state_deltas, &expected, self.verifier.as_ref())?;
+        Ok(())
+    }
+}
+
+/// 可选的 checkpoint 查询参数
+#[derive(Debug, Default, Deserialize)]
+pub struct CheckpointQuery {
+    pub block_number: Option<u64>,
+    /// 十六进制
+    pub chkp_root: Option<String>,
+}
+
+/// 合约元数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractMeta {
+    pub cft_root: CftRoot,
+    pub cstate_height: CstateHeight,
+}
+
+/// 提交 End Cap
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitRequest {
+    pub endcap: EndCapProof,
+    pub state_deltas: Vec<CstateDelta>,
+    /// 签名所用的 SDKey 策略 (须与用户叶登记的公钥一致)
+    pub policy: SdkeyPolicy,
+}
+
+impl SubmitRequest {
+    /// 按 Content-Type 解析 JSON 或线格式请求体
+    fn parse(headers: &HeaderMap, body: &[u8]) -> Result<Self> {
+        let content_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if !content_type.starts_with("application/octet-stream") {
+            return serde_json::from_slice(body)
+                .map_err(|e| PsyGuardError::SerializationError(format!("提交请求解析失败: {}", e)));
+        }
+
+        let (endcap, rest) = codec::decode_prefix::<EndCapProof>(body)?;
+        let state_deltas = if rest.is_empty() { vec![] } else { codec::decode(rest)? };
+        let policy = headers
+            .get(POLICY_HEADER)
+            .ok_or_else(|| PsyGuardError::InvalidInput(format!("缺少 {} 请求头", POLICY_HEADER)))?
+            .to_str()
+            .map_err(|e| PsyGuardError::InvalidInput(format!("{} 请求头无效: {}", POLICY_HEADER, e)))?;
+        Ok(Self { endcap, state_deltas, policy: SdkeyPolicy::from_json(policy)? })
+    }
+}
+
+/// 中继错误
+#[derive(Debug)]
+pub enum RelayError {
+    Core(PsyGuardError),
+    RateLimited { user_id: String, retry_after: Duration },
+}
+
+impl From<PsyGuardError> for RelayError {
+    fn from(error: PsyGuardError) -> Self {
+        Self::Core(error)
+    }
+}
+
+impl IntoResponse for RelayError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::RateLimited { user_id, retry_after } => {
+                let error = format!("用户 {:?} 请求过于频繁", user_id);
+                // 向上取整到秒
+                let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, seconds.to_string())],
+                    Json(ErrorResponse { error }),
+                )
+                    .into_response()
+            }
+            Self::Core(error) => {
+                let status = match error {
+                    PsyGuardError::NotFound(_) => StatusCode::NOT_FOUND,
+                    PsyGuardError::InvalidInput(_)
+                    | PsyGuardError::SerializationError(_)
+                    | PsyGuardError::EndCapVerificationFailed(_) => StatusCode::BAD_REQUEST,
+                    PsyGuardError::NetworkError(_)
+                    | PsyGuardError::MerkleProofInvalid(_)
+                    | PsyGuardError::GconVerificationFailed(_) => StatusCode::BAD_GATEWAY,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                (status, Json(ErrorResponse { error: error.to_string() })).into_response()
+            }
+        }
+    }
+}
+
+type RelayResult<T> = std::result::Result<T, RelayError>;
+type ApiResult<T> = RelayResult<Json<T>>;
+type RelayState = State<Arc<Relay>>;
+
+/// 构建中继路由
+pub fn router(relay: Arc<Relay>) -> Router {
+    Router::new()
+        .route("/v1/checkpoints/latest", get(latest_checkpoint))
+        .route("/v1/users/{user_id}/leaf", get(user_leaf))
+        .route("/v1/contracts/{contract_id}/meta", get(contract_meta))
+        .route("/v1/users/{user_id}/contracts/{contract_id}/slots/{slot}", get(cstate_slot))
+        .route("/v1/endcaps", post(submit_endcap))
+        .with_state(relay)
+}
+
+async fn latest_checkpoint(State(relay): RelayState) -> ApiResult<CheckpointRef> {
+    Ok(Json(relay.upstream.network.latest_finalized_chkp().await?))
+}
+
+async fn user_leaf(
+    State(relay): RelayState,
+    Path(user_id): Path<String>,
+    Query(query): Query<CheckpointQuery>,
+) -> ApiResult<UserLeafCtx> {
+    let user_id = UserId(user_id);
+    relay.rate_limit(&user_id)?;
+    let checkpoint = relay.checkpoint(query).await?;
+    Ok(Json(relay.upstream.network.fetch_user_leaf(&user_id, &checkpoint).await?))
+}
+
+async fn contract_meta(
+    State(relay): RelayState,
+    Path(contract_id): Path<String>,
+    Query(query): Query<CheckpointQuery>,
+) -> ApiResult<ContractMeta> {
+    let checkpoint = relay.checkpoint(query).await?;
+    let (cft_root, cstate_height) = relay
+        .upstream
+        .network
+        .fetch_contract_meta(&ContractId(contract_id), &checkpoint)
+        .await?;
+    Ok(Json(ContractMeta { cft_root, cstate_height }))
+}
+
+async fn cstate_slot(
+    State(relay): RelayState,
+    Path((user_id, contract_id, slot)): Path<(String, String, u64)>,
+    Query(query): Query<CheckpointQuery>,
+) -> ApiResult<CstateReadWitness> {
+    let checkpoint = relay.checkpoint(query).await?;
+    let read = relay
+        .upstream
+        .network
+        .fetch_cstate_leaf(&UserId(user_id), &ContractId(contract_id), slot, &checkpoint)
+        .await?;
+    Ok(Json(read))
+}
+
+async fn submit_endcap(
+    State(relay): RelayState,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<SubmitReceipt> {
+    let request = SubmitRequest::parse(&headers, &body)?;
+    // 通过签名校验后才计入该用户的配额，他人无法用无效 End Cap 耗尽受害者的配额
+    relay.validate(&request).await?;
+    relay.rate_limit(&request.endcap.ups_header.user_id)?;
+    log::info!(
+        "转发用户 {:?} 的 End Cap ({} 个状态变更)",
+        request.endcap.ups_header.user_id.0,
+        request.state_deltas.len()
+    );
+    let receipt = relay
+        .upstream
+        .submitter
+        .submit_endcap(&request.endcap, request.state_deltas)
+        .await?;
+    Ok(Json(receipt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyguard_core::profile::NetworkProfile;
+    use psyguard_core::sdkey::SdkeyPolicyBuilder;
+    use psyguard_core::ups::UpsSession;
+    use psyguard_provers::mock::MOCK_SDKEY_VERIFIER_DATA;
+    use psyguard_provers::{MockNetworkState, MockProver, MockSubmitter};
+    use psyguard_stub_server::{Fixtures, StubChain, StubServer};
+    use reqwest::StatusCode;
+
+    async fn spawn(upstream: Upstream, rate_limit: u32) -> String {
+        let relay = Relay::new(
+            upstream,
+            Arc::new(MockProver::new()),
+            MOCK_SDKEY_VERIFIER_DATA.to_vec(),
+            RateLimiter::new(rate_limit, Duration::from_secs(60)),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(Arc::new(relay))).await });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_relay_reads_and_rate_limits_per_user() {
+        let url = spawn(Upstream::mock(), 2).await;
+        let client = reqwest::Client::new();
+
+        let chkp: CheckpointRef = client
+            .get(format!("{}/v1/checkpoints/latest", url))
+            .send().await.unwrap()
+            .json().await.unwrap();
+        assert_eq!(chkp.block_number, 1);
+
+        let leaf = format!("{}/v1/users/alice/leaf", url);
+        for _ in 0..2 {
+            let ctx: UserLeafCtx = client.get(&leaf).send().await.unwrap().json().await.unwrap();
+            assert_eq!(ctx.balance, 1000);
+        }
+        let limited = client.get(&leaf).send().await.unwrap();
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(limited.headers().contains_key(header::RETRY_AFTER));
+
+        let bob = client.get(format!("{}/v1/users/bob/leaf", url)).send().await.unwrap();
+        assert_eq!(bob.status(), StatusCode::OK);
+        let carol = client.get(format!("{}/v1/users/carol/leaf", url)).send().await.unwrap();
+        assert_eq!(carol.status(), StatusCode::NOT_FOUND);
+        let partial = client
+            .get(format!("{}/v1/users/bob/leaf?block_number=1", url))
+            .send().await.unwrap();
+        assert_eq!(partial.status(), StatusCode::BAD_REQUEST);
+    }
+
+    async fn finalize(upstream: &Upstream, policy: &SdkeyPolicy) -> EndCapProof {
+        let session = UpsSession::new_async(
+            UserId("alice".to_string()),
+            upstream.network.clone(),
+            Arc::new(MockProver::new()),
+        )
+        .await
+        .unwrap();
+        session.finalize_async(policy).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_relay_validates_endcaps() {
+        let policy = SdkeyPolicyBuilder::new().with_daily_limit(100).build();
+        let network = MockNetworkState::new();
+        network.add_user(UserId("alice".to_string()), 1000);
+        network.register_sdkey(&UserId("alice".to_string()), &policy).unwrap();
+        let upstream = Upstream {
+            network: Arc::new(SyncAdapter::new(Arc::new(network))),
+            submitter: Arc::new(SyncAdapter::new(Arc::new(MockSubmitter::new()))),
+        };
+        let url = spawn(upstream.clone(), 10).await;
+        let client = reqwest::Client::new();
+
+        let request = SubmitRequest {
+            endcap: finalize(&upstream, &policy).await,
+            state_deltas: vec![],
+            policy: policy.clone(),
+        };
+        let submit = |request: &SubmitRequest| {
+            client.post(format!("{}/v1/endcaps", url)).json(request).send()
+        };
+        let accepted = submit(&request).await.unwrap();
+        assert_eq!(accepted.status(), StatusCode::OK);
+        accepted.json::<SubmitReceipt>().await.unwrap();
+
+        // 以未登记的 SDKey 签名的 End Cap 自洽，但与上游用户叶中的公钥哈希不符
+        let forged_policy = SdkeyPolicyBuilder::new().with_daily_limit(1_000_000).build();
+        let forged_key = SubmitRequest {
+            endcap: finalize(&upstream, &forged_policy).await,
+            state_deltas: vec![],
+            policy: forged_policy.clone(),
+        };
+        assert!(Prover::verify_endcap(&MockProver::new(), &forged_key.endcap).unwrap());
+        // 连同头部用户叶一起伪造公钥哈希也无济于事
+        let mut forged_leaf_key = forged_key.clone();
+        forged_leaf_key.endcap.ups_header.user_leaf_ctx.public_key_hash =
+            MockProver::sdkey_public_key_hash(&forged_policy);
+        let mut wrong_leaf = request.clone();
+        wrong_leaf.endcap.ups_header.user_leaf_ctx.balance = 1_000_000;
+        // 谎报策略 (如隐瞒 2FA 要求) 导出的公钥与登记的不符
+        let wrong_policy = SubmitRequest { policy: SdkeyPolicyBuilder::new().build(), ..request.clone() };
+        for forged in [forged_key, forged_leaf_key, wrong_leaf, wrong_policy] {
+            let rejected = submit(&forged).await.unwrap();
+            assert_eq!(rejected.status(), StatusCode::BAD_REQUEST);
+            let body: ErrorResponse = rejected.json().await.unwrap();
+            assert!(body.error.contains("End Cap"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relay_charges_quota_only_for_valid_endcaps() {
+        let policy = SdkeyPolicyBuilder::new().with_daily_limit(100).build();
+        let network = MockNetworkState::new();
+        network.add_user(UserId("alice".to_string()), 1000);
+        network.register_sdkey(&UserId("alice".to_string()), &policy).unwrap();
+        let upstream = Upstream {
+            network: Arc::new(SyncAdapter::new(Arc::new(network))),
+            submitter: Arc::new(SyncAdapter::new(Arc::new(MockSubmitter::new()))),
+        };
+        let url = spawn(upstream.clone(), 1).await;
+        let client = reqwest::Client::new();
+        let submit = |request: &SubmitRequest| {
+            client.post(format!("{}/v1/endcaps", url)).json(request).send()
+        };
+
+        let request = SubmitRequest {
+            endcap: finalize(&upstream, &policy).await,
+            state_deltas: vec![],
+            policy: policy.clone(),
+        };
+
+        // 以 alice 名义提交的无效 End Cap 被拒绝，但不消耗她的配额
+        let mut junk = request.clone();
+        junk.endcap.ups_header.user_leaf_ctx.balance = 1_000_000;
+        for _ in 0..3 {
+            assert_eq!(submit(&junk).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        }
+        assert_eq!(submit(&request).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(submit(&request).await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_relay_accepts_wire_format_endcaps() {
+        let policy = SdkeyPolicyBuilder::new().with_daily_limit(100).build();
+        let network = MockNetworkState::new();
+        network.add_user(UserId("alice".to_string()), 1000);
+        network.register_sdkey(&UserId("alice".to_string()), &policy).unwrap();
+        let upstream = Upstream {
+            network: Arc::new(SyncAdapter::new(Arc::new(network))),
+            submitter: Arc::new(SyncAdapter::new(Arc::new(MockSubmitter::new()))),
+        };
+        let url = spawn(upstream.clone(), 10).await;
+        let client = reqwest::Client::new();
+
+        // 与 WasmUpsSession::export_endcap 相同: 终结会话后按线格式编码
+        let bytes = codec::encode(&finalize(&upstream, &policy).await);
+        let policy_json = serde_json::to_string(&policy).unwrap();
+        let submit = |body: Vec<u8>, policy: Option<&str>| {
+            let mut request = client
+                .post(format!("{}/v1/endcaps", url))
+                .header(header::CONTENT_TYPE, "application/octet-stream")
+                .body(body);
+            if let Some(policy) = policy {
+                request = request.header(POLICY_HEADER, policy.to_string());
+            }
+            request.send()
+        };
+
+        let accepted = submit(bytes.clone(), Some(&policy_json)).await.unwrap();
+        assert_eq!(accepted.status(), StatusCode::OK);
+        accepted.json::<SubmitReceipt>().await.unwrap();
+
+        // 其后紧接状态变更帧
+        let mut with_deltas = bytes.clone();
+        with_deltas.extend_from_slice(&codec::encode(&Vec::<CstateDelta>::new()));
+        assert_eq!(submit(with_deltas, Some(&policy_json)).await.unwrap().status(), StatusCode::OK);
+
+        // 缺少策略头、策略与登记公钥不符、帧被截断
+        let lax = serde_json::to_string(&SdkeyPolicyBuilder::new().build()).unwrap();
+        for (body, policy) in [
+            (bytes.clone(), None),
+            (bytes.clone(), Some(lax.as_str())),
+            (bytes[..bytes.len() - 1].to_vec(), Some(policy_json.as_str())),
+        ] {
+            assert_eq!(submit(body, policy).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relay_over_http_upstream() {
+        let profile = NetworkProfile::local();
+        let chain = Arc::new(StubChain::build(&Fixtures::demo(), &profile).unwrap());
+        let stub = StubServer::spawn(chain.clone()).await.unwrap();
+        let url = spawn(Upstream::http(&stub.url(), &profile).unwrap(), 10).await;
+        let client = reqwest::Client::new();
+
+        let slot: CstateReadWitness = client
+            .get(format!("{}/v1/users/alice/contracts/token/slots/1", url))
+            .send().await.unwrap()
+            .json().await.unwrap();
+        assert_eq!(slot.value.as_deref(), Some(&b"alice"[..]));
+        assert_eq!(slot.owner.0, "alice");
+        assert_eq!(slot.cstate_root, chain.ucon("alice").unwrap().contract_states[&ContractId("token".to_string())]);
+
+        // token 的共享状态由 alice 持有，按 bob 读取时被拒绝
+        let status = client
+            .get(format!("{}/v1/users/bob/contracts/token/slots/1", url))
+            .send().await.unwrap()
+            .status();
+        assert!(!status.is_success());
+
+        let chkp = chain.checkpoint_ref();
+        let leaf = client
+            .get(format!(
+                "{}/v1/users/bob/leaf?block_number={}&chkp_root={}",
+                url,
+                chkp.block_number,
+                hex::encode(chkp.chkp_root)
+            ))
+            .send().await.unwrap();
+        assert_eq!(leaf.json::<UserLeafCtx>().await.unwrap().balance, 250);
+
+        // 上游无法证明的 checkpoint 不会被转发
+        let stale = client
+            .get(format!("{}/v1/users/bob/leaf?block_number={}&chkp_root={}", url, chkp.block_number, "00".repeat(32)))
+            .send().await.unwrap();
+        assert_eq!(stale.status(), StatusCode::BAD_GATEWAY);
+    }
+}
@@ -0,0 +1,210 @@
+//! 中继配置
+//!
+//! 从环境变量读取:
+//!
+//! | 变量 | 默认值 | 说明 |
+//! |------|--------|------|
+//! | `PSYGUARD_RELAY_LISTEN` | `127.0.0.1:8787` | 监听地址 |
+//! | `PSYGUARD_RELAY_UPSTREAM` | `mock` | `mock` 或 Realm/DA 接口根地址 |
+//! | `PSYGUARD_RELAY_NETWORK` | `local` | `local` 或 `psy`，决定校验证明所用的哈希 |
+//! | `PSYGUARD_RELAY_RATE_LIMIT` | `60` | 每个用户每分钟的请求数 |
+//! | `PSYGUARD_RELAY_RATE_KEYS` | `10000` | 限流器同时跟踪的最大用户数 |
+//!
+//! End Cap 校验器 ([`VerifierKind`]) 不从环境变量读取。默认的 Mock 校验器只能配合 Mock 上游；
+//! 连接 Realm/DA 时须以库的方式嵌入中继并提供真实校验器，否则 [`RelayConfig::relay`] 拒绝启动。
+
+use crate::api::Relay;
+use crate::limiter::{RateLimiter, DEFAULT_MAX_KEYS};
+use crate::upstream::Upstream;
+use psyguard_core::profile::NetworkProfile;
+use psyguard_core::*;
+use psyguard_provers::mock::MOCK_SDKEY_VERIFIER_DATA;
+use psyguard_provers::MockProver;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 上游类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpstreamKind {
+    Mock,
+    Http(String),
+}
+
+/// End Cap 校验器
+#[derive(Clone)]
+pub enum VerifierKind {
+    /// MockProver: 签名承诺可由公开数据重算，任何人都能伪造，只能配合 Mock 上游
+    Mock,
+    /// 真实的证明校验器及 SDKey 签名电路的 verifier data
+    Custom {
+        verifier: Arc<dyn Prover>,
+        sdkey_verifier_data: Vec<u8>,
+    },
+}
+
+impl fmt::Debug for VerifierKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifierKind::Mock => f.write_str("Mock"),
+            VerifierKind::Custom { sdkey_verifier_data, .. } => f
+                .debug_struct("Custom")
+                .field("sdkey_verifier_data", &hex::encode(sdkey_verifier_data))
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// 中继配置
+#[derive(Debug, Clone)]
+pub struct RelayConfig {
+    pub listen: SocketAddr,
+    pub upstream: UpstreamKind,
+    pub profile: NetworkProfile,
+    pub verifier: VerifierKind,
+    /// 每个用户在 rate_window 内的最大请求数
+    pub rate_limit: u32,
+    pub rate_window: Duration,
+    /// 限流器同时跟踪的最大用户数
+    pub rate_keys: usize,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from(([127, 0, 0, 1], 8787)),
+            upstream: UpstreamKind::Mock,
+            profile: NetworkProfile::local(),
+            verifier: VerifierKind::Mock,
+            rate_limit: 60,
+            rate_window: Duration::from_secs(60),
+            rate_keys: DEFAULT_MAX_KEYS,
+        }
+    }
+}
+
+impl RelayConfig {
+    /// 从环境变量读取，未设置的项使用默认值
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut config = Self::default();
+
+        if let Some(listen) = var("PSYGUARD_RELAY_LISTEN") {
+            config.listen = listen.parse().map_err(|e| {
+                PsyGuardError::InvalidInput(format!("PSYGUARD_RELAY_LISTEN={} 无效: {}", listen, e))
+            })?;
+        }
+        if let Some(upstream) = var("PSYGUARD_RELAY_UPSTREAM") {
+            config.upstream = match upstream.as_str() {
+                "mock" => UpstreamKind::Mock,
+                url => UpstreamKind::Http(url.to_string()),
+            };
+        }
+        if let Some(network) = var("PSYGUARD_RELAY_NETWORK") {
+            config.profile = match network.as_str() {
+                "local" => NetworkProfile::local(),
+                "psy" => NetworkProfile::psy(),
+                other => {
+                    return Err(PsyGuardError::InvalidInput(format!(
+                        "PSYGUARD_RELAY_NETWORK={} 无效，应为 local 或 psy",
+                        other
+                    )))
+                }
+            };
+        }
+        if let Some(limit) = var("PSYGUARD_RELAY_RATE_LIMIT") {
+            config.rate_limit = limit.parse().map_err(|e| {
+                PsyGuardError::InvalidInput(format!("PSYGUARD_RELAY_RATE_LIMIT={} 无效: {}", limit, e))
+            })?;
+        }
+        if let Some(keys) = var("PSYGUARD_RELAY_RATE_KEYS") {
+            config.rate_keys = keys.parse().map_err(|e| {
+                PsyGuardError::InvalidInput(format!("PSYGUARD_RELAY_RATE_KEYS={} 无效: {}", keys, e))
+            })?;
+        }
+
+        Ok(config)
+    }
+
+    /// 按配置构建中继；Mock 校验器配合 HTTP 上游时拒绝启动
+    pub fn relay(&self) -> Result<Relay> {
+        let (verifier, sdkey_verifier_data): (Arc<dyn Prover>, Vec<u8>) = match (&self.verifier, &self.upstream) {
+            (VerifierKind::Mock, UpstreamKind::Http(url)) => {
+                return Err(PsyGuardError::InvalidInput(format!(
+                    "上游 {} 需要真实的 End Cap 校验器，Mock 校验器的签名可被任何人伪造",
+                    url
+                )))
+            }
+            (VerifierKind::Mock, UpstreamKind::Mock) => {
+                (Arc::new(MockProver::new()), MOCK_SDKEY_VERIFIER_DATA.to_vec())
+            }
+            (VerifierKind::Custom { verifier, sdkey_verifier_data }, _) => {
+                (verifier.clone(), sdkey_verifier_data.clone())
+            }
+        };
+
+        Ok(Relay::new(
+            self.upstream()?,
+            verifier,
+            sdkey_verifier_data,
+            RateLimiter::new(self.rate_limit, self.rate_window).with_max_keys(self.rate_keys),
+        ))
+    }
+
+    /// 按配置构建上游
+    pub fn upstream(&self) -> Result<Upstream> {
+        match &self.upstream {
+            UpstreamKind::Mock => Ok(Upstream::mock()),
+            UpstreamKind::Http(url) => Upstream::http(url, &self.profile),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_config_from_vars() {
+        let vars: HashMap<&str, &str> = [
+            ("PSYGUARD_RELAY_UPSTREAM", "http://127.0.0.1:8080"),
+            ("PSYGUARD_RELAY_NETWORK", "psy"),
+            ("PSYGUARD_RELAY_RATE_LIMIT", "5"),
+            ("PSYGUARD_RELAY_RATE_KEYS", "100"),
+        ]
+        .into();
+        let config = RelayConfig::from_vars(|name| vars.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.upstream, UpstreamKind::Http("http://127.0.0.1:8080".to_string()));
+        assert_eq!(config.profile, NetworkProfile::psy());
+        assert_eq!(config.rate_limit, 5);
+        assert_eq!(config.rate_keys, 100);
+        assert_eq!(config.listen, RelayConfig::default().listen);
+
+        let bad = RelayConfig::from_vars(|name| (name == "PSYGUARD_RELAY_NETWORK").then(|| "main".to_string()));
+        assert!(matches!(bad, Err(PsyGuardError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_config_refuses_mock_verifier_on_http_upstream() {
+        let http = RelayConfig {
+            upstream: UpstreamKind::Http("http://127.0.0.1:8080".to_string()),
+            ..RelayConfig::default()
+        };
+        assert!(matches!(http.relay(), Err(PsyGuardError::InvalidInput(_))));
+
+        let custom = RelayConfig {
+            verifier: VerifierKind::Custom {
+                verifier: Arc::new(MockProver::new()),
+                sdkey_verifier_data: MOCK_SDKEY_VERIFIER_DATA.to_vec(),
+            },
+            ..http
+        };
+        assert!(custom.relay().is_ok());
+        assert!(RelayConfig::default().relay().is_ok());
+    }
+}
@@ -0,0 +1,15 @@
+//! PsyGuard Relay - 轻量中继
+//!
+//! 为浏览器插件转发对 Realm/Coordinator/DA 的读取与 End Cap 提交，
+//! 上游可插拔 (Mock 或 REST)，提交前用 psyguard-core 的 End Cap 校验拒绝无效请求。
+//! 参考: 《2-Miners & Roles on Psy.md》- Realm/Coordinator/DA 的职责
+
+pub mod api;
+pub mod config;
+pub mod limiter;
+pub mod upstream;
+
+pub use api::{router, Relay, RelayError};
+pub use config::{RelayConfig, VerifierKind};
+pub use limiter::RateLimiter;
+pub use upstream::Upstream;
@@ -0,0 +1,97 @@
+//! 按用户 ID 限流
+//!
+//! 固定窗口计数: 每个用户在一个窗口内最多 max_requests 次请求。
+//! 同时跟踪的用户数有上限，表满时新用户需等最早的窗口过期。
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 默认最多同时跟踪的用户数
+pub const DEFAULT_MAX_KEYS: usize = 10_000;
+
+/// 固定窗口限流器
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    max_keys: usize,
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            max_keys: DEFAULT_MAX_KEYS,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 设置同时跟踪的最大用户数
+    pub fn with_max_keys(mut self, max_keys: usize) -> Self {
+        self.max_keys = max_keys;
+        self
+    }
+
+    /// 记录一次请求；超出限额时返回距窗口结束的时间
+    pub fn check(&self, user_id: &str) -> Result<(), Duration> {
+        self.check_at(user_id, Instant::now())
+    }
+
+    fn check_at(&self, user_id: &str, now: Instant) -> Result<(), Duration> {
+        let mut windows = self.windows.lock().unwrap();
+        // 顺带清理已过期的窗口
+        windows.retain(|_, (start, _)| now.duration_since(*start) < self.window);
+
+        if !windows.contains_key(user_id) && windows.len() >= self.max_keys {
+            let oldest = windows.values().map(|(start, _)| *start).min().unwrap_or(now);
+            return Err(self.window - now.duration_since(oldest));
+        }
+
+        let (start, count) = windows.entry(user_id.to_string()).or_insert((now, 0));
+        if *count >= self.max_requests {
+            return Err(self.window - now.duration_since(*start));
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_per_user_window() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(limiter.check_at("alice", start).is_ok());
+        assert!(limiter.check_at("alice", start).is_ok());
+        let retry = limiter.check_at("alice", start + Duration::from_secs(20)).unwrap_err();
+        assert_eq!(retry, Duration::from_secs(40));
+
+        // 其他用户不受影响，窗口结束后重新计数
+        assert!(limiter.check_at("bob", start).is_ok());
+        assert!(limiter.check_at("alice", start + Duration::from_secs(60)).is_ok());
+    }
+
+    #[test]
+    fn test_rate_limiter_caps_tracked_keys() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60)).with_max_keys(2);
+        let start = Instant::now();
+
+        assert!(limiter.check_at("alice", start).is_ok());
+        assert!(limiter.check_at("bob", start + Duration::from_secs(10)).is_ok());
+
+        // 表满时新用户被拒绝，已跟踪的用户照常计数
+        let retry = limiter.check_at("mallory", start + Duration::from_secs(30)).unwrap_err();
+        assert_eq!(retry, Duration::from_secs(30));
+        assert!(limiter.check_at("alice", start + Duration::from_secs(30)).is_ok());
+        assert_eq!(limiter.windows.lock().unwrap().len(), 2);
+
+        // alice 的窗口过期后腾出位置
+        assert!(limiter.check_at("mallory", start + Duration::from_secs(60)).is_ok());
+    }
+}
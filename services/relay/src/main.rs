@@ -0,0 +1,15 @@
+//! 中继服务入口，配置见 psyguard_relay::config
+
+use psyguard_relay::{router, RelayConfig};
+use std::sync::Arc;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = RelayConfig::from_env()?;
+    let relay = config.relay()?;
+
+    let listener = tokio::net::TcpListener::bind(config.listen).await?;
+    println!("中继监听 http://{} (上游 {:?})", listener.local_addr()?, config.upstream);
+    axum::serve(listener, router(Arc::new(relay))).await?;
+    Ok(())
+}
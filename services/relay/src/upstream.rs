@@ -0,0 +1,39 @@
+//! 上游后端
+//!
+//! 中继只依赖 psyguard-core 的异步接口，上游可以是进程内 Mock，
+//! 也可以是 Realm/DA 的 REST 接口 (响应中的证明由 HttpNetworkState 校验)。
+
+use psyguard_core::profile::NetworkProfile;
+use psyguard_core::*;
+use psyguard_http::{HttpNetworkState, HttpSubmitter};
+use psyguard_provers::{MockNetworkState, MockSubmitter};
+use std::sync::Arc;
+
+/// 网络状态与提交后端
+#[derive(Clone)]
+pub struct Upstream {
+    pub network: Arc<dyn AsyncNetworkState>,
+    pub submitter: Arc<dyn AsyncSubmitter>,
+}
+
+impl Upstream {
+    /// 进程内 Mock (预置 alice、bob 两个用户)
+    pub fn mock() -> Self {
+        let network = MockNetworkState::new();
+        network.add_user(UserId("alice".to_string()), 1000);
+        network.add_user(UserId("bob".to_string()), 1000);
+
+        Self {
+            network: Arc::new(SyncAdapter::new(Arc::new(network))),
+            submitter: Arc::new(SyncAdapter::new(Arc::new(MockSubmitter::new()))),
+        }
+    }
+
+    /// Realm/DA REST 接口，见 psyguard_http::schema
+    pub fn http(base_url: &str, profile: &NetworkProfile) -> Result<Self> {
+        Ok(Self {
+            network: Arc::new(HttpNetworkState::new(base_url, profile)?),
+            submitter: Arc::new(HttpSubmitter::new(base_url)?),
+        })
+    }
+}